@@ -0,0 +1,22 @@
+fn main() {
+    // 启用 `ffi` 特性时生成 C 头文件
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo::rerun-if-changed=src/ffi.rs");
+        let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+        let config = cbindgen::Config {
+            language: cbindgen::Language::C,
+            include_guard: Some("RAY_TRACING_H".to_string()),
+            autogen_warning: Some("/* 由 cbindgen 自动生成, 请勿手动修改 */".to_string()),
+            cpp_compat: true,
+            usize_is_size_t: true,
+            ..Default::default()
+        };
+        cbindgen::Builder::new()
+            .with_config(config)
+            .with_src(format!("{crate_dir}/src/ffi.rs"))
+            .generate()
+            .expect("failed to generate C header")
+            .write_to_file(format!("{crate_dir}/include/ray_tracing.h"));
+    }
+}
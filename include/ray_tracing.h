@@ -0,0 +1,148 @@
+#ifndef RAY_TRACING_H
+#define RAY_TRACING_H
+
+/* 由 cbindgen 自动生成, 请勿手动修改 */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * 接口函数的返回值
+ */
+typedef enum RtStatus {
+  /**
+   * 成功
+   */
+  RtOk = 0,
+  /**
+   * 传入了空指针
+   */
+  RtNullPointer = 1,
+  /**
+   * 参数无效, 如非正的半径, 未设置相机或场景为空
+   */
+  RtInvalidArgument = 2,
+  /**
+   * 渲染被进度回调取消
+   */
+  RtCancelled = 3,
+  /**
+   * 渲染中发生了内部错误 (panic), `buffer` 的内容未定义
+   */
+  RtPanic = 4,
+} RtStatus;
+
+/**
+ * 材质的种类
+ */
+typedef enum RtMaterialKind {
+  /**
+   * 漫反射, `param` 不使用
+   */
+  RtLambertian = 0,
+  /**
+   * 金属, `param` 为模糊度
+   */
+  RtMetal = 1,
+  /**
+   * 电介质, `param` 为折射率, `color` 不使用
+   */
+  RtDielectric = 2,
+  /**
+   * 自发光, `param` 为强度
+   */
+  RtEmissive = 3,
+} RtMaterialKind;
+
+/**
+ * 由调用者逐步构建的场景, 对 C 不透明
+ */
+typedef struct RtScene RtScene;
+
+/**
+ * 材质
+ */
+typedef struct RtMaterial {
+  enum RtMaterialKind kind;
+  float color[3];
+  float param;
+} RtMaterial;
+
+/**
+ * 进度回调: `progress` 为已完成的比例 (0 到 1), 返回非零值时取消渲染
+ */
+typedef int (*RtProgressFn)(float progress, void *user_data);
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * 创建空场景, 以 `rt_scene_free` 释放
+ */
+struct RtScene *rt_scene_new(void);
+
+/**
+ * 释放场景, `scene` 可为空
+ *
+ * # Safety
+ *
+ * `scene` 须为 `rt_scene_new` 返回且尚未释放的指针
+ */
+void rt_scene_free(struct RtScene *scene);
+
+/**
+ * 向场景添加球体
+ *
+ * # Safety
+ *
+ * `scene` 须为 `rt_scene_new` 返回且尚未释放的指针, `center` 须指向 3 个 `float`
+ */
+enum RtStatus rt_scene_add_sphere(struct RtScene *scene,
+                                  const float *center,
+                                  float radius,
+                                  struct RtMaterial material);
+
+/**
+ * 设置相机, `vertical_fov` 以度为单位, `aperture` 为 0 时没有景深
+ *
+ * # Safety
+ *
+ * `scene` 须为 `rt_scene_new` 返回且尚未释放的指针, `look_from` 与 `look_at` 须各指向 3 个 `float`
+ */
+enum RtStatus rt_scene_set_camera(struct RtScene *scene,
+                                  const float *look_from,
+                                  const float *look_at,
+                                  float vertical_fov,
+                                  float aperture,
+                                  float focus_dist);
+
+/**
+ * 以每个像素 `samples` 个采样渲染场景, 写入 `buffer` 中 `width * height` 个 RGB 像素 (8 位,
+ * 首行为图像顶部)
+ *
+ * 每渲染若干行在调用线程上调用一次 `progress`, 其返回非零值时停止并返回 `RtCancelled`,
+ * 此时 `buffer` 中只有部分行已写入; 宽或高为零, 场景中没有实体, 或 `3 * width * height` 溢出时返回
+ * `RtInvalidArgument`; 渲染中的 panic 不会展开到调用方, 而是返回 `RtPanic`
+ *
+ * # Safety
+ *
+ * `scene` 须为 `rt_scene_new` 返回且尚未释放的指针, `buffer` 须可写入 `3 * width * height` 字节
+ */
+enum RtStatus rt_render(const struct RtScene *scene,
+                        size_t width,
+                        size_t height,
+                        size_t samples,
+                        size_t max_depth,
+                        uint8_t *buffer,
+                        RtProgressFn progress,
+                        void *user_data);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* RAY_TRACING_H */
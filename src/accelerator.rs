@@ -0,0 +1,32 @@
+use crate::bvh::{AaBb, ClosestPoint};
+use crate::hittable::Hittable;
+
+use clap::ValueEnum;
+use nalgebra::Vector3;
+
+/// 光线求交加速结构
+pub trait Accelerator: Hittable + Send {
+    /// 结构中各结点 (或格子) 的包围盒及其层级, 用于可视化
+    fn boxes(&self) -> Vec<(AaBb, usize)>;
+
+    /// 所有实体表面上距 `point` 最近的点, 没有实体时为 `None`
+    fn closest_point(&self, point: &Vector3<f32>) -> Option<ClosestPoint>;
+
+    /// 结点数与每个结点的字节数, 由调用方输出; 不统计结点的结构为 `None`
+    fn stats(&self) -> Option<(usize, usize)> {
+        None
+    }
+}
+
+/// 加速结构类型
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum AcceleratorKind {
+    /// 层次包围盒
+    Bvh,
+
+    /// 子结点包围盒量化存储的 BVH, 内存约为前者的一半
+    Qbvh,
+
+    /// 均匀网格, 适合分布均匀的密集场景
+    Grid,
+}
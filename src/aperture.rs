@@ -0,0 +1,57 @@
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::texture::{self, ColorSpace};
+
+/// 光圈形状: 按灰度图像的亮度重要性采样镜头上的位置, 用于星形, 心形等形状的焦外光斑
+pub struct Aperture {
+    width: usize,
+    height: usize,
+
+    /// 各像素亮度的累积分布 (首行为图像顶部), 末项为 1
+    cdf: Vec<f32>,
+}
+
+impl Aperture {
+    /// 读取光圈图像 (二进制 PPM), 图像铺满镜头的外接正方形
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+        let (width, height, texels) = texture::read_image(path, ColorSpace::Srgb)?;
+
+        let mut total = 0.0;
+        let mut cdf: Vec<f32> = texels
+            .iter()
+            .map(|texel| {
+                total += texel.mean();
+                total
+            })
+            .collect();
+        if total <= 0.0 {
+            return Err(Error::invalid_data(
+                path,
+                "aperture image is completely black",
+            ));
+        }
+        cdf.iter_mut().for_each(|c| *c /= total);
+
+        Ok(Self { width, height, cdf })
+    }
+
+    /// 由 [0, 1)² 内的采样值 `(u, v)` 在 `[-1, 1]²` 中按亮度采样一点, 在选中的像素内均匀分布
+    ///
+    /// `u` 选出像素后重新缩放到 [0, 1) 作为像素内的水平偏移
+    pub fn sample(&self, (u, v): (f32, f32)) -> (f32, f32) {
+        let index = self
+            .cdf
+            .partition_point(|&c| c <= u)
+            .min(self.cdf.len() - 1);
+        let (column, row) = (index % self.width, index / self.width);
+
+        let lower = if index == 0 { 0.0 } else { self.cdf[index - 1] };
+        let dx = ((u - lower) / (self.cdf[index] - lower)).clamp(0.0, 1.0 - f32::EPSILON);
+        let x = (column as f32 + dx) / self.width as f32;
+        let y = (row as f32 + v) / self.height as f32;
+
+        (2.0 * x - 1.0, 1.0 - 2.0 * y)
+    }
+}
@@ -0,0 +1,151 @@
+use crate::light::Light;
+use crate::ray::Ray;
+use crate::sun_sky::SunSky;
+use crate::texture::{self, TextureId};
+
+use nalgebra::{Rotation3, Vector3};
+
+/// 背景 (光线未击中任何实体时的颜色)
+#[derive(Clone, Copy, Debug)]
+pub enum Background {
+    /// 由白到天蓝的渐变天空
+    Sky,
+
+    /// 均匀颜色
+    Uniform(Vector3<f32>),
+
+    /// 由正下方的 `bottom` 到正上方的 `top` 的竖直渐变
+    Gradient {
+        bottom: Vector3<f32>,
+        top: Vector3<f32>,
+    },
+
+    /// 经纬度 (等距柱状投影) 环境贴图
+    Image(TextureId),
+
+    /// 摄影棚背景: 上半球由地平线处的 `horizon` 渐变到天顶的 `zenith`, 下半球为均匀的 `ground`,
+    /// 两者在地平线上下 `softness` (仰角的正弦) 以内平滑过渡, 不需要 HDR 贴图即可得到干净的产品照明
+    Studio {
+        zenith: Vector3<f32>,
+        horizon: Vector3<f32>,
+        ground: Vector3<f32>,
+        softness: f32,
+    },
+
+    /// 由纬度, 日期与时刻确定的太阳与天空, 太阳同时作为平行光参与直接光照
+    SunSky(SunSky),
+}
+
+impl Background {
+    /// 默认的摄影棚背景: 明亮的顶光, 中灰的地平线与较暗的地面
+    pub const STUDIO: Self = Self::Studio {
+        zenith: Vector3::new(1.0, 1.0, 1.0),
+        horizon: Vector3::new(0.55, 0.55, 0.56),
+        ground: Vector3::new(0.25, 0.25, 0.25),
+        softness: 0.1,
+    };
+
+    /// 单位方向上的背景颜色
+    fn color_in(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        match self {
+            Self::Sky => {
+                let t = 0.5 * (direction.y + 1.0);
+
+                (1.0 - t) * Vector3::new(1.0, 1.0, 1.0) + t * Vector3::new(0.5, 0.7, 1.0)
+            }
+
+            Self::Uniform(color) => *color,
+
+            Self::Gradient { bottom, top } => bottom.lerp(top, 0.5 * (direction.y + 1.0)),
+
+            Self::Image(texture) => {
+                let u = direction.z.atan2(direction.x) / std::f32::consts::TAU + 0.5;
+                let v = direction.y.clamp(-1.0, 1.0).asin() / std::f32::consts::PI + 0.5;
+
+                texture::sample(*texture, (u, v))
+            }
+
+            Self::Studio {
+                zenith,
+                horizon,
+                ground,
+                softness,
+            } => {
+                let sky = horizon.lerp(zenith, direction.y.max(0.0));
+                let t =
+                    ((direction.y + softness) / (2.0 * softness).max(f32::EPSILON)).clamp(0.0, 1.0);
+
+                ground.lerp(&sky, t * t * (3.0 - 2.0 * t))
+            }
+
+            Self::SunSky(sky) => sky.radiance(direction),
+        }
+    }
+}
+
+/// 环境: 背景及其朝向与强度
+#[derive(Clone, Copy, Debug)]
+pub struct Environment {
+    pub background: Background,
+
+    /// 世界方向到背景方向的旋转
+    rotation: Rotation3<f32>,
+
+    /// 强度倍数
+    pub intensity: f32,
+}
+
+impl Environment {
+    /// 不旋转, 强度为 1 的环境
+    pub fn new(background: Background) -> Self {
+        Self {
+            background,
+            rotation: Rotation3::identity(),
+            intensity: 1.0,
+        }
+    }
+
+    /// 将背景绕竖直轴旋转 `azimuth`, 再向上抬起 `elevation` (均为角度)
+    pub fn rotated(self, azimuth: f32, elevation: f32) -> Self {
+        let rotation = Rotation3::from_axis_angle(&Vector3::y_axis(), azimuth.to_radians())
+            * Rotation3::from_axis_angle(&Vector3::z_axis(), elevation.to_radians());
+
+        Self {
+            rotation: rotation.inverse(),
+            ..self
+        }
+    }
+
+    /// 强度乘以 `intensity`
+    pub fn scaled(self, intensity: f32) -> Self {
+        Self { intensity, ..self }
+    }
+
+    /// 光线方向上的背景颜色
+    pub fn color(&self, ray: &Ray) -> Vector3<f32> {
+        let direction = self.rotation * ray.direction().normalize();
+
+        self.intensity * self.background.color_in(&direction)
+    }
+
+    /// 光线方向上不含太阳圆盘的背景颜色, 用于已经直接采样过太阳的光线
+    pub fn color_without_sun(&self, ray: &Ray) -> Vector3<f32> {
+        match &self.background {
+            Background::SunSky(sky) => {
+                self.intensity * sky.sky(&(self.rotation * ray.direction().normalize()))
+            }
+            _ => self.color(ray),
+        }
+    }
+
+    /// 太阳对应的平行光 (世界方向, 乘以强度), 背景不是太阳与天空或太阳在地平线以下时为 `None`
+    pub fn sun(&self) -> Option<Light> {
+        let Background::SunSky(sky) = &self.background else {
+            return None;
+        };
+        let irradiance = self.intensity * sky.sun_irradiance();
+
+        (irradiance.max() > 0.0)
+            .then(|| Light::directional(self.rotation.inverse() * sky.sun_direction(), irradiance))
+    }
+}
@@ -0,0 +1,91 @@
+use crate::hittable::Hittable;
+use crate::ray::{Ray, RayEpsilon, RayKind};
+use crate::rng;
+use crate::sampling::random_cosine_direction;
+
+use clap::ValueEnum;
+use nalgebra::Vector3;
+use rayon::prelude::*;
+
+/// 带有 UV 参数化的表面, 可被烘焙到纹理
+pub trait Parameterized: Sync {
+    /// UV 坐标处的表面位置与法线
+    fn surface_at(&self, u: f32, v: f32) -> (Vector3<f32>, Vector3<f32>);
+}
+
+/// 烘焙内容
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum BakeMode {
+    /// 环境光遮蔽
+    Ao,
+
+    /// 辐照度
+    Irradiance,
+}
+
+/// 烘焙设置
+pub struct BakeSettings {
+    /// 烘焙内容
+    pub mode: BakeMode,
+
+    /// 每个纹素的采样数
+    pub samples: usize,
+
+    /// 环境光遮蔽的最大距离
+    pub ao_distance: f32,
+
+    /// 遮挡光线的自相交容差
+    pub epsilon: RayEpsilon,
+}
+
+/// 将表面的环境光遮蔽或辐照度烘焙为 `nx * ny` 的纹理 (线性值, 首行对应 v = 1)
+pub fn bake(
+    surface: &impl Parameterized,
+    scene: &(impl Hittable + ?Sized),
+    nx: usize,
+    ny: usize,
+    settings: &BakeSettings,
+    radiance: impl Fn(Ray) -> Vector3<f32> + Sync,
+) -> Vec<u8> {
+    (0..ny)
+        .into_par_iter()
+        .rev()
+        .flat_map_iter(|y| {
+            let radiance = &radiance;
+            (0..nx).flat_map(move |x| {
+                let u = (x as f32 + 0.5) / nx as f32;
+                let v = (y as f32 + 0.5) / ny as f32;
+                let (position, normal) = surface.surface_at(u, v);
+                rng::start_pixel((x, y), 0);
+
+                // 余弦加权采样, 辐照度 / π 即为采样的平均辐射度
+                let mut col = Vector3::zeros();
+                for _ in 0..settings.samples {
+                    let ray = Ray::from(
+                        position,
+                        random_cosine_direction(&normal, &mut rng::local()),
+                    );
+                    col += match settings.mode {
+                        BakeMode::Ao => {
+                            let ray = ray.with_kind(RayKind::Shadow);
+                            let max = settings.ao_distance / ray.direction().magnitude();
+                            if scene
+                                .hit(&ray, settings.epsilon.t_min(&ray, 0.0), max)
+                                .is_some()
+                            {
+                                Vector3::zeros()
+                            } else {
+                                Vector3::new(1.0, 1.0, 1.0)
+                            }
+                        }
+                        BakeMode::Irradiance => radiance(ray),
+                    };
+                }
+
+                let col =
+                    col.map(|c| (255.99 * (c / settings.samples as f32).clamp(0.0, 1.0)) as u8);
+                [col.x, col.y, col.z]
+            })
+        })
+        .collect()
+}
@@ -1,15 +1,23 @@
+use crate::accelerator::Accelerator;
 use crate::hittable::{HitRecord, Hittable};
+use crate::mesh::Geometry;
 use crate::ray::Ray;
 
 use nalgebra::Vector3;
 use std::cmp::Ordering;
 use std::sync::Arc;
 
-/// 一个结点最多包含的实体
-const MAX_OBJECTS: usize = 7;
+/// 一个叶子结点默认最多包含的实体: 是否终止为叶子结点由 SAH 决定, 上限只防止大量重叠的实体堆在同一个叶子中
+pub const MAX_OBJECTS: usize = 64;
+
+/// SAH 中遍历一个内部结点的相对开销
+const TRAVERSAL_COST: f32 = 0.5;
+
+/// SAH 中与一个实体求交的相对开销
+const INTERSECTION_COST: f32 = 1.0;
 
 /// 轴对齐包围盒
-#[derive(Clone)]
+#[derive(Clone, Debug)]
 pub struct AaBb {
     /// 最小点
     pub min: Vector3<f32>,
@@ -27,7 +35,7 @@ impl AaBb {
     }
 
     /// 能包裹两个包围盒的最小包围盒
-    fn surrounding_box(box0: &Self, box1: &Self) -> Self {
+    pub fn surrounding_box(box0: &Self, box1: &Self) -> Self {
         let small = box0.min.zip_map(&box1.min, f32::min);
         let big = box0.max.zip_map(&box1.max, f32::max);
 
@@ -38,7 +46,7 @@ impl AaBb {
     }
 
     /// 能包裹多个实体的最小包围盒
-    fn all_surrounding_box(objects: &[Arc<dyn Bounded + Sync + Send>]) -> Self {
+    pub fn all_surrounding_box(objects: &[Arc<dyn Bounded + Sync + Send>]) -> Self {
         let mut surround = Self::new();
 
         for obj in objects {
@@ -50,19 +58,60 @@ impl AaBb {
         surround
     }
 
+    /// 包围盒的表面积
+    fn surface_area(&self) -> f32 {
+        let d = self.max - self.min;
+        if d.min() < 0.0 {
+            return 0.0;
+        }
+
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// 点到包围盒距离的平方, 位于包围盒内部时为 0
+    pub fn distance_squared(&self, point: &Vector3<f32>) -> f32 {
+        let below = self.min - point;
+        let above = point - self.max;
+
+        below
+            .zip_map(&above, |b, a| b.max(a).max(0.0))
+            .magnitude_squared()
+    }
+
     /// 光线与包围盒相交
     pub fn hit(&self, ray: &Ray) -> bool {
-        let inv_d = ray.direction().map(|d| 1.0 / d);
-        let t0s = (self.min - ray.origin()).component_mul(&inv_d);
-        let t1s = (self.max - ray.origin()).component_mul(&inv_d);
+        let (near, far) = self.span(ray);
+
+        far > near
+    }
 
-        let t_min_vec = t0s.zip_map(&t1s, f32::min);
-        let t_max_vec = t0s.zip_map(&t1s, f32::max);
+    /// 光线进入与离开包围盒的参数 t, 未穿过时前者不小于后者
+    pub fn span(&self, ray: &Ray) -> (f32, f32) {
+        // 按方向的符号直接取各轴上先进入与后离开的一侧, 无需比较
+        let bounds = [&self.min, &self.max];
+        let (origin, inv_d, sign) = (ray.origin(), ray.inv_direction(), ray.sign());
+
+        let mut t_min = f32::NEG_INFINITY;
+        let mut t_max = f32::INFINITY;
+        for axis in 0..3 {
+            let near = (bounds[sign[axis]][axis] - origin[axis]) * inv_d[axis];
+            let far = (bounds[1 - sign[axis]][axis] - origin[axis]) * inv_d[axis];
+            t_min = t_min.max(near);
+            t_max = t_max.min(far);
+        }
 
-        let t_min = t_min_vec.max();
-        let t_max = t_max_vec.min();
+        (t_min, t_max)
+    }
 
-        t_max > t_min.max(t_min)
+    /// 包围盒的 8 个顶点
+    pub fn corners(&self) -> [Vector3<f32>; 8] {
+        std::array::from_fn(|i| {
+            Vector3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            )
+        })
     }
 
     /// 选取分割轴 (包围盒最长边所在的轴)
@@ -82,10 +131,58 @@ impl AaBb {
     }
 }
 
+/// 实体表面上距查询点最近的点
+#[derive(Clone, Debug)]
+pub struct ClosestPoint {
+    /// 实体在场景中的编号, 由 `Scene` 赋值
+    pub object: usize,
+
+    /// 最近点的位置
+    pub position: Vector3<f32>,
+
+    /// 查询点到表面的有符号距离, 位于实体内部时为负
+    pub distance: f32,
+}
+
 /// 可被 BVH 管理的有界实体
 pub trait Bounded: Hittable + Send {
     /// 实体的包围盒
     fn bounding_box(&self) -> AaBb;
+
+    /// 实体表面上距 `point` 最近的点
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint;
+
+    /// 实体是否无界, 此时包围盒只是放入加速结构所用的近似, 不计入场景主体的尺度
+    fn unbounded(&self) -> bool {
+        false
+    }
+
+    /// 实体为自发光的三角形网格时为其几何数据, 由直接光照作为网格光源采样
+    fn emitter(&self) -> Option<Arc<Geometry>> {
+        None
+    }
+
+    /// 实体由三角形构成 (三角形, 网格及其实例) 时为各个三角形的顶点, 用于叠加线框
+    fn triangles(&self) -> Vec<[Vector3<f32>; 3]> {
+        Vec::new()
+    }
+}
+
+/// 在 `objects` 中查找表面距 `point` 更近的实体, 更新 `best`
+pub fn closest_among(
+    objects: &[Arc<dyn Bounded + Sync + Send>],
+    point: &Vector3<f32>,
+    best: &mut Option<ClosestPoint>,
+) {
+    for obj in objects {
+        let candidate = obj.closest_point(point);
+        if best
+            .as_ref()
+            .is_none_or(|b| candidate.distance.abs() < b.distance.abs())
+        {
+            *best = Some(candidate);
+        }
+    }
 }
 
 impl Hittable for Vec<Arc<dyn Bounded + Sync + Send>> {
@@ -106,6 +203,35 @@ impl Hittable for Vec<Arc<dyn Bounded + Sync + Send>> {
     }
 }
 
+/// 共享的实体, 如被多个实例引用的几何体
+impl Hittable for Arc<dyn Bounded + Sync + Send> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        (**self).hit(ray, t_min, t_max)
+    }
+}
+
+impl Bounded for Arc<dyn Bounded + Sync + Send> {
+    fn bounding_box(&self) -> AaBb {
+        (**self).bounding_box()
+    }
+
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        (**self).closest_point(point)
+    }
+
+    fn unbounded(&self) -> bool {
+        (**self).unbounded()
+    }
+
+    fn emitter(&self) -> Option<Arc<Geometry>> {
+        (**self).emitter()
+    }
+
+    fn triangles(&self) -> Vec<[Vector3<f32>; 3]> {
+        (**self).triangles()
+    }
+}
+
 /// BVH 结点
 pub enum BVHNode {
     /// 叶子结点, 包含一个实体
@@ -123,39 +249,91 @@ pub enum BVHNode {
 
 impl BVHNode {
     /// 构建 BVH 树
-    pub fn build(mut objects: Vec<Arc<dyn Bounded + Sync + Send>>) -> Self {
-        if objects.len() <= MAX_OBJECTS {
-            Self::Leaf { objects }
-        } else {
-            let surround = AaBb::all_surrounding_box(&objects);
-            let axis = surround.split_axis();
+    pub fn build(objects: Vec<Arc<dyn Bounded + Sync + Send>>) -> Self {
+        Self::build_with_leaf_size(objects, MAX_OBJECTS)
+    }
 
-            objects.sort_by(|a, b| {
-                let box_a = a.bounding_box();
-                let box_b = b.bounding_box();
+    /// 构建 BVH 树, 每个叶子结点最多包含 `leaf_size` 个实体
+    ///
+    /// 沿最长轴按表面积启发式 (SAH) 选取分割位置, 当分割的开销不低于直接与所有实体求交时,
+    /// 结点终止为叶子结点; 超过 `leaf_size` 个实体的结点总是分割
+    pub fn build_with_leaf_size(
+        mut objects: Vec<Arc<dyn Bounded + Sync + Send>>,
+        leaf_size: usize,
+    ) -> Self {
+        if objects.len() <= 1 {
+            return Self::Leaf { objects };
+        }
+
+        let surround = AaBb::all_surrounding_box(&objects);
+        let axis = surround.split_axis();
 
-                box_a.min[axis]
-                    .partial_cmp(&box_b.min[axis])
-                    .unwrap_or(Ordering::Equal)
-            });
+        objects.sort_by(|a, b| {
+            let box_a = a.bounding_box();
+            let box_b = b.bounding_box();
 
-            let right = objects.split_off(objects.len() / 2);
-            let left = objects;
+            box_a.min[axis]
+                .partial_cmp(&box_b.min[axis])
+                .unwrap_or(Ordering::Equal)
+        });
 
-            let left = Self::build(left);
-            let right = Self::build(right);
-            let bbox = AaBb::surrounding_box(&left.bounding_box(), &right.bounding_box());
+        let (split, split_cost) = Self::sah_split(&objects, &surround);
+        let leaf_cost = objects.len() as f32 * INTERSECTION_COST;
+        if objects.len() <= leaf_size && split_cost >= leaf_cost {
+            return Self::Leaf { objects };
+        }
+
+        let right = objects.split_off(split);
+        let left = objects;
+
+        let left = Self::build_with_leaf_size(left, leaf_size);
+        let right = Self::build_with_leaf_size(right, leaf_size);
+        let bbox = AaBb::surrounding_box(&left.bounding_box(), &right.bounding_box());
+
+        Self::Node {
+            left: Arc::new(left),
+            right: Arc::new(right),
+            bbox,
+        }
+    }
 
-            Self::Node {
-                left: Arc::new(left),
-                right: Arc::new(right),
-                bbox,
+    /// 在已排序的实体间选取 SAH 开销最小的分割位置, 返回位置及其开销
+    ///
+    /// 包围盒退化 (表面积为零) 时从中间分割
+    fn sah_split(objects: &[Arc<dyn Bounded + Sync + Send>], surround: &AaBb) -> (usize, f32) {
+        let n = objects.len();
+        let area = surround.surface_area();
+        if area <= 0.0 {
+            return (n / 2, TRAVERSAL_COST + n as f32 * INTERSECTION_COST);
+        }
+
+        // 自右向左累积的包围盒表面积
+        let mut right_areas = vec![0.0; n];
+        let mut right_box = AaBb::new();
+        for i in (1..n).rev() {
+            right_box = AaBb::surrounding_box(&right_box, &objects[i].bounding_box());
+            right_areas[i] = right_box.surface_area();
+        }
+
+        // 自左向右扫描各分割位置
+        let mut best = (n / 2, f32::INFINITY);
+        let mut left_box = AaBb::new();
+        for i in 1..n {
+            left_box = AaBb::surrounding_box(&left_box, &objects[i - 1].bounding_box());
+            let cost = TRAVERSAL_COST
+                + INTERSECTION_COST
+                    * (left_box.surface_area() * i as f32 + right_areas[i] * (n - i) as f32)
+                    / area;
+            if cost < best.1 {
+                best = (i, cost);
             }
         }
+
+        best
     }
 
     /// 当前结点的包围盒
-    fn bounding_box(&self) -> AaBb {
+    pub fn bounding_box(&self) -> AaBb {
         match self {
             Self::Leaf { objects } => AaBb::all_surrounding_box(objects),
             Self::Node { bbox, .. } => bbox.clone(),
@@ -191,3 +369,96 @@ impl Hittable for BVHNode {
         }
     }
 }
+
+impl Accelerator for BVHNode {
+    /// 所有结点的包围盒及其所在深度
+    fn boxes(&self) -> Vec<(AaBb, usize)> {
+        let mut boxes = vec![];
+        let mut stack = vec![(self, 0)];
+
+        while let Some((node, depth)) = stack.pop() {
+            boxes.push((node.bounding_box(), depth));
+            if let Self::Node { left, right, .. } = node {
+                stack.push((left, depth + 1));
+                stack.push((right, depth + 1));
+            }
+        }
+
+        boxes
+    }
+
+    /// 分支限界: 优先访问较近的子树, 跳过包围盒比当前最近表面更远的子树
+    fn closest_point(&self, point: &Vector3<f32>) -> Option<ClosestPoint> {
+        let mut best: Option<ClosestPoint> = None;
+        let mut stack = vec![(self, 0.0)];
+
+        while let Some((node, distance_squared)) = stack.pop() {
+            if best
+                .as_ref()
+                .is_some_and(|b| distance_squared >= b.distance.powi(2))
+            {
+                continue;
+            }
+
+            match node {
+                Self::Leaf { objects } => closest_among(objects, point, &mut best),
+                Self::Node { left, right, .. } => {
+                    let left = (left.as_ref(), left.bounding_box().distance_squared(point));
+                    let right = (right.as_ref(), right.bounding_box().distance_squared(point));
+                    let (near, far) = if left.1 <= right.1 {
+                        (left, right)
+                    } else {
+                        (right, left)
+                    };
+                    stack.push(far);
+                    stack.push(near);
+                }
+            }
+        }
+
+        best
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::material::Material;
+    use crate::sphere::Sphere;
+
+    /// `count` 个完全重合的球体
+    fn cluster(count: usize) -> Vec<Arc<dyn Bounded + Sync + Send>> {
+        (0..count)
+            .map(|_| {
+                Arc::new(Sphere::from(Vector3::zeros(), 1.0, Material::CLAY))
+                    as Arc<dyn Bounded + Sync + Send>
+            })
+            .collect()
+    }
+
+    #[test]
+    fn overlapping_cluster_becomes_a_single_leaf() {
+        // 多于旧上限 7 个, 任何分割都无法分开它们, SAH 应当终止为一个叶子结点
+        match BVHNode::build(cluster(12)) {
+            BVHNode::Leaf { objects } => assert_eq!(objects.len(), 12),
+            BVHNode::Node { .. } => panic!("overlapping cluster was split"),
+        }
+    }
+
+    /// 各叶子结点中实体数的最大值
+    fn largest_leaf(node: &BVHNode) -> usize {
+        match node {
+            BVHNode::Leaf { objects } => objects.len(),
+            BVHNode::Node { left, right, .. } => largest_leaf(left).max(largest_leaf(right)),
+        }
+    }
+
+    #[test]
+    fn leaf_size_caps_overlapping_cluster() {
+        assert_eq!(
+            largest_leaf(&BVHNode::build_with_leaf_size(cluster(12), 4)),
+            4
+        );
+    }
+}
@@ -0,0 +1,217 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+
+use crate::bvh::{AaBb, BVHNode, Bounded};
+
+use nalgebra::Vector3;
+
+/// 缓存文件的标识与格式版本
+const MAGIC: &[u8; 8] = b"RTBVHC01";
+
+/// 缓存文件所在的目录, 未设置时不使用缓存
+static DIRECTORY: OnceLock<PathBuf> = OnceLock::new();
+
+/// 将构建好的 BVH 缓存到目录 `dir`, 之后同一组实体的 BVH 直接从缓存读取
+pub fn enable(dir: impl Into<PathBuf>) {
+    let _ = DIRECTORY.set(dir.into());
+}
+
+/// 构建 `objects` 的 BVH, 开启缓存时先查找缓存, 未命中时构建后写入缓存
+///
+/// BVH 的构建只取决于各实体的包围盒 (按顺序) 与叶子结点大小, 缓存以它们的哈希为键;
+/// 文件中只保存树的结构与叶子中实体的序号, 读取时按序号取回 `objects` 中的实体。
+/// 读写缓存失败时打印警告并照常构建
+pub fn build(objects: Vec<Arc<dyn Bounded + Sync + Send>>, leaf_size: usize) -> BVHNode {
+    let Some(dir) = DIRECTORY.get() else {
+        return BVHNode::build_with_leaf_size(objects, leaf_size);
+    };
+
+    let key = scene_key(&objects, leaf_size);
+    let path = dir.join(format!("{key:016x}.bvh"));
+    if path.exists() {
+        match load(&path, key, &objects) {
+            Ok(node) => return node,
+            Err(err) => eprintln!("\rIgnoring BVH cache: {err}"),
+        }
+    }
+
+    let node = BVHNode::build_with_leaf_size(objects.clone(), leaf_size);
+    if let Err(err) = save(&path, key, &node, &objects) {
+        eprintln!("\rFailed to write BVH cache: {err}");
+    }
+
+    node
+}
+
+/// 实体的包围盒 (按顺序) 与叶子结点大小的 64 位 FNV-1a 哈希
+pub fn scene_key(objects: &[Arc<dyn Bounded + Sync + Send>], leaf_size: usize) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut feed = |bytes: &[u8]| {
+        for &byte in bytes {
+            hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    };
+
+    feed(&(leaf_size as u64).to_le_bytes());
+    feed(&(objects.len() as u64).to_le_bytes());
+    for obj in objects {
+        let bbox = obj.bounding_box();
+        for c in bbox.min.iter().chain(bbox.max.iter()) {
+            feed(&c.to_le_bytes());
+        }
+    }
+
+    hash
+}
+
+/// 将 `node` 写入缓存文件: 文件头为标识, 键与实体数, 其后按先序排列各结点
+///
+/// 叶子结点为 `0`, 实体数与各实体在 `objects` 中的序号; 内部结点为 `1` 与包围盒, 其后为左右子树。
+/// 先写入临时文件再重命名, 并行构建的其他网格不会读到写了一半的文件
+pub fn save(
+    path: &Path,
+    key: u64,
+    node: &BVHNode,
+    objects: &[Arc<dyn Bounded + Sync + Send>],
+) -> io::Result<()> {
+    let index: HashMap<*const (), u32> = objects
+        .iter()
+        .enumerate()
+        .map(|(i, obj)| (Arc::as_ptr(obj) as *const (), i as u32))
+        .collect();
+
+    let mut data = MAGIC.to_vec();
+    data.extend(key.to_le_bytes());
+    data.extend((objects.len() as u32).to_le_bytes());
+
+    let mut stack = vec![node];
+    while let Some(node) = stack.pop() {
+        match node {
+            BVHNode::Leaf { objects } => {
+                data.push(0);
+                data.extend((objects.len() as u32).to_le_bytes());
+                for obj in objects {
+                    let i = index[&(Arc::as_ptr(obj) as *const ())];
+                    data.extend(i.to_le_bytes());
+                }
+            }
+            BVHNode::Node { left, right, bbox } => {
+                data.push(1);
+                for c in bbox.min.iter().chain(bbox.max.iter()) {
+                    data.extend(c.to_le_bytes());
+                }
+                stack.push(right);
+                stack.push(left);
+            }
+        }
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let temp_path = path.with_extension(format!("{}.tmp", std::process::id()));
+    fs::write(&temp_path, data)?;
+
+    fs::rename(temp_path, path)
+}
+
+/// 读取缓存文件, 重建 `objects` 的 BVH; 文件的键或实体数与 `objects` 不符时返回错误
+pub fn load(
+    path: &Path,
+    key: u64,
+    objects: &[Arc<dyn Bounded + Sync + Send>],
+) -> io::Result<BVHNode> {
+    let invalid = |msg: &str| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}: {msg}", path.display()),
+        )
+    };
+    let data = fs::read(path)?;
+
+    let mut reader = Reader::new(&data);
+    if reader.take(MAGIC.len()) != Some(MAGIC.as_slice()) {
+        return Err(invalid("not a BVH cache file"));
+    }
+    if reader.u64() != Some(key) || reader.u32() != Some(objects.len() as u32) {
+        return Err(invalid("cache does not match the scene"));
+    }
+
+    let node = reader
+        .node(objects)
+        .ok_or_else(|| invalid("truncated or corrupted cache"))?;
+    if !reader.is_empty() {
+        return Err(invalid("trailing data after the tree"));
+    }
+
+    Ok(node)
+}
+
+/// 按小端序读取缓存文件的游标, 数据不足时各方法返回 `None`
+pub(crate) struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub(crate) const fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    /// 是否已读完所有数据
+    pub(crate) const fn is_empty(&self) -> bool {
+        self.pos == self.data.len()
+    }
+
+    pub(crate) fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos.checked_add(n)?)?;
+        self.pos += n;
+        Some(bytes)
+    }
+
+    pub(crate) fn u32(&mut self) -> Option<u32> {
+        Some(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn u64(&mut self) -> Option<u64> {
+        Some(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn f32(&mut self) -> Option<f32> {
+        Some(f32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn vector(&mut self) -> Option<Vector3<f32>> {
+        Some(Vector3::new(self.f32()?, self.f32()?, self.f32()?))
+    }
+
+    /// 读取以当前位置为根的子树, 序号越界时返回 `None`
+    fn node(&mut self, objects: &[Arc<dyn Bounded + Sync + Send>]) -> Option<BVHNode> {
+        match self.take(1)?[0] {
+            0 => {
+                let count = self.u32()?;
+                let objects = (0..count)
+                    .map(|_| objects.get(self.u32()? as usize).cloned())
+                    .collect::<Option<_>>()?;
+                Some(BVHNode::Leaf { objects })
+            }
+            1 => {
+                let bbox = AaBb {
+                    min: self.vector()?,
+                    max: self.vector()?,
+                };
+                let left = self.node(objects)?;
+                let right = self.node(objects)?;
+                Some(BVHNode::Node {
+                    left: Arc::new(left),
+                    right: Arc::new(right),
+                    bbox,
+                })
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,85 @@
+use std::hint::black_box;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::bvh::{BVHNode, Bounded, MAX_OBJECTS};
+use crate::camera::Camera;
+use crate::hittable::Hittable;
+use crate::ray::Ray;
+use crate::rng::get_rng;
+
+use nalgebra::Vector3;
+use rand::Rng;
+
+/// 参与比较的叶子结点大小
+const LEAF_SIZES: [usize; 9] = [1, 2, 4, 8, 12, 16, 24, 32, 64];
+
+/// 测试光线中主光线的数量
+const PRIMARY_RAYS: usize = 20_000;
+
+/// 每个叶子结点大小重复计时的次数, 取最短的一次
+const REPEATS: usize = 3;
+
+/// 生成测试光线: 相机主光线, 以及从其交点出发的漫反射次级光线
+fn sample_rays(objects: &[Arc<dyn Bounded + Sync + Send>], camera: &Camera) -> Vec<Ray> {
+    let scene = BVHNode::build(objects.to_vec());
+    let mut rng = get_rng();
+    let mut rays = Vec::with_capacity(2 * PRIMARY_RAYS);
+
+    for _ in 0..PRIMARY_RAYS {
+        let ray = camera.camera_ray(rng.random(), rng.random());
+        if let Some(hit) = scene.hit(&ray, 0.001, f32::MAX) {
+            let direction = loop {
+                let v = Vector3::new(
+                    rng.random_range(-1.0..1.0),
+                    rng.random_range(-1.0..1.0),
+                    rng.random_range(-1.0..1.0),
+                );
+                if v.magnitude_squared() < 1.0 {
+                    break hit.normal + v;
+                }
+            };
+            rays.push(Ray::from(hit.position, direction));
+        }
+        rays.push(ray);
+    }
+
+    rays
+}
+
+/// 以测试光线遍历 BVH 的耗时
+fn time_traversal(scene: &BVHNode, rays: &[Ray]) -> Duration {
+    (0..REPEATS)
+        .map(|_| {
+            let timer = Instant::now();
+            for ray in rays {
+                black_box(scene.hit(ray, 0.001, f32::MAX));
+            }
+            timer.elapsed()
+        })
+        .min()
+        .unwrap_or_default()
+}
+
+/// 在实际场景上对各叶子结点大小计时, 返回遍历最快的一个
+pub fn tune_leaf_size(objects: &[Arc<dyn Bounded + Sync + Send>], camera: &Camera) -> usize {
+    let rays = sample_rays(objects, camera);
+    eprintln!("Tuning BVH leaf size with {} rays", rays.len());
+
+    let mut best = (MAX_OBJECTS, Duration::MAX);
+    for leaf_size in LEAF_SIZES {
+        let scene = BVHNode::build_with_leaf_size(objects.to_vec(), leaf_size);
+        let elapsed = time_traversal(&scene, &rays);
+        eprintln!(
+            "  leaf size {leaf_size:>2}: {:>8.1} ns/ray",
+            elapsed.as_nanos() as f64 / rays.len() as f64
+        );
+
+        if elapsed < best.1 {
+            best = (leaf_size, elapsed);
+        }
+    }
+    eprintln!("Picked leaf size {}", best.0);
+
+    best.0
+}
@@ -1,19 +1,19 @@
-use crate::ray::Ray;
+use std::sync::Arc;
 
-use nalgebra::Vector3;
-use rand::Rng;
-use std::f32;
+use crate::aperture::Aperture;
+use crate::filter::Filter;
+use crate::ray::{Ray, RayCone, RayKind};
+use crate::rng;
+use crate::sampler::Sampler;
+use crate::sampling::sample_unit_disk;
 
-/// 在单位圆内随机采样一点
-fn random_in_unit_disk() -> Vector3<f32> {
-    let mut rng = rand::rng();
-    let theta = rng.random_range(0.0..std::f32::consts::PI * 2.0);
-    let r = rng.random::<f32>().cbrt();
+use rand::Rng;
 
-    Vector3::new(r * theta.sin(), r * theta.cos(), 0.0)
-}
+use nalgebra::Vector3;
+use std::f32;
 
 /// 相机
+#[derive(Clone)]
 pub struct Camera {
     /// 位置
     origin: Vector3<f32>,
@@ -35,6 +35,15 @@ pub struct Camera {
 
     /// 镜头半径 (景深)
     lens_radius: f32,
+
+    /// 主光线的扩张角 (一个像素所张的角度), 为零时不追踪光线锥
+    pixel_spread: f32,
+
+    /// 光圈形状, 为 `None` 时为圆形
+    aperture: Option<Arc<Aperture>>,
+
+    /// 图像的宽度和高度 (像素)
+    resolution: (usize, usize),
 }
 
 impl Camera {
@@ -66,6 +75,9 @@ impl Camera {
             u,
             v,
             lens_radius: aperture / 2.0,
+            pixel_spread: 0.0,
+            aperture: None,
+            resolution: (1, 1),
         }
     }
 
@@ -96,13 +108,125 @@ impl Camera {
             u,
             v,
             lens_radius: 0.0,
+            pixel_spread: 0.0,
+            aperture: None,
+            resolution: (1, 1),
+        }
+    }
+
+    /// 设置图像的分辨率, 并按图像高度 `ny` 设置主光线锥的扩张角, 使纹理按像素的足迹过滤
+    pub fn with_resolution(mut self, nx: usize, ny: usize) -> Self {
+        self.resolution = (nx, ny);
+        let center = self.lower_left_corner + 0.5 * (self.horizontal + self.vertical);
+        self.pixel_spread =
+            self.vertical.magnitude() / (center - self.origin).magnitude() / ny as f32;
+        self
+    }
+
+    /// 按光圈图像的亮度采样镜头位置, 焦外光斑呈现光圈的形状
+    pub fn with_aperture(mut self, aperture: Arc<Aperture>) -> Self {
+        self.aperture = Some(aperture);
+        self
+    }
+
+    /// 沿相机的右向量平移 `offset` 而像平面不动的相机, 立体像对的视差在像平面处为零
+    pub fn shifted(&self, offset: f32) -> Self {
+        Self {
+            origin: self.origin + offset * self.u,
+            ..self.clone()
+        }
+    }
+
+    /// 主光线的光线锥
+    const fn cone(&self) -> RayCone {
+        RayCone {
+            width: 0.0,
+            spread: self.pixel_spread,
         }
     }
 
+    /// 将世界坐标中的点投影到像平面, 返回像平面坐标 (s, t), 点在相机后方时返回 `None`
+    pub fn project(&self, point: &Vector3<f32>) -> Option<(f32, f32)> {
+        let normal = self.horizontal.cross(&self.vertical);
+        let direction = point - self.origin;
+        let denom = direction.dot(&normal);
+        if denom.abs() < f32::EPSILON {
+            return None;
+        }
+
+        let k = (self.lower_left_corner - self.origin).dot(&normal) / denom;
+        if k <= 0.0 {
+            return None;
+        }
+
+        let offset = self.origin + k * direction - self.lower_left_corner;
+        Some((
+            offset.dot(&self.horizontal) / self.horizontal.norm_squared(),
+            offset.dot(&self.vertical) / self.vertical.norm_squared(),
+        ))
+    }
+
+    /// 点沿视线方向到相机的距离 (相机坐标系中的 z 深度)
+    pub fn view_depth(&self, point: &Vector3<f32>) -> f32 {
+        let forward = self.vertical.cross(&self.horizontal).normalize();
+
+        (point - self.origin).dot(&forward)
+    }
+
+    /// 对焦距离: 像平面 (合焦平面) 沿视线方向到相机的距离
+    pub fn focus_distance(&self) -> f32 {
+        self.view_depth(&(self.lower_left_corner + 0.5 * (self.horizontal + self.vertical)))
+    }
+
+    /// 从镜头中心发出光线 (不带景深)
+    pub fn center_ray(&self, s: f32, t: f32) -> Ray {
+        Ray::from(
+            self.origin,
+            self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin,
+        )
+        .with_kind(RayKind::Camera)
+        .with_cone(self.cone())
+    }
+
     /// 从相机发出光线
     pub fn camera_ray(&self, s: f32, t: f32) -> Ray {
+        let mut rng = rng::local();
+        self.lens_ray(s, t, (rng.random(), rng.random()))
+    }
+
+    /// 像素 `pixel` (以图像左下角为原点) 的一条主光线, 返回光线与采样的滤波权重
+    ///
+    /// `sampler` 须已对该像素调用 `start_sample`: 第一个二维采样按重建滤波器 `filter` 决定像素内的偏移,
+    /// 第二个二维采样决定镜头上的位置, 同一采样器与序号总是得到相同的光线, 与渲染的顺序和方式无关
+    pub fn generate_ray(
+        &self,
+        pixel: (usize, usize),
+        sampler: &mut dyn Sampler,
+        filter: &Filter,
+        time: Option<f32>,
+    ) -> (Ray, f32) {
+        let ((dx, dy), weight) = filter.sample(sampler.next_2d());
+        let (nx, ny) = self.resolution;
+        let s = (pixel.0 as f32 + 0.5 + dx) / nx as f32;
+        let t = (pixel.1 as f32 + 0.5 + dy) / ny as f32;
+
+        (
+            self.lens_ray(s, t, sampler.next_2d()).with_time(time),
+            weight,
+        )
+    }
+
+    /// 从镜头上由采样值 `lens` 决定的位置, 穿过像平面坐标 (s, t) 的光线
+    fn lens_ray(&self, s: f32, t: f32, lens: (f32, f32)) -> Ray {
         // 在镜头平面上采样
-        let rd = self.lens_radius * random_in_unit_disk();
+        let rd = self.lens_radius
+            * match &self.aperture {
+                Some(aperture) => {
+                    let (x, y) = aperture.sample(lens);
+                    Vector3::new(x, y, 0.0)
+                }
+                None => sample_unit_disk(lens),
+            };
         let offset = self.u * rd.x + self.v * rd.y;
 
         // 从镜头平面采样点到像平面采样点的光线
@@ -110,5 +234,7 @@ impl Camera {
             self.origin + offset,
             self.lower_left_corner + s * self.horizontal + t * self.vertical - self.origin - offset,
         )
+        .with_kind(RayKind::Camera)
+        .with_cone(self.cone())
     }
 }
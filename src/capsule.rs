@@ -0,0 +1,188 @@
+use std::f32::consts::{PI, TAU};
+
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampling::Onb;
+
+use nalgebra::{Point3, Similarity3, Vector3};
+
+/// 胶囊体: 球心沿线段 `start`–`end` 扫过的球, 即两端为半球的圆柱
+///
+/// 表面上每一点的法线都由线段上的最近点指向该点, 适合角色代理与圆头的杆件
+#[derive(Clone)]
+pub struct Capsule {
+    /// 线段起点
+    start: Vector3<f32>,
+
+    /// 以线段方向为 `w` 的局部坐标系
+    frame: Onb,
+
+    /// 线段长度
+    length: f32,
+
+    /// 半径
+    radius: f32,
+
+    /// 材质
+    material: Material,
+}
+
+impl Capsule {
+    /// 线段 `start`–`end` 扫过半径为 `radius` 的球, 两端重合时为球体
+    pub fn new(start: Vector3<f32>, end: Vector3<f32>, radius: f32, material: Material) -> Self {
+        let axis = end - start;
+
+        Self {
+            start,
+            frame: Onb::build_from_w(&axis.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::y)),
+            length: axis.magnitude(),
+            radius,
+            material,
+        }
+    }
+
+    /// 经相似变换后的胶囊体
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        let rotation = transform.isometry.rotation;
+
+        Self {
+            start: transform.transform_point(&Point3::from(self.start)).coords,
+            frame: Onb {
+                u: rotation * self.frame.u,
+                v: rotation * self.frame.v,
+                w: rotation * self.frame.w,
+            },
+            length: self.length * transform.scaling(),
+            radius: self.radius * transform.scaling(),
+            ..self.clone()
+        }
+    }
+
+    /// 线段终点
+    fn end(&self) -> Vector3<f32> {
+        self.start + self.length * self.frame.w
+    }
+
+    /// 线段上离 `point` 最近的点
+    fn nearest_on_axis(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        let s = (point - self.start)
+            .dot(&self.frame.w)
+            .clamp(0.0, self.length);
+
+        self.start + s * self.frame.w
+    }
+
+    /// 世界向量在局部坐标系中的坐标
+    fn to_local(&self, v: &Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(
+            v.dot(&self.frame.u),
+            v.dot(&self.frame.v),
+            v.dot(&self.frame.w),
+        )
+    }
+}
+
+/// 二次方程 `a t² + 2 b t + c = 0` 的两个实根 (由小到大), 无实根时为 `NaN`
+fn roots(a: f32, b: f32, c: f32) -> [f32; 2] {
+    let disc = b * b - a * c;
+    if a.abs() < 1e-12 || disc < 0.0 {
+        return [f32::NAN; 2];
+    }
+
+    let sqrt_disc = disc.sqrt();
+    let (t0, t1) = ((-b - sqrt_disc) / a, (-b + sqrt_disc) / a);
+    [t0.min(t1), t0.max(t1)]
+}
+
+impl Hittable for Capsule {
+    /// 圆柱侧面与两端的球面分别求交, 各自只保留在胶囊体表面上的部分
+    ///
+    /// 纹理坐标: `u` 为绕轴的角度比例, `v` 为自起点一端的极点沿经线量起的弧长比例
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let o = self.to_local(&(ray.origin() - self.start));
+        let d = self.to_local(&ray.direction());
+        let r2 = self.radius * self.radius;
+
+        // 侧面: x² + y² = r², 0 ≤ z ≤ l
+        let side = roots(
+            d.x * d.x + d.y * d.y,
+            o.x * d.x + o.y * d.y,
+            o.x * o.x + o.y * o.y - r2,
+        )
+        .map(|t| (t, (0.0..=self.length).contains(&(o.z + t * d.z))));
+
+        // 两端的半球: 与球心的距离为 r, 且在线段以外的一侧
+        let cap = |center: f32, outside: fn(f32, f32) -> bool| {
+            let oc = Vector3::new(o.x, o.y, o.z - center);
+            roots(d.dot(&d), oc.dot(&d), oc.dot(&oc) - r2)
+                .map(|t| (t, outside(o.z + t * d.z, center)))
+        };
+        let start = cap(0.0, |z, center| z < center);
+        let end = cap(self.length, |z, center| z > center);
+
+        let t = side
+            .into_iter()
+            .chain(start)
+            .chain(end)
+            .filter(|&(t, on_surface)| on_surface && t > t_min && t < t_max)
+            .map(|(t, _)| t)
+            .min_by(f32::total_cmp)?;
+
+        let position = ray.point_at_t(t);
+        let normal = (position - self.nearest_on_axis(&position)) / self.radius;
+
+        // 经线由起点一端的四分之一圆弧, 侧面的直线与终点一端的四分之一圆弧组成
+        let local = self.to_local(&(position - self.start));
+        let quarter = 0.5 * PI * self.radius;
+        let arc = if local.z < 0.0 {
+            self.radius * (local.xy().norm() / self.radius).min(1.0).asin()
+        } else if local.z > self.length {
+            quarter
+                + self.length
+                + self.radius * ((local.z - self.length) / self.radius).min(1.0).asin()
+        } else {
+            quarter + local.z
+        };
+        let meridian = 2.0 * quarter + self.length;
+        let phi = local.y.atan2(local.x).rem_euclid(TAU);
+        let width = ray.cone().width_at(t * ray.direction().magnitude());
+
+        Some(HitRecord {
+            distance: t,
+            position,
+            normal,
+            uv: (phi / TAU, arc / meridian),
+            footprint: width / meridian,
+            material: self.material,
+            object: 0,
+        })
+    }
+}
+
+impl Bounded for Capsule {
+    /// 两端球体包围盒的并
+    fn bounding_box(&self) -> AaBb {
+        let extent = Vector3::repeat(self.radius);
+        let end = self.end();
+
+        AaBb {
+            min: self.start.inf(&end) - extent,
+            max: self.start.sup(&end) + extent,
+        }
+    }
+
+    /// 与球体相同, 球心取线段上的最近点, 位于内部时距离为负
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let center = self.nearest_on_axis(point);
+        let offset = point - center;
+        let direction = offset.try_normalize(f32::EPSILON).unwrap_or(self.frame.u);
+
+        ClosestPoint {
+            object: 0,
+            position: center + self.radius * direction,
+            distance: offset.magnitude() - self.radius,
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::render::Framebuffer;
+
+/// 文件头
+const MAGIC: &[u8; 8] = b"RTCKPT3\n";
+
+/// 标识之后的文件头字段数 (各 8 字节)
+const HEADER_FIELDS: usize = 6;
+
+/// 渲染断点: 中断时已完成的行, 用于之后继续渲染
+pub struct Checkpoint {
+    pub nx: usize,
+    pub ny: usize,
+    pub ns: usize,
+
+    /// 场景种子, 继续渲染时须构建相同的场景
+    pub seed: u64,
+
+    /// 渲染设置的哈希 (`RenderSettings::fingerprint`), 继续渲染时须相同
+    pub settings: u64,
+
+    /// 已完成的部分图像
+    pub framebuffer: Framebuffer,
+}
+
+impl Checkpoint {
+    /// 断点文件的路径
+    pub fn path(file_name: &str) -> String {
+        format!("{file_name}.checkpoint")
+    }
+
+    /// 写入断点文件 (先写临时文件再重命名)
+    pub fn save(&self, file_name: &str) -> io::Result<()> {
+        let path = Self::path(file_name);
+        let temp_path = format!("{path}.tmp");
+
+        let mut file = BufWriter::new(File::create(&temp_path)?);
+        file.write_all(MAGIC)?;
+        let transparent = self.framebuffer.alpha.is_some() as u64;
+        for value in [
+            self.nx as u64,
+            self.ny as u64,
+            self.ns as u64,
+            self.seed,
+            self.settings,
+            transparent,
+        ] {
+            file.write_all(&value.to_le_bytes())?;
+        }
+        let rows_done = &self.framebuffer.rows_done;
+        file.write_all(&rows_done.iter().map(|&d| d as u8).collect::<Vec<_>>())?;
+        file.write_all(&self.framebuffer.pixels)?;
+        if let Some(alpha) = &self.framebuffer.alpha {
+            file.write_all(alpha)?;
+        }
+        file.flush()?;
+        drop(file);
+
+        fs::rename(temp_path, path)
+    }
+
+    /// 读取断点文件, 文件头中的分辨率须与文件大小相符
+    pub fn load(file_name: &str) -> Result<Self> {
+        let path = Self::path(file_name);
+        let invalid = |msg: &str| Error::invalid_data(Path::new(&path), msg);
+        let file = File::open(&path)?;
+        let len = file.metadata()?.len();
+        let mut file = BufReader::new(file);
+
+        let mut magic = [0; 8];
+        file.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a checkpoint file"));
+        }
+
+        let mut header = [0u64; HEADER_FIELDS];
+        for value in &mut header {
+            let mut bytes = [0; 8];
+            file.read_exact(&mut bytes)?;
+            *value = u64::from_le_bytes(bytes);
+        }
+        let [nx, ny, ns, seed, settings, transparent] = header;
+
+        // 分配缓冲之前核对大小: 每行一个完成标记, 每个像素 3 个颜色分量与可选的不透明度
+        let channels = if transparent != 0 { 4 } else { 3 };
+        let expected = nx
+            .checked_mul(ny)
+            .and_then(|pixels| pixels.checked_mul(channels))
+            .and_then(|bytes| bytes.checked_add(ny))
+            .and_then(|bytes| bytes.checked_add((MAGIC.len() + 8 * HEADER_FIELDS) as u64));
+        if nx == 0 || ny == 0 || expected != Some(len) {
+            return Err(invalid("checkpoint size does not match its resolution"));
+        }
+        let (nx, ny, ns) = (nx as usize, ny as usize, ns as usize);
+
+        let mut rows_done = vec![0; ny];
+        file.read_exact(&mut rows_done)?;
+        let mut pixels = vec![0; 3 * nx * ny];
+        file.read_exact(&mut pixels)?;
+        let alpha = if transparent != 0 {
+            let mut alpha = vec![0; nx * ny];
+            file.read_exact(&mut alpha)?;
+            Some(alpha)
+        } else {
+            None
+        };
+
+        Ok(Self {
+            nx,
+            ny,
+            ns,
+            seed,
+            settings,
+            framebuffer: Framebuffer {
+                pixels,
+                alpha,
+                deep: None,
+                rows_done: rows_done.into_iter().map(|d| d != 0).collect(),
+                radiance: None,
+                times: None,
+            },
+        })
+    }
+
+    /// 删除断点文件 (不存在时忽略)
+    pub fn remove(file_name: &str) -> io::Result<()> {
+        match fs::remove_file(Self::path(file_name)) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
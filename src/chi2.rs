@@ -0,0 +1,512 @@
+use crate::hittable::HitRecord;
+use crate::material::{
+    Material, Parameter, Scatter, fiber_tangent, flake_normal, hair_attenuation,
+    hair_logistic_scale, hair_variance, reflect, refract, schlick,
+};
+use crate::ray::Ray;
+use crate::rng;
+
+use nalgebra::Vector3;
+use rayon::prelude::*;
+use std::f32::consts::PI;
+
+/// 每种材质采样的方向数
+const SAMPLES: usize = 1_000_000;
+
+/// 样本分为若干组, 每组以各自的种子重置 RNG, 结果与线程调度无关
+const CHUNKS: usize = 64;
+
+/// cos θ 方向的分箱数 (覆盖整个球面)
+const THETA_BINS: usize = 20;
+
+/// φ 方向的分箱数
+const PHI_BINS: usize = 40;
+
+/// 计算期望值时每个分箱在每个维度上的细分数
+const SUBDIVISIONS: usize = 32;
+
+/// 合并分箱的最小期望计数
+const MIN_EXPECTED: f64 = 5.0;
+
+/// 显著性水平
+const SIGNIFICANCE: f64 = 0.01;
+
+/// 局部坐标系: 法线为 z 轴
+const NORMAL: Vector3<f32> = Vector3::new(0.0, 0.0, 1.0);
+
+/// `direction ∝ center + radius * p` (p 在单位球内均匀分布) 时方向的概率密度
+fn ball_offset_pdf(center: &Vector3<f32>, radius: f32, direction: &Vector3<f32>) -> f32 {
+    let b = direction.dot(center);
+    let disc = b * b - center.norm_squared() + radius * radius;
+    if disc < 0.0 {
+        return 0.0;
+    }
+
+    let t2 = b + disc.sqrt();
+    if t2 <= 0.0 {
+        return 0.0;
+    }
+    let t1 = (b - disc.sqrt()).max(0.0);
+
+    (t2.powi(3) - t1.powi(3)) / (4.0 * PI * radius.powi(3))
+}
+
+/// 标准 GGX 分布经 `wo` 反射后的方向概率密度, 反射到表面以下的方向为零
+fn ggx_reflection_pdf(wo: &Vector3<f32>, alpha: f32, wi: &Vector3<f32>) -> f32 {
+    let Some(h) = (wo + wi).try_normalize(f32::EPSILON) else {
+        return 0.0;
+    };
+    if wi.z <= 0.0 {
+        return 0.0;
+    }
+
+    // 采样的半程向量总在法线一侧, 与其方向相反的 h 给出同一个反射方向
+    let cos = h.z.abs();
+    let a2 = alpha * alpha;
+    let d = a2 / (PI * (cos * cos * (a2 - 1.0) + 1.0).powi(2));
+
+    d * cos / (4.0 * wo.dot(&h).abs())
+}
+
+/// 第一类零阶修正贝塞尔函数 I₀ (级数展开)
+fn bessel_i0(x: f64) -> f64 {
+    let (mut sum, mut term, mut k) = (1.0, 1.0, 1.0);
+    while term > sum * 1e-12 {
+        term *= (x / 2.0).powi(2) / (k * k);
+        sum += term;
+        k += 1.0;
+    }
+
+    sum
+}
+
+/// 毛发的纵向散射函数 Mp, 以 sin 和 cos 表示入射角与 (经毛鳞片偏移的) 出射角
+fn hair_mp(sin_i: f32, cos_i: f32, sin_o: f32, cos_o: f32, variance: f32) -> f32 {
+    let v = f64::from(variance);
+    let a = f64::from(cos_i * cos_o) / v;
+    let b = f64::from(sin_i * sin_o) / v;
+
+    ((-b).exp() * bessel_i0(a) / (2.0 * v * (1.0 / v).sinh())) as f32
+}
+
+/// 截断到 [-π, π] 的 logistic 分布的概率密度
+fn trimmed_logistic(x: f32, s: f32) -> f32 {
+    let cdf = |x: f32| 1.0 / (1.0 + (-x / s).exp());
+    let e = (-x.abs() / s).exp();
+
+    e / (s * (1.0 + e).powi(2)) / (cdf(PI) - cdf(-PI))
+}
+
+/// 毛发散射方向的概率密度: 各波瓣按衰减选取, 纵向为 Mp, 方位角为以几何偏转为中心的 logistic 分布
+fn hair_pdf(material: &Material, incoming: &Vector3<f32>) -> impl Fn(&Vector3<f32>) -> f32 + Sync {
+    let Material::Hair {
+        sigma_a,
+        beta_m,
+        beta_n,
+        alpha,
+        eta,
+    } = *material
+    else {
+        unreachable!("not a hair material");
+    };
+
+    // 与 `Material::scatter` 相同的纤维坐标系与各波瓣的选取概率
+    let t = fiber_tangent(&NORMAL);
+    let b = t.cross(&NORMAL);
+    let wo = -incoming.normalize();
+    let sin_theta_o = wo.dot(&t).clamp(-1.0, 1.0);
+    let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+    let phi_o = wo.dot(&b).atan2(wo.dot(&NORMAL));
+    let h = phi_o.sin().clamp(-1.0, 1.0);
+    let gamma_o = h.asin();
+    let sin_theta_t = sin_theta_o / eta;
+    let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).max(1e-4).sqrt();
+    let etap = (eta * eta - sin_theta_o * sin_theta_o).max(0.0).sqrt() / cos_theta_o.max(1e-4);
+    let sin_gamma_t = (h / etap).clamp(-1.0, 1.0);
+    let cos_gamma_t = (1.0 - sin_gamma_t * sin_gamma_t).sqrt();
+    let gamma_t = sin_gamma_t.asin();
+    let transmittance = sigma_a.map(|s| (-s * 2.0 * cos_gamma_t / cos_theta_t).exp());
+    let weights = hair_attenuation(cos_theta_o, h, eta, &transmittance).map(|a| a.mean());
+    let total: f32 = weights.iter().sum();
+
+    let variance = hair_variance(beta_m);
+    let s = hair_logistic_scale(beta_n);
+    let shifts = [-2.0 * alpha, alpha, 4.0 * alpha, 0.0];
+
+    move |d: &Vector3<f32>| {
+        let sin_i = d.dot(&t).clamp(-1.0, 1.0);
+        let cos_i = (1.0 - sin_i * sin_i).max(0.0).sqrt();
+        let phi_i = d.dot(&b).atan2(d.dot(&NORMAL));
+
+        (0..4)
+            .map(|p| {
+                let theta_op = sin_theta_o.asin() + shifts[p];
+                let mp = hair_mp(sin_i, cos_i, theta_op.sin(), theta_op.cos(), variance);
+                let np = if p < 3 {
+                    let p = p as f32;
+                    let center = 2.0 * p * gamma_t - 2.0 * gamma_o + p * PI;
+                    trimmed_logistic((phi_i - phi_o - center + PI).rem_euclid(2.0 * PI) - PI, s)
+                } else {
+                    1.0 / (2.0 * PI)
+                };
+                weights[p] / total * mp * np
+            })
+            .sum()
+    }
+}
+
+/// 分箱对应的方向, `(i, j)` 为分箱序号, `(a, b)` 为分箱内 [0, 1) 的偏移
+fn bin_direction(i: usize, j: usize, a: f32, b: f32) -> Vector3<f32> {
+    let cos_theta = -1.0 + 2.0 * (i as f32 + a) / THETA_BINS as f32;
+    let phi = 2.0 * PI * (j as f32 + b) / PHI_BINS as f32;
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+
+    Vector3::new(sin_theta * phi.cos(), sin_theta * phi.sin(), cos_theta)
+}
+
+/// 方向所在的分箱
+fn bin_index(direction: &Vector3<f32>) -> usize {
+    let d = direction.normalize();
+    let i = (((d.z + 1.0) / 2.0 * THETA_BINS as f32) as usize).min(THETA_BINS - 1);
+    let phi = d.y.atan2(d.x).rem_euclid(2.0 * PI);
+    let j = ((phi / (2.0 * PI) * PHI_BINS as f32) as usize).min(PHI_BINS - 1);
+
+    i * PHI_BINS + j
+}
+
+/// 对概率密度在每个分箱上积分, 返回各分箱的概率
+fn integrate(pdf: &(impl Fn(&Vector3<f32>) -> f32 + Sync)) -> Vec<f64> {
+    let solid_angle = 4.0 * PI / (THETA_BINS * PHI_BINS * SUBDIVISIONS * SUBDIVISIONS) as f32;
+
+    (0..THETA_BINS * PHI_BINS)
+        .into_par_iter()
+        .map(|bin| {
+            let (i, j) = (bin / PHI_BINS, bin % PHI_BINS);
+            let mut sum = 0.0;
+            for a in 0..SUBDIVISIONS {
+                for b in 0..SUBDIVISIONS {
+                    let a = (a as f32 + 0.5) / SUBDIVISIONS as f32;
+                    let b = (b as f32 + 0.5) / SUBDIVISIONS as f32;
+                    sum += f64::from(pdf(&bin_direction(i, j, a, b)) * solid_angle);
+                }
+            }
+            sum
+        })
+        .collect()
+}
+
+/// 互补误差函数 (Abramowitz & Stegun 7.1.26)
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1.0 / (1.0 + 0.327_591_1 * z);
+    let poly = t
+        * (0.254_829_592
+            + t * (-0.284_496_736
+                + t * (1.421_413_741 + t * (-1.453_152_027 + t * 1.061_405_429))));
+    let r = poly * (-z * z).exp();
+
+    if x >= 0.0 { r } else { 2.0 - r }
+}
+
+/// 卡方分布的上尾概率 (Wilson–Hilferty 近似)
+fn chi2_p_value(chi2: f64, dof: usize) -> f64 {
+    let k = dof as f64;
+    let z = ((chi2 / k).cbrt() - (1.0 - 2.0 / (9.0 * k))) / (2.0 / (9.0 * k)).sqrt();
+
+    0.5 * erfc(z / std::f64::consts::SQRT_2)
+}
+
+/// 在各分箱的概率上加入离散方向 (理想镜面反射或折射) 的概率
+fn with_deltas(mut probabilities: Vec<f64>, deltas: &[(Vector3<f32>, f32)]) -> Vec<f64> {
+    for (direction, probability) in deltas {
+        probabilities[bin_index(direction)] += f64::from(*probability);
+    }
+
+    probabilities
+}
+
+/// 对一种材质做卡方检验, `expected` 为各分箱的期望概率; 返回卡方值, 自由度与 p 值
+fn test_material(
+    material: &Material,
+    incoming: &Vector3<f32>,
+    mut expected: Vec<f64>,
+    samples: usize,
+    seed: u64,
+) -> (f64, usize, f64) {
+    let hit = HitRecord {
+        distance: 1.0,
+        position: Vector3::zeros(),
+        normal: NORMAL,
+        uv: (0.0, 0.0),
+        footprint: 0.0,
+        material: *material,
+        object: 0,
+    };
+    let ray = Ray::from(-incoming, *incoming);
+
+    // 观测: 最后一个分箱记录被吸收 (未散射) 的样本
+    let observed = (0..CHUNKS)
+        .into_par_iter()
+        .map(|chunk| {
+            rng::reseed(seed.wrapping_add(chunk as u64));
+
+            let mut counts = vec![0usize; THETA_BINS * PHI_BINS + 1];
+            for _ in samples * chunk / CHUNKS..samples * (chunk + 1) / CHUNKS {
+                let index = match material.scatter(&ray, &hit) {
+                    Some((scattered, _, _)) => bin_index(&scattered.direction()),
+                    None => THETA_BINS * PHI_BINS,
+                };
+                counts[index] += 1;
+            }
+            counts
+        })
+        .reduce(
+            || vec![0usize; THETA_BINS * PHI_BINS + 1],
+            |a, b| a.iter().zip(&b).map(|(x, y)| x + y).collect(),
+        );
+
+    // 期望: 剩余的概率质量即为吸收
+    let scattered: f64 = expected.iter().sum();
+    expected.push((1.0 - scattered).max(0.0));
+
+    // 合并期望过小的分箱后计算卡方值
+    let (mut chi2, mut cells) = (0.0, 0);
+    let (mut pooled_observed, mut pooled_expected) = (0.0, 0.0);
+    for (obs, exp) in observed.iter().zip(&expected) {
+        let (obs, exp) = (*obs as f64, exp * samples as f64);
+        if exp < MIN_EXPECTED {
+            pooled_observed += obs;
+            pooled_expected += exp;
+        } else {
+            chi2 += (obs - exp).powi(2) / exp;
+            cells += 1;
+        }
+    }
+    if pooled_expected > 0.0 {
+        chi2 += (pooled_observed - pooled_expected).powi(2) / pooled_expected;
+        cells += 1;
+    } else if pooled_observed > 0.0 {
+        chi2 = f64::INFINITY;
+    }
+
+    let dof = cells.max(2) - 1;
+    (chi2, dof, chi2_p_value(chi2, dof))
+}
+
+/// 各材质散射方向的卡方检验: 将采样的方向分箱统计, 与解析的概率密度比较,
+/// 返回是否全部通过
+pub fn run() -> bool {
+    check(SAMPLES, rand::random())
+}
+
+/// 以每种材质 `samples` 个样本, 种子 `seed` 做卡方检验并打印结果, 返回是否全部通过
+fn check(samples: usize, seed: u64) -> bool {
+    let white = Vector3::new(1.0, 1.0, 1.0);
+
+    // 入射方向与法线成 45°
+    let incoming = Vector3::new(1.0, 0.0, -1.0).normalize();
+    let mirrored = incoming - 2.0 * incoming.dot(&NORMAL) * NORMAL;
+    let above = |d: &Vector3<f32>| if d.z > 0.0 { 1.0 } else { 0.0 };
+
+    // 漫反射: 法线加上单位球内的随机点
+    let lambertian = |d: &Vector3<f32>| ball_offset_pdf(&NORMAL, 1.0, d);
+
+    // 金属: 镜面反射方向加上模糊, 低于表面的被吸收
+    let metal = |fuzz: f32| move |d: &Vector3<f32>| above(d) * ball_offset_pdf(&mirrored, fuzz, d);
+
+    // 绒面: 按掠射角权重选择逆反射, 低于表面时退回漫反射
+    let (sheen_roughness, sheen_prob) = (0.3, (1.0 - (-incoming.z)).powi(2));
+    let retro = move |d: &Vector3<f32>| above(d) * ball_offset_pdf(&-incoming, sheen_roughness, d);
+    let retro_mass: f64 = integrate(&retro).iter().sum();
+    let sheen = move |d: &Vector3<f32>| {
+        sheen_prob * retro(d) + (1.0 - sheen_prob * retro_mass as f32) * lambertian(d)
+    };
+
+    // 玻璃: 按 Schlick 反射系数在镜面反射与折射之间选择
+    let ref_idx = 1.5;
+    let cosine = -incoming.dot(&NORMAL) / incoming.magnitude();
+    let reflect_prob = schlick(cosine, ref_idx);
+    let refracted =
+        refract(&incoming, &NORMAL, 1.0 / ref_idx).expect("no total internal reflection");
+    let dielectric = vec![0.0; THETA_BINS * PHI_BINS];
+    let dielectric = with_deltas(
+        dielectric,
+        &[
+            (reflect(&incoming, &NORMAL), reflect_prob),
+            (refracted, 1.0 - reflect_prob),
+        ],
+    );
+
+    // 车漆: 清漆层的镜面反射, 金属片的镜面反射 (低于表面时退回底色), 其余为底色的漫反射
+    let (coat_ior, flake_density, flake_size, flake_roughness) = (1.5, 0.5, 0.1, 0.3);
+    let unit_direction = incoming.normalize();
+    let coat = schlick((-unit_direction.dot(&NORMAL)).clamp(0.0, 1.0), coat_ior);
+    let flake = reflect(
+        &unit_direction,
+        &flake_normal(&Vector3::zeros(), &NORMAL, flake_size, flake_roughness),
+    );
+    let flake_prob = if flake.dot(&NORMAL) > 0.0 {
+        (1.0 - coat) * flake_density
+    } else {
+        0.0
+    };
+    let base_prob = 1.0 - coat - flake_prob;
+    let car_paint = with_deltas(
+        integrate(&|d: &Vector3<f32>| base_prob * lambertian(d)),
+        &[
+            (reflect(&unit_direction, &NORMAL), coat),
+            (flake, flake_prob),
+        ],
+    );
+
+    // 原则化材质: 按镜面反射所占的比例在 GGX 反射与漫反射之间选择, 反射到表面以下的被吸收
+    let roughness: f32 = 0.5;
+    let principled = |metallic: f32| {
+        let wo = -incoming.normalize();
+        let f0 = 0.08 * 0.5 * (1.0 - metallic) + metallic;
+        let fresnel = f0 + (1.0 - f0) * (1.0 - wo.z).powi(5);
+        let spec_prob = fresnel + (1.0 - fresnel) * metallic;
+        let alpha = (roughness * roughness).max(1e-3);
+        move |d: &Vector3<f32>| {
+            spec_prob * ggx_reflection_pdf(&wo, alpha, d) + (1.0 - spec_prob) * lambertian(d)
+        }
+    };
+    let principled_material = |metallic: f32| {
+        Material::principled(
+            white,
+            Parameter::Constant(roughness),
+            Parameter::Constant(metallic),
+            Parameter::Constant(0.5),
+        )
+    };
+
+    // 毛发: 入射方向与纤维垂直, 各波瓣的纵向与方位角分布均有解析形式
+    let hair = Material::hair_from_melanin(0.5, 0.1, 0.3, 0.3);
+
+    let metal_low = metal(0.3);
+    let metal_high = metal(1.0);
+    let results = [
+        (
+            "lambertian",
+            test_material(
+                &Material::lambertian(white),
+                &incoming,
+                integrate(&lambertian),
+                samples,
+                seed,
+            ),
+        ),
+        (
+            "metal (fuzz 0.3)",
+            test_material(
+                &Material::metal(white, 0.3),
+                &incoming,
+                integrate(&metal_low),
+                samples,
+                seed,
+            ),
+        ),
+        (
+            "metal (fuzz 1.0)",
+            test_material(
+                &Material::metal(white, 1.0),
+                &incoming,
+                integrate(&metal_high),
+                samples,
+                seed,
+            ),
+        ),
+        (
+            "sheen",
+            test_material(
+                &Material::sheen(white, white, sheen_roughness),
+                &incoming,
+                integrate(&sheen),
+                samples,
+                seed,
+            ),
+        ),
+        (
+            "dielectric",
+            test_material(
+                &Material::dielectric(ref_idx),
+                &incoming,
+                dielectric,
+                samples,
+                seed,
+            ),
+        ),
+        (
+            "car paint",
+            test_material(
+                &Material::car_paint(
+                    white,
+                    white,
+                    flake_density,
+                    flake_size,
+                    flake_roughness,
+                    coat_ior,
+                ),
+                &incoming,
+                car_paint,
+                samples,
+                seed,
+            ),
+        ),
+        (
+            "principled (dielectric)",
+            test_material(
+                &principled_material(0.0),
+                &incoming,
+                integrate(&principled(0.0)),
+                samples,
+                seed,
+            ),
+        ),
+        (
+            "principled (metal)",
+            test_material(
+                &principled_material(1.0),
+                &incoming,
+                integrate(&principled(1.0)),
+                samples,
+                seed,
+            ),
+        ),
+        (
+            "hair",
+            test_material(
+                &hair,
+                &incoming,
+                integrate(&hair_pdf(&hair, &incoming)),
+                samples,
+                seed,
+            ),
+        ),
+    ];
+
+    let mut passed = true;
+    for (name, (chi2, dof, p)) in results {
+        let ok = p > SIGNIFICANCE;
+        passed &= ok;
+
+        println!(
+            "{:<24} chi2 = {:>12.1}  dof = {:>4}  p = {:.4}  {}",
+            name,
+            chi2,
+            dof,
+            p,
+            if ok { "ok" } else { "FAILED" }
+        );
+    }
+
+    passed
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn scattering_matches_pdfs() {
+        assert!(super::check(200_000, 0x00c4_1215));
+    }
+}
@@ -0,0 +1,224 @@
+use std::f32::consts::TAU;
+
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampling::Onb;
+
+use nalgebra::{Point3, Similarity3, Vector2, Vector3};
+
+/// 圆锥: 顶点在 `apex`, 沿 `axis` 张开半角 `half_angle`, 截取到高度 `height`, 底面为圆盘
+///
+/// 侧面与底面均解析求交, 法线朝外; 不封底时只有侧面 (如聚光灯的光锥)
+#[derive(Clone)]
+pub struct Cone {
+    /// 顶点
+    apex: Vector3<f32>,
+
+    /// 以轴为 `w` 的局部坐标系
+    frame: Onb,
+
+    /// 半角的正切
+    tan: f32,
+
+    /// 高度
+    height: f32,
+
+    /// 是否有底面
+    capped: bool,
+
+    /// 材质
+    material: Material,
+}
+
+impl Cone {
+    /// 顶点在 `apex`, 沿 `axis` 方向半角为 `half_angle` (度), 高为 `height` 的封底圆锥
+    pub fn new(
+        apex: Vector3<f32>,
+        axis: Vector3<f32>,
+        half_angle: f32,
+        height: f32,
+        material: Material,
+    ) -> Self {
+        Self {
+            apex,
+            frame: Onb::build_from_w(&axis.normalize()),
+            tan: half_angle.to_radians().tan(),
+            height,
+            capped: true,
+            material,
+        }
+    }
+
+    /// 去掉底面, 只保留侧面
+    pub const fn open(mut self) -> Self {
+        self.capped = false;
+        self
+    }
+
+    /// 经相似变换后的圆锥
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        let rotation = transform.isometry.rotation;
+
+        Self {
+            apex: transform.transform_point(&Point3::from(self.apex)).coords,
+            frame: Onb {
+                u: rotation * self.frame.u,
+                v: rotation * self.frame.v,
+                w: rotation * self.frame.w,
+            },
+            height: self.height * transform.scaling(),
+            ..self.clone()
+        }
+    }
+
+    /// 底面半径
+    fn radius(&self) -> f32 {
+        self.height * self.tan
+    }
+
+    /// 世界向量在局部坐标系中的坐标
+    fn to_local(&self, v: &Vector3<f32>) -> Vector3<f32> {
+        Vector3::new(
+            v.dot(&self.frame.u),
+            v.dot(&self.frame.v),
+            v.dot(&self.frame.w),
+        )
+    }
+}
+
+impl Hittable for Cone {
+    /// 纹理坐标: 侧面为绕轴的角度比例与沿轴的高度比例, 底面为圆盘所在正方形内的比例
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let o = self.to_local(&(ray.origin() - self.apex));
+        let d = self.to_local(&ray.direction());
+        let k = self.tan * self.tan;
+
+        // 侧面: x² + y² = k z², 0 ≤ z ≤ h
+        let a = d.x * d.x + d.y * d.y - k * d.z * d.z;
+        let b = o.x * d.x + o.y * d.y - k * o.z * d.z;
+        let c = o.x * o.x + o.y * o.y - k * o.z * o.z;
+        let roots = if a.abs() < 1e-12 {
+            // 光线平行于母线, 方程退化为一次
+            if b == 0.0 {
+                [f32::NAN; 2]
+            } else {
+                [-c / (2.0 * b), f32::NAN]
+            }
+        } else {
+            let disc = b * b - a * c;
+            if disc < 0.0 {
+                [f32::NAN; 2]
+            } else {
+                let sqrt_disc = disc.sqrt();
+                let (t0, t1) = ((-b - sqrt_disc) / a, (-b + sqrt_disc) / a);
+                [t0.min(t1), t0.max(t1)]
+            }
+        };
+        let side = roots
+            .into_iter()
+            .filter(|&t| t > t_min && t < t_max)
+            .find(|&t| (0.0..=self.height).contains(&(o.z + t * d.z)));
+
+        // 底面: z = h, x² + y² ≤ r²
+        let radius = self.radius();
+        let base = (self.capped && d.z != 0.0)
+            .then(|| (self.height - o.z) / d.z)
+            .filter(|&t| t > t_min && t < side.unwrap_or(t_max))
+            .filter(|&t| {
+                let p = o + t * d;
+                p.x * p.x + p.y * p.y <= radius * radius
+            });
+
+        let width = |t: f32| ray.cone().width_at(t * ray.direction().magnitude());
+        let (t, normal, uv, footprint) = match (side, base) {
+            (_, Some(t)) => {
+                let p = o + t * d;
+                let uv = (0.5 + p.x / (2.0 * radius), 0.5 + p.y / (2.0 * radius));
+                (t, self.frame.w, uv, width(t) / (2.0 * radius))
+            }
+            (Some(t), None) => {
+                let p = o + t * d;
+                let local = Vector3::new(p.x, p.y, -k * p.z);
+                let normal = local
+                    .try_normalize(f32::EPSILON)
+                    .map_or(-self.frame.w, |n| self.frame.local(&n));
+                let phi = p.y.atan2(p.x).rem_euclid(TAU);
+                (
+                    t,
+                    normal,
+                    (phi / TAU, p.z / self.height),
+                    width(t) / self.height,
+                )
+            }
+            (None, None) => return None,
+        };
+
+        Some(HitRecord {
+            distance: t,
+            position: ray.point_at_t(t),
+            normal,
+            uv,
+            footprint,
+            material: self.material,
+            object: 0,
+        })
+    }
+}
+
+impl Bounded for Cone {
+    /// 顶点与底面圆盘的包围盒
+    fn bounding_box(&self) -> AaBb {
+        let center = self.apex + self.height * self.frame.w;
+        let w = self.frame.w;
+        let disc = self.radius()
+            * Vector3::new(
+                (1.0 - w.x * w.x).max(0.0).sqrt(),
+                (1.0 - w.y * w.y).max(0.0).sqrt(),
+                (1.0 - w.z * w.z).max(0.0).sqrt(),
+            );
+
+        AaBb {
+            min: self.apex.inf(&(center - disc)),
+            max: self.apex.sup(&(center + disc)),
+        }
+    }
+
+    /// 在过轴与该点的半平面内求到母线与底面半径的最近点, 位于封底圆锥内部时距离为负
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let local = self.to_local(&(point - self.apex));
+        let (r, z) = (local.xy().norm(), local.z);
+        let radial = if r > f32::EPSILON {
+            local.xy() / r
+        } else {
+            Vector2::x()
+        };
+
+        // 半平面内的母线为 (0, 0) 到 (R, h) 的线段, 底面为 (0, h) 到 (R, h) 的线段
+        let rim = Vector2::new(self.radius(), self.height);
+        let q = Vector2::new(r, z);
+        let on_side = rim * (q.dot(&rim) / rim.norm_squared()).clamp(0.0, 1.0);
+        let on_base = Vector2::new(r.min(rim.x), self.height);
+        let nearest = if self.capped && (q - on_base).norm() < (q - on_side).norm() {
+            on_base
+        } else {
+            on_side
+        };
+
+        let position = self.apex
+            + self.frame.local(&Vector3::new(
+                nearest.x * radial.x,
+                nearest.x * radial.y,
+                nearest.y,
+            ));
+        let inside = self.capped && z >= 0.0 && z <= self.height && r <= z * self.tan;
+        let distance = (point - position).magnitude();
+
+        ClosestPoint {
+            object: 0,
+            position,
+            distance: if inside { -distance } else { distance },
+        }
+    }
+}
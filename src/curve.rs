@@ -0,0 +1,533 @@
+use std::f32::consts::{PI, SQRT_2};
+use std::sync::Arc;
+
+use crate::accelerator::Accelerator;
+use crate::bvh::{AaBb, BVHNode, Bounded, ClosestPoint, MAX_OBJECTS};
+use crate::bvh_cache;
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampling::{Onb, random_unit_vector};
+
+use nalgebra::{Similarity3, UnitQuaternion, Vector2, Vector3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+
+/// 求交时递归二分的最大层数
+const MAX_DEPTH: f32 = 10.0;
+
+/// 毛发中每条曲线切分的段数, 每段有各自的包围盒
+const SEGMENTS: usize = 4;
+
+/// 求最近点时沿曲线采样的点数
+const CLOSEST_SAMPLES: usize = 16;
+
+/// 曲线的截面
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CurveKind {
+    /// 扁平的带子, 始终正对光线, 法线为光线的反方向
+    Flat,
+
+    /// 圆管: 仍按正对光线的带子求交, 法线随交点在宽度方向上的位置偏转, 着色如同圆柱
+    Cylinder,
+}
+
+/// 三次贝塞尔曲线: 沿中心线扫过的带子或圆管, 宽度自起点到终点线性变化, 用于毛发与草
+///
+/// 求交时把控制点变换到以光线为 z 轴的坐标系中, 递归二分曲线直到各段近似为直线,
+/// 再判断光线到线段的距离是否在该处宽度的一半以内 (Nakamaru 与 Ohno 的方法)
+#[derive(Clone)]
+pub struct Curve {
+    /// 控制点
+    points: [Vector3<f32>; 4],
+
+    /// 整条曲线起点与终点处的宽度
+    widths: [f32; 2],
+
+    /// 作为整条曲线的一段时在其上的参数区间, 宽度与纹理坐标按整条曲线的参数计算
+    range: [f32; 2],
+
+    /// 截面
+    kind: CurveKind,
+
+    /// 材质
+    material: Material,
+}
+
+impl Curve {
+    /// 由控制点 `points` 与起点, 终点处的宽度构建曲线
+    pub const fn new(
+        points: [Vector3<f32>; 4],
+        widths: [f32; 2],
+        kind: CurveKind,
+        material: Material,
+    ) -> Self {
+        Self {
+            points,
+            widths,
+            range: [0.0, 1.0],
+            kind,
+            material,
+        }
+    }
+
+    /// 经相似变换后的曲线, 宽度随之缩放
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        Self {
+            points: self
+                .points
+                .map(|p| transform.transform_point(&p.into()).coords),
+            widths: self.widths.map(|width| width * transform.scaling()),
+            ..self.clone()
+        }
+    }
+
+    /// 切分为 `count` 段, 每段是参数区间等分后的子曲线
+    pub fn segments(&self, count: usize) -> impl Iterator<Item = Self> + '_ {
+        let [start, end] = self.range;
+        (0..count).map(move |i| {
+            let (a, b) = (i as f32 / count as f32, (i + 1) as f32 / count as f32);
+            Self {
+                points: subcurve(&self.points, a, b),
+                range: [start + (end - start) * a, start + (end - start) * b],
+                ..self.clone()
+            }
+        })
+    }
+
+    /// 参数 `t` (0 到 1, 在本段上) 处在整条曲线上的参数
+    fn global(&self, t: f32) -> f32 {
+        self.range[0] + (self.range[1] - self.range[0]) * t
+    }
+
+    /// 参数 `t` (0 到 1, 在本段上) 处的宽度
+    fn width_at(&self, t: f32) -> f32 {
+        let u = self.global(t);
+        self.widths[0] + (self.widths[1] - self.widths[0]) * u
+    }
+
+    /// 本段上的最大宽度
+    fn max_width(&self) -> f32 {
+        self.width_at(0.0).max(self.width_at(1.0))
+    }
+
+    /// 在以光线为 z 轴的坐标系中递归二分控制点为 `points` 的子曲线 (本段上的参数区间为 `span`),
+    /// 返回交点的参数与沿光线的距离
+    fn recurse(
+        &self,
+        points: &[Vector3<f32>; 4],
+        span: (f32, f32),
+        depth: u32,
+        (z_min, z_max): (f32, f32),
+    ) -> Option<(f32, f32)> {
+        // 控制点的凸包扩大半个宽度后须包含光线 (原点) 且与距离区间相交
+        let half = self.max_width() / 2.0;
+        let lower = points.iter().fold(points[0], |a, p| a.inf(p)) - Vector3::repeat(half);
+        let upper = points.iter().fold(points[0], |a, p| a.sup(p)) + Vector3::repeat(half);
+        if lower.x > 0.0 || upper.x < 0.0 || lower.y > 0.0 || upper.y < 0.0 {
+            return None;
+        }
+        if upper.z < z_min || lower.z > z_max {
+            return None;
+        }
+
+        if depth > 0 {
+            let (left, right) = split(points, 0.5);
+            let middle = (span.0 + span.1) / 2.0;
+            // 后半段只接受比前半段的交点更近的交点
+            let first = self.recurse(&left, (span.0, middle), depth - 1, (z_min, z_max));
+            let z_max = first.map_or(z_max, |(_, z)| z);
+            let second = self.recurse(&right, (middle, span.1), depth - 1, (z_min, z_max));
+            return second.or(first);
+        }
+
+        // 光线须位于两端切线的垂线之间, 相邻两段在接缝处不会重复击中
+        let [p0, p1, p2, p3] = points.map(|p| p.xy());
+        if (p1 - p0).dot(&-p0) < 0.0 || (p2 - p3).dot(&-p3) < 0.0 {
+            return None;
+        }
+
+        // 视为直线段, 取其上离光线最近的点
+        let segment = p3 - p0;
+        let w = if segment.norm_squared() > 0.0 {
+            (-p0.dot(&segment) / segment.norm_squared()).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let center = bezier(points, w);
+        let t = span.0 + (span.1 - span.0) * w;
+        let width = self.width_at(t);
+        if center.xy().norm_squared() > width * width / 4.0 {
+            return None;
+        }
+        // 自曲线上出发的次级光线与出发处的距离不超过半个宽度, 忽略这样的交点以免自相交
+        if center.z <= z_min.max(width / 2.0) || center.z > z_max {
+            return None;
+        }
+
+        Some((t, center.z))
+    }
+}
+
+/// 三次贝塞尔曲线在 `t` 处的点
+fn bezier(points: &[Vector3<f32>; 4], t: f32) -> Vector3<f32> {
+    let s = 1.0 - t;
+    s * s * s * points[0]
+        + 3.0 * s * s * t * points[1]
+        + 3.0 * s * t * t * points[2]
+        + t * t * t * points[3]
+}
+
+/// 三次贝塞尔曲线在 `t` 处的切向量
+fn derivative(points: &[Vector3<f32>; 4], t: f32) -> Vector3<f32> {
+    let s = 1.0 - t;
+    3.0 * (s * s * (points[1] - points[0])
+        + 2.0 * s * t * (points[2] - points[1])
+        + t * t * (points[3] - points[2]))
+}
+
+/// 以 de Casteljau 算法在 `t` 处把曲线分为两段
+fn split(points: &[Vector3<f32>; 4], t: f32) -> ([Vector3<f32>; 4], [Vector3<f32>; 4]) {
+    let lerp = |a: Vector3<f32>, b: Vector3<f32>| a + (b - a) * t;
+    let [a, b, c, d] = *points;
+    let (ab, bc, cd) = (lerp(a, b), lerp(b, c), lerp(c, d));
+    let (abc, bcd) = (lerp(ab, bc), lerp(bc, cd));
+    let middle = lerp(abc, bcd);
+
+    ([a, ab, abc, middle], [middle, bcd, cd, d])
+}
+
+/// 参数区间 `[a, b]` 上的子曲线
+fn subcurve(points: &[Vector3<f32>; 4], a: f32, b: f32) -> [Vector3<f32>; 4] {
+    let (head, _) = split(points, b);
+    if b <= 0.0 {
+        return head;
+    }
+
+    split(&head, a / b).1
+}
+
+impl Hittable for Curve {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let max_width = self.max_width();
+        if max_width <= 0.0 {
+            return None;
+        }
+
+        // 变换到以光线起点为原点, 光线方向为 z 轴的坐标系, z 即沿光线的距离
+        let length = ray.direction().magnitude();
+        let direction = ray.direction() / length;
+        let rotation = UnitQuaternion::rotation_between(&direction, &Vector3::z())
+            .unwrap_or_else(|| UnitQuaternion::from_axis_angle(&Vector3::x_axis(), PI));
+        let points = self.points.map(|p| rotation * (p - ray.origin()));
+
+        // 二分到各段与直线的偏差小于宽度的 5% 为止
+        let bend = (0..2)
+            .map(|i| (points[i] - 2.0 * points[i + 1] + points[i + 2]).magnitude())
+            .fold(0.0, f32::max);
+        let tolerance = max_width * 0.05;
+        let depth = ((SQRT_2 * 6.0 * bend / (8.0 * tolerance)).log2() / 2.0).clamp(0.0, MAX_DEPTH);
+
+        let (t, z) = self.recurse(
+            &points,
+            (0.0, 1.0),
+            depth as u32,
+            (t_min * length, t_max * length),
+        )?;
+        let distance = z / length;
+        let position = ray.point_at_t(distance);
+
+        // 正对光线的法线, 与切线及宽度方向正交; `v` 为交点在宽度方向上的位置 (-1 到 1)
+        let tangent = derivative(&self.points, t)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(|| Onb::build_from_w(&direction).u);
+        let facing = (-direction + tangent * tangent.dot(&direction))
+            .try_normalize(f32::EPSILON)
+            .unwrap_or(-direction);
+        let side = tangent.cross(&facing);
+        let offset = (position - bezier(&self.points, t)).dot(&side);
+        let v = (2.0 * offset / self.width_at(t).max(f32::EPSILON)).clamp(-1.0, 1.0);
+        let normal = match self.kind {
+            CurveKind::Flat => facing,
+            CurveKind::Cylinder => facing * (1.0 - v * v).sqrt() + side * v,
+        };
+
+        // 整条曲线的长度以控制多边形估计
+        let polygon = (0..3)
+            .map(|i| (self.points[i + 1] - self.points[i]).magnitude())
+            .sum::<f32>()
+            / (self.range[1] - self.range[0]);
+
+        Some(HitRecord {
+            distance,
+            position,
+            normal,
+            uv: (self.global(t), (v + 1.0) / 2.0),
+            footprint: ray.cone().width_at(z) / polygon.max(f32::EPSILON),
+            material: self.material,
+            object: 0,
+        })
+    }
+}
+
+impl Bounded for Curve {
+    /// 控制点的包围盒向外扩大最大宽度的一半
+    fn bounding_box(&self) -> AaBb {
+        let half = Vector3::repeat(self.max_width() / 2.0);
+        let points = &self.points;
+
+        AaBb {
+            min: points.iter().fold(points[0], |a, p| a.inf(p)) - half,
+            max: points.iter().fold(points[0], |a, p| a.sup(p)) + half,
+        }
+    }
+
+    /// 沿中心线采样, 取最近的采样点所在的截面上离 `point` 最近的点
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let (t, center) = (0..=CLOSEST_SAMPLES)
+            .map(|i| {
+                let t = i as f32 / CLOSEST_SAMPLES as f32;
+                (t, bezier(&self.points, t))
+            })
+            .min_by(|a, b| {
+                (point - a.1)
+                    .norm_squared()
+                    .total_cmp(&(point - b.1).norm_squared())
+            })
+            .unwrap_or((0.0, self.points[0]));
+        let offset = point - center;
+        let (length, half) = (offset.magnitude(), self.width_at(t) / 2.0);
+        let position =
+            center + offset.try_normalize(f32::EPSILON).unwrap_or_default() * length.min(half);
+
+        ClosestPoint {
+            object: 0,
+            position,
+            distance: (length - half).max(0.0),
+        }
+    }
+}
+
+/// 毛发: 大量曲线, 各自切分为几段, 由自身的 BVH 加速求交
+///
+/// 作为场景中的单个实体, 所有曲线的可见性与编号相同
+pub struct Hair {
+    /// 切分前的曲线
+    curves: Vec<Curve>,
+    bvh: BVHNode,
+    bbox: AaBb,
+}
+
+impl Hair {
+    pub fn new(curves: Vec<Curve>) -> Self {
+        let segments: Vec<Arc<dyn Bounded + Sync + Send>> = curves
+            .iter()
+            .flat_map(|curve| curve.segments(SEGMENTS))
+            .map(|segment| Arc::new(segment) as Arc<dyn Bounded + Sync + Send>)
+            .collect();
+        let bbox = AaBb::all_surrounding_box(&segments);
+
+        Self {
+            curves,
+            bvh: bvh_cache::build(segments, MAX_OBJECTS),
+            bbox,
+        }
+    }
+
+    /// 在自角点 `corner` 起沿 x 与 z 轴大小为 `size` 的矩形上随机种下 `count` 片草叶
+    ///
+    /// 草叶为扁平的带子, 高约 `height`, 向随机的方向弯曲, 宽度自根部的 `width` 收窄到叶尖为零;
+    /// 同一 `seed` 生成同样的草地
+    pub fn grass(
+        corner: Vector3<f32>,
+        size: Vector2<f32>,
+        count: usize,
+        height: f32,
+        width: f32,
+        material: Material,
+        seed: u64,
+    ) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let curves = (0..count)
+            .map(|_| {
+                let root = corner
+                    + Vector3::new(
+                        rng.random::<f32>() * size.x,
+                        0.0,
+                        rng.random::<f32>() * size.y,
+                    );
+                let angle = rng.random::<f32>() * 2.0 * PI;
+                let lean = Vector3::new(angle.cos(), 0.0, angle.sin());
+                let h = height * rng.random_range(0.6..1.0);
+                let bend = h * rng.random_range(0.1..0.5);
+                let up = Vector3::y() * h;
+
+                Curve::new(
+                    [
+                        root,
+                        root + up / 3.0,
+                        root + up * 2.0 / 3.0 + lean * bend / 3.0,
+                        root + up * (1.0 - bend / h / 2.0) + lean * bend,
+                    ],
+                    [width, 0.0],
+                    CurveKind::Flat,
+                    material,
+                )
+            })
+            .collect();
+
+        Self::new(curves)
+    }
+
+    /// 在以 `center` 为中心, 半径为 `radius` 的球面上随机长出 `count` 根毛
+    ///
+    /// 毛为圆管, 长约 `length`, 自法线方向出发受重力向下弯曲, 宽度自根部的 `width` 收窄到尖端为零;
+    /// 同一 `seed` 生成同样的毛发
+    pub fn fur(
+        center: Vector3<f32>,
+        radius: f32,
+        count: usize,
+        length: f32,
+        width: f32,
+        material: Material,
+        seed: u64,
+    ) -> Self {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let curves = (0..count)
+            .map(|_| {
+                let normal = random_unit_vector(&mut rng);
+                let root = center + normal * radius;
+                let l = length * rng.random_range(0.7..1.0);
+                // 重力在法线的垂直方向上的分量, 使毛贴着表面向下垂
+                let gravity = -Vector3::y() + normal * normal.y;
+                let jitter = random_unit_vector(&mut rng) * 0.1;
+
+                Curve::new(
+                    [
+                        root,
+                        root + normal * l / 3.0,
+                        root + (normal * 0.6 + (gravity + jitter) * 0.15) * l,
+                        root + (normal * 0.8 + (gravity + jitter) * 0.35) * l,
+                    ],
+                    [width, 0.0],
+                    CurveKind::Cylinder,
+                    material,
+                )
+            })
+            .collect();
+
+        Self::new(curves)
+    }
+
+    /// 切分前的曲线
+    pub fn curves(&self) -> &[Curve] {
+        &self.curves
+    }
+
+    /// 经相似变换后的毛发, 重新构建 BVH
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        Self::new(
+            self.curves
+                .iter()
+                .map(|curve| curve.transformed(transform))
+                .collect(),
+        )
+    }
+}
+
+impl Hittable for Hair {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        if !self.bbox.hit(ray) {
+            return None;
+        }
+
+        self.bvh.hit(ray, t_min, t_max)
+    }
+}
+
+impl Bounded for Hair {
+    fn bounding_box(&self) -> AaBb {
+        self.bbox.clone()
+    }
+
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        self.bvh.closest_point(point).unwrap_or(ClosestPoint {
+            object: 0,
+            position: *point,
+            distance: f32::INFINITY,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 沿 x 轴自 0 到 3 的直线, 宽度自 0.2 变为 0.1
+    fn straight() -> Curve {
+        let points = [0.0, 1.0, 2.0, 3.0].map(|x| Vector3::new(x, 0.0, 0.0));
+
+        Curve::new(points, [0.2, 0.1], CurveKind::Flat, Material::CLAY)
+    }
+
+    /// 自 `(x, y, 5)` 沿 -z 方向的光线
+    fn down(x: f32, y: f32) -> Ray {
+        Ray::from(Vector3::new(x, y, 5.0), Vector3::new(0.0, 0.0, -1.0))
+    }
+
+    #[test]
+    fn straight_curve_hits_within_half_width() {
+        let curve = straight();
+
+        let hit = curve.hit(&down(1.5, 0.0), 0.0, f32::MAX).unwrap();
+        assert!((hit.distance - 5.0).abs() < 1e-4);
+        assert!((hit.uv.0 - 0.5).abs() < 1e-3);
+        assert!((hit.normal - Vector3::z()).norm() < 1e-4);
+
+        // t = 0.5 处宽 0.15
+        assert!(curve.hit(&down(1.5, 0.07), 0.0, f32::MAX).is_some());
+        assert!(curve.hit(&down(1.5, 0.08), 0.0, f32::MAX).is_none());
+        assert!(curve.hit(&down(3.5, 0.0), 0.0, f32::MAX).is_none());
+        assert!(curve.hit(&down(1.5, 0.0), 0.0, 4.0).is_none());
+    }
+
+    #[test]
+    fn width_varies_linearly_along_the_curve() {
+        let curve = straight();
+        for (t, width) in [(0.0, 0.2), (0.5, 0.15), (1.0, 0.1)] {
+            assert!((curve.width_at(t) - width).abs() < 1e-6);
+        }
+
+        // 段上的参数按整条曲线换算
+        let second = curve.segments(2).nth(1).unwrap();
+        assert!((second.width_at(0.0) - 0.15).abs() < 1e-6);
+        assert!((second.width_at(1.0) - 0.1).abs() < 1e-6);
+    }
+
+    #[test]
+    fn segment_boxes_contain_the_curve() {
+        let points = [
+            Vector3::new(0.0, 0.0, 0.0),
+            Vector3::new(1.0, 2.0, 0.0),
+            Vector3::new(2.0, -2.0, 1.0),
+            Vector3::new(3.0, 0.0, 0.0),
+        ];
+        let curve = Curve::new(points, [0.2, 0.1], CurveKind::Cylinder, Material::CLAY);
+
+        for (i, segment) in curve.segments(SEGMENTS).enumerate() {
+            let bounds = segment.bounding_box();
+            let inside = |p: Vector3<f32>| {
+                (0..3).all(|a| p[a] >= bounds.min[a] - 1e-5 && p[a] <= bounds.max[a] + 1e-5)
+            };
+            for k in 0..=16 {
+                // 段上的点连同其截面的半个宽度
+                let t = (i as f32 + k as f32 / 16.0) / SEGMENTS as f32;
+                let (point, half) = (bezier(&points, t), Vector3::repeat(curve.width_at(t) / 2.0));
+                assert!(
+                    inside(point - half) && inside(point + half),
+                    "segment {i} misses t = {t}"
+                );
+            }
+        }
+    }
+}
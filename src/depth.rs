@@ -0,0 +1,105 @@
+use crate::accelerator::Accelerator;
+use crate::camera::Camera;
+use crate::ray::RayEpsilon;
+
+use clap::ValueEnum;
+use rayon::prelude::*;
+
+/// 深度的编码方式
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DepthEncoding {
+    /// 线性: 近平面为 0, 远平面为 1
+    Linear,
+
+    /// 倒数: 近平面为 1, 远平面为 0, 近处的精度更高
+    Inverse,
+}
+
+/// 深度通道设置
+pub struct DepthSettings {
+    /// 近平面距离, 未指定时取最近的击中点
+    pub near: Option<f32>,
+
+    /// 远平面距离, 未指定时取最远的击中点
+    pub far: Option<f32>,
+
+    /// 编码方式
+    pub encoding: DepthEncoding,
+}
+
+/// 深度通道
+pub struct DepthPass {
+    /// 每个像素的 z 深度 (首行为图像顶部), 未击中时为无穷远
+    pub depths: Vec<f32>,
+}
+
+impl DepthPass {
+    /// 从每个像素的中心发出主光线, 记录击中点沿视线方向的深度
+    pub fn render(
+        scene: &dyn Accelerator,
+        camera: &Camera,
+        nx: usize,
+        ny: usize,
+        epsilon: RayEpsilon,
+    ) -> Self {
+        let depths = (0..ny)
+            .into_par_iter()
+            .rev()
+            .flat_map_iter(|y| {
+                (0..nx).map(move |x| {
+                    let u = (x as f32 + 0.5) / nx as f32;
+                    let v = (y as f32 + 0.5) / ny as f32;
+
+                    let ray = camera.center_ray(u, v);
+
+                    scene
+                        .hit(&ray, epsilon.t_min(&ray, 0.0), f32::MAX)
+                        .map_or(f32::INFINITY, |hit| camera.view_depth(&hit.position))
+                })
+            })
+            .collect();
+
+        Self { depths }
+    }
+
+    /// 近平面与远平面, 未指定的一端取自击中点的深度范围
+    fn range(&self, settings: &DepthSettings) -> (f32, f32) {
+        let finite = self.depths.iter().copied().filter(|d| d.is_finite());
+        let near = settings
+            .near
+            .unwrap_or_else(|| finite.clone().fold(f32::INFINITY, f32::min));
+        let far = settings
+            .far
+            .unwrap_or_else(|| finite.fold(f32::NEG_INFINITY, f32::max));
+
+        if near.is_finite() && far > near {
+            (near, far)
+        } else {
+            (0.0, 1.0)
+        }
+    }
+
+    /// 按近平面与远平面归一化到 [0, 1], 未击中的像素视为无穷远
+    pub fn normalized(&self, settings: &DepthSettings) -> Vec<f32> {
+        let (near, far) = self.range(settings);
+
+        self.depths
+            .iter()
+            .map(|&depth| {
+                let depth = depth.clamp(near, far);
+                match settings.encoding {
+                    DepthEncoding::Linear => (depth - near) / (far - near),
+                    DepthEncoding::Inverse => (1.0 / depth - 1.0 / far) / (1.0 / near - 1.0 / far),
+                }
+            })
+            .collect()
+    }
+
+    /// 8 位灰度的 RGB 像素
+    pub fn to_rgb(normalized: &[f32]) -> Vec<u8> {
+        normalized
+            .iter()
+            .flat_map(|&d| [(255.99 * d.clamp(0.0, 1.0)) as u8; 3])
+            .collect()
+    }
+}
@@ -0,0 +1,136 @@
+use std::f32::consts::PI;
+
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sphere::Sphere;
+
+use nalgebra::{Affine3, Matrix3, Matrix4, Point3, Rotation3, Similarity3, Vector3};
+
+/// 椭球: 单位球经仿射变换 (非均匀缩放, 旋转与平移) 得到
+///
+/// 光线变换到单位球所在的局部空间求交, 参数 `t` 不变; 法线以变换的逆转置变换回世界空间,
+/// 纹理坐标与球体相同 (经纬度)
+#[derive(Clone)]
+pub struct Ellipsoid {
+    /// 局部空间中以原点为中心的单位球
+    sphere: Sphere,
+
+    /// 局部空间到世界空间的变换
+    to_world: Affine3<f32>,
+
+    /// 世界空间到局部空间的变换
+    to_local: Affine3<f32>,
+
+    /// 法线的变换: `to_world` 线性部分的逆转置
+    normal_matrix: Matrix3<f32>,
+
+    /// 与椭球体积相同的球的半径, 用于估计纹理足迹
+    mean_radius: f32,
+}
+
+impl Ellipsoid {
+    /// 以 `center` 为中心, 沿坐标轴的半轴长为 `radii` 的椭球
+    pub fn new(center: Vector3<f32>, radii: Vector3<f32>, material: Material) -> Self {
+        let mut matrix = Matrix4::new_nonuniform_scaling(&radii);
+        matrix.fixed_view_mut::<3, 1>(0, 3).copy_from(&center);
+
+        Self::from_affine(Affine3::from_matrix_unchecked(matrix), material)
+    }
+
+    /// 由单位球到世界空间的仿射变换 `to_world` 构建椭球, 变换须可逆
+    pub fn from_affine(to_world: Affine3<f32>, material: Material) -> Self {
+        let linear = to_world.matrix().fixed_view::<3, 3>(0, 0).into_owned();
+        let inverse = linear.try_inverse().unwrap_or_else(Matrix3::identity);
+
+        Self {
+            sphere: Sphere::from(Vector3::zeros(), 1.0, material),
+            to_world,
+            to_local: to_world.inverse(),
+            normal_matrix: inverse.transpose(),
+            mean_radius: linear.determinant().abs().cbrt(),
+        }
+    }
+
+    /// 绕中心旋转后的椭球
+    pub fn rotated(self, rotation: &Rotation3<f32>) -> Self {
+        let center = self.center();
+        let mut matrix = self.to_world.into_inner();
+        let linear = rotation.matrix() * matrix.fixed_view::<3, 3>(0, 0);
+        matrix.fixed_view_mut::<3, 3>(0, 0).copy_from(&linear);
+        matrix.fixed_view_mut::<3, 1>(0, 3).copy_from(&center);
+
+        Self::from_affine(Affine3::from_matrix_unchecked(matrix), *self.material())
+    }
+
+    /// 经相似变换后的椭球
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        let matrix = transform.to_homogeneous() * self.to_world.matrix();
+
+        Self::from_affine(Affine3::from_matrix_unchecked(matrix), *self.material())
+    }
+
+    /// 中心
+    fn center(&self) -> Vector3<f32> {
+        self.to_world.matrix().fixed_view::<3, 1>(0, 3).into_owned()
+    }
+
+    fn material(&self) -> &Material {
+        self.sphere.material()
+    }
+}
+
+impl Hittable for Ellipsoid {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        // 方向不归一化, 局部空间中的 t 与世界空间相同
+        let origin = self.to_local * Point3::from(ray.origin());
+        let direction = self.to_local * ray.direction();
+        let local = Ray::from(origin.coords, direction);
+        let hit = self.sphere.hit(&local, t_min, t_max)?;
+
+        let width = ray
+            .cone()
+            .width_at(hit.distance * ray.direction().magnitude());
+
+        Some(HitRecord {
+            position: ray.point_at_t(hit.distance),
+            normal: (self.normal_matrix * hit.normal).normalize(),
+            footprint: width / (PI * self.mean_radius),
+            ..hit
+        })
+    }
+}
+
+impl Bounded for Ellipsoid {
+    /// 单位球经线性变换后在各轴上的半宽为矩阵对应行的长度
+    fn bounding_box(&self) -> AaBb {
+        let linear = self.to_world.matrix().fixed_view::<3, 3>(0, 0);
+        let extent = Vector3::from_fn(|i, _| linear.row(i).norm());
+        let center = self.center();
+
+        AaBb {
+            min: center - extent,
+            max: center + extent,
+        }
+    }
+
+    /// 近似: 取局部空间中沿球心方向投影到单位球上的点, 距离在球形时精确,
+    /// 越扁长偏差越大, 但始终是到表面上某点的距离; 位于内部时为负
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let local = (self.to_local * Point3::from(*point)).coords;
+        let direction = local.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::y);
+        let position = (self.to_world * Point3::from(direction)).coords;
+        let distance = (point - position).magnitude();
+
+        ClosestPoint {
+            object: 0,
+            position,
+            distance: if local.norm_squared() < 1.0 {
+                -distance
+            } else {
+                distance
+            },
+        }
+    }
+}
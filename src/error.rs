@@ -0,0 +1,91 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// 渲染器的错误
+#[derive(Debug)]
+pub enum Error {
+    /// 读写文件失败, 信息中带有文件路径
+    Io(io::Error),
+
+    /// 文本文件 (场景文件, 材质文件) 的语法错误, 行号与列号从 1 起
+    Parse {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+
+    /// 纹理文件 (或 UDIM 纹理集的所有图块) 不存在
+    MissingTexture(PathBuf),
+
+    /// 文件的内容无效, 如不支持的格式或损坏的数据
+    InvalidData { path: PathBuf, message: String },
+
+    /// 无效的渲染设置, 如互相矛盾的参数或不存在的实体
+    InvalidSettings(String),
+
+    /// 自检 (白炉测试, 卡方检验) 或批量渲染中的场景未通过
+    Failed(String),
+}
+
+/// 渲染器的结果
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl Error {
+    /// 文件 `path` 第 `line` 行 (从 1 起) 中的语法错误, 出错的片段 `at` 须为该行文本 `text` 的子串,
+    /// 列号为其在行中的字符位置
+    pub fn parse(path: &Path, line: usize, text: &str, at: &str, message: String) -> Self {
+        let offset = at.as_ptr().addr().wrapping_sub(text.as_ptr().addr());
+        let column = text
+            .get(..offset)
+            .map_or(1, |before| before.chars().count() + 1);
+
+        Self::Parse {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message,
+        }
+    }
+
+    /// 文件 `path` 的内容无效
+    pub fn invalid_data(path: &Path, message: impl Into<String>) -> Self {
+        Self::InvalidData {
+            path: path.to_path_buf(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{err}"),
+            Self::Parse {
+                path,
+                line,
+                column,
+                message,
+            } => write!(f, "{}:{line}:{column}: {message}", path.display()),
+            Self::MissingTexture(path) => write!(f, "{}: texture not found", path.display()),
+            Self::InvalidData { path, message } => write!(f, "{}: {message}", path.display()),
+            Self::InvalidSettings(message) | Self::Failed(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
@@ -0,0 +1,89 @@
+use nalgebra::Vector3;
+
+/// 中灰的线性亮度
+const MIDDLE_GRAY: f32 = 0.18;
+
+/// 直方图覆盖的对数亮度范围 (EV), 超出的亮度计入两端的分箱
+const MIN_EV: f32 = -16.0;
+const MAX_EV: f32 = 16.0;
+
+/// 直方图的分箱数, 每个分箱宽 1/8 EV
+const BINS: usize = 256;
+
+/// 曝光
+#[derive(Clone, Copy, Debug)]
+pub enum Exposure {
+    /// 固定曝光, 附带曝光补偿 (EV)
+    Manual(f32),
+
+    /// 按亮度直方图将中位数置于中灰, 再施加曝光补偿 (EV)
+    Auto(f32),
+}
+
+impl Exposure {
+    /// 曝光补偿 (EV)
+    pub const fn compensation(self) -> f32 {
+        match self {
+            Self::Manual(ev) | Self::Auto(ev) => ev,
+        }
+    }
+
+    /// 曝光补偿对应的线性倍率, 自动曝光在得出直方图之前也以此预览
+    pub fn scale(self) -> f32 {
+        self.compensation().exp2()
+    }
+}
+
+/// 对数亮度的直方图
+pub struct Histogram {
+    bins: [usize; BINS],
+    count: usize,
+}
+
+impl Default for Histogram {
+    fn default() -> Self {
+        Self {
+            bins: [0; BINS],
+            count: 0,
+        }
+    }
+}
+
+impl Histogram {
+    /// 计入线性颜色的亮度, 全黑的像素 (如透明背景) 不计入
+    pub fn add(&mut self, color: &Vector3<f32>) {
+        let luminance = 0.2126 * color.x + 0.7152 * color.y + 0.0722 * color.z;
+        if luminance <= 0.0 || !luminance.is_finite() {
+            return;
+        }
+
+        let t = (luminance.log2() - MIN_EV) / (MAX_EV - MIN_EV);
+        self.bins[((t * BINS as f32) as usize).min(BINS - 1)] += 1;
+        self.count += 1;
+    }
+
+    /// 亮度中位数所在分箱的中心 (EV), 直方图为空时返回 `None`
+    fn median(&self) -> Option<f32> {
+        let mut remaining = self.count.checked_sub(1)? / 2;
+        let bin = self.bins.iter().position(|&n| {
+            if remaining < n {
+                true
+            } else {
+                remaining -= n;
+                false
+            }
+        })?;
+
+        Some(MIN_EV + (bin as f32 + 0.5) * (MAX_EV - MIN_EV) / BINS as f32)
+    }
+
+    /// 将亮度中位数置于中灰并施加 `compensation` EV 补偿的线性倍率, 直方图为空时只施加补偿
+    pub fn exposure(&self, compensation: f32) -> f32 {
+        let ev = self.median().map_or(compensation, |median| {
+            MIDDLE_GRAY.log2() - median + compensation
+        });
+        eprintln!("\rAuto exposure: {ev:+.2} EV{}", " ".repeat(20));
+
+        ev.exp2()
+    }
+}
@@ -0,0 +1,304 @@
+//! 供 C/C++ 程序嵌入的接口, 头文件 `include/ray_tracing.h` 由构建脚本生成
+
+use std::ffi::{c_int, c_void};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+
+use crate::accelerator::AcceleratorKind;
+use crate::background::{Background, Environment};
+use crate::bvh::MAX_OBJECTS;
+use crate::exposure::Exposure;
+use crate::filter::Filter;
+use crate::graph::Lens;
+use crate::light::Lights;
+use crate::material::Material;
+use crate::output::BitDepth;
+use crate::precision::Precision;
+use crate::render::{BounceLimits, RenderSettings, camera_sample, resolve_pixel};
+use crate::rng::{self, get_rng};
+use crate::sampler::{SamplerKind, build_sampler};
+use crate::scene::{Scene, SceneObject};
+use crate::scene_file::CameraSpec;
+use crate::sphere::Sphere;
+
+use nalgebra::Vector3;
+use rayon::prelude::*;
+
+/// 每次报告进度之间渲染的行数
+const BAND_ROWS: usize = 16;
+
+/// 接口函数的返回值
+#[repr(C)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RtStatus {
+    /// 成功
+    RtOk = 0,
+
+    /// 传入了空指针
+    RtNullPointer = 1,
+
+    /// 参数无效, 如非正的半径, 未设置相机或场景为空
+    RtInvalidArgument = 2,
+
+    /// 渲染被进度回调取消
+    RtCancelled = 3,
+
+    /// 渲染中发生了内部错误 (panic), `buffer` 的内容未定义
+    RtPanic = 4,
+}
+
+/// 材质的种类
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub enum RtMaterialKind {
+    /// 漫反射, `param` 不使用
+    RtLambertian = 0,
+
+    /// 金属, `param` 为模糊度
+    RtMetal = 1,
+
+    /// 电介质, `param` 为折射率, `color` 不使用
+    RtDielectric = 2,
+
+    /// 自发光, `param` 为强度
+    RtEmissive = 3,
+}
+
+/// 材质
+#[repr(C)]
+#[derive(Clone, Copy, Debug)]
+pub struct RtMaterial {
+    pub kind: RtMaterialKind,
+    pub color: [f32; 3],
+    pub param: f32,
+}
+
+impl From<RtMaterial> for Material {
+    fn from(material: RtMaterial) -> Self {
+        let color = Vector3::from(material.color);
+        match material.kind {
+            RtMaterialKind::RtLambertian => Self::lambertian(color),
+            RtMaterialKind::RtMetal => Self::metal(color, material.param),
+            RtMaterialKind::RtDielectric => Self::dielectric(material.param),
+            RtMaterialKind::RtEmissive => Self::emissive(color, material.param),
+        }
+    }
+}
+
+/// 进度回调: `progress` 为已完成的比例 (0 到 1), 返回非零值时取消渲染
+pub type RtProgressFn = Option<extern "C" fn(progress: f32, user_data: *mut c_void) -> c_int>;
+
+/// 由调用者逐步构建的场景, 对 C 不透明
+pub struct RtScene {
+    objects: Vec<SceneObject>,
+    camera: Option<CameraSpec>,
+}
+
+/// 创建空场景, 以 `rt_scene_free` 释放
+#[unsafe(no_mangle)]
+pub extern "C" fn rt_scene_new() -> *mut RtScene {
+    Box::into_raw(Box::new(RtScene {
+        objects: vec![],
+        camera: None,
+    }))
+}
+
+/// 释放场景, `scene` 可为空
+///
+/// # Safety
+///
+/// `scene` 须为 `rt_scene_new` 返回且尚未释放的指针
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rt_scene_free(scene: *mut RtScene) {
+    if !scene.is_null() {
+        drop(unsafe { Box::from_raw(scene) });
+    }
+}
+
+/// 向场景添加球体
+///
+/// # Safety
+///
+/// `scene` 须为 `rt_scene_new` 返回且尚未释放的指针, `center` 须指向 3 个 `float`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rt_scene_add_sphere(
+    scene: *mut RtScene,
+    center: *const f32,
+    radius: f32,
+    material: RtMaterial,
+) -> RtStatus {
+    let (Some(scene), Some(center)) = (unsafe { scene.as_mut() }, unsafe { vector(center) }) else {
+        return RtStatus::RtNullPointer;
+    };
+    if radius <= 0.0 || !radius.is_finite() {
+        return RtStatus::RtInvalidArgument;
+    }
+
+    let name = format!("sphere{}", scene.objects.len());
+    let sphere = Sphere::from(center, radius, material.into());
+    scene.objects.push(SceneObject::new(name, Arc::new(sphere)));
+
+    RtStatus::RtOk
+}
+
+/// 设置相机, `vertical_fov` 以度为单位, `aperture` 为 0 时没有景深
+///
+/// # Safety
+///
+/// `scene` 须为 `rt_scene_new` 返回且尚未释放的指针, `look_from` 与 `look_at` 须各指向 3 个 `float`
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rt_scene_set_camera(
+    scene: *mut RtScene,
+    look_from: *const f32,
+    look_at: *const f32,
+    vertical_fov: f32,
+    aperture: f32,
+    focus_dist: f32,
+) -> RtStatus {
+    let (Some(scene), Some(look_from), Some(look_at)) = (
+        unsafe { scene.as_mut() },
+        unsafe { vector(look_from) },
+        unsafe { vector(look_at) },
+    ) else {
+        return RtStatus::RtNullPointer;
+    };
+    if look_from == look_at || vertical_fov <= 0.0 || vertical_fov >= 180.0 {
+        return RtStatus::RtInvalidArgument;
+    }
+
+    scene.camera = Some(CameraSpec {
+        look_from,
+        look_at,
+        lens: Lens {
+            vertical_fov,
+            aperture,
+            focus_dist,
+        },
+    });
+
+    RtStatus::RtOk
+}
+
+/// 以每个像素 `samples` 个采样渲染场景, 写入 `buffer` 中 `width * height` 个 RGB 像素 (8 位,
+/// 首行为图像顶部)
+///
+/// 每渲染若干行在调用线程上调用一次 `progress`, 其返回非零值时停止并返回 `RtCancelled`,
+/// 此时 `buffer` 中只有部分行已写入; 宽或高为零, 场景中没有实体, 或 `3 * width * height` 溢出时返回
+/// `RtInvalidArgument`; 渲染中的 panic 不会展开到调用方, 而是返回 `RtPanic`
+///
+/// # Safety
+///
+/// `scene` 须为 `rt_scene_new` 返回且尚未释放的指针, `buffer` 须可写入 `3 * width * height` 字节
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn rt_render(
+    scene: *const RtScene,
+    width: usize,
+    height: usize,
+    samples: usize,
+    max_depth: usize,
+    buffer: *mut u8,
+    progress: RtProgressFn,
+    user_data: *mut c_void,
+) -> RtStatus {
+    let Some(scene) = (unsafe { scene.as_ref() }) else {
+        return RtStatus::RtNullPointer;
+    };
+    if buffer.is_null() {
+        return RtStatus::RtNullPointer;
+    }
+    let Some(camera) = &scene.camera else {
+        return RtStatus::RtInvalidArgument;
+    };
+    if width == 0 || height == 0 || samples == 0 || scene.objects.is_empty() {
+        return RtStatus::RtInvalidArgument;
+    }
+    // 缓冲区的字节数溢出或超出切片的上限时无法寻址
+    let Some(len) = width
+        .checked_mul(height)
+        .and_then(|pixels| pixels.checked_mul(3))
+        .filter(|&len| len <= isize::MAX as usize)
+    else {
+        return RtStatus::RtInvalidArgument;
+    };
+    let buffer = unsafe { std::slice::from_raw_parts_mut(buffer, len) };
+
+    // 展开跨越 FFI 边界是未定义行为, panic 在此处截获
+    panic::catch_unwind(AssertUnwindSafe(|| {
+        let (nx, ny) = (width, height);
+        let camera = camera.build(nx as f32 / ny as f32).with_resolution(nx, ny);
+        let accelerator = Scene::build(scene.objects.clone(), AcceleratorKind::Bvh, MAX_OBJECTS);
+        let settings = RenderSettings {
+            max_depth,
+            min_throughput: 0.0,
+            specular_depth: max_depth,
+            bounce_limits: BounceLimits::UNLIMITED,
+            clamp_direct: None,
+            clamp_indirect: None,
+            exposure: Exposure::Manual(0.0),
+            clay: false,
+            environment: Environment::new(Background::Sky),
+            lights: Lights::default(),
+            sampler: SamplerKind::Stratified,
+            filter: Filter::default(),
+            cp_rotation: false,
+            precision: Precision::F32,
+            bit_depth: BitDepth::Eight,
+            dither: false,
+            transparent: false,
+            partial_write: None,
+            epsilon: accelerator.ray_epsilon(),
+        };
+        let spp = build_sampler(settings.sampler, samples, get_rng(), false).samples_per_pixel();
+
+        // 按行带渲染, 行带之间在调用线程上报告进度
+        for (band, rows) in buffer
+            .chunks_mut((3 * nx).saturating_mul(BAND_ROWS))
+            .enumerate()
+        {
+            rows.par_chunks_mut(3 * nx)
+                .enumerate()
+                .for_each(|(index, row)| {
+                    // 首行为图像顶部
+                    let y = ny - 1 - (band * BAND_ROWS + index);
+                    let mut sampler = build_sampler(settings.sampler, samples, get_rng(), false);
+                    for (x, pixel) in row.chunks_exact_mut(3).enumerate() {
+                        if let Some(rng) = rng::start_pixel((x, y), 0) {
+                            sampler = build_sampler(settings.sampler, samples, rng, false);
+                        }
+                        let mut col = Vector3::zeros();
+                        for index in 0..spp {
+                            sampler.start_sample((x, y), index);
+                            let (ray, weight) = camera.generate_ray(
+                                (x, y),
+                                sampler.as_mut(),
+                                &settings.filter,
+                                None,
+                            );
+                            let (c, _) = camera_sample(ray, &accelerator, &settings);
+                            col += weight * c;
+                        }
+                        let (rgb, _) =
+                            resolve_pixel(&(col / spp as f32), 1.0, settings.precision, (x, y));
+                        pixel.copy_from_slice(&rgb);
+                    }
+                });
+
+            let done = ((band + 1) * BAND_ROWS).min(ny) as f32 / ny as f32;
+            if let Some(progress) = progress
+                && progress(done, user_data) != 0
+            {
+                return RtStatus::RtCancelled;
+            }
+        }
+
+        RtStatus::RtOk
+    }))
+    .unwrap_or(RtStatus::RtPanic)
+}
+
+/// 读取 `ptr` 处的 3 个 `float`, 空指针时为 `None`
+unsafe fn vector(ptr: *const f32) -> Option<Vector3<f32>> {
+    let v = unsafe { ptr.cast::<[f32; 3]>().as_ref() }?;
+
+    Some(Vector3::from(*v))
+}
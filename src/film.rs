@@ -0,0 +1,235 @@
+use crate::exposure::{Exposure, Histogram};
+use crate::filter::Filter;
+use crate::output::BitDepth;
+use crate::render::{Framebuffer, RenderSettings, resolve_deep, resolve_pixel};
+
+use nalgebra::{Vector3, Vector4};
+
+/// 图像上的矩形区域 `[x0, x1) × [y0, y1)`, 原点在左下角, 与采样坐标一致
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Window {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Window {
+    /// 整幅图像
+    pub const fn full(nx: usize, ny: usize) -> Self {
+        Self {
+            x0: 0,
+            y0: 0,
+            x1: nx,
+            y1: ny,
+        }
+    }
+
+    pub const fn width(&self) -> usize {
+        self.x1 - self.x0
+    }
+
+    pub const fn height(&self) -> usize {
+        self.y1 - self.y0
+    }
+
+    pub const fn contains(&self, x: usize, y: usize) -> bool {
+        x >= self.x0 && x < self.x1 && y >= self.y0 && y < self.y1
+    }
+
+    /// 两个区域的交集, 不相交时为 `None`
+    pub fn intersection(&self, other: &Self) -> Option<Self> {
+        let window = Self {
+            x0: self.x0.max(other.x0),
+            y0: self.y0.max(other.y0),
+            x1: self.x1.min(other.x1),
+            y1: self.y1.min(other.y1),
+        };
+
+        (window.x0 < window.x1 && window.y0 < window.y1).then_some(window)
+    }
+}
+
+/// 胶片: 每个像素的加权颜色 (预乘) 与不透明度之和, 以及权重之和
+///
+/// 只保存裁剪区域 `window` 内的像素, 区域外的采样被忽略; 各线程, 图块或进程可各自累积
+/// 一份, 再合并为整幅图像
+pub struct Film {
+    /// 整幅图像的分辨率
+    nx: usize,
+    ny: usize,
+
+    window: Window,
+
+    /// 区域内的像素, 首行为区域底部
+    sums: Vec<Vector4<f32>>,
+    weights: Vec<f32>,
+}
+
+impl Film {
+    /// 覆盖整幅图像的胶片
+    pub fn new(nx: usize, ny: usize) -> Self {
+        Self::with_window(nx, ny, Window::full(nx, ny))
+    }
+
+    /// 只覆盖 `window` (限制在图像内) 的胶片
+    pub fn with_window(nx: usize, ny: usize, window: Window) -> Self {
+        let window = window
+            .intersection(&Window::full(nx, ny))
+            .unwrap_or(Window {
+                x0: 0,
+                y0: 0,
+                x1: 0,
+                y1: 0,
+            });
+        let area = window.width() * window.height();
+
+        Self {
+            nx,
+            ny,
+            window,
+            sums: vec![Vector4::zeros(); area],
+            weights: vec![0.0; area],
+        }
+    }
+
+    /// 裁剪区域
+    pub const fn window(&self) -> Window {
+        self.window
+    }
+
+    /// 像素 `(x, y)` 在区域内的序号
+    fn index(&self, x: usize, y: usize) -> Option<usize> {
+        self.window
+            .contains(x, y)
+            .then(|| (y - self.window.y0) * self.window.width() + (x - self.window.x0))
+    }
+
+    /// 以权重 `weight` 向像素 `(x, y)` 累加一个采样
+    pub fn add_sample(
+        &mut self,
+        (x, y): (usize, usize),
+        color: &Vector3<f32>,
+        alpha: f32,
+        weight: f32,
+    ) {
+        if let Some(index) = self.index(x, y) {
+            self.sums[index] += weight * Vector4::new(color.x, color.y, color.z, alpha);
+            self.weights[index] += weight;
+        }
+    }
+
+    /// 将图像平面上 `(px, py)` (以像素为单位) 处的采样按滤波器权重累加到中心在半径内的所有像素
+    pub fn splat(
+        &mut self,
+        (px, py): (f32, f32),
+        color: &Vector3<f32>,
+        alpha: f32,
+        filter: &Filter,
+    ) {
+        let radius = filter.radius();
+        let range = |p: f32, lo: usize, hi: usize| {
+            let first = (p - 0.5 - radius).ceil().max(lo as f32) as usize;
+            let last = (p - 0.5 + radius).floor() + 1.0;
+            first..(last.clamp(0.0, hi as f32) as usize)
+        };
+
+        let window = self.window;
+        for y in range(py, window.y0, window.y1) {
+            for x in range(px, window.x0, window.x1) {
+                let weight = filter.evaluate(x as f32 + 0.5 - px, y as f32 + 0.5 - py);
+                self.add_sample((x, y), color, alpha, weight);
+            }
+        }
+    }
+
+    /// 累加另一张胶片与本区域重叠的部分
+    pub fn merge(&mut self, other: &Self) {
+        let Some(overlap) = self.window.intersection(&other.window) else {
+            return;
+        };
+
+        for y in overlap.y0..overlap.y1 {
+            for x in overlap.x0..overlap.x1 {
+                let (Some(i), Some(j)) = (self.index(x, y), other.index(x, y)) else {
+                    continue;
+                };
+                self.sums[i] += other.sums[j];
+                self.weights[i] += other.weights[j];
+            }
+        }
+    }
+
+    /// 合并两张胶片, 用于并行归约
+    pub fn merged(mut self, other: Self) -> Self {
+        self.merge(&other);
+
+        self
+    }
+
+    /// 裁剪出 `window` 内的部分
+    pub fn cropped(&self, window: Window) -> Self {
+        let mut film = Self::with_window(self.nx, self.ny, window);
+        film.merge(self);
+
+        film
+    }
+
+    /// 像素 `(x, y)` 的加权平均 (预乘的颜色与不透明度), 区域外或没有采样时为 0
+    pub fn pixel(&self, x: usize, y: usize) -> (Vector3<f32>, f32) {
+        match self.index(x, y) {
+            Some(index) if self.weights[index] > 0.0 => {
+                let mean = self.sums[index] / self.weights[index];
+                (mean.xyz(), mean.w)
+            }
+            _ => (Vector3::zeros(), 0.0),
+        }
+    }
+
+    /// 按曝光与精度编码为帧缓冲, 区域外的像素为黑色
+    ///
+    /// 自动曝光时按区域内有采样的像素的亮度直方图选取曝光
+    pub fn to_framebuffer(&self, settings: &RenderSettings) -> Framebuffer {
+        let window = self.window;
+        let pixels = || {
+            (window.y0..window.y1)
+                .flat_map(move |y| (window.x0..window.x1).map(move |x| (x, y)))
+                .map(|(x, y)| ((x, y), self.pixel(x, y)))
+        };
+
+        let scale = match settings.exposure {
+            Exposure::Manual(_) => settings.exposure.scale(),
+            Exposure::Auto(compensation) => {
+                let mut histogram = Histogram::default();
+                for (_, (color, alpha)) in pixels() {
+                    if alpha > 0.0 {
+                        histogram.add(&(color / alpha));
+                    }
+                }
+                histogram.exposure(compensation)
+            }
+        };
+
+        let (nx, ny) = (self.nx, self.ny);
+        let mut rgb = vec![0; 3 * nx * ny];
+        let mut alpha = settings.transparent.then(|| vec![0; nx * ny]);
+        let mut deep = (settings.bit_depth == BitDepth::Sixteen).then(|| vec![0; 3 * nx * ny]);
+        for ((x, y), (color, a)) in pixels() {
+            let offset = (ny - 1 - y) * nx + x;
+            if let Some(deep) = &mut deep {
+                let encoded = resolve_deep(&(scale * color), a, settings.dither, (x, y));
+                deep[3 * offset..3 * offset + 3].copy_from_slice(&encoded);
+            }
+            let (encoded, a) = resolve_pixel(&(scale * color), a, settings.precision, (x, y));
+            rgb[3 * offset..3 * offset + 3].copy_from_slice(&encoded);
+            if let Some(alpha) = &mut alpha {
+                alpha[offset] = a;
+            }
+        }
+
+        Framebuffer {
+            deep,
+            ..Framebuffer::from_pixels(rgb, alpha, ny)
+        }
+    }
+}
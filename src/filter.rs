@@ -0,0 +1,174 @@
+use std::f32::consts::PI;
+
+use clap::ValueEnum;
+
+/// 重要性采样时将 `[-r, r]` 划分的格子数
+const TABLE_SIZE: usize = 256;
+
+/// Mitchell–Netravali 滤波器的参数 B 与 C
+const MITCHELL_B: f32 = 1.0 / 3.0;
+const MITCHELL_C: f32 = 1.0 / 3.0;
+
+/// 像素重建滤波器的类型
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum FilterKind {
+    /// 盒式滤波: 像素内均匀平均
+    Box,
+
+    /// 三角 (帐篷) 滤波
+    Triangle,
+
+    /// 截断的高斯滤波, 标准差为半径的 1/3
+    Gaussian,
+
+    /// Mitchell–Netravali 三次滤波 (B = C = 1/3), 有负的旁瓣, 边缘更锐利
+    Mitchell,
+
+    /// Blackman–Harris 窗
+    BlackmanHarris,
+}
+
+impl FilterKind {
+    /// 默认半径 (像素)
+    pub const fn default_radius(self) -> f32 {
+        match self {
+            Self::Box => 0.5,
+            Self::Triangle => 1.0,
+            Self::Gaussian => 1.5,
+            Self::Mitchell | Self::BlackmanHarris => 2.0,
+        }
+    }
+}
+
+/// 可分离的像素重建滤波器 `f(x, y) = f(x) f(y)`
+///
+/// 以滤波器重要性采样实现: 像素内偏移按 `|f|` 的分布采样, 每个采样带有权重
+/// `f / pdf` (归一化使其期望为 1), 像素值仍为采样的平均, 负的旁瓣表现为负的权重
+pub struct Filter {
+    kind: FilterKind,
+
+    /// 半径 (像素), 采样偏移落在以像素中心为中心, 边长为 2r 的正方形内
+    radius: f32,
+
+    /// `|f|` 在各格子中点取值的累积分布, 首项为 0, 末项为 1
+    cdf: Vec<f32>,
+
+    /// 各格子中点处 `f` 的值
+    values: Vec<f32>,
+
+    /// `∫|f| / ∫f`, 各维度权重的归一化因子
+    normalization: f32,
+}
+
+impl Filter {
+    /// 构建滤波器, 预先计算 `|f|` 的累积分布
+    pub fn new(kind: FilterKind, radius: f32) -> Self {
+        let cell = 2.0 * radius / TABLE_SIZE as f32;
+        let values: Vec<f32> = (0..TABLE_SIZE)
+            .map(|i| evaluate_1d(kind, radius, -radius + (i as f32 + 0.5) * cell))
+            .collect();
+
+        let mut cdf = Vec::with_capacity(TABLE_SIZE + 1);
+        cdf.push(0.0);
+        for value in &values {
+            cdf.push(cdf.last().unwrap() + value.abs());
+        }
+        let total = *cdf.last().unwrap();
+        cdf.iter_mut().for_each(|c| *c /= total);
+        cdf[TABLE_SIZE] = 1.0;
+
+        Self {
+            kind,
+            radius,
+            cdf,
+            normalization: total / values.iter().sum::<f32>(),
+            values,
+        }
+    }
+
+    /// 滤波器类型
+    pub const fn kind(&self) -> FilterKind {
+        self.kind
+    }
+
+    /// 半径 (像素)
+    pub const fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    /// 滤波器在偏移 `(x, y)` 处的值 (未归一化)
+    pub fn evaluate(&self, x: f32, y: f32) -> f32 {
+        evaluate_1d(self.kind, self.radius, x) * evaluate_1d(self.kind, self.radius, y)
+    }
+
+    /// 由 [0, 1)² 内的采样得到相对像素中心的偏移及其权重
+    pub fn sample(&self, (u, v): (f32, f32)) -> ((f32, f32), f32) {
+        let (x, wx) = self.sample_1d(u);
+        let (y, wy) = self.sample_1d(v);
+
+        ((x, y), wx * wy)
+    }
+
+    /// 一维的偏移与权重
+    fn sample_1d(&self, u: f32) -> (f32, f32) {
+        // 盒式滤波直接均匀采样
+        if let FilterKind::Box = self.kind {
+            return ((2.0 * u - 1.0) * self.radius, 1.0);
+        }
+
+        // 所在格子 i 满足 cdf[i] <= u < cdf[i + 1], 格子内均匀分布
+        let i = self.cdf.partition_point(|&c| c <= u).clamp(1, TABLE_SIZE) - 1;
+        let (lo, hi) = (self.cdf[i], self.cdf[i + 1]);
+        let cell = 2.0 * self.radius / TABLE_SIZE as f32;
+        let x = -self.radius + (i as f32 + (u - lo) / (hi - lo)) * cell;
+
+        // f / pdf 再除以 ∫f: pdf 在格子内为常数 |values[i]| / ∫|f|
+        let weight = evaluate_1d(self.kind, self.radius, x) / self.values[i].abs();
+
+        (x, weight * self.normalization)
+    }
+}
+
+/// 半径为 0.5 的盒式滤波, 与像素内均匀抖动相同
+impl Default for Filter {
+    fn default() -> Self {
+        Self::new(FilterKind::Box, FilterKind::Box.default_radius())
+    }
+}
+
+/// 一维滤波器在 `x` 处的值, 半径之外为 0
+fn evaluate_1d(kind: FilterKind, radius: f32, x: f32) -> f32 {
+    if x.abs() > radius {
+        return 0.0;
+    }
+
+    match kind {
+        FilterKind::Box => 1.0,
+        FilterKind::Triangle => radius - x.abs(),
+        FilterKind::Gaussian => {
+            // 减去半径处的值, 使边界连续
+            let sigma = radius / 3.0;
+            let gaussian = |x: f32| (-x * x / (2.0 * sigma * sigma)).exp();
+            gaussian(x) - gaussian(radius)
+        }
+        FilterKind::Mitchell => {
+            let (b, c) = (MITCHELL_B, MITCHELL_C);
+            let x = 2.0 * x.abs() / radius;
+            let polynomial = if x > 1.0 {
+                (-b - 6.0 * c) * x.powi(3) + (6.0 * b + 30.0 * c) * x * x
+                    - (12.0 * b + 48.0 * c) * x
+                    + (8.0 * b + 24.0 * c)
+            } else {
+                (12.0 - 9.0 * b - 6.0 * c) * x.powi(3)
+                    + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                    + (6.0 - 2.0 * b)
+            };
+            polynomial / 6.0
+        }
+        FilterKind::BlackmanHarris => {
+            let t = (x + radius) / (2.0 * radius);
+            0.35875 - 0.48829 * (2.0 * PI * t).cos() + 0.14128 * (4.0 * PI * t).cos()
+                - 0.01168 * (6.0 * PI * t).cos()
+        }
+    }
+}
@@ -0,0 +1,119 @@
+use crate::background::{Background, Environment};
+use crate::exposure::Exposure;
+use crate::filter::Filter;
+use crate::light::Lights;
+use crate::material::{Material, Parameter};
+use crate::output::BitDepth;
+use crate::precision::Precision;
+use crate::ray::{Ray, RayEpsilon};
+use crate::render::{BounceLimits, RenderSettings, ray_color};
+use crate::rng;
+use crate::sampler::SamplerKind;
+use crate::sampling::{random_in_unit_sphere, random_unit_vector};
+use crate::sphere::Sphere;
+
+use nalgebra::Vector3;
+use rayon::prelude::*;
+
+/// 每种材质追踪的路径数
+const PATHS: usize = 200_000;
+
+/// 允许的能量偏差
+const TOLERANCE: f32 = 0.005;
+
+/// 反照率全为白色的各种材质
+fn white_materials() -> Vec<(&'static str, Material)> {
+    let white = Vector3::new(1.0, 1.0, 1.0);
+
+    vec![
+        ("lambertian", Material::lambertian(white)),
+        ("metal", Material::metal(white, 0.0)),
+        ("metal (fuzz 1.0)", Material::metal(white, 1.0)),
+        ("dielectric", Material::dielectric(1.5)),
+        (
+            "car paint",
+            Material::car_paint(white, white, 0.3, 0.01, 0.2, 1.5),
+        ),
+        ("sheen", Material::sheen(white, white, 0.3)),
+        (
+            "principled",
+            Material::principled(
+                white,
+                Parameter::Constant(0.5),
+                Parameter::Constant(0.0),
+                Parameter::Constant(0.5),
+            ),
+        ),
+        (
+            "principled (metal)",
+            Material::principled(
+                white,
+                Parameter::Constant(0.5),
+                Parameter::Constant(1.0),
+                Parameter::Constant(0.5),
+            ),
+        ),
+        (
+            "hair",
+            Material::hair(Vector3::zeros(), 0.3, 0.3, 0.035, 1.55),
+        ),
+    ]
+}
+
+/// 白炉测试: 在均匀白色环境中, 白色材质的球体应与背景无法区分 (结果收敛到 1),
+/// 偏离说明材质丢失或凭空产生了能量, 返回是否全部通过
+pub fn run(max_depth: usize) -> bool {
+    let settings = RenderSettings {
+        max_depth,
+        min_throughput: 0.0,
+        specular_depth: max_depth,
+        bounce_limits: BounceLimits::UNLIMITED,
+        clamp_direct: None,
+        clamp_indirect: None,
+        exposure: Exposure::Manual(0.0),
+        clay: false,
+        environment: Environment::new(Background::Uniform(Vector3::new(1.0, 1.0, 1.0))),
+        lights: Lights::default(),
+        sampler: SamplerKind::Stratified,
+        filter: Filter::default(),
+        cp_rotation: false,
+        precision: Precision::F32,
+        bit_depth: BitDepth::Eight,
+        dither: false,
+        transparent: false,
+        partial_write: None,
+        epsilon: RayEpsilon::DEFAULT,
+    };
+
+    let mut passed = true;
+    for (name, material) in white_materials() {
+        let sphere = Sphere::from(Vector3::zeros(), 1.0, material);
+
+        // 从球外各个方向射向球内的光线
+        let sum = (0..PATHS)
+            .into_par_iter()
+            .map(|_| {
+                let mut rng = rng::local();
+                let origin = 3.0 * random_unit_vector(&mut rng);
+                let target = 0.9 * random_in_unit_sphere(&mut rng);
+
+                ray_color(Ray::from(origin, target - origin), &sphere, &settings)
+            })
+            .reduce(Vector3::zeros, |a, b| a + b);
+
+        let mean = sum / PATHS as f32;
+        let ok = mean.iter().all(|c| (c - 1.0).abs() < TOLERANCE);
+        passed &= ok;
+
+        println!(
+            "{:<18} {:.4} {:.4} {:.4}  {}",
+            name,
+            mean.x,
+            mean.y,
+            mean.z,
+            if ok { "ok" } else { "FAILED" }
+        );
+    }
+
+    passed
+}
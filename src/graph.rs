@@ -0,0 +1,421 @@
+use std::sync::Arc;
+
+use crate::bvh::Bounded;
+use crate::camera::Camera;
+use crate::capsule::Capsule;
+use crate::cone::Cone;
+use crate::curve::{Curve, Hair};
+use crate::ellipsoid::Ellipsoid;
+use crate::heightfield::Heightfield;
+use crate::instance::Instance;
+use crate::material::Material;
+use crate::material_library::MaterialLibrary;
+use crate::mesh::{Mesh, TriangleMesh};
+use crate::plane::Plane;
+use crate::quad::Quad;
+use crate::quadric::Quadric;
+use crate::scene::SceneObject;
+use crate::sdf::Sdf;
+use crate::sphere::Sphere;
+use crate::transformed::Transformed;
+use crate::triangle::Triangle;
+
+use nalgebra::{Point3, Similarity3, Vector3};
+
+/// 场景图中结点的编号
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct NodeId(usize);
+
+/// 附着在结点上的相机镜头: 在结点的局部坐标系中位于原点, 朝 -z 方向看, y 轴向上
+#[derive(Clone, Copy, Debug)]
+pub struct Lens {
+    /// 垂直视角 (度)
+    pub vertical_fov: f32,
+
+    /// 光圈直径
+    pub aperture: f32,
+
+    /// 对焦距离 (局部坐标系中的长度)
+    pub focus_dist: f32,
+}
+
+/// 可附着在结点上的实体
+#[derive(Clone)]
+pub enum Primitive {
+    /// 独立的球体
+    Sphere(Sphere),
+
+    /// 共享几何体的实例
+    Instance(Instance),
+
+    /// 二次曲面
+    Quadric(Quadric),
+
+    /// 三角形
+    Triangle(Triangle),
+
+    /// 四边形
+    Quad(Quad),
+
+    /// 平面
+    Plane(Plane),
+
+    /// 圆锥
+    Cone(Cone),
+
+    /// 胶囊体
+    Capsule(Capsule),
+
+    /// 椭球
+    Ellipsoid(Ellipsoid),
+
+    /// 有符号距离场
+    Sdf(Sdf),
+
+    /// 三次贝塞尔曲线
+    Curve(Curve),
+
+    /// 毛发, 曲线在各结点间共享; 变换为恒等时直接使用已构建的加速结构
+    Hair(Arc<Hair>),
+
+    /// 高度场, 网格数据在各结点间共享; 变换不为恒等时经 `Transformed` 放置
+    Heightfield(Heightfield),
+
+    /// 三角形网格, 顶点缓冲在各结点间共享; 变换为恒等时直接使用已构建的加速结构
+    Mesh(Arc<Mesh>),
+}
+
+impl Primitive {
+    /// 经相似变换后的实体
+    fn transformed(&self, transform: &Similarity3<f32>) -> Arc<dyn Bounded + Sync + Send> {
+        match self {
+            Self::Sphere(sphere) => Arc::new(sphere.transformed(transform)),
+            Self::Instance(instance) => Arc::new(instance.transformed(transform)),
+            Self::Quadric(quadric) => Arc::new(quadric.transformed(transform)),
+            Self::Triangle(triangle) => Arc::new(triangle.transformed(transform)),
+            Self::Quad(quad) => Arc::new(quad.transformed(transform)),
+            Self::Plane(plane) => Arc::new(plane.transformed(transform)),
+            Self::Cone(cone) => Arc::new(cone.transformed(transform)),
+            Self::Capsule(capsule) => Arc::new(capsule.transformed(transform)),
+            Self::Ellipsoid(ellipsoid) => Arc::new(ellipsoid.transformed(transform)),
+            Self::Sdf(sdf) => Arc::new(sdf.transformed(transform)),
+            Self::Curve(curve) => Arc::new(curve.transformed(transform)),
+            Self::Hair(hair) if *transform == Similarity3::identity() => hair.clone(),
+            Self::Hair(hair) => Arc::new(hair.transformed(transform)),
+            Self::Heightfield(field) if *transform == Similarity3::identity() => {
+                Arc::new(field.clone())
+            }
+            Self::Heightfield(field) => Arc::new(Transformed::similar(field.clone(), transform)),
+            Self::Mesh(mesh) if *transform == Similarity3::identity() => mesh.clone(),
+            Self::Mesh(mesh) => Arc::new(Mesh::new(mesh.source().transformed(transform))),
+        }
+    }
+}
+
+impl From<Sphere> for Primitive {
+    fn from(sphere: Sphere) -> Self {
+        Self::Sphere(sphere)
+    }
+}
+
+impl From<Instance> for Primitive {
+    fn from(instance: Instance) -> Self {
+        Self::Instance(instance)
+    }
+}
+
+impl From<Quadric> for Primitive {
+    fn from(quadric: Quadric) -> Self {
+        Self::Quadric(quadric)
+    }
+}
+
+impl From<Triangle> for Primitive {
+    fn from(triangle: Triangle) -> Self {
+        Self::Triangle(triangle)
+    }
+}
+
+impl From<Quad> for Primitive {
+    fn from(quad: Quad) -> Self {
+        Self::Quad(quad)
+    }
+}
+
+impl From<Plane> for Primitive {
+    fn from(plane: Plane) -> Self {
+        Self::Plane(plane)
+    }
+}
+
+impl From<Cone> for Primitive {
+    fn from(cone: Cone) -> Self {
+        Self::Cone(cone)
+    }
+}
+
+impl From<Capsule> for Primitive {
+    fn from(capsule: Capsule) -> Self {
+        Self::Capsule(capsule)
+    }
+}
+
+impl From<Ellipsoid> for Primitive {
+    fn from(ellipsoid: Ellipsoid) -> Self {
+        Self::Ellipsoid(ellipsoid)
+    }
+}
+
+impl From<Sdf> for Primitive {
+    fn from(sdf: Sdf) -> Self {
+        Self::Sdf(sdf)
+    }
+}
+
+impl From<Curve> for Primitive {
+    fn from(curve: Curve) -> Self {
+        Self::Curve(curve)
+    }
+}
+
+impl From<Hair> for Primitive {
+    fn from(hair: Hair) -> Self {
+        Self::Hair(Arc::new(hair))
+    }
+}
+
+impl From<Heightfield> for Primitive {
+    fn from(field: Heightfield) -> Self {
+        Self::Heightfield(field)
+    }
+}
+
+impl From<TriangleMesh> for Primitive {
+    fn from(mesh: TriangleMesh) -> Self {
+        Self::Mesh(Arc::new(Mesh::new(mesh)))
+    }
+}
+
+impl From<Mesh> for Primitive {
+    fn from(mesh: Mesh) -> Self {
+        Self::Mesh(Arc::new(mesh))
+    }
+}
+
+/// 场景图的结点
+struct Node {
+    /// 名称
+    name: String,
+
+    /// 父结点, 根结点没有父结点
+    parent: Option<NodeId>,
+
+    /// 相对父结点的变换
+    transform: Similarity3<f32>,
+
+    /// 附着的实体 (自发光的实体即为光源) 及其名称, 引用具名材质时带有其在材质库中的下标
+    primitives: Vec<(String, Primitive, Option<usize>)>,
+
+    /// 附着的相机
+    lens: Option<Lens>,
+}
+
+/// 层级式场景图: 具名的结点组成一棵树, 每个结点带有相对父结点的变换,
+/// 可附着实体与相机; 构建加速结构前展开为世界坐标中的实体列表
+///
+/// 场景图带有一个材质库, 以 `attach_with_material` 附着的实体引用其中的具名材质,
+/// 展开后以 `materials` 的材质表替换场景的材质, 修改一条定义即作用于所有引用它的实体
+pub struct SceneGraph {
+    nodes: Vec<Node>,
+    materials: MaterialLibrary,
+}
+
+impl Default for SceneGraph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SceneGraph {
+    /// 根结点
+    pub const ROOT: NodeId = NodeId(0);
+
+    /// 只有根结点的场景图
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                name: String::new(),
+                parent: None,
+                transform: Similarity3::identity(),
+                primitives: vec![],
+                lens: None,
+            }],
+            materials: MaterialLibrary::new(),
+        }
+    }
+
+    /// 在 `parent` 下添加一个结点
+    pub fn add_node(
+        &mut self,
+        parent: NodeId,
+        name: impl Into<String>,
+        transform: Similarity3<f32>,
+    ) -> NodeId {
+        self.nodes.push(Node {
+            name: name.into(),
+            parent: Some(parent),
+            transform,
+            primitives: vec![],
+            lens: None,
+        });
+
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// 设置结点相对父结点的变换, 子树随之移动 (如关节的转动)
+    pub fn set_transform(&mut self, node: NodeId, transform: Similarity3<f32>) {
+        self.nodes[node.0].transform = transform;
+    }
+
+    /// 在结点上附着一个实体, 其坐标位于结点的局部坐标系中
+    pub fn attach(
+        &mut self,
+        node: NodeId,
+        name: impl Into<String>,
+        primitive: impl Into<Primitive>,
+    ) {
+        self.nodes[node.0]
+            .primitives
+            .push((name.into(), primitive.into(), None));
+    }
+
+    /// 在材质库中定义名为 `name` 的材质, 名称已定义时替换原定义
+    pub fn define_material(&mut self, name: impl Into<String>, material: Material) {
+        self.materials.define(name, material);
+    }
+
+    /// 材质库中名为 `name` 的材质, 用于构建引用它的实体
+    pub fn material(&self, name: &str) -> Option<Material> {
+        self.materials.get(name)
+    }
+
+    /// 材质库, 展开后其材质表即场景的材质表
+    pub const fn materials(&self) -> &MaterialLibrary {
+        &self.materials
+    }
+
+    /// 在结点上附着一个引用材质库中具名材质 `material` 的实体, 材质未定义时返回 `false`
+    ///
+    /// 实体着色时使用材质库中的定义; 光源按实体自身的材质采样, 自发光的实体应以 `attach` 附着
+    pub fn attach_with_material(
+        &mut self,
+        node: NodeId,
+        name: impl Into<String>,
+        primitive: impl Into<Primitive>,
+        material: &str,
+    ) -> bool {
+        let Some(index) = self.materials.index(material) else {
+            return false;
+        };
+        self.nodes[node.0]
+            .primitives
+            .push((name.into(), primitive.into(), Some(index)));
+
+        true
+    }
+
+    /// 在结点上附着相机
+    pub fn attach_camera(&mut self, node: NodeId, lens: Lens) {
+        self.nodes[node.0].lens = Some(lens);
+    }
+
+    /// 结点的路径, 由根结点以下各级的名称以 `/` 连接, 根结点为空字符串
+    pub fn path(&self, node: NodeId) -> String {
+        let mut names = vec![];
+        let mut current = Some(node);
+        while let Some(id) = current {
+            let node = &self.nodes[id.0];
+            if node.parent.is_some() {
+                names.push(node.name.as_str());
+            }
+            current = node.parent;
+        }
+        names.reverse();
+
+        names.join("/")
+    }
+
+    /// 按路径查找结点
+    pub fn find(&self, path: &str) -> Option<NodeId> {
+        (0..self.nodes.len())
+            .map(NodeId)
+            .find(|&id| self.path(id) == path)
+    }
+
+    /// 结点的局部坐标系到世界坐标系的变换
+    pub fn world_transform(&self, node: NodeId) -> Similarity3<f32> {
+        let node = &self.nodes[node.0];
+
+        match node.parent {
+            Some(parent) => self.world_transform(parent) * node.transform,
+            None => node.transform,
+        }
+    }
+
+    /// 附着的实体在路径下的名称
+    fn qualified_name(&self, node: NodeId, name: &str) -> String {
+        let path = self.path(node);
+        if path.is_empty() {
+            name.to_string()
+        } else {
+            format!("{path}/{name}")
+        }
+    }
+
+    /// 展开为世界坐标中的实体列表, 实体以 `结点路径/实体名称` 命名,
+    /// 引用具名材质的实体带有其在材质库中的下标
+    pub fn flatten(&self) -> Vec<SceneObject> {
+        let mut objects = vec![];
+        for (index, node) in self.nodes.iter().enumerate() {
+            let id = NodeId(index);
+            let transform = self.world_transform(id);
+            for (name, primitive, material) in &node.primitives {
+                let mut object = SceneObject::new(
+                    self.qualified_name(id, name),
+                    primitive.transformed(&transform),
+                );
+                object.material = *material;
+                objects.push(object);
+            }
+        }
+
+        objects
+    }
+
+    /// 所有附着的相机及其所在结点的路径, `aspect` 为图像的宽高比
+    pub fn cameras(&self, aspect: f32) -> Vec<(String, Camera)> {
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter_map(|(index, node)| {
+                let lens = node.lens?;
+                let id = NodeId(index);
+                let transform = self.world_transform(id);
+                let look_from = transform.transform_point(&Point3::origin());
+                let look_at = transform.transform_point(&Point3::new(0.0, 0.0, -1.0));
+                let view_up = transform.transform_vector(&Vector3::y());
+
+                let camera = Camera::from(
+                    look_from.coords,
+                    look_at.coords,
+                    view_up,
+                    lens.vertical_fov,
+                    aspect,
+                    lens.aperture,
+                    lens.focus_dist * transform.scaling(),
+                );
+                Some((self.path(id), camera))
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,222 @@
+use std::sync::Arc;
+
+use crate::accelerator::Accelerator;
+use crate::bvh::{AaBb, Bounded, ClosestPoint, closest_among};
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+
+use nalgebra::Vector3;
+
+/// 平均每个格子包含的实体数
+const OBJECTS_PER_CELL: f32 = 2.0;
+
+/// 每个轴上最多的格子数
+const MAX_RESOLUTION: usize = 128;
+
+/// 包围盒最长边超过中位数的多少倍时, 实体不放入网格 (如作为地面的大球)
+const OVERSIZED_FACTOR: f32 = 16.0;
+
+/// 实体列表
+type Objects = Vec<Arc<dyn Bounded + Sync + Send>>;
+
+/// 将实体分为过大的 (包围盒最长边超过中位数的 `OVERSIZED_FACTOR` 倍) 与其余的两组
+pub(crate) fn partition_oversized(objects: Objects) -> (Objects, Objects) {
+    let longest_edge = |obj: &Arc<dyn Bounded + Sync + Send>| {
+        let bbox = obj.bounding_box();
+        (bbox.max - bbox.min).max()
+    };
+    let mut edges: Vec<f32> = objects.iter().map(longest_edge).collect();
+    edges.sort_by(f32::total_cmp);
+    let threshold = edges.get(edges.len() / 2).copied().unwrap_or(0.0) * OVERSIZED_FACTOR;
+
+    objects
+        .into_iter()
+        .partition(|obj| longest_edge(obj) > threshold)
+}
+
+/// 均匀网格
+pub struct UniformGrid {
+    /// 网格中的实体
+    objects: Vec<Arc<dyn Bounded + Sync + Send>>,
+
+    /// 过大而不放入网格的实体, 每条光线都与之求交
+    oversized: Vec<Arc<dyn Bounded + Sync + Send>>,
+
+    /// 网格的包围盒
+    bounds: AaBb,
+
+    /// 各轴上的格子数
+    resolution: [usize; 3],
+
+    /// 格子的尺寸
+    cell_size: Vector3<f32>,
+
+    /// 每个格子中实体的下标, 按 x, y, z 的顺序展开
+    cells: Vec<Vec<usize>>,
+}
+
+impl UniformGrid {
+    /// 构建均匀网格, 格子数与实体数成正比, 格子尽量接近立方体
+    pub fn build(objects: Vec<Arc<dyn Bounded + Sync + Send>>) -> Self {
+        // 分出过大的实体, 以免网格范围被其撑大
+        let (oversized, objects) = partition_oversized(objects);
+
+        let bounds = AaBb::all_surrounding_box(&objects);
+        let extent = (bounds.max - bounds.min).map(|d| d.max(f32::EPSILON));
+
+        // 每单位长度的格子数
+        let volume = extent.x * extent.y * extent.z;
+        let density = (objects.len() as f32 / OBJECTS_PER_CELL / volume).cbrt();
+        let resolution: [usize; 3] = std::array::from_fn(|axis| {
+            ((extent[axis] * density).round() as usize).clamp(1, MAX_RESOLUTION)
+        });
+        let cell_size = Vector3::from_fn(|axis, _| extent[axis] / resolution[axis] as f32);
+
+        let mut grid = Self {
+            objects: vec![],
+            oversized,
+            bounds,
+            resolution,
+            cell_size,
+            cells: vec![vec![]; resolution.iter().product()],
+        };
+
+        // 将实体放入其包围盒覆盖的所有格子
+        for (index, obj) in objects.iter().enumerate() {
+            let bbox = obj.bounding_box();
+            let lo = grid.cell_of(&bbox.min);
+            let hi = grid.cell_of(&bbox.max);
+            for z in lo[2]..=hi[2] {
+                for y in lo[1]..=hi[1] {
+                    for x in lo[0]..=hi[0] {
+                        let cell = grid.cell_index([x, y, z]);
+                        grid.cells[cell].push(index);
+                    }
+                }
+            }
+        }
+        grid.objects = objects;
+
+        grid
+    }
+
+    /// 点所在的格子
+    fn cell_of(&self, point: &Vector3<f32>) -> [usize; 3] {
+        std::array::from_fn(|axis| {
+            let offset = (point[axis] - self.bounds.min[axis]) / self.cell_size[axis];
+            (offset.max(0.0) as usize).min(self.resolution[axis] - 1)
+        })
+    }
+
+    /// 格子在 `cells` 中的下标
+    const fn cell_index(&self, [x, y, z]: [usize; 3]) -> usize {
+        (z * self.resolution[1] + y) * self.resolution[0] + x
+    }
+
+    /// 光线进入与离开网格时的参数, 未穿过网格时为 `None`
+    fn clip(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, f32)> {
+        let inv_d = ray.inv_direction();
+        let t0s = (self.bounds.min - ray.origin()).component_mul(&inv_d);
+        let t1s = (self.bounds.max - ray.origin()).component_mul(&inv_d);
+
+        let t_enter = t0s.zip_map(&t1s, f32::min).max().max(t_min);
+        let t_exit = t0s.zip_map(&t1s, f32::max).min().min(t_max);
+
+        (t_enter <= t_exit).then_some((t_enter, t_exit))
+    }
+}
+
+impl Hittable for UniformGrid {
+    /// 以 3D-DDA 逐格遍历光线经过的格子
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let mut closest_hit = self.oversized.hit(ray, t_min, t_max);
+        let mut closest = closest_hit.as_ref().map_or(t_max, |hit| hit.distance);
+
+        let Some((t_enter, t_exit)) = self.clip(ray, t_min, closest) else {
+            return closest_hit;
+        };
+        let direction = ray.direction();
+
+        // 起始格子, 以及各轴上到下一个格子边界的参数和跨越一个格子的参数增量
+        let mut cell = self.cell_of(&ray.point_at_t(t_enter));
+        let mut t_next = [f32::INFINITY; 3];
+        let mut t_delta = [f32::INFINITY; 3];
+        let mut step = [0_isize; 3];
+        for axis in 0..3 {
+            if direction[axis] > 0.0 {
+                let boundary =
+                    self.bounds.min[axis] + (cell[axis] + 1) as f32 * self.cell_size[axis];
+                t_next[axis] = (boundary - ray.origin()[axis]) / direction[axis];
+                t_delta[axis] = self.cell_size[axis] / direction[axis];
+                step[axis] = 1;
+            } else if direction[axis] < 0.0 {
+                let boundary = self.bounds.min[axis] + cell[axis] as f32 * self.cell_size[axis];
+                t_next[axis] = (boundary - ray.origin()[axis]) / direction[axis];
+                t_delta[axis] = -self.cell_size[axis] / direction[axis];
+                step[axis] = -1;
+            }
+        }
+
+        loop {
+            for &index in &self.cells[self.cell_index(cell)] {
+                if let Some(hit) = self.objects[index].hit(ray, t_min, closest) {
+                    closest = hit.distance;
+                    closest_hit = Some(hit);
+                }
+            }
+
+            // 交点位于当前格子之内时, 之后的格子不会有更近的交点
+            let axis = (0..3)
+                .min_by(|&a, &b| t_next[a].total_cmp(&t_next[b]))
+                .unwrap_or(0);
+            if closest <= t_next[axis] || t_next[axis] > t_exit {
+                return closest_hit;
+            }
+
+            // 前进到下一个格子
+            let next = cell[axis] as isize + step[axis];
+            if next < 0 || next >= self.resolution[axis] as isize {
+                return closest_hit;
+            }
+            cell[axis] = next as usize;
+            t_next[axis] += t_delta[axis];
+        }
+    }
+}
+
+impl Accelerator for UniformGrid {
+    /// 网格的包围盒, 以及所有非空格子
+    fn boxes(&self) -> Vec<(AaBb, usize)> {
+        let mut boxes = vec![(self.bounds.clone(), 0)];
+
+        for z in 0..self.resolution[2] {
+            for y in 0..self.resolution[1] {
+                for x in 0..self.resolution[0] {
+                    if self.cells[self.cell_index([x, y, z])].is_empty() {
+                        continue;
+                    }
+                    let min = self.bounds.min
+                        + Vector3::new(x as f32, y as f32, z as f32).component_mul(&self.cell_size);
+                    boxes.push((
+                        AaBb {
+                            min,
+                            max: min + self.cell_size,
+                        },
+                        1,
+                    ));
+                }
+            }
+        }
+
+        boxes
+    }
+
+    /// 逐个比较所有实体
+    fn closest_point(&self, point: &Vector3<f32>) -> Option<ClosestPoint> {
+        let mut best = None;
+        closest_among(&self.oversized, point, &mut best);
+        closest_among(&self.objects, point, &mut best);
+
+        best
+    }
+}
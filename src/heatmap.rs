@@ -0,0 +1,64 @@
+use nalgebra::Vector3;
+
+/// 热力图色阶的控制点, 从最快到最慢: 黑, 红, 黄, 白
+const RAMP: [Vector3<f32>; 4] = [
+    Vector3::new(0.0, 0.0, 0.0),
+    Vector3::new(0.8, 0.0, 0.0),
+    Vector3::new(1.0, 0.8, 0.0),
+    Vector3::new(1.0, 1.0, 1.0),
+];
+
+/// 最慢的图块
+pub struct SlowestTile {
+    /// 左上角的像素坐标 (首行为图像顶部)
+    pub x: usize,
+    pub y: usize,
+
+    /// 渲染耗时 (秒)
+    pub time: f32,
+}
+
+/// 色阶上 `t` (0 到 1) 处的颜色
+fn ramp(t: f32) -> [u8; 3] {
+    let position = t.clamp(0.0, 1.0) * (RAMP.len() - 1) as f32;
+    let index = (position as usize).min(RAMP.len() - 2);
+    let color = RAMP[index].lerp(&RAMP[index + 1], position - index as f32);
+
+    [0, 1, 2].map(|c| (255.99 * color[c]) as u8)
+}
+
+/// 按每个像素的耗时 `times` (首行为图像顶部) 绘制耗时热力图: 每个边长为 `tile` 的图块
+/// 填充一种颜色, 亮度表示其耗时相对最慢图块的比例; 同时返回最慢的图块
+pub fn render(times: &[f32], nx: usize, ny: usize, tile: usize) -> (Vec<u8>, SlowestTile) {
+    let (tx, ty) = (nx.div_ceil(tile), ny.div_ceil(tile));
+
+    // 各图块的总耗时
+    let mut tiles = vec![0.0; tx * ty];
+    for (offset, time) in times.iter().enumerate() {
+        let (x, y) = (offset % nx, offset / nx);
+        tiles[(y / tile) * tx + x / tile] += time;
+    }
+
+    let (slowest, &max) = tiles
+        .iter()
+        .enumerate()
+        .max_by(|a, b| a.1.total_cmp(b.1))
+        .unwrap_or((0, &0.0));
+
+    let mut pixels = vec![0; 3 * nx * ny];
+    for (offset, pixel) in pixels.chunks_exact_mut(3).enumerate() {
+        let (x, y) = (offset % nx, offset / nx);
+        let time = tiles[(y / tile) * tx + x / tile];
+        let t = if max > 0.0 { time / max } else { 0.0 };
+        pixel.copy_from_slice(&ramp(t));
+    }
+
+    (
+        pixels,
+        SlowestTile {
+            x: (slowest % tx) * tile,
+            y: (slowest / tx) * tile,
+            time: max,
+        },
+    )
+}
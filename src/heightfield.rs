@@ -0,0 +1,443 @@
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::error::{self, Error};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::texture::{self, ColorSpace};
+use crate::triangle;
+
+use nalgebra::{Vector2, Vector3};
+
+/// 四叉树的最大层数, 限定遍历栈的大小 (可容纳边长 2³¹ 个格子的网格)
+const MAX_LEVELS: usize = 32;
+
+/// 高度场: 规则网格上的高度值, 每个格子沿对角线分为两个三角形, 用于地形
+///
+/// 顶点沿 x 轴排成 `columns` 列, 沿 z 轴排成若干行, 按行存储; 法线由相邻顶点的高度差插值得到,
+/// 纹理坐标铺满整个网格. 以格子高度的最小最大值四叉树加速: 光线自根结点由近及远下降,
+/// 只与被穿过的包围盒中的格子求交
+#[derive(Clone)]
+pub struct Heightfield {
+    /// 网格数据, 在变换后的副本间共享
+    grid: Arc<Grid>,
+
+    /// 材质
+    material: Material,
+}
+
+/// 高度场的网格与四叉树
+struct Grid {
+    /// 每行的顶点数
+    columns: usize,
+
+    /// 行数
+    rows: usize,
+
+    /// 网格在最小 x, z 处的角点, y 为高度的基准
+    corner: Vector3<f32>,
+
+    /// 一个格子沿 x 与 z 轴的边长
+    cell: Vector2<f32>,
+
+    /// 各顶点的高度 (世界坐标中的 y)
+    heights: Vec<f32>,
+
+    /// 各顶点的法线
+    normals: Vec<Vector3<f32>>,
+
+    /// 四叉树各层结点的高度范围, 第 0 层为单个格子, 每层的边长减半, 末层只有根结点
+    levels: Vec<Level>,
+}
+
+/// 四叉树的一层
+struct Level {
+    /// 沿 x 轴的结点数
+    width: usize,
+
+    /// 各结点覆盖的格子中高度的最小值与最大值
+    ranges: Vec<(f32, f32)>,
+}
+
+impl Heightfield {
+    /// 由按行存储的高度 `heights` (每行 `columns` 个, 沿 z 轴排列的行) 构建高度场,
+    /// 网格铺在以 `corner` 为角点, 沿 x 与 z 轴的大小为 `extent` 的矩形上, 高度为相对 `corner.y` 的偏移;
+    /// 少于两行两列或高度的个数不是 `columns` 的整数倍时为 `None`
+    pub fn new(
+        heights: &[f32],
+        columns: usize,
+        corner: Vector3<f32>,
+        extent: Vector2<f32>,
+        material: Material,
+    ) -> Option<Self> {
+        if columns < 2 || !heights.len().is_multiple_of(columns) || heights.len() / columns < 2 {
+            return None;
+        }
+        let rows = heights.len() / columns;
+        let cell = Vector2::new(
+            extent.x / (columns - 1) as f32,
+            extent.y / (rows - 1) as f32,
+        );
+        let heights: Vec<f32> = heights.iter().map(|h| corner.y + h).collect();
+
+        // 顶点法线由相邻顶点的中心差分 (边界处为单侧差分) 得到
+        let height = |i: usize, j: usize| heights[j * columns + i];
+        let normals = (0..rows)
+            .flat_map(|j| (0..columns).map(move |i| (i, j)))
+            .map(|(i, j)| {
+                let (left, right) = (i.saturating_sub(1), (i + 1).min(columns - 1));
+                let (back, front) = (j.saturating_sub(1), (j + 1).min(rows - 1));
+                let dx = (height(right, j) - height(left, j)) / ((right - left) as f32 * cell.x);
+                let dz = (height(i, front) - height(i, back)) / ((front - back) as f32 * cell.y);
+                Vector3::new(-dx, 1.0, -dz).normalize()
+            })
+            .collect();
+
+        let cells = (columns - 1, rows - 1);
+        let first = Level {
+            width: cells.0,
+            ranges: (0..cells.1)
+                .flat_map(|j| (0..cells.0).map(move |i| (i, j)))
+                .map(|(i, j)| {
+                    let corners = [
+                        height(i, j),
+                        height(i + 1, j),
+                        height(i, j + 1),
+                        height(i + 1, j + 1),
+                    ];
+                    corners
+                        .iter()
+                        .fold((f32::INFINITY, f32::NEG_INFINITY), |(lo, hi), &h| {
+                            (lo.min(h), hi.max(h))
+                        })
+                })
+                .collect(),
+        };
+        let mut levels = vec![first];
+        let mut size = cells;
+        while size.0 > 1 || size.1 > 1 {
+            let below = levels.last().unwrap();
+            let next = (size.0.div_ceil(2), size.1.div_ceil(2));
+            let ranges = (0..next.1)
+                .flat_map(|j| (0..next.0).map(move |i| (i, j)))
+                .map(|(i, j)| {
+                    let mut range = (f32::INFINITY, f32::NEG_INFINITY);
+                    for (ci, cj) in [(0, 0), (1, 0), (0, 1), (1, 1)] {
+                        let (x, z) = (2 * i + ci, 2 * j + cj);
+                        if x < size.0 && z < size.1 {
+                            let (lo, hi) = below.ranges[z * below.width + x];
+                            range = (range.0.min(lo), range.1.max(hi));
+                        }
+                    }
+                    range
+                })
+                .collect();
+            levels.push(Level {
+                width: next.0,
+                ranges,
+            });
+            size = next;
+        }
+
+        Some(Self {
+            grid: Arc::new(Grid {
+                columns,
+                rows,
+                corner,
+                cell,
+                heights,
+                normals,
+                levels,
+            }),
+            material,
+        })
+    }
+
+    /// 读取灰度高度图 (二进制 PPM, 按线性值解码, 可为 16 位), 像素的亮度乘以 `size.y` 为高度,
+    /// 图像铺在以 `corner` 为角点, 沿 x 与 z 轴的大小为 `size.x`, `size.z` 的矩形上 (首行在最小 z 处)
+    pub fn load(
+        path: impl AsRef<Path>,
+        corner: Vector3<f32>,
+        size: Vector3<f32>,
+        material: Material,
+    ) -> error::Result<Self> {
+        let path = path.as_ref();
+        let (width, _, texels) = texture::read_image(path, ColorSpace::Linear)?;
+        let heights: Vec<f32> = texels.iter().map(|texel| texel.mean() * size.y).collect();
+
+        Self::new(&heights, width, corner, size.xz(), material)
+            .ok_or_else(|| Error::invalid_data(path, "height map must be at least 2×2"))
+    }
+
+    /// 地表在 `(x, z)` 处的高度, 在网格之外时为 `None`
+    pub fn height_at(&self, x: f32, z: f32) -> Option<f32> {
+        let grid = &self.grid;
+        let u = (x - grid.corner.x) / grid.cell.x;
+        let v = (z - grid.corner.z) / grid.cell.y;
+        let (columns, rows) = ((grid.columns - 1) as f32, (grid.rows - 1) as f32);
+        if !(0.0..=columns).contains(&u) || !(0.0..=rows).contains(&v) {
+            return None;
+        }
+
+        let (i, j) = (
+            (u as usize).min(grid.columns - 2),
+            (v as usize).min(grid.rows - 2),
+        );
+        let (fu, fv) = (u - i as f32, v - j as f32);
+        let [a, b, c, d] = grid.cell_heights(i, j);
+
+        // 与求交所用的三角形一致: 对角线自 (i + 1, j) 到 (i, j + 1)
+        Some(if fu + fv <= 1.0 {
+            a + (b - a) * fu + (c - a) * fv
+        } else {
+            d + (c - d) * (1.0 - fu) + (b - d) * (1.0 - fv)
+        })
+    }
+}
+
+impl Grid {
+    /// 顶点 `(i, j)` 的下标
+    const fn index(&self, i: usize, j: usize) -> usize {
+        j * self.columns + i
+    }
+
+    /// 顶点 `(i, j)` 的位置
+    fn vertex(&self, i: usize, j: usize) -> Vector3<f32> {
+        Vector3::new(
+            self.corner.x + i as f32 * self.cell.x,
+            self.heights[self.index(i, j)],
+            self.corner.z + j as f32 * self.cell.y,
+        )
+    }
+
+    /// 格子 `(i, j)` 四个角的高度, 依次为 `(i, j)`, `(i + 1, j)`, `(i, j + 1)`, `(i + 1, j + 1)`
+    fn cell_heights(&self, i: usize, j: usize) -> [f32; 4] {
+        [(i, j), (i + 1, j), (i, j + 1), (i + 1, j + 1)]
+            .map(|(i, j)| self.heights[self.index(i, j)])
+    }
+
+    /// 第 `level` 层结点 `(i, j)` 的包围盒
+    fn node_box(&self, level: usize, i: usize, j: usize) -> AaBb {
+        let span = 1 << level;
+        let (x0, z0) = (i * span, j * span);
+        let (x1, z1) = (
+            (x0 + span).min(self.columns - 1),
+            (z0 + span).min(self.rows - 1),
+        );
+        let node = &self.levels[level];
+        let (lo, hi) = node.ranges[j * node.width + i];
+
+        AaBb {
+            min: Vector3::new(
+                self.corner.x + x0 as f32 * self.cell.x,
+                lo - triangle::MIN_THICKNESS,
+                self.corner.z + z0 as f32 * self.cell.y,
+            ),
+            max: Vector3::new(
+                self.corner.x + x1 as f32 * self.cell.x,
+                hi + triangle::MIN_THICKNESS,
+                self.corner.z + z1 as f32 * self.cell.y,
+            ),
+        }
+    }
+
+    /// 光线与格子 `(i, j)` 的两个三角形求交, 返回距离与插值的法线
+    fn hit_cell(
+        &self,
+        i: usize,
+        j: usize,
+        ray: &Ray,
+        t_min: f32,
+        t_max: f32,
+    ) -> Option<(f32, Vector3<f32>)> {
+        // 两个三角形都按逆时针朝上, 共用对角线 (i + 1, j)–(i, j + 1)
+        let triangles = [
+            [(i, j), (i, j + 1), (i + 1, j)],
+            [(i + 1, j + 1), (i + 1, j), (i, j + 1)],
+        ];
+
+        let mut closest = t_max;
+        let mut result = None;
+        for corners in triangles {
+            let vertices = corners.map(|(i, j)| self.vertex(i, j));
+            if let Some((t, u, v)) = triangle::intersect(&vertices, ray, t_min, closest) {
+                let [a, b, c] = corners.map(|(i, j)| self.normals[self.index(i, j)]);
+                closest = t;
+                result = Some((t, (a * (1.0 - u - v) + b * u + c * v).normalize()));
+            }
+        }
+
+        result
+    }
+}
+
+impl Hittable for Heightfield {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let grid = &self.grid;
+        let direction = ray.direction();
+        let flip = ((direction.x < 0.0) as usize, (direction.z < 0.0) as usize);
+        // 子结点由近及远的顺序: 先沿光线方向靠前的一侧
+        let order = [
+            (flip.0, flip.1),
+            (1 - flip.0, flip.1),
+            (flip.0, 1 - flip.1),
+            (1 - flip.0, 1 - flip.1),
+        ];
+
+        let mut stack = [(0, 0, 0); 3 * MAX_LEVELS + 1];
+        stack[0] = (grid.levels.len() - 1, 0, 0);
+        let mut depth = 1;
+        let mut closest = t_max;
+        let mut found = None;
+        while depth > 0 {
+            depth -= 1;
+            let (level, i, j) = stack[depth];
+            let (near, far) = grid.node_box(level, i, j).span(ray);
+            if near > far || near > closest || far < t_min {
+                continue;
+            }
+
+            if level == 0 {
+                if let Some((t, normal)) = grid.hit_cell(i, j, ray, t_min, closest) {
+                    closest = t;
+                    found = Some(normal);
+                }
+                continue;
+            }
+
+            // 逆序入栈, 使靠前的子结点先出栈
+            let below = &grid.levels[level - 1];
+            let height = below.ranges.len() / below.width;
+            for &(ci, cj) in order.iter().rev() {
+                let (x, z) = (2 * i + ci, 2 * j + cj);
+                if x < below.width && z < height {
+                    stack[depth] = (level - 1, x, z);
+                    depth += 1;
+                }
+            }
+        }
+
+        let normal = found?;
+        let position = ray.point_at_t(closest);
+        let extent = Vector2::new(
+            grid.cell.x * (grid.columns - 1) as f32,
+            grid.cell.y * (grid.rows - 1) as f32,
+        );
+        let uv = (
+            (position.x - grid.corner.x) / extent.x,
+            (position.z - grid.corner.z) / extent.y,
+        );
+        let width = ray.cone().width_at(closest * direction.magnitude());
+
+        Some(HitRecord {
+            distance: closest,
+            position,
+            normal,
+            uv,
+            footprint: width / extent.max(),
+            material: self.material,
+            object: 0,
+        })
+    }
+}
+
+impl Bounded for Heightfield {
+    fn bounding_box(&self) -> AaBb {
+        let grid = &self.grid;
+
+        grid.node_box(grid.levels.len() - 1, 0, 0)
+    }
+
+    /// 近似: 取点在网格上的竖直投影 (网格之外时先移到边界上), 位于地表以下时距离为负
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let bounds = self.bounding_box();
+        let x = point.x.clamp(bounds.min.x, bounds.max.x);
+        let z = point.z.clamp(bounds.min.z, bounds.max.z);
+        let height = self.height_at(x, z).unwrap_or(bounds.min.y);
+        let position = Vector3::new(x, height, z);
+        let distance = (point - position).magnitude();
+
+        ClosestPoint {
+            object: 0,
+            position,
+            distance: if point.y < height {
+                -distance
+            } else {
+                distance
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 以原点为角点, 边长 2 × 2 的 3 × 3 个顶点
+    fn field(heights: &[f32; 9]) -> Heightfield {
+        Heightfield::new(
+            heights,
+            3,
+            Vector3::zeros(),
+            Vector2::new(2.0, 2.0),
+            Material::CLAY,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn height_at_interpolates_both_triangles_of_a_cell() {
+        // 格子 (0, 0) 的四个角不共面: (0, 0) = 0, (1, 0) = 1, (0, 1) = 2, (1, 1) = 5
+        let field = field(&[0.0, 1.0, 0.0, 2.0, 5.0, 0.0, 0.0, 0.0, 0.0]);
+
+        // 对角线自 (1, 0) 到 (0, 1), 两侧各取一点
+        assert_eq!(field.height_at(0.25, 0.25), Some(0.75));
+        assert_eq!(field.height_at(0.75, 0.75), Some(3.25));
+        assert_eq!(field.height_at(1.0, 1.0), Some(5.0));
+        assert_eq!(field.height_at(-0.1, 1.0), None);
+        assert_eq!(field.height_at(1.0, 2.1), None);
+    }
+
+    #[test]
+    fn ray_hits_the_cell_below_it() {
+        // 斜面 y = x
+        let field = field(&[0.0, 1.0, 2.0, 0.0, 1.0, 2.0, 0.0, 1.0, 2.0]);
+        let down = Vector3::new(0.0, -1.0, 0.0);
+
+        let hit = field
+            .hit(
+                &Ray::from(Vector3::new(1.5, 10.0, 0.5), down),
+                0.0,
+                f32::MAX,
+            )
+            .unwrap();
+        assert!((hit.distance - 8.5).abs() < 1e-5);
+        assert!((hit.position - Vector3::new(1.5, 1.5, 0.5)).norm() < 1e-5);
+        assert!((hit.normal - Vector3::new(-1.0, 1.0, 0.0).normalize()).norm() < 1e-5);
+        assert!((hit.uv.0 - 0.75).abs() < 1e-5 && (hit.uv.1 - 0.25).abs() < 1e-5);
+
+        let outside = Ray::from(Vector3::new(2.5, 10.0, 0.5), down);
+        assert!(field.hit(&outside, 0.0, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn new_rejects_degenerate_grids() {
+        let new = |heights: &[f32], columns| {
+            Heightfield::new(
+                heights,
+                columns,
+                Vector3::zeros(),
+                Vector2::new(1.0, 1.0),
+                Material::CLAY,
+            )
+        };
+
+        assert!(new(&[0.0; 4], 2).is_some());
+        assert!(new(&[0.0; 4], 1).is_none());
+        assert!(new(&[0.0; 3], 3).is_none());
+        assert!(new(&[0.0; 5], 2).is_none());
+        assert!(new(&[], 2).is_none());
+    }
+}
@@ -16,8 +16,17 @@ pub struct HitRecord {
     /// 交点处的表面法线
     pub normal: Vector3<f32>,
 
+    /// 交点处的纹理坐标
+    pub uv: (f32, f32),
+
+    /// 光线锥在交点处覆盖的纹理坐标宽度, 用于纹理过滤
+    pub footprint: f32,
+
     /// 交点处的材质
     pub material: Material,
+
+    /// 被击中实体在场景中的编号, 由 `Scene` 赋值
+    pub object: usize,
 }
 
 /// 可被光线击中
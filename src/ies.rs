@@ -0,0 +1,136 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::{self, Error};
+
+/// IES 配光曲线 (IESNA LM-63, C 类光度): 灯具在各方向上的发光强度
+///
+/// 竖直角 0° 为灯具正下方 (天底), 180° 为正上方; 水平角绕竖直轴从 0° 量到 360°。
+/// 强度以峰值归一化, 灯光的亮度由灯光自身的强度决定
+pub struct IesProfile {
+    /// 竖直角 (度), 递增
+    vertical: Vec<f32>,
+
+    /// 水平角 (度), 递增
+    horizontal: Vec<f32>,
+
+    /// 各水平角下各竖直角的相对强度, `values[h * vertical.len() + v]`
+    values: Vec<f32>,
+}
+
+impl IesProfile {
+    /// 读取 `.ies` 文件, 只支持 C 类光度; `TILT` 数据被跳过
+    pub fn load(path: impl AsRef<Path>) -> error::Result<Self> {
+        let path = path.as_ref();
+        let data = fs::read(path)?;
+        let text = String::from_utf8_lossy(&data);
+
+        Self::parse(&text).map_err(|msg| Error::invalid_data(path, msg))
+    }
+
+    /// 解析文件内容
+    fn parse(text: &str) -> Result<Self, &'static str> {
+        // 文件头: `TILT=` 之前为关键字行
+        let tilt_start = text.find("TILT=").ok_or("missing `TILT=` line")?;
+        let tilt_line = text[tilt_start..].lines().next().unwrap_or_default();
+        let mut numbers = text[tilt_start + tilt_line.len()..]
+            .split(|c: char| c.is_ascii_whitespace() || c == ',')
+            .filter(|field| !field.is_empty())
+            .map(|field| field.parse::<f32>().map_err(|_| "expected a number"));
+        let mut next = || numbers.next().unwrap_or(Err("unexpected end of file"));
+
+        // 灯具内的倾斜数据: 几何类型, 角度数, 角度与倍数
+        if tilt_line.trim_end() == "TILT=INCLUDE" {
+            next()?;
+            let count = next()? as usize;
+            for _ in 0..2 * count {
+                next()?;
+            }
+        }
+
+        // 灯数, 每灯流明, 强度倍数 (归一化后无关), 竖直角数, 水平角数, 光度类型
+        let [_, _, _, vertical_count, horizontal_count, photometric_type] =
+            [(); 6].map(|()| next());
+        let vertical_count = vertical_count? as usize;
+        let horizontal_count = horizontal_count? as usize;
+        if photometric_type? != 1.0 {
+            return Err("only type C photometry is supported");
+        }
+        if vertical_count == 0 || horizontal_count == 0 {
+            return Err("no angles");
+        }
+        // 单位, 宽, 长, 高, 镇流器系数, 保留字段, 输入功率
+        for _ in 0..7 {
+            next()?;
+        }
+
+        let mut read = |count: usize| (0..count).map(|_| next()).collect::<Result<Vec<_>, _>>();
+        let vertical = read(vertical_count)?;
+        let horizontal = read(horizontal_count)?;
+        let values = read(vertical_count * horizontal_count)?;
+        if !vertical.is_sorted() || !horizontal.is_sorted() {
+            return Err("angles are not increasing");
+        }
+
+        let peak = values.iter().copied().fold(0.0, f32::max);
+        if peak <= 0.0 {
+            return Err("no positive candela value");
+        }
+
+        Ok(Self {
+            vertical,
+            horizontal,
+            values: values.iter().map(|value| value.max(0.0) / peak).collect(),
+        })
+    }
+
+    /// 竖直角 `theta` 与水平角 `phi` (均为度) 方向上的相对强度, 在表中双线性插值
+    ///
+    /// 表只覆盖部分水平角时按文件的对称性展开: 只有一个水平角时绕竖直轴对称,
+    /// 止于 90° 时四个象限对称, 止于 180° 时关于 0°–180° 平面对称
+    pub fn intensity(&self, theta: f32, phi: f32) -> f32 {
+        let (first, last) = (self.vertical[0], self.vertical[self.vertical.len() - 1]);
+        if theta < first || theta > last {
+            return 0.0;
+        }
+
+        let phi = phi.rem_euclid(360.0);
+        let span = self.horizontal[self.horizontal.len() - 1] - self.horizontal[0];
+        let phi = if self.horizontal.len() == 1 {
+            self.horizontal[0]
+        } else if span <= 90.0 {
+            let phi = if phi > 180.0 { 360.0 - phi } else { phi };
+            if phi > 90.0 { 180.0 - phi } else { phi }
+        } else if span <= 180.0 {
+            if phi > 180.0 { 360.0 - phi } else { phi }
+        } else {
+            phi
+        };
+
+        let (v, tv) = interval(&self.vertical, theta);
+        let (h, th) = interval(&self.horizontal, phi);
+        let n = self.vertical.len();
+        let at = |h: usize, v: usize| self.values[h * n + v];
+        let row = |h: usize| {
+            let v1 = (v + 1).min(n - 1);
+            at(h, v) * (1.0 - tv) + at(h, v1) * tv
+        };
+        let h1 = (h + 1).min(self.horizontal.len() - 1);
+
+        row(h) * (1.0 - th) + row(h1) * th
+    }
+}
+
+/// `x` 所在的区间的下标与区间内的比例, 超出范围时取端点
+fn interval(angles: &[f32], x: f32) -> (usize, f32) {
+    let upper = angles.partition_point(|&a| a <= x);
+    if upper == 0 {
+        return (0, 0.0);
+    }
+    if upper == angles.len() {
+        return (angles.len() - 1, 0.0);
+    }
+
+    let (a, b) = (angles[upper - 1], angles[upper]);
+    (upper - 1, (x - a) / (b - a))
+}
@@ -0,0 +1,119 @@
+use std::any::Any;
+use std::sync::Arc;
+
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sphere::Sphere;
+use crate::transformed::Transformed;
+
+use nalgebra::{Matrix4, Rotation3, Similarity3, Translation3, UnitQuaternion, Vector3};
+
+/// 共享几何体的实例: 任意几何体 (球体, 网格等) 经仿射变换后放置, 可覆盖几何体自身的材质
+///
+/// 大量相同形状的实体 (如上万棵相同的树) 只保存一份几何体及其加速结构, 每个实例只记录
+/// 自己的变换与覆盖材质在共享材质表中的下标
+#[derive(Clone)]
+pub struct Instance {
+    /// 经变换放置的共享几何体
+    placed: Transformed<Arc<dyn Bounded + Sync + Send>>,
+
+    /// 共享的材质表
+    materials: Arc<[Material]>,
+
+    /// 覆盖材质在材质表中的下标, 为 `None` 时使用几何体自身的材质
+    material: Option<u32>,
+}
+
+impl Instance {
+    /// 以仿射变换 `transform` 放置共享的几何体, 变换不可逆时为 `None`
+    pub fn new(geometry: Arc<dyn Bounded + Sync + Send>, transform: Matrix4<f32>) -> Option<Self> {
+        Some(Self {
+            placed: Transformed::new(geometry, transform)?,
+            materials: Arc::new([]),
+            material: None,
+        })
+    }
+
+    /// 以共享材质表中的第 `material` 项覆盖几何体的材质, 下标超出材质表时为 `None`
+    pub fn with_material(self, materials: Arc<[Material]>, material: usize) -> Option<Self> {
+        let material = u32::try_from(material)
+            .ok()
+            .filter(|&index| (index as usize) < materials.len())?;
+
+        Some(Self {
+            materials,
+            material: Some(material),
+            ..self
+        })
+    }
+
+    /// 共享的几何体
+    pub fn geometry(&self) -> &Arc<dyn Bounded + Sync + Send> {
+        self.placed.inner()
+    }
+
+    /// 覆盖材质
+    fn material(&self) -> Option<Material> {
+        self.material
+            .and_then(|index| self.materials.get(index as usize).copied())
+    }
+
+    /// 再经相似变换后的实例
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        Self {
+            placed: self.placed.transformed(transform),
+            ..self.clone()
+        }
+    }
+
+    /// 几何体为球体时展开为独立的球体 (如作为烘焙目标), 非均匀缩放时取平均半径
+    pub fn to_sphere(&self) -> Option<Sphere> {
+        let sphere = (self.geometry().as_ref() as &dyn Any).downcast_ref::<Sphere>()?;
+        let matrix = self.placed.matrix();
+        let linear = matrix.fixed_view::<3, 3>(0, 0).into_owned();
+        let scale = linear.determinant().abs().cbrt();
+        let rotation = Rotation3::from_matrix(&(linear / scale));
+        let transform = Similarity3::from_parts(
+            Translation3::from(Vector3::from(matrix.fixed_view::<3, 1>(0, 3))),
+            UnitQuaternion::from_rotation_matrix(&rotation),
+            scale,
+        );
+        let sphere = sphere.transformed(&transform);
+
+        Some(match self.material() {
+            Some(material) => sphere.with_material(material),
+            None => sphere,
+        })
+    }
+}
+
+impl Hittable for Instance {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let hit = self.placed.hit(ray, t_min, t_max)?;
+
+        Some(HitRecord {
+            material: self.material().unwrap_or(hit.material),
+            ..hit
+        })
+    }
+}
+
+impl Bounded for Instance {
+    fn bounding_box(&self) -> AaBb {
+        self.placed.bounding_box()
+    }
+
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        self.placed.closest_point(point)
+    }
+
+    fn unbounded(&self) -> bool {
+        self.placed.unbounded()
+    }
+
+    fn triangles(&self) -> Vec<[Vector3<f32>; 3]> {
+        self.placed.triangles()
+    }
+}
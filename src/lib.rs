@@ -0,0 +1,72 @@
+//! Rust 实现的迷你光线追踪器
+
+pub mod accelerator;
+pub mod aperture;
+pub mod background;
+pub mod bake;
+pub mod bvh;
+pub mod bvh_cache;
+pub mod calibrate;
+pub mod camera;
+pub mod capsule;
+pub mod checkpoint;
+pub mod chi2;
+pub mod cone;
+pub mod curve;
+pub mod depth;
+pub mod ellipsoid;
+pub mod error;
+pub mod exposure;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod film;
+pub mod filter;
+pub mod furnace;
+pub mod graph;
+pub mod grid;
+pub mod heatmap;
+pub mod heightfield;
+pub mod hittable;
+pub mod ies;
+pub mod instance;
+pub mod light;
+pub mod lookdev;
+pub mod lru;
+pub mod material;
+pub mod material_library;
+pub mod memory;
+pub mod mesh;
+pub mod mesh_cache;
+pub mod output;
+pub mod overlay;
+pub mod placement;
+pub mod plane;
+pub mod ply;
+pub mod point_cloud;
+pub mod precision;
+pub mod preview;
+pub mod progressive;
+pub mod qbvh;
+pub mod quad;
+pub mod quadric;
+pub mod ray;
+pub mod render;
+pub mod rng;
+pub mod sampler;
+pub mod sampling;
+pub mod scene;
+pub mod scene_file;
+pub mod sdf;
+pub mod sphere;
+pub mod splat;
+pub mod stats;
+pub mod stereo;
+pub mod stl;
+pub mod subdivision;
+pub mod sun_sky;
+pub mod texture;
+pub mod toon;
+pub mod transformed;
+pub mod triangle;
+pub mod turntable;
+pub mod watch;
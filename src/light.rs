@@ -0,0 +1,415 @@
+use std::sync::Arc;
+
+use crate::ies::IesProfile;
+use crate::material::Material;
+use crate::mesh::Geometry;
+use crate::ray::Ray;
+use crate::render::luminance;
+use crate::sampling::{Onb, sample_spherical_triangle, sample_triangle};
+use crate::triangle;
+
+use clap::ValueEnum;
+use nalgebra::Vector3;
+use rand::Rng;
+
+/// 灯光的配光: 发光强度随方向的分布
+#[derive(Clone)]
+pub enum Distribution {
+    /// 各向同性的点光源
+    Isotropic,
+
+    /// 聚光灯: 与主轴夹角的余弦不小于 `cos_inner` 时全亮, 在 `cos_outer` 处平滑衰减为零
+    Spot { cos_inner: f32, cos_outer: f32 },
+
+    /// IES 配光曲线, 竖直角 0° 沿主轴
+    Ies(Arc<IesProfile>),
+
+    /// 无穷远处的平行光 (如太阳), 主轴指向灯光, 没有距离衰减
+    Directional,
+}
+
+/// 点状灯光 (无体积), 只能由直接光照采样, 路径追踪的光线不会击中
+///
+/// 亮度随距离按平方反比衰减, 随方向按配光衰减; 平行光的强度即为辐照度
+#[derive(Clone)]
+pub struct Light {
+    /// 位置
+    position: Vector3<f32>,
+
+    /// 主轴方向上的发光强度 (每球面度的辐射通量)
+    intensity: Vector3<f32>,
+
+    /// 以主轴为 `w` 的局部坐标系, IES 配光的水平角从 `u` 量起
+    frame: Onb,
+
+    distribution: Distribution,
+}
+
+impl Light {
+    /// 位于 `position` 的点光源, 发光强度为 `color` 乘以 `intensity`
+    pub fn point(position: Vector3<f32>, color: Vector3<f32>, intensity: f32) -> Self {
+        Self {
+            position,
+            intensity: intensity * color,
+            frame: Onb::build_from_w(&Vector3::y()),
+            distribution: Distribution::Isotropic,
+        }
+    }
+
+    /// 朝向 `direction` 的聚光灯: 半角 `inner` 以内全亮, 到半角 `outer` 处衰减为零 (均为度)
+    pub fn spot(
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        color: Vector3<f32>,
+        intensity: f32,
+        outer: f32,
+        inner: f32,
+    ) -> Self {
+        Self {
+            distribution: Distribution::Spot {
+                cos_inner: inner.min(outer).to_radians().cos(),
+                cos_outer: outer.to_radians().cos(),
+            },
+            ..Self::point(position, color, intensity).aimed(direction)
+        }
+    }
+
+    /// 配光为 IES 曲线 `profile` 的灯光, 曲线的天底 (竖直角 0°) 朝向 `direction`,
+    /// 峰值方向上的发光强度为 `color` 乘以 `intensity`
+    pub fn ies(
+        position: Vector3<f32>,
+        direction: Vector3<f32>,
+        color: Vector3<f32>,
+        intensity: f32,
+        profile: Arc<IesProfile>,
+    ) -> Self {
+        Self {
+            distribution: Distribution::Ies(profile),
+            ..Self::point(position, color, intensity).aimed(direction)
+        }
+    }
+
+    /// 来自 `direction` 方向 (指向灯光) 的平行光, 垂直于光线的表面接收到的辐照度为 `irradiance`
+    pub fn directional(direction: Vector3<f32>, irradiance: Vector3<f32>) -> Self {
+        Self {
+            position: Vector3::zeros(),
+            intensity: irradiance,
+            frame: Onb::build_from_w(&direction.normalize()),
+            distribution: Distribution::Directional,
+        }
+    }
+
+    /// 主轴改为 `direction`
+    fn aimed(self, direction: Vector3<f32>) -> Self {
+        Self {
+            frame: Onb::build_from_w(&direction.normalize()),
+            ..self
+        }
+    }
+
+    pub const fn position(&self) -> Vector3<f32> {
+        self.position
+    }
+
+    /// 沿单位方向 `direction` (自灯光出发) 的发光强度
+    pub fn intensity(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        let cos_theta = direction.dot(&self.frame.w);
+        let falloff = match &self.distribution {
+            Distribution::Isotropic | Distribution::Directional => 1.0,
+            Distribution::Spot {
+                cos_inner,
+                cos_outer,
+            } => {
+                let t = ((cos_theta - cos_outer) / (cos_inner - cos_outer).max(f32::EPSILON))
+                    .clamp(0.0, 1.0);
+                t * t * (3.0 - 2.0 * t)
+            }
+            Distribution::Ies(profile) => {
+                let theta = cos_theta.clamp(-1.0, 1.0).acos().to_degrees();
+                let phi = direction
+                    .dot(&self.frame.v)
+                    .atan2(direction.dot(&self.frame.u))
+                    .to_degrees();
+                profile.intensity(theta, phi)
+            }
+        };
+
+        falloff * self.intensity
+    }
+
+    /// 位于 `point` 处垂直于光线的表面接收到的辐照度, 以及指向灯光的单位方向与距离
+    pub fn irradiance(&self, point: &Vector3<f32>) -> (Vector3<f32>, Vector3<f32>, f32) {
+        if let Distribution::Directional = self.distribution {
+            return (self.intensity, self.frame.w, f32::INFINITY);
+        }
+
+        let offset = self.position - point;
+        let distance_squared = offset.norm_squared().max(f32::MIN_POSITIVE);
+        let distance = distance_squared.sqrt();
+        let to_light = offset / distance;
+
+        (
+            self.intensity(&-to_light) / distance_squared,
+            to_light,
+            distance,
+        )
+    }
+}
+
+/// 网格光源中三角形上点的采样方式
+#[derive(Clone, Copy, Debug, Default, ValueEnum)]
+pub enum MeshLightSampling {
+    /// 在三角形上按面积均匀采样
+    #[default]
+    Area,
+
+    /// 在三角形对着色点所张的立体角内均匀采样, 光源近而大 (如贴近表面的灯管与曲面灯板) 时噪点更少
+    SolidAngle,
+}
+
+/// 网格光源: 自发光的三角形网格, 按面积选取三角形
+#[derive(Clone)]
+pub struct MeshLight {
+    /// 网格在场景中的编号
+    object: usize,
+
+    geometry: Arc<Geometry>,
+
+    /// 各三角形面积的累积和
+    cdf: Vec<f32>,
+
+    /// 按面积加权的中心, 用于估计光源到着色点的距离
+    center: Vector3<f32>,
+
+    /// 辐射亮度的估计 (纹理按 1 计), 用于在灯光间选取
+    brightness: f32,
+}
+
+impl MeshLight {
+    /// 编号为 `object` 的自发光网格
+    pub fn new(object: usize, geometry: Arc<Geometry>) -> Self {
+        let mut total = 0.0;
+        let mut center = Vector3::zeros();
+        let cdf = (0..geometry.face_count())
+            .map(|face| {
+                let [a, b, c] = geometry.vertices(geometry.face(face));
+                let area = 0.5 * (b - a).cross(&(c - a)).norm();
+                center += area * (a + b + c) / 3.0;
+                total += area;
+                total
+            })
+            .collect();
+        let brightness = match geometry.material() {
+            Material::Emissive {
+                color,
+                texture,
+                intensity,
+            } => intensity * texture.map_or(luminance(&color), |_| 1.0),
+            _ => 0.0,
+        };
+
+        Self {
+            object,
+            geometry,
+            cdf,
+            center: if total > 0.0 { center / total } else { center },
+            brightness,
+        }
+    }
+
+    /// 总面积
+    fn area(&self) -> f32 {
+        self.cdf.last().copied().unwrap_or(0.0)
+    }
+
+    /// `point` 处接收到的辐照度的粗略估计, 距离不小于光源的尺度
+    fn weight(&self, point: &Vector3<f32>) -> f32 {
+        let area = self.area();
+
+        self.brightness * area / (self.center - point).norm_squared().max(area)
+    }
+
+    /// 为 `point` 在光源上采样一点, 返回辐射亮度, 指向它的单位方向, 距离与立体角上的概率密度
+    fn sample(
+        &self,
+        point: &Vector3<f32>,
+        sampling: MeshLightSampling,
+        rng: &mut impl Rng,
+    ) -> Option<(Vector3<f32>, Vector3<f32>, f32, f32)> {
+        let area = self.area();
+        let target = rng.random::<f32>() * area;
+        let face = self
+            .cdf
+            .partition_point(|&c| c <= target)
+            .min(self.cdf.len() - 1);
+        let face_area = self.cdf[face] - face.checked_sub(1).map_or(0.0, |i| self.cdf[i]);
+        let indices = self.geometry.face(face);
+        let vertices = self.geometry.vertices(indices);
+        let uv = (rng.random(), rng.random());
+
+        // 立体角采样得到方向后与三角形求交, 退化时退回面积采样
+        let solid_angle = match sampling {
+            MeshLightSampling::SolidAngle => {
+                let directions = vertices.map(|v| (v - point).normalize());
+                sample_spherical_triangle(&directions, uv).and_then(|(direction, omega)| {
+                    let ray = Ray::from(*point, direction);
+                    let (t, u, v) = triangle::intersect(&vertices, &ray, 0.0, f32::MAX)?;
+                    Some(([1.0 - u - v, u, v], t, face_area / area / omega))
+                })
+            }
+            MeshLightSampling::Area => None,
+        };
+        let (weights, distance, pdf) = match solid_angle {
+            Some(sample) => sample,
+            None => {
+                let weights = sample_triangle(uv);
+                let position: Vector3<f32> = (0..3).map(|k| weights[k] * vertices[k]).sum();
+                let offset = position - point;
+                let distance = offset.norm();
+                let [a, b, c] = vertices;
+                let normal = (b - a).cross(&(c - a)).try_normalize(f32::EPSILON)?;
+                let cosine = normal.dot(&offset).abs() / distance;
+                if cosine <= 0.0 || distance <= 0.0 {
+                    return None;
+                }
+                (weights, distance, distance * distance / (area * cosine))
+            }
+        };
+        let [a, b, c] = vertices;
+        let position = weights[0] * a + weights[1] * b + weights[2] * c;
+        let direction = (position - point) / distance;
+
+        // 与求交时相同: 顶点颜色替换辐射颜色, 没有纹理坐标时为重心坐标
+        let material = self.geometry.material();
+        let material = match self.geometry.color(indices, weights) {
+            Some(color) => material.with_albedo(color),
+            None => material,
+        };
+        let uv = self
+            .geometry
+            .uv(indices, weights)
+            .unwrap_or((weights[1], weights[2]));
+
+        Some((material.emitted(uv, 0.0), direction, distance, pdf))
+    }
+}
+
+/// 为直接光照选取的灯光
+pub struct LightSample {
+    /// 垂直于光线的表面接收到的辐照度除以采样的概率 (密度), 已计入灯光的选取
+    pub incident: Vector3<f32>,
+
+    /// 指向灯光的单位方向
+    pub direction: Vector3<f32>,
+
+    /// 到灯光的距离
+    pub distance: f32,
+}
+
+/// 场景中的所有灯光 (点状灯光与网格光源), 直接光照时每次选取一个
+#[derive(Clone, Default)]
+pub struct Lights {
+    lights: Vec<Light>,
+
+    meshes: Vec<MeshLight>,
+
+    /// 网格光源上点的采样方式
+    sampling: MeshLightSampling,
+}
+
+impl Lights {
+    pub const fn new(lights: Vec<Light>) -> Self {
+        Self {
+            lights,
+            meshes: vec![],
+            sampling: MeshLightSampling::Area,
+        }
+    }
+
+    /// 加入网格光源 (编号与几何数据), 光源上的点按 `sampling` 采样
+    pub fn with_meshes(
+        mut self,
+        meshes: impl IntoIterator<Item = (usize, Arc<Geometry>)>,
+        sampling: MeshLightSampling,
+    ) -> Self {
+        self.meshes.extend(
+            meshes
+                .into_iter()
+                .map(|(object, geometry)| MeshLight::new(object, geometry))
+                .filter(|mesh| mesh.area() > 0.0 && mesh.brightness > 0.0),
+        );
+        self.sampling = sampling;
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lights.is_empty() && self.meshes.is_empty()
+    }
+
+    /// 网格光源的数量
+    pub fn mesh_count(&self) -> usize {
+        self.meshes.len()
+    }
+
+    /// 编号为 `object` 的实体是否为网格光源, 其辐射已由直接光照计入
+    pub fn is_emitter(&self, object: usize) -> bool {
+        self.meshes.iter().any(|mesh| mesh.object == object)
+    }
+
+    /// 各灯光在 `point` 处的选取权重, 点状灯光在前
+    fn weights(&self, point: &Vector3<f32>) -> impl Iterator<Item = f32> {
+        let lights = self
+            .lights
+            .iter()
+            .map(|light| luminance(&light.irradiance(point).0));
+        let meshes = self.meshes.iter().map(|mesh| mesh.weight(point));
+
+        lights.chain(meshes).map(|weight| weight.max(0.0))
+    }
+
+    /// 为 `point` 处的直接光照选取一个灯光并采样
+    ///
+    /// 选取的概率与各灯光在该点处的辐照度 (亮度) 成正比: 点状灯光已计入配光与距离衰减,
+    /// 聚光灯照射范围以外与 IES 曲线暗区的灯光不会被选中; 网格光源按其面积与到中心的距离估计
+    pub fn sample(&self, point: &Vector3<f32>, rng: &mut impl Rng) -> Option<LightSample> {
+        let total: f32 = self.weights(point).sum();
+        if total <= 0.0 || !total.is_finite() {
+            return None;
+        }
+
+        // 按累积权重选取, 舍入误差使 `target` 未降到零以下时取最后一个有效的灯光
+        let mut target = rng.random::<f32>() * total;
+        let mut chosen = None;
+        for (index, weight) in self.weights(point).enumerate() {
+            if weight > 0.0 {
+                chosen = Some((index, weight / total));
+                target -= weight;
+                if target < 0.0 {
+                    break;
+                }
+            }
+        }
+        let (index, probability) = chosen?;
+
+        match self.lights.get(index) {
+            Some(light) => {
+                let (irradiance, direction, distance) = light.irradiance(point);
+                Some(LightSample {
+                    incident: irradiance / probability,
+                    direction,
+                    distance,
+                })
+            }
+            None => {
+                let mesh = &self.meshes[index - self.lights.len()];
+                let (radiance, direction, distance, pdf) =
+                    mesh.sample(point, self.sampling, rng)?;
+                Some(LightSample {
+                    incident: radiance / (pdf * probability),
+                    direction,
+                    distance,
+                })
+            }
+        }
+    }
+}
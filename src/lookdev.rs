@@ -0,0 +1,179 @@
+use std::fs;
+use std::path::Path;
+
+use crate::background::Background;
+use crate::error::{Error, Result};
+use crate::graph::Lens;
+use crate::hittable::HittableList;
+use crate::material::{Material, Parameter};
+use crate::plane::Plane;
+use crate::quadric::Quadric;
+use crate::scene_file::{self, CameraSpec, SceneFile};
+use crate::sphere::Sphere;
+
+use nalgebra::Vector3;
+
+/// 材质球场景的背景: 由浅灰到深灰的竖直渐变
+pub const BACKDROP: Background = Background::Gradient {
+    bottom: Vector3::new(0.6, 0.6, 0.62),
+    top: Vector3::new(0.08, 0.08, 0.09),
+};
+
+/// 内置的预览材质及其名称
+pub const PRESETS: [(&str, Material); 9] = [
+    ("clay", Material::CLAY),
+    (
+        "chrome",
+        Material::metal(Vector3::new(0.95, 0.95, 0.95), 0.0),
+    ),
+    ("gold", Material::metal(Vector3::new(1.0, 0.78, 0.34), 0.05)),
+    (
+        "brushed-steel",
+        Material::metal(Vector3::new(0.7, 0.7, 0.72), 0.3),
+    ),
+    ("glass", Material::dielectric(1.5)),
+    (
+        "plastic",
+        Material::principled(
+            Vector3::new(0.8, 0.1, 0.1),
+            Parameter::Constant(0.3),
+            Parameter::Constant(0.0),
+            Parameter::Constant(0.5),
+        ),
+    ),
+    (
+        "rubber",
+        Material::principled(
+            Vector3::new(0.05, 0.05, 0.05),
+            Parameter::Constant(0.8),
+            Parameter::Constant(0.0),
+            Parameter::Constant(0.3),
+        ),
+    ),
+    (
+        "car-paint",
+        Material::car_paint(
+            Vector3::new(0.05, 0.15, 0.5),
+            Vector3::new(0.8, 0.8, 0.9),
+            0.3,
+            0.005,
+            0.3,
+            1.5,
+        ),
+    ),
+    (
+        "velvet",
+        Material::sheen(
+            Vector3::new(0.4, 0.05, 0.1),
+            Vector3::new(0.9, 0.6, 0.7),
+            0.3,
+        ),
+    ),
+];
+
+/// 按名称查找内置材质, 否则读取材质文件
+///
+/// 材质文件中第一条非注释语句为材质定义, 写法与场景文件中形状语句的材质部分相同,
+/// 如 `metal 0.8 0.6 0.2 0.05`
+pub fn load_material(name: &str) -> Result<Material> {
+    if let Some((_, material)) = PRESETS.iter().find(|(preset, _)| *preset == name) {
+        return Ok(*material);
+    }
+
+    let path = Path::new(name);
+    let text = fs::read_to_string(path).map_err(|err| {
+        Error::InvalidSettings(format!(
+            "`{name}` is neither a preset material ({}) nor a readable file: {err}",
+            PRESETS.map(|(preset, _)| preset).join(", ")
+        ))
+    })?;
+    let (index, line, fields) = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| {
+            let content = line.split('#').next().unwrap_or_default();
+            (index, line, content.split_whitespace().collect::<Vec<_>>())
+        })
+        .find(|(_, _, fields)| !fields.is_empty())
+        .ok_or_else(|| Error::Parse {
+            path: path.to_path_buf(),
+            line: 1,
+            column: 1,
+            message: "no material definition".to_string(),
+        })?;
+
+    scene_file::parse_material(&fields).map_err(|err| {
+        let at = err.field.unwrap_or(fields[0]);
+        Error::parse(path, index + 1, line, at, err.message)
+    })
+}
+
+/// 材质球场景: 立在底座与握柄上的材质球, 地面与三点布光 (主光, 补光与轮廓光)
+///
+/// 灯光均在画面之外, 配合 `BACKDROP` 作为背景
+pub fn scene(material: Material) -> SceneFile {
+    let stand = Material::lambertian(Vector3::new(0.05, 0.05, 0.05));
+    let mut list = HittableList::default();
+
+    // 地面
+    list.push(Plane::new(
+        Vector3::zeros(),
+        Vector3::y(),
+        Material::lambertian(Vector3::new(0.4, 0.4, 0.4)),
+    ));
+
+    // 材质球, 扁平的底座与收腰的握柄
+    list.push(Sphere::from(Vector3::new(0.0, 1.4, 0.0), 0.9, material));
+    list.push(Quadric::ellipsoid(
+        Vector3::zeros(),
+        Vector3::new(0.7, 0.12, 0.7),
+        stand,
+    ));
+    list.push(Quadric::hyperboloid(
+        Vector3::new(0.0, 0.35, 0.0),
+        0.1,
+        0.5,
+        stand,
+    ));
+
+    // 三点布光: 左前上方的主光, 右前方较暗的补光, 右后上方勾勒轮廓的轮廓光
+    for (center, radius, color, intensity) in [
+        (
+            Vector3::new(-4.0, 5.0, 4.0),
+            1.5,
+            Vector3::new(1.0, 0.95, 0.85),
+            6.0,
+        ),
+        (
+            Vector3::new(5.0, 2.5, 3.0),
+            1.5,
+            Vector3::new(0.85, 0.9, 1.0),
+            2.0,
+        ),
+        (
+            Vector3::new(3.0, 6.0, -5.0),
+            1.0,
+            Vector3::new(1.0, 1.0, 1.0),
+            10.0,
+        ),
+    ] {
+        list.push(Sphere::from(
+            center,
+            radius,
+            Material::emissive(color, intensity),
+        ));
+    }
+
+    SceneFile {
+        camera: Some(CameraSpec {
+            look_from: Vector3::new(0.0, 1.6, 7.0),
+            look_at: Vector3::new(0.0, 1.0, 0.0),
+            lens: Lens {
+                vertical_fov: 25.0,
+                aperture: 0.0,
+                focus_dist: 1.0,
+            },
+        }),
+        ..SceneFile::from(list)
+    }
+}
@@ -0,0 +1,131 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+
+/// 按字节预算淘汰最久未使用条目的缓存
+///
+/// 条目按最近一次使用的时刻排序, 查找, 放入与淘汰一个条目均为 O(log n)
+pub struct Lru<K, V> {
+    entries: HashMap<K, Entry<V>>,
+
+    /// 最近一次使用的时刻到键, 首个为最久未使用的条目
+    order: BTreeMap<u64, K>,
+
+    /// 逻辑时钟, 每次访问递增
+    clock: u64,
+
+    /// 条目的总大小 (字节)
+    used: usize,
+}
+
+/// 缓存中的条目
+struct Entry<V> {
+    value: V,
+
+    /// 大小 (字节)
+    size: usize,
+
+    /// 最近一次使用的时刻
+    last_used: u64,
+}
+
+impl<K, V> Default for Lru<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+            used: 0,
+        }
+    }
+}
+
+impl<K: Copy + Eq + Hash, V> Lru<K, V> {
+    /// 键为 `key` 的条目, 并记为最近使用
+    pub fn get(&mut self, key: &K) -> Option<&V> {
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.last_used);
+        entry.last_used = self.clock;
+        self.order.insert(self.clock, *key);
+        self.clock += 1;
+
+        Some(&entry.value)
+    }
+
+    /// 放入 `size` 字节的条目, 先淘汰最久未使用的条目直到总大小不超过 `budget`
+    /// (缓存为空时总是放入), 返回淘汰的条目数; 键已存在时保留原有的条目, 返回 `None`
+    pub fn insert(&mut self, key: K, value: V, size: usize, budget: usize) -> Option<usize> {
+        if self.entries.contains_key(&key) {
+            return None;
+        }
+
+        let mut evicted = 0;
+        while self.used + size > budget {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            if let Some(entry) = self.entries.remove(&oldest) {
+                self.used -= entry.size;
+                evicted += 1;
+            }
+        }
+
+        self.entries.insert(
+            key,
+            Entry {
+                value,
+                size,
+                last_used: self.clock,
+            },
+        );
+        self.order.insert(self.clock, key);
+        self.clock += 1;
+        self.used += size;
+
+        Some(evicted)
+    }
+
+    /// 条目的总大小 (字节)
+    pub const fn used(&self) -> usize {
+        self.used
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let mut lru = Lru::default();
+        lru.insert(1, 'a', 1, 2);
+        lru.insert(2, 'b', 1, 2);
+        lru.get(&1);
+
+        assert_eq!(lru.insert(3, 'c', 1, 2), Some(1));
+        assert_eq!(lru.get(&2), None);
+        assert_eq!(lru.get(&1), Some(&'a'));
+        assert_eq!(lru.get(&3), Some(&'c'));
+        assert_eq!(lru.used(), 2);
+    }
+
+    #[test]
+    fn oversized_entry_replaces_everything() {
+        let mut lru = Lru::default();
+        lru.insert(1, 'a', 1, 4);
+        lru.insert(2, 'b', 1, 4);
+
+        assert_eq!(lru.insert(3, 'c', 8, 4), Some(2));
+        assert_eq!(lru.get(&3), Some(&'c'));
+        assert_eq!(lru.used(), 8);
+    }
+
+    #[test]
+    fn existing_key_is_kept() {
+        let mut lru = Lru::default();
+        lru.insert(1, 'a', 1, 4);
+
+        assert_eq!(lru.insert(1, 'b', 1, 4), None);
+        assert_eq!(lru.get(&1), Some(&'a'));
+        assert_eq!(lru.used(), 1);
+    }
+}
@@ -1,37 +1,69 @@
-mod bvh;
-mod camera;
-mod hittable;
-mod material;
-mod ray;
-mod rng;
-mod sphere;
-
-use std::fs::File;
+use std::any::Any;
 use std::iter::repeat_n;
-use std::sync::Arc;
-#[cfg(not(feature = "benchmark"))]
-use std::sync::atomic::{AtomicUsize, Ordering};
-#[cfg(not(feature = "benchmark"))]
-use std::time::Instant;
-use std::{
-    f32,
-    io::{self, Write},
+use std::path::{Path, PathBuf};
+use std::process::{self, ExitCode};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use std::{f32, fs, io};
+
+use ray_tracing::accelerator::{Accelerator, AcceleratorKind};
+use ray_tracing::aperture::Aperture;
+use ray_tracing::background::{Background, Environment};
+use ray_tracing::bake::{BakeMode, BakeSettings};
+use ray_tracing::bvh::{Bounded, MAX_OBJECTS};
+use ray_tracing::bvh_cache;
+use ray_tracing::camera::Camera;
+use ray_tracing::capsule::Capsule;
+use ray_tracing::checkpoint::Checkpoint;
+use ray_tracing::cone::Cone;
+use ray_tracing::curve::{Curve, Hair};
+use ray_tracing::depth::{DepthEncoding, DepthPass, DepthSettings};
+use ray_tracing::ellipsoid::Ellipsoid;
+use ray_tracing::error::{self, Error};
+use ray_tracing::exposure::Exposure;
+use ray_tracing::filter::{Filter, FilterKind};
+use ray_tracing::graph::{Primitive, SceneGraph};
+use ray_tracing::heatmap;
+use ray_tracing::heightfield::Heightfield;
+use ray_tracing::hittable::HittableList;
+use ray_tracing::instance::Instance;
+use ray_tracing::light::{Lights, MeshLightSampling};
+use ray_tracing::lookdev;
+use ray_tracing::material::{Material, Parameter};
+use ray_tracing::memory;
+use ray_tracing::mesh::Mesh;
+use ray_tracing::output::{BitDepth, Image, ImageFormat, PartialWrite};
+use ray_tracing::placement::{GroundMap, Placement};
+use ray_tracing::plane::Plane;
+use ray_tracing::point_cloud::{PointCloud, SplatShape};
+use ray_tracing::precision::Precision;
+use ray_tracing::progressive::Termination;
+use ray_tracing::quad::Quad;
+use ray_tracing::quadric::Quadric;
+use ray_tracing::ray::RayKind;
+use ray_tracing::render::{BounceLimits, Framebuffer, INTERRUPTED, RenderSettings};
+use ray_tracing::rng::{self, scene_seed};
+use ray_tracing::sampler::SamplerKind;
+use ray_tracing::scene::{Scene, SceneObject};
+use ray_tracing::scene_file::SceneFile;
+use ray_tracing::sdf::Sdf;
+use ray_tracing::sphere::Sphere;
+use ray_tracing::stats::{self, RenderStats};
+use ray_tracing::texture::{self, ColorSpace, TextureId};
+use ray_tracing::toon::ToonSettings;
+use ray_tracing::triangle::Triangle;
+use ray_tracing::turntable::Turntable;
+use ray_tracing::{
+    bake, calibrate, chi2, furnace, output, overlay, preview, progressive, render, splat, stereo,
+    toon, watch,
 };
 
-use crate::bvh::{BVHNode, Bounded};
-use crate::camera::Camera;
-use crate::hittable::{Hittable, HittableList};
-use crate::material::Material;
-use crate::ray::Ray;
-use crate::rng::get_rng;
-use crate::sphere::Sphere;
-
-use clap::Parser;
-use material::Scatter;
-use nalgebra::Vector3;
-use rand::Rng;
-use rand::seq::IndexedRandom;
-use rayon::prelude::*;
+use clap::{ArgGroup, Parser, ValueEnum};
+use nalgebra::{Matrix4, Vector3};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
 
 // 小球材质的比例
 const LAMBERTIAN_PROP: usize = 10;
@@ -42,6 +74,7 @@ const DIELECTRIC_PROP: usize = 2;
 #[derive(Parser, Debug)]
 #[command(name = "ray-tracing")]
 #[command(about = "Rust 实现的迷你光线追踪器", long_about = None)]
+#[command(group(ArgGroup::new("overlays").args(["bvh_overlay", "wireframe"]).multiple(true)))]
 struct Args {
     /// 图像宽度
     #[arg(long, default_value_t = 1200)]
@@ -55,19 +88,539 @@ struct Args {
     #[arg(long, default_value_t = 50)]
     ns: usize,
 
-    /// 最大追踪深度
+    /// 最大追踪深度, 理想镜面 (玻璃, 无模糊的金属) 的反弹不计入
     #[arg(long, default_value_t = 50)]
     depth: usize,
 
+    /// 路径通量低于该值时提前终止路径
+    #[arg(long, value_name = "X", default_value_t = 0.0)]
+    min_throughput: f32,
+
+    /// 包括理想镜面反弹在内的最大追踪深度, 未指定时与 `depth` 相同
+    #[arg(long, value_name = "N")]
+    specular_depth: Option<usize>,
+
+    /// 路径上漫反射的最大反弹次数, 未指定时不限制
+    #[arg(long, value_name = "N")]
+    diffuse_bounces: Option<usize>,
+
+    /// 路径上镜面及光泽反射的最大反弹次数, 未指定时不限制
+    #[arg(long, value_name = "N")]
+    specular_bounces: Option<usize>,
+
+    /// 路径上透射的最大反弹次数, 未指定时不限制
+    #[arg(long, value_name = "N")]
+    transmission_bounces: Option<usize>,
+
+    /// 直接光照 (至多一次散射后到达相机) 每次贡献各通道的上限, 未指定时不限制
+    #[arg(long, value_name = "X")]
+    clamp_direct: Option<f32>,
+
+    /// 间接光照 (两次及以上散射后到达相机) 每次贡献各通道的上限, 用于抑制萤火虫噪点
+    #[arg(long, value_name = "X")]
+    clamp_indirect: Option<f32>,
+
+    /// 曝光补偿 (EV)
+    #[arg(
+        long,
+        value_name = "EV",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    exposure: f32,
+
+    /// 按亮度直方图自动曝光, 使亮度中位数为中灰, 再施加 `exposure` 的补偿
+    #[arg(long, conflicts_with = "resume")]
+    auto_exposure: bool,
+
     /// 是否写入文件
     #[arg(long)]
     dry: bool,
+
+    /// 场景种子, 未指定时随机选取 (启用 benchmark / course 时为固定值)
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// 确定性渲染, 用于 CI 的基准图像比对: 固定场景种子, 每个像素的随机数只由像素坐标决定,
+    /// 浮点累加的顺序与线程数及调度无关, 不做依赖时间的调整
+    #[arg(long, conflicts_with_all = ["time_budget", "tune_bvh"])]
+    deterministic: bool,
+
+    /// 从上次中断时保存的断点继续渲染
+    #[arg(long)]
+    resume: bool,
+
+    /// 像素采样器
+    #[arg(long, value_enum, default_value_t = SamplerKind::Stratified)]
+    sampler: SamplerKind,
+
+    /// 网格光源 (自发光的三角形网格) 上点的采样方式
+    #[arg(long, value_enum, default_value_t = MeshLightSampling::Area)]
+    mesh_light_sampling: MeshLightSampling,
+
+    /// 像素重建滤波器
+    #[arg(long, value_enum, default_value_t = FilterKind::Box)]
+    filter: FilterKind,
+
+    /// 重建滤波器的半径 (像素), 缺省为各滤波器的默认半径
+    #[arg(long, value_name = "PIXELS", value_parser = parse_filter_radius)]
+    filter_radius: Option<f32>,
+
+    /// 采样按重建滤波器的权重分散写入半径内的相邻像素, 而不是按滤波器重要性采样
+    #[arg(long, conflicts_with_all = ["passes", "time_budget", "error_target", "resume", "write_interval"])]
+    splat: bool,
+
+    /// 是否对像素采样施加 Cranley–Patterson 旋转 (逐像素的随机偏移)
+    #[arg(long)]
+    cp_rotation: bool,
+
+    /// 渐进式渲染的轮数: 首轮均匀采样, 之后按估计误差优先重新采样图块
+    #[arg(long, value_name = "N", conflicts_with = "resume")]
+    passes: Option<usize>,
+
+    /// 渐进式渲染的时间预算, 如 `60s`, `2m` 或秒数: 按误差不断追加采样, 到时写入当前结果
+    #[arg(long, value_name = "DURATION", value_parser = parse_duration, conflicts_with_all = ["passes", "resume"])]
+    time_budget: Option<Duration>,
+
+    /// 渐进式渲染的误差目标 (正数): 按误差不断追加采样, 直到 95% 的像素的相对误差 (均值估计的方差与亮度平方之比) 不超过此值,
+    /// 或平均每个像素的采样数达到 `ns`
+    #[arg(long, value_name = "ERROR", value_parser = parse_error_target, conflicts_with_all = ["passes", "time_budget", "resume"])]
+    error_target: Option<f32>,
+
+    /// 帧缓冲的数值精度, `f16` 使渐进式渲染的累积缓冲内存减半, 并以抖动转换为 8 位输出
+    #[arg(long, value_enum, default_value_t = Precision::F32)]
+    precision: Precision,
+
+    /// 透明背景: 主光线未击中任何实体的像素不透明度为 0, 阴影捕捉只保留阴影, 需输出为 PNG 或 PAM
+    #[arg(long)]
+    transparent: bool,
+
+    /// 输出格式, 默认为 PPM (透明背景时为 PNG)
+    #[arg(long, value_enum)]
+    format: Option<ImageFormat>,
+
+    /// 输出图像每个通道的位数, 16 位只用于路径追踪的最终图像 (预览与部分结果仍为 8 位)
+    #[arg(long, value_enum, default_value_t = BitDepth::Eight)]
+    bit_depth: BitDepth,
+
+    /// 16 位输出施加逐像素的三角分布抖动
+    #[arg(long)]
+    dither: bool,
+
+    /// 同时输出深度通道 `<输出文件名>_depth`: 每个像素中心的 z 深度按近平面与远平面归一化的灰度图
+    #[arg(long)]
+    depth_pass: bool,
+
+    /// 深度通道的近平面距离, 默认为最近的击中点
+    #[arg(long, value_name = "DIST", requires = "depth_pass")]
+    depth_near: Option<f32>,
+
+    /// 深度通道的远平面距离, 默认为最远的击中点
+    #[arg(long, value_name = "DIST", requires = "depth_pass")]
+    depth_far: Option<f32>,
+
+    /// 深度通道的编码方式
+    #[arg(long, value_enum, default_value_t = DepthEncoding::Linear, requires = "depth_pass")]
+    depth_encoding: DepthEncoding,
+
+    /// 深度通道输出为 32 位浮点的 OpenEXR, 而不是 8 位图像
+    #[arg(long, requires = "depth_pass")]
+    depth_exr: bool,
+
+    /// 同时输出耗时热力图 `<输出文件名>_heatmap`: 每个图块的亮度表示其渲染耗时, 仅路径追踪
+    #[arg(long)]
+    heatmap: bool,
+
+    /// 耗时热力图的图块边长 (像素)
+    #[arg(
+        long,
+        value_name = "PIXELS",
+        default_value_t = 16,
+        requires = "heatmap"
+    )]
+    heatmap_tile: usize,
+
+    /// 渲染过程中每隔多少秒将当前结果写入输出文件
+    #[arg(long, value_name = "SECS")]
+    write_interval: Option<f32>,
+
+    /// 转台: 相机绕场景主体 (不含地面等过大实体) 的包围球环绕一周, 渲染 N 帧序列 `<输出文件名>_0000` 等
+    #[arg(long, value_name = "N", conflicts_with_all = ["resume", "preview"])]
+    turntable: Option<usize>,
+
+    /// 转台相机的仰角 (角度)
+    #[arg(
+        long,
+        value_name = "DEG",
+        default_value_t = 20.0,
+        requires = "turntable"
+    )]
+    turntable_elevation: f32,
+
+    /// 红青立体图: 相机左右各偏移一半眼距渲染立体像对, 左眼取红色通道 (按亮度), 右眼取绿色与蓝色通道
+    #[arg(
+        long,
+        value_name = "SEPARATION",
+        conflicts_with_all = ["resume", "preview", "transparent", "turntable"]
+    )]
+    anaglyph: Option<f32>,
+
+    /// 依次渲染的场景文件 (可含通配符 `*`, `?`), 共用其余参数, 输出以场景文件名命名; 未指定时渲染程序生成的场景
+    #[arg(long, value_name = "FILE", num_args = 1.., conflicts_with = "resume")]
+    scene: Vec<PathBuf>,
+
+    /// 相机相对渲染: 场景文件中的位置以双精度减去相机位置后再转换为单精度, 用于远离原点的场景
+    #[arg(long, requires = "scene")]
+    camera_relative: bool,
+
+    /// 材质预览: 将内置材质 (如 `gold`, `glass`, `car-paint`) 或材质文件中的材质渲染到材质球场景中,
+    /// 输出以材质名或文件名命名
+    #[arg(long, value_name = "NAME|FILE", conflicts_with_all = ["scene", "resume"])]
+    preview_material: Option<String>,
+
+    /// 加入场景的点云 (文本格式, 每行 `x y z [r g b [radius]]`), 作为一个名为 `points` 的实体
+    #[arg(long, value_name = "FILE")]
+    points: Option<PathBuf>,
+
+    /// 点云中未指定半径的点的半径
+    #[arg(long, value_name = "RADIUS", default_value_t = 0.02)]
+    point_radius: f32,
+
+    /// 点云中点的形状
+    #[arg(long, value_enum, default_value_t = SplatShape::Disk)]
+    point_shape: SplatShape,
+
+    /// 将渲染统计 (各阶段耗时, 追踪的光线数与速率, 加速结构构建时间, 内存峰值) 以 JSON 写入该文件
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["scene", "turntable", "bake"])]
+    stats_json: Option<PathBuf>,
+
+    /// 统计各实体的求交次数与击中次数 (有额外开销), 渲染后列出求交次数最多的 N 个实体, 并写入 `--stats-json`
+    #[arg(long, value_name = "N", conflicts_with_all = ["scene", "turntable", "bake"])]
+    object_stats: Option<usize>,
+
+    /// 正式渲染之前, 先以 1/8, 1/4, 1/2 分辨率 (每像素一个采样) 逐级渲染预览并写入输出文件
+    #[arg(long, conflicts_with = "resume")]
+    preview: bool,
+
+    /// 热重载: 渲染后继续监视场景文件, 文件保存后重新解析, 重建加速结构并从预览开始重新渲染
+    /// (只有材质改变时沿用加速结构), 渲染中途保存时中止当前渲染; Ctrl-C 退出
+    #[arg(long, requires_all = ["preview", "scene"], conflicts_with_all = ["stats_json", "object_stats"])]
+    watch: bool,
+
+    /// 积分器
+    #[arg(long, value_enum, default_value_t = Integrator::Path)]
+    integrator: Integrator,
+
+    /// 卡通渲染的色阶数 (未指定调色板时生效)
+    #[arg(long, default_value_t = 4)]
+    toon_bands: usize,
+
+    /// 卡通渲染的调色板, 由暗到亮的十六进制颜色, 如 `#202040,#8080c0,#ffffff`
+    #[arg(long, value_delimiter = ',', value_parser = toon::parse_color)]
+    toon_palette: Vec<Vector3<f32>>,
+
+    /// 白模渲染: 散射光线的材质替换为中性灰的漫反射, 自发光材质保持不变
+    #[arg(long)]
+    clay: bool,
+
+    /// 是否在图像上叠加加速结构 (BVH 结点或网格格子) 的包围盒线框
+    #[arg(long)]
+    bvh_overlay: bool,
+
+    /// 是否在图像上叠加三角形 (三角形, 网格及其实例) 的线框
+    #[arg(long)]
+    wireframe: bool,
+
+    /// 只绘制线框 (不渲染), 需与 `--bvh-overlay` 或 `--wireframe` 一同使用
+    #[arg(long, requires = "overlays")]
+    overlay_only: bool,
+
+    /// 将合焦区域 (深度与对焦距离相差不超过容差) 染为绿色, 配合 `--preview` 或较少的采样数可快速调整对焦距离与光圈
+    #[arg(long)]
+    focus_overlay: bool,
+
+    /// 合焦区域的深度容差, 缺省为对焦距离的 5%
+    #[arg(long, value_name = "DIST", requires = "focus_overlay")]
+    focus_tolerance: Option<f32>,
+
+    /// 烘焙模式: 将场景中第 N 个球体的光照烘焙为 UV 纹理, 而不是渲染相机图像
+    #[arg(long, value_name = "N")]
+    bake: Option<usize>,
+
+    /// 烘焙内容
+    #[arg(long, value_enum, default_value_t = BakeMode::Ao)]
+    bake_mode: BakeMode,
+
+    /// 白炉测试: 检查各材质在均匀白色环境中是否守恒能量
+    #[arg(long)]
+    furnace: bool,
+
+    /// 卡方检验: 检查各材质的散射方向分布是否符合解析的概率密度
+    #[arg(long)]
+    chi2: bool,
+
+    /// 环境光遮蔽的最大距离
+    #[arg(long, default_value_t = 1.0)]
+    ao_distance: f32,
+
+    /// 光线求交加速结构
+    #[arg(long, value_enum, default_value_t = AcceleratorKind::Bvh)]
+    accelerator: AcceleratorKind,
+
+    /// BVH 叶子结点最多包含的实体数 (硬上限, 不超过上限时由 SAH 决定是否分割)
+    #[arg(long, value_name = "N", default_value_t = MAX_OBJECTS)]
+    bvh_leaf_size: usize,
+
+    /// 缓存构建好的 BVH 的目录, 再次渲染相同的场景或网格时直接读取, 跳过构建
+    #[arg(long, value_name = "DIR")]
+    bvh_cache: Option<PathBuf>,
+
+    /// 在实际场景上测试多种叶子结点大小, 自动选取遍历最快的一个 (覆盖 `--bvh-leaf-size`)
+    #[arg(long)]
+    tune_bvh: bool,
+
+    /// 光圈形状的灰度图像 (二进制 PPM), 镜头位置按其亮度采样, 焦外光斑呈现该形状 (如星形, 心形)
+    #[arg(long, value_name = "FILE")]
+    aperture_shape: Option<PathBuf>,
+
+    /// 大球中漫反射球的图像纹理 (二进制 PPM), 纹理按图块在用到时读取; 路径含 `<UDIM>` 时为 UDIM 纹理集
+    #[arg(long, value_name = "FILE")]
+    texture: Option<PathBuf>,
+
+    /// 大球中漫反射球改为自发光, 辐射取自该图像纹理 (二进制 PPM), 如屏幕或灯板
+    #[arg(long, value_name = "FILE", conflicts_with = "texture")]
+    emission: Option<PathBuf>,
+
+    /// 自发光纹理的强度倍数
+    #[arg(long, default_value_t = 1.0, requires = "emission")]
+    emission_intensity: f32,
+
+    /// 大球中金属球的粗糙度贴图 (灰度的二进制 PPM), 指定任一贴图时金属球改为原则化材质
+    #[arg(long, value_name = "FILE")]
+    roughness_map: Option<PathBuf>,
+
+    /// 大球中金属球的金属度贴图 (灰度的二进制 PPM)
+    #[arg(long, value_name = "FILE")]
+    metallic_map: Option<PathBuf>,
+
+    /// 大球中金属球的 glTF 式打包贴图 (二进制 PPM): 绿色通道为粗糙度, 蓝色通道为金属度
+    #[arg(long, value_name = "FILE", conflicts_with_all = ["roughness_map", "metallic_map"])]
+    orm_map: Option<PathBuf>,
+
+    /// 大球中金属球的高光强度贴图 (灰度的二进制 PPM)
+    #[arg(long, value_name = "FILE")]
+    specular_map: Option<PathBuf>,
+
+    /// 大球中金属球的底色贴图 (二进制 PPM, 乘以金属球原有的颜色), 指定时金属球改为原则化材质
+    #[arg(long, value_name = "FILE")]
+    base_color_map: Option<PathBuf>,
+
+    /// 大球中金属球的切线空间法线贴图 (二进制 PPM, 绿色通道朝纹理坐标 v 增大的方向), 指定时金属球改为原则化材质
+    #[arg(long, value_name = "FILE")]
+    normal_map: Option<PathBuf>,
+
+    /// 随机场景中小球的密度图: 灰度的二进制 PPM (俯视铺满小球所在的区域, 首行在最小 z 处)
+    /// 或 `noise:<frequency>` (每单位长度的周期数), 值为各处放置小球的概率
+    #[arg(long, value_name = "MAP", value_parser = parse_ground_map)]
+    density_map: Option<MapSource>,
+
+    /// 随机场景中小球的大小图, 写法同 `--density-map`, 值 0 到 1 对应半径的 0.5 到 2 倍
+    #[arg(long, value_name = "MAP", value_parser = parse_ground_map)]
+    scale_map: Option<MapSource>,
+
+    /// 随机场景中小球的材质图, 写法同 `--density-map`, 值由小到大依次选取漫反射, 金属与玻璃
+    #[arg(long, value_name = "MAP", value_parser = parse_ground_map)]
+    material_map: Option<MapSource>,
+
+    /// 覆盖纹理文件的色彩空间, 如 `albedo.ppm=linear`, 可多次指定; 未指定时按文件头中的标记
+    /// (如 `# colorspace: linear`), 没有标记时颜色贴图按 sRGB, 粗糙度等数据贴图按线性解码
+    #[arg(long, value_name = "FILE=SPACE", value_parser = parse_color_space)]
+    color_space: Vec<(PathBuf, ColorSpace)>,
+
+    /// 纹理缓存的内存预算 (MiB), 超出时淘汰最久未使用的图块
+    #[arg(long, value_name = "MIB", default_value_t = texture::DEFAULT_BUDGET >> 20)]
+    texture_cache: usize,
+
+    /// 网格与纹理的总内存预算 (MiB): 超出预算的网格改为按页读取其 `.meshcache` 缓存,
+    /// 常驻网格以外的部分由网格分页与纹理缓存平分 (覆盖 `--texture-cache`)
+    #[arg(long, value_name = "MIB", conflicts_with = "texture_cache")]
+    memory_budget: Option<usize>,
+
+    /// 经纬度环境贴图 (二进制 PPM), 替代默认的渐变天空
+    #[arg(long, value_name = "FILE")]
+    environment: Option<PathBuf>,
+
+    /// 环境绕竖直轴旋转的角度
+    #[arg(
+        long,
+        value_name = "DEG",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    env_azimuth: f32,
+
+    /// 环境向上抬起的角度
+    #[arg(
+        long,
+        value_name = "DEG",
+        default_value_t = 0.0,
+        allow_negative_numbers = true
+    )]
+    env_elevation: f32,
+
+    /// 环境的强度倍数
+    #[arg(long, default_value_t = 1.0)]
+    env_intensity: f32,
+
+    /// 使用摄影棚背景: 渐变的上半球, 较暗的地面与柔和的地平线, 适合产品照明
+    #[arg(long, conflicts_with = "environment")]
+    studio: bool,
+
+    /// 地面使用阴影捕捉材质: 只呈现其他实体投下的阴影, 其余部分显示背后的环境, 便于合成到照片上
+    #[arg(long)]
+    shadow_catcher: bool,
+
+    /// 对相机光线不可见的实体名称 (如 `sphere3`), 可多次指定
+    #[arg(long, value_name = "NAME")]
+    camera_invisible: Vec<String>,
+
+    /// 对阴影光线不可见 (不投下阴影) 的实体名称, 可多次指定
+    #[arg(long, value_name = "NAME")]
+    shadow_invisible: Vec<String>,
+
+    /// 对间接光线不可见 (不出现在反射与折射中) 的实体名称, 可多次指定
+    #[arg(long, value_name = "NAME")]
+    indirect_invisible: Vec<String>,
+
+    /// 渲染使用的线程数, 默认为 CPU 核心数
+    #[arg(long, value_name = "N")]
+    threads: Option<usize>,
+
+    /// 以低优先级渲染, 不影响机器上的其他任务
+    #[arg(long)]
+    low_priority: bool,
+}
+
+/// 积分器
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Integrator {
+    /// 路径追踪
+    Path,
+
+    /// 卡通 (非真实感) 渲染: 色阶量化的漫反射 + 描边
+    Toon,
+}
+
+/// 场景选项
+struct SceneOptions {
+    /// 大球中漫反射球的纹理
+    texture: Option<TextureId>,
+
+    /// 大球中漫反射球的自发光纹理及强度
+    emission: Option<(TextureId, f32)>,
+
+    /// 大球中金属球的粗糙度, 金属度与高光强度参数, 未指定时取光滑金属的值
+    surface_maps: [Option<Parameter>; 3],
+
+    /// 大球中金属球的底色贴图与法线贴图
+    color_maps: [Option<TextureId>; 2],
+
+    /// 地面是否为阴影捕捉材质
+    shadow_catcher: bool,
+
+    /// 小球的摆放方式
+    placement: Placement,
+}
+
+impl SceneOptions {
+    /// 大球中的漫反射材质, 指定纹理时以纹理为颜色, 指定自发光纹理时改为自发光
+    fn big_lambertian(&self, albedo: Vector3<f32>) -> Material {
+        if let Some((texture, intensity)) = self.emission {
+            return Material::textured_emissive(texture, intensity);
+        }
+
+        self.texture
+            .map_or(Material::lambertian(albedo), Material::textured_lambertian)
+    }
+
+    /// 大球中的金属材质, 指定任一表面贴图时改为原则化材质, 未指定的参数取光滑金属的值
+    fn big_metal(&self, albedo: Vector3<f32>) -> Material {
+        if self.surface_maps.iter().all(Option::is_none)
+            && self.color_maps.iter().all(Option::is_none)
+        {
+            return Material::metal(albedo, 0.0);
+        }
+
+        let [roughness, metallic, specular] = [(0, 0.0), (1, 1.0), (2, 0.5)]
+            .map(|(i, default)| self.surface_maps[i].unwrap_or(Parameter::Constant(default)));
+        let [base_color, normal] = self.color_maps;
+        let material = Material::principled(albedo, roughness, metallic, specular);
+        let material = base_color.map_or(material, |map| material.with_base_color_map(map));
+
+        normal.map_or(material, |map| material.with_normal_map(map))
+    }
+
+    /// 地面材质
+    fn ground(&self) -> Material {
+        if self.shadow_catcher {
+            Material::ShadowCatcher
+        } else {
+            Material::lambertian(Vector3::new(0.5, 0.5, 0.5))
+        }
+    }
+}
+
+/// 随机场景中的小球: 共享一个单位球几何体与一张材质表, 每个小球只记录球心, 半径与材质下标
+#[derive(Default)]
+struct SmallSpheres {
+    /// 材质表, 所有玻璃球共用其中一项
+    materials: Vec<Material>,
+
+    /// 玻璃材质在材质表中的下标
+    dielectric: Option<usize>,
+
+    /// 球心, 半径及材质下标
+    centers: Vec<(Vector3<f32>, f32, usize)>,
+}
+
+impl SmallSpheres {
+    fn push(&mut self, center: Vector3<f32>, radius: f32, dielectric: bool, material: Material) {
+        let index = match (dielectric, self.dielectric) {
+            (true, Some(index)) => index,
+            _ => {
+                self.materials.push(material);
+                let index = self.materials.len() - 1;
+                if dielectric {
+                    self.dielectric = Some(index);
+                }
+                index
+            }
+        };
+
+        self.centers.push((center, radius, index));
+    }
+
+    /// 以实例加入场景
+    fn instantiate(self, scene: &mut HittableList) {
+        let geometry: Arc<dyn Bounded + Sync + Send> =
+            Arc::new(Sphere::from(Vector3::zeros(), 1.0, Material::CLAY));
+        let materials: Arc<[Material]> = self.materials.into();
+
+        for (center, radius, material) in self.centers {
+            let transform = Matrix4::new_translation(&center) * Matrix4::new_scaling(radius);
+            scene.push(
+                Instance::new(geometry.clone(), transform)
+                    .and_then(|instance| instance.with_material(materials.clone(), material))
+                    .expect("positive radius and material index pushed with the table"),
+            );
+        }
+    }
 }
 
 /// 终章的场景
 #[allow(unused)]
-fn final_scene() -> HittableList {
-    let mut rng = get_rng();
+fn final_scene(seed: u64, options: &SceneOptions) -> HittableList {
+    let mut rng = StdRng::seed_from_u64(seed);
     let origin = Vector3::new(4.0, 0.2, 0.0);
     let mut scene = HittableList::default();
 
@@ -75,7 +628,7 @@ fn final_scene() -> HittableList {
     scene.push(Sphere::from(
         Vector3::new(0.0, -1000.0, 0.0),
         1000.0,
-        Material::lambertian(Vector3::new(0.5, 0.5, 0.5)),
+        options.ground(),
     ));
 
     let mut materials_list = vec![];
@@ -84,16 +637,19 @@ fn final_scene() -> HittableList {
     materials_list.extend(repeat_n(2, DIELECTRIC_PROP));
 
     // 小球
+    let mut small_spheres = SmallSpheres::default();
+    let placement = &options.placement;
     for a in -11..11 {
         for b in -11..11 {
-            let center = Vector3::new(
+            let (x, z) = (
                 a as f32 + 0.9 * rng.random::<f32>(),
-                0.2,
                 b as f32 + 0.9 * rng.random::<f32>(),
             );
+            let radius = 0.2 * placement.scale(x, z);
+            let center = Vector3::new(x, radius, z);
 
-            if (center - origin).magnitude() > 0.9 {
-                let material_pick = *materials_list.choose(&mut rng).unwrap();
+            if (center - origin).magnitude() > 0.9 && placement.keep(x, z, &mut rng) {
+                let material_pick = *placement.choose(x, z, &materials_list, &mut rng);
 
                 let material: Material = if material_pick == 0 {
                     Material::lambertian(Vector3::new(
@@ -114,10 +670,11 @@ fn final_scene() -> HittableList {
                     Material::dielectric(1.5)
                 };
 
-                scene.push(Sphere::from(center, 0.2, material));
+                small_spheres.push(center, radius, material_pick == 2, material);
             }
         }
     }
+    small_spheres.instantiate(&mut scene);
 
     // 大球
     scene.push(Sphere::from(
@@ -129,13 +686,13 @@ fn final_scene() -> HittableList {
     scene.push(Sphere::from(
         Vector3::new(-4.0, 1.0, 0.0),
         1.0,
-        Material::lambertian(Vector3::new(0.4, 0.2, 0.1)),
+        options.big_lambertian(Vector3::new(0.4, 0.2, 0.1)),
     ));
 
     scene.push(Sphere::from(
         Vector3::new(4.0, 1.0, 0.0),
         1.0,
-        Material::metal(Vector3::new(0.7, 0.6, 0.5), 0.0),
+        options.big_metal(Vector3::new(0.7, 0.6, 0.5)),
     ));
 
     scene
@@ -143,17 +700,13 @@ fn final_scene() -> HittableList {
 
 /// 大球横排场景
 #[allow(unused)]
-fn lined_up_scene() -> HittableList {
-    let mut rng = get_rng();
+fn lined_up_scene(seed: u64, options: &SceneOptions) -> HittableList {
+    let mut rng = StdRng::seed_from_u64(seed);
     let mut scene = HittableList::default();
     let mut list = vec![];
 
     // 地面
-    let plane = Sphere::from(
-        Vector3::new(0.0, -1000.0, 0.0),
-        1000.0,
-        Material::lambertian(Vector3::new(0.5, 0.5, 0.5)),
-    );
+    let plane = Sphere::from(Vector3::new(0.0, -1000.0, 0.0), 1000.0, options.ground());
     scene.push(plane.clone());
 
     // 大球
@@ -164,7 +717,7 @@ fn lined_up_scene() -> HittableList {
     let lambertian = Sphere::from(
         Vector3::new(0.0, 1.0, 0.0),
         1.0,
-        Material::lambertian(Vector3::new(0.0, 0.5, 1.0)),
+        options.big_lambertian(Vector3::new(0.0, 0.5, 1.0)),
     );
     scene.push(lambertian.clone());
     list.push(lambertian);
@@ -172,13 +725,13 @@ fn lined_up_scene() -> HittableList {
     let metal = Sphere::from(
         Vector3::new(3.2, 1.0, 0.4),
         1.0,
-        Material::metal(Vector3::new(1.0, 1.0, 1.0), 0.0),
+        options.big_metal(Vector3::new(1.0, 1.0, 1.0)),
     );
     scene.push(metal.clone());
     list.push(metal);
 
     // 小球
-    let radius = 0.2;
+    let placement = &options.placement;
     let wander = 0.75;
     let edge = 11;
     let mut materials_list = vec![];
@@ -186,19 +739,26 @@ fn lined_up_scene() -> HittableList {
     materials_list.extend(repeat_n(1, METAL_PROP));
     materials_list.extend(repeat_n(2, DIELECTRIC_PROP));
 
+    let mut small_spheres = SmallSpheres::default();
     for a in -edge..edge {
         'positions: for b in -edge..edge {
             let x = a as f32 + wander * rng.random::<f32>();
             let z = b as f32 + wander * rng.random::<f32>();
+            let radius = 0.2 * placement.scale(x, z);
             let center = Sphere::correct_center(Vector3::new(x, radius, z), radius, &plane);
 
-            for obj in &list {
-                if Sphere::overlaps(center, radius, obj) {
-                    continue 'positions;
-                }
+            let overlaps_small = small_spheres
+                .centers
+                .iter()
+                .any(|(other, r, _)| (center - other).magnitude() < radius + r);
+            if overlaps_small || list.iter().any(|obj| Sphere::overlaps(center, radius, obj)) {
+                continue 'positions;
+            }
+            if !placement.keep(x, z, &mut rng) {
+                continue 'positions;
             }
 
-            let material_pick = *materials_list.choose(&mut rng).unwrap();
+            let material_pick = *placement.choose(x, z, &materials_list, &mut rng);
             let material: Material = if material_pick == 0 {
                 Material::lambertian(Vector3::new(
                     rng.random::<f32>() * rng.random::<f32>(),
@@ -218,11 +778,10 @@ fn lined_up_scene() -> HittableList {
                 Material::dielectric(1.5)
             };
 
-            let sphere = Sphere::from(center, radius, material);
-            scene.push(sphere.clone());
-            list.push(sphere);
+            small_spheres.push(center, radius, material_pick == 2, material);
         }
     }
+    small_spheres.instantiate(&mut scene);
 
     scene
 }
@@ -267,155 +826,1037 @@ fn build_camera(nx: usize, ny: usize) -> Camera {
     }
 }
 
-fn write_image(image: Vec<u8>, nx: usize, ny: usize) -> io::Result<()> {
-    eprint!("Writing file...");
-    let image = image
-        .chunks(3)
-        .map(|col| format!("{} {} {}", col[0], col[1], col[2]))
-        .collect::<Vec<_>>()
-        .join("\n");
+/// 首次 Ctrl-C 停止开始新的行并保存断点, 再次 Ctrl-C 立即退出 (批量渲染时可重复调用)
+fn set_interrupt_handler() -> io::Result<()> {
+    let result = ctrlc::set_handler(|| {
+        if INTERRUPTED.swap(true, Ordering::SeqCst) {
+            process::exit(130);
+        }
+        eprintln!("\rInterrupted, finishing rows in flight...");
+    });
 
-    let file_name = if cfg!(feature = "benchmark") {
-        "benchmark"
-    } else if cfg!(feature = "course") {
-        "course"
+    match result {
+        Ok(()) | Err(ctrlc::Error::MultipleHandlers) => Ok(()),
+        Err(e) => Err(io::Error::other(e)),
+    }
+}
+
+/// 解析时长: 以 `s`, `m`, `h` 结尾或不带单位的秒数
+fn parse_duration(s: &str) -> Result<Duration, String> {
+    let (number, unit) = match s.strip_suffix(['s', 'm', 'h']) {
+        Some(number) => (number, &s[number.len()..]),
+        None => (s, "s"),
+    };
+    let value: f32 = number
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid duration `{s}`"))?;
+    let seconds = match unit {
+        "m" => value * 60.0,
+        "h" => value * 3600.0,
+        _ => value,
+    };
+
+    Duration::try_from_secs_f32(seconds).map_err(|_| format!("invalid duration `{s}`"))
+}
+
+/// 解析重建滤波器的半径, 须为正数
+fn parse_filter_radius(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(radius) if radius > 0.0 && radius.is_finite() => Ok(radius),
+        _ => Err(format!(
+            "invalid filter radius `{s}`, expected a positive number"
+        )),
+    }
+}
+
+/// 解析渐进式渲染的误差目标, 须为正数
+fn parse_error_target(s: &str) -> Result<f32, String> {
+    match s.trim().parse::<f32>() {
+        Ok(target) if target > 0.0 && target.is_finite() => Ok(target),
+        _ => Err(format!(
+            "invalid error target `{s}`, expected a positive number"
+        )),
+    }
+}
+
+/// 解析 `FILE=SPACE` 形式的纹理色彩空间
+fn parse_color_space(s: &str) -> Result<(PathBuf, ColorSpace), String> {
+    let (path, space) = s
+        .rsplit_once('=')
+        .ok_or_else(|| format!("invalid color space override `{s}`, expected `FILE=SPACE`"))?;
+    let space = ColorSpace::from_str(space, true)
+        .map_err(|_| format!("unknown color space `{space}`, expected `srgb` or `linear`"))?;
+
+    Ok((PathBuf::from(path), space))
+}
+
+/// 随机场景中分布图的来源
+#[derive(Clone, Debug)]
+enum MapSource {
+    /// 灰度贴图文件
+    File(PathBuf),
+
+    /// 分形值噪声, 参数为每单位长度的周期数
+    Noise(f32),
+}
+
+/// 解析分布图: `noise:<frequency>` 或贴图文件的路径
+fn parse_ground_map(s: &str) -> Result<MapSource, String> {
+    let Some(frequency) = s.strip_prefix("noise:") else {
+        return Ok(MapSource::File(PathBuf::from(s)));
+    };
+
+    match frequency.trim().parse::<f32>() {
+        Ok(frequency) if frequency > 0.0 && frequency.is_finite() => {
+            Ok(MapSource::Noise(frequency))
+        }
+        _ => Err(format!(
+            "invalid noise frequency `{frequency}`, expected a positive number"
+        )),
+    }
+}
+
+/// 输出格式, 未指定时为 PPM (透明背景时为 PNG)
+fn output_format(format: Option<ImageFormat>, transparent: bool) -> ImageFormat {
+    format.unwrap_or(if transparent {
+        ImageFormat::Png
     } else {
-        "result"
+        ImageFormat::Ppm
+    })
+}
+
+/// 展开文件名中的通配符 `*` 与 `?` (仅限最后一级), 匹配结果按名称排序, 没有通配符的路径原样保留
+fn expand_glob(pattern: &Path) -> io::Result<Vec<PathBuf>> {
+    let name = pattern
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    if !name.contains(['*', '?']) {
+        return Ok(vec![pattern.to_path_buf()]);
+    }
+
+    // 通配符匹配: `*` 匹配任意多个字符, `?` 匹配一个字符
+    fn matches(pattern: &[char], name: &[char]) -> bool {
+        match (pattern.first(), name.first()) {
+            (None, None) => true,
+            (Some('*'), _) => {
+                matches(&pattern[1..], name) || (!name.is_empty() && matches(pattern, &name[1..]))
+            }
+            (Some('?'), Some(_)) => matches(&pattern[1..], &name[1..]),
+            (Some(p), Some(n)) if p == n => matches(&pattern[1..], &name[1..]),
+            _ => false,
+        }
+    }
+
+    let pattern_chars: Vec<char> = name.chars().collect();
+    let dir = match pattern.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
     };
-    let file_path = format!("{file_name}.ppm");
-    writeln!(
-        &mut File::create(&file_path)?,
-        "P3\n{nx} {ny}\n255\n{image}",
-    )?;
-    eprintln!("\rFile written{}", " ".repeat(10));
+    let mut paths = vec![];
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let entry_name: Vec<char> = entry.file_name().to_string_lossy().chars().collect();
+        if matches(&pattern_chars, &entry_name) {
+            paths.push(dir.join(entry.file_name()));
+        }
+    }
+    if paths.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("{}: no matching scene files", pattern.display()),
+        ));
+    }
+    paths.sort();
 
-    Ok(())
+    Ok(paths)
 }
 
-/// 光线颜色
-fn ray_color(mut ray: Ray, scene: &impl Hittable, max_depth: usize) -> Vector3<f32> {
-    let mut color = Vector3::new(1.0, 1.0, 1.0);
-
-    // 在设定的深度以内
-    for _ in 0..max_depth {
-        if let Some(hit) = scene.hit(&ray, 0.001, f32::MAX) {
-            // 击中: 更新颜色和光线
-            if let Some((scattered, attenuation)) = hit.material.scatter(&ray, &hit) {
-                color = color.zip_map(&attenuation, |l, r| l * r);
-                ray = scattered;
-            } else {
-                break;
+/// 依次渲染多个场景文件, 输出以场景文件名命名, 最后打印各场景的耗时与输出路径
+fn run_batch(args: &Args) -> error::Result<()> {
+    let mut files = vec![];
+    for pattern in &args.scene {
+        files.extend(expand_glob(pattern)?);
+    }
+
+    let mut summary = vec![];
+    for (index, file) in files.iter().enumerate() {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            break;
+        }
+        eprintln!("[{}/{}] {}", index + 1, files.len(), file.display());
+
+        let timer = Instant::now();
+        let result = run(args, Some(file));
+        if let Err(e) = &result {
+            eprintln!("Failed to render {}: {e}", file.display());
+        }
+        summary.push((file, timer.elapsed(), result));
+    }
+
+    // 汇总表
+    let format = output_format(args.format, args.transparent);
+    let width = files
+        .iter()
+        .map(|file| file.display().to_string().len())
+        .max()
+        .unwrap_or(0)
+        .max("Scene".len());
+    println!("{:<width$}  {:>9}  Output", "Scene", "Time");
+    let mut failed = 0;
+    for (file, elapsed, result) in &summary {
+        let output = match result {
+            Ok(()) if args.dry => "-".to_string(),
+            Ok(()) => format!("{}.{}", scene_output_name(file), format.extension()),
+            Err(e) => {
+                failed += 1;
+                format!("failed: {e}")
             }
-        } else {
-            // 未击中: 打到天空, 设为背景颜色
-            let unit_direction = ray.direction().normalize();
-            let t = 0.5 * (unit_direction[1] + 1.0);
-            let sky = (1.0 - t) * Vector3::new(1.0, 1.0, 1.0) + t * Vector3::new(0.5, 0.7, 1.0);
+        };
+        println!(
+            "{:<width$}  {:>8.1}s  {output}",
+            file.display(),
+            elapsed.as_secs_f32()
+        );
+    }
+
+    if failed > 0 {
+        Err(Error::Failed(format!(
+            "{failed} of {} scenes failed",
+            summary.len()
+        )))
+    } else {
+        Ok(())
+    }
+}
+
+/// 场景文件变化后是否需要重新渲染, 由监视线程设置
+static RELOAD: AtomicBool = AtomicBool::new(false);
+
+/// 热重载时保留的场景及其场景文件的几何指纹, 下一次只有材质改变时沿用其加速结构
+static WATCHED_SCENE: Mutex<Option<(u64, Scene)>> = Mutex::new(None);
+
+/// 保存文件时编辑器可能分几次写入, 重新解析前等待的时间
+const RELOAD_DEBOUNCE: Duration = Duration::from_millis(100);
 
-            return color.zip_map(&sky, |l, r| l * r);
+/// 渲染场景文件, 之后每当文件被保存就重新渲染, 直到 Ctrl-C
+///
+/// 只监视场景文件本身, 其引用的网格与配光文件的修改需要再保存一次场景文件
+fn run_watch(args: &Args) -> error::Result<()> {
+    let mut files = vec![];
+    for pattern in &args.scene {
+        files.extend(expand_glob(pattern)?);
+    }
+    let [file] = &files[..] else {
+        return Err(Error::InvalidSettings(
+            "`--watch` takes a single scene file".to_string(),
+        ));
+    };
+
+    set_interrupt_handler()?;
+    let _watcher = watch::watch(file, || {
+        if !RELOAD.swap(true, Ordering::SeqCst) {
+            eprintln!("\rScene changed, reloading...");
         }
+        // 中止进行中的渲染
+        INTERRUPTED.store(true, Ordering::SeqCst);
+    })?;
+
+    loop {
+        thread::sleep(RELOAD_DEBOUNCE);
+        RELOAD.store(false, Ordering::SeqCst);
+        INTERRUPTED.store(false, Ordering::SeqCst);
+
+        // 场景文件有误时报告错误, 等待下一次保存
+        if let Err(e) = run(args, Some(file)) {
+            eprintln!("Failed to render {}: {e}", file.display());
+        }
+
+        if INTERRUPTED.load(Ordering::SeqCst) && !RELOAD.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+        if !RELOAD.load(Ordering::SeqCst) {
+            eprintln!("Watching {} for changes, Ctrl-C to quit", file.display());
+            while !RELOAD.load(Ordering::SeqCst) {
+                if INTERRUPTED.load(Ordering::SeqCst) {
+                    return Ok(());
+                }
+                thread::sleep(RELOAD_DEBOUNCE);
+            }
+        }
+    }
+}
+
+/// 由场景文件的实体构建场景, `material_indices` 为各实体的材质在材质表中的下标
+fn build_scene(
+    args: &Args,
+    list: HittableList,
+    material_indices: &[Option<usize>],
+    camera: &Camera,
+) -> error::Result<Scene> {
+    let mut graph = SceneGraph::new();
+    let mut materials = vec![];
+    for (index, obj) in list.list.into_iter().enumerate() {
+        let Some((kind, primitive)) = primitive(obj) else {
+            continue;
+        };
+        graph.attach(SceneGraph::ROOT, format!("{kind}{index}"), primitive);
+        materials.push(material_indices.get(index).copied().flatten());
+    }
+    let mut objects = graph.flatten();
+    for (object, material) in objects.iter_mut().zip(materials) {
+        object.material = material;
+    }
+    if let Some(path) = &args.points {
+        let cloud = PointCloud::load(path, args.point_radius, args.point_shape)?;
+        eprint!("\rLoaded {} points, building accelerator...", cloud.len());
+        objects.push(SceneObject::new("points", Arc::new(cloud)));
+    }
+    for (names, flag) in [
+        (&args.camera_invisible, RayKind::Camera),
+        (&args.shadow_invisible, RayKind::Shadow),
+        (&args.indirect_invisible, RayKind::Indirect),
+    ] {
+        for name in names {
+            let Some(object) = objects.iter_mut().find(|obj| &obj.name == name) else {
+                return Err(Error::InvalidSettings(format!(
+                    "no object named `{name}` in the scene"
+                )));
+            };
+            let visibility = &mut object.visibility;
+            match flag {
+                RayKind::Camera => visibility.camera = false,
+                RayKind::Shadow => visibility.shadow = false,
+                RayKind::Indirect => visibility.indirect = false,
+            }
+        }
+    }
+    let leaf_size = if args.tune_bvh && !matches!(args.accelerator, AcceleratorKind::Grid) {
+        eprintln!();
+        let bounded: Vec<_> = objects.iter().map(|obj| obj.object.clone()).collect();
+        calibrate::tune_leaf_size(&bounded, camera)
+    } else {
+        args.bvh_leaf_size
+    };
+
+    Ok(Scene::build(objects, args.accelerator, leaf_size))
+}
+
+/// 场景文件的输出文件名 (不含扩展名): 场景文件名去掉扩展名
+fn scene_output_name(file: &Path) -> String {
+    file.file_stem().map_or_else(
+        || "result".to_string(),
+        |stem| stem.to_string_lossy().into_owned(),
+    )
+}
+
+/// 场景文件中的实体对应的场景图实体及其名称前缀, 不是已知的实体类型时返回 `None`
+fn primitive(obj: Box<dyn Any>) -> Option<(&'static str, Primitive)> {
+    let obj = match obj.downcast::<Sphere>() {
+        Ok(sphere) => return Some(("sphere", Primitive::from(*sphere))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Instance>() {
+        Ok(instance) => return Some((instance_kind(&instance), Primitive::from(*instance))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Quadric>() {
+        Ok(quadric) => return Some(("quadric", Primitive::from(*quadric))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Triangle>() {
+        Ok(triangle) => return Some(("triangle", Primitive::from(*triangle))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Quad>() {
+        Ok(quad) => return Some(("quad", Primitive::from(*quad))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Plane>() {
+        Ok(plane) => return Some(("plane", Primitive::from(*plane))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Cone>() {
+        Ok(cone) => return Some(("cone", Primitive::from(*cone))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Capsule>() {
+        Ok(capsule) => return Some(("capsule", Primitive::from(*capsule))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Ellipsoid>() {
+        Ok(ellipsoid) => return Some(("ellipsoid", Primitive::from(*ellipsoid))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Sdf>() {
+        Ok(sdf) => return Some(("sdf", Primitive::from(*sdf))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Heightfield>() {
+        Ok(heightfield) => return Some(("heightfield", Primitive::from(*heightfield))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Curve>() {
+        Ok(curve) => return Some(("curve", Primitive::from(*curve))),
+        Err(obj) => obj,
+    };
+    let obj = match obj.downcast::<Hair>() {
+        Ok(hair) => return Some(("hair", Primitive::from(*hair))),
+        Err(obj) => obj,
+    };
+
+    obj.downcast::<Mesh>()
+        .ok()
+        .map(|mesh| ("mesh", Primitive::from(*mesh)))
+}
+
+/// 实例在场景中的名称前缀, 按共享几何体的类型
+fn instance_kind(instance: &Instance) -> &'static str {
+    let geometry = instance.geometry().as_ref() as &dyn Any;
+    if geometry.is::<Sphere>() {
+        "sphere"
+    } else if geometry.is::<Mesh>() {
+        "mesh"
+    } else {
+        "instance"
     }
+}
 
-    Vector3::zeros()
+fn main() -> ExitCode {
+    match try_main() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {e}");
+            ExitCode::FAILURE
+        }
+    }
 }
 
-fn main() -> io::Result<()> {
+fn try_main() -> error::Result<()> {
     let args = Args::parse();
-    let (nx, ny, ns, max_depth, dry) = (args.nx, args.ny, args.ns, args.depth, args.dry);
+    if args.deterministic {
+        rng::set_deterministic();
+    }
+
+    // 降低进程优先级
+    if args.low_priority {
+        lower_priority();
+    }
+
+    if let Some(dir) = &args.bvh_cache {
+        bvh_cache::enable(dir.clone());
+    }
+    if let Some(mib) = args.memory_budget {
+        memory::set_budget(mib << 20);
+    }
+
+    // 渲染使用独立的线程池, 不影响全局线程池
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(args.threads.unwrap_or(0))
+        .thread_name(|index| format!("render-{index}"))
+        .build()
+        .map_err(io::Error::other)?;
+
+    pool.install(|| {
+        if args.watch {
+            run_watch(&args)
+        } else if args.scene.is_empty() {
+            run(&args, None)
+        } else {
+            run_batch(&args)
+        }
+    })
+}
+
+/// 将进程调整为最低调度优先级
+#[cfg(unix)]
+fn lower_priority() {
+    // SAFETY: `nice` 只修改当前进程的调度优先级
+    if unsafe { libc::nice(19) } == -1 {
+        eprintln!("Failed to lower priority: {}", io::Error::last_os_error());
+    }
+}
+
+#[cfg(not(unix))]
+fn lower_priority() {
+    eprintln!("Low priority mode is not supported on this platform");
+}
+
+/// 渲染程序生成的场景, 或场景文件 `scene_file` 描述的场景
+fn run(args: &Args, scene_file: Option<&Path>) -> error::Result<()> {
+    let (mut nx, mut ny, mut ns, max_depth, dry) =
+        (args.nx, args.ny, args.ns, args.depth, args.dry);
+
+    // 输出文件名
+    let file_name = if let Some(file) = scene_file {
+        &scene_output_name(file)
+    } else if let Some(name) = &args.preview_material {
+        &scene_output_name(Path::new(name))
+    } else if cfg!(feature = "benchmark") {
+        "benchmark"
+    } else if cfg!(feature = "course") {
+        "course"
+    } else {
+        "result"
+    };
+
+    // 从断点继续时沿用断点的分辨率, 采样率与场景种子
+    let mut seed = args.seed.unwrap_or_else(scene_seed);
+    let mut framebuffer = Framebuffer::new(nx, ny, args.transparent);
+    let mut resumed_settings = None;
+    if args.resume {
+        let checkpoint = Checkpoint::load(file_name)?;
+        (nx, ny, ns, seed) = (checkpoint.nx, checkpoint.ny, checkpoint.ns, checkpoint.seed);
+        resumed_settings = Some(checkpoint.settings);
+        framebuffer = checkpoint.framebuffer;
+        eprintln!(
+            "Resuming {nx}x{ny} render with {} of {ny} rows done",
+            framebuffer.rows_done.iter().filter(|&&done| done).count()
+        );
+    }
+
+    // 透明背景需要能保存不透明度的格式
+    let transparent = framebuffer.alpha.is_some();
+    let format = output_format(args.format, transparent);
+    if transparent && !format.supports_alpha() {
+        return Err(Error::InvalidSettings(format!(
+            "`--transparent` requires an output format with alpha, not {format:?}"
+        )));
+    }
+
+    // 散射方向的卡方检验
+    if args.chi2 {
+        return if chi2::run() {
+            Ok(())
+        } else {
+            Err(Error::Failed("chi-square test failed".to_string()))
+        };
+    }
+
+    // 白炉测试
+    if args.furnace {
+        return if furnace::run(max_depth) {
+            Ok(())
+        } else {
+            Err(Error::Failed("white furnace test failed".to_string()))
+        };
+    }
+
+    // 注册纹理
+    texture::set_budget(args.texture_cache << 20);
+    let register = |path: &PathBuf, fallback: ColorSpace| match args
+        .color_space
+        .iter()
+        .rev()
+        .find(|(file, _)| file == path)
+    {
+        Some(&(_, color_space)) => texture::register_as(path, color_space),
+        None => texture::register(path, fallback),
+    };
+    let color_map = |path| register(path, ColorSpace::Srgb);
+    let data_map = |path| register(path, ColorSpace::Linear);
+    let texture = args.texture.as_ref().map(color_map).transpose()?;
+    let background = match &args.environment {
+        Some(path) => Background::Image(color_map(path)?),
+        None if args.studio => Background::STUDIO,
+        None if args.preview_material.is_some() => lookdev::BACKDROP,
+        None => Background::Sky,
+    };
 
     // 构建场景
+    let mut surface_maps = [None; 3];
+    for (map, path) in
+        surface_maps
+            .iter_mut()
+            .zip([&args.roughness_map, &args.metallic_map, &args.specular_map])
+    {
+        *map = path
+            .as_ref()
+            .map(data_map)
+            .transpose()?
+            .map(Parameter::grayscale);
+    }
+
+    // ORM 贴图: 绿色通道为粗糙度, 蓝色通道为金属度 (红色通道的环境光遮蔽由路径追踪自然得到)
+    if let Some(path) = &args.orm_map {
+        let texture = data_map(path)?;
+        surface_maps[0] = Some(Parameter::Texture {
+            texture,
+            channel: 1,
+        });
+        surface_maps[1] = Some(Parameter::Texture {
+            texture,
+            channel: 2,
+        });
+    }
+    let color_maps = [
+        args.base_color_map.as_ref().map(color_map).transpose()?,
+        args.normal_map.as_ref().map(data_map).transpose()?,
+    ];
+    let emission = args.emission.as_ref().map(color_map).transpose()?;
+    // 小球的分布图铺满小球所在的区域, 各噪声取不同的种子
+    let mut ground_maps = [None; 3];
+    for (salt, (map, source)) in (1..).zip(ground_maps.iter_mut().zip([
+        &args.density_map,
+        &args.scale_map,
+        &args.material_map,
+    ])) {
+        *map = match source {
+            Some(MapSource::File(path)) => Some(GroundMap::Texture(data_map(path)?)),
+            &Some(MapSource::Noise(frequency)) => Some(GroundMap::Noise {
+                frequency,
+                seed: (seed ^ salt) as u32,
+            }),
+            None => None,
+        };
+    }
+    let [density, scale, material] = ground_maps;
+    let placement = Placement {
+        density,
+        scale,
+        material,
+        extent: 11.0,
+    };
+    let scene_options = SceneOptions {
+        texture,
+        emission: emission.map(|texture| (texture, args.emission_intensity)),
+        surface_maps,
+        color_maps,
+        shadow_catcher: args.shadow_catcher,
+        placement,
+    };
+    // 各阶段的耗时等统计
+    let mut stats = RenderStats {
+        width: nx,
+        height: ny,
+        samples: ns,
+        stages: vec![],
+        objects: vec![],
+    };
+
+    let stage_timer = Instant::now();
     eprint!("Constructing scene...");
-    let scene_list = if cfg!(feature = "benchmark") {
-        final_scene()
-    } else {
-        lined_up_scene()
+    let loaded = match scene_file {
+        Some(file) if args.camera_relative => SceneFile::load_camera_relative(file)?,
+        Some(file) => SceneFile::load(file)?,
+        None if let Some(name) = &args.preview_material => {
+            lookdev::scene(lookdev::load_material(name)?)
+        }
+        None if cfg!(feature = "benchmark") => SceneFile::from(final_scene(seed, &scene_options)),
+        None => SceneFile::from(lined_up_scene(seed, &scene_options)),
+    };
+    let SceneFile {
+        list: scene_list,
+        camera: camera_spec,
+        lights,
+        background: scene_background,
+        materials,
+        material_indices,
+        geometry,
+        ..
+    } = loaded;
+    // 场景中的背景在命令行未指定背景时取代默认背景
+    let background = match scene_background {
+        Some(scene_background) if args.environment.is_none() && !args.studio => scene_background,
+        _ => background,
     };
     eprintln!("\rScene constructed{}", " ".repeat(10));
+    stats.stages.push(("scene", stage_timer.elapsed()));
 
-    // 构建 BVH
-    eprint!("Building BVH...");
-    let objects: Vec<_> = scene_list
-        .list
-        .into_iter()
-        .filter_map(|obj| {
-            let hittable_ref = obj.as_ref();
-            (hittable_ref as &dyn std::any::Any)
-                .downcast_ref::<Sphere>()
-                .map(|sphere| Arc::new(sphere.clone()) as Arc<dyn Bounded + Sync + Send>)
-        })
-        .collect();
-    let scene = BVHNode::build(objects);
-    eprintln!("\rBVH built{}", " ".repeat(10));
+    // 烘焙目标
+    let bake_target = match args.bake {
+        Some(index) => {
+            let target = scene_list.list.get(index).and_then(|obj| {
+                let obj = obj.as_ref() as &dyn Any;
+                obj.downcast_ref::<Sphere>()
+                    .cloned()
+                    .or_else(|| obj.downcast_ref::<Instance>().and_then(Instance::to_sphere))
+            });
+            if target.is_none() {
+                return Err(Error::InvalidSettings(format!(
+                    "object {index} is not a sphere in the scene"
+                )));
+            }
+            target
+        }
+        None => None,
+    };
 
     // 构建相机
-    let camera = build_camera(nx, ny);
+    let mut camera = camera_spec
+        .map_or_else(
+            || build_camera(nx, ny),
+            |spec| spec.build(nx as f32 / ny as f32),
+        )
+        .with_resolution(nx, ny);
+    if let Some(path) = &args.aperture_shape {
+        camera = camera.with_aperture(Arc::new(Aperture::load(path)?));
+    }
 
-    // gamma 修正闭包
-    let correct_gamma = |c: &f32| (255.99 * (c / ns as f32).sqrt().clamp(0.0, 1.0)) as u8;
+    // 构建加速结构; 热重载时场景文件的几何未变则沿用上次的场景, 只替换材质表
+    let stage_timer = Instant::now();
+    let cached = WATCHED_SCENE
+        .lock()
+        .unwrap()
+        .take()
+        .filter(|(key, _)| args.watch && *key == geometry);
+    let mut scene = match cached {
+        Some((_, scene)) => {
+            eprintln!("Geometry unchanged, reusing accelerator");
+            scene
+        }
+        None => {
+            eprint!("Building accelerator...");
+            let scene = build_scene(args, scene_list, &material_indices, &camera)?;
+            eprintln!("\rAccelerator built{}", " ".repeat(10));
+            if let Some((nodes, size)) = scene.stats() {
+                eprintln!("Quantized BVH: {nodes} nodes, {size} bytes each");
+            }
+            scene
+        }
+    };
+    scene.set_materials(materials.into_materials())?;
+    if args.memory_budget.is_some() {
+        // 网格均已载入, 纹理缓存取预算中剩余的部分
+        texture::set_budget(memory::cache_budget());
+    }
+    stats.stages.push(("accelerator", stage_timer.elapsed()));
 
-    // 跟踪渲染进度
-    #[cfg(not(feature = "benchmark"))]
-    let finished_count = Arc::new(AtomicUsize::new(0));
-    #[cfg(not(feature = "benchmark"))]
-    let timer = Instant::now();
+    // 太阳作为平行光, 与天空使用同一旋转与强度
+    let environment = Environment::new(background)
+        .rotated(args.env_azimuth, args.env_elevation)
+        .scaled(args.env_intensity);
+    let lights = lights.into_iter().chain(environment.sun()).collect();
 
-    // 并行渲染
-    let sqrt_ns = (ns as f32).sqrt() as usize;
-    let _image = (0..ny)
-        .into_par_iter()
-        .rev()
-        .flat_map(|y| {
-            let rng = &mut get_rng();
-
-            // 更新进度
-            #[cfg(not(feature = "benchmark"))]
-            {
-                let count = finished_count.fetch_add(1, Ordering::SeqCst) + 1;
-                let elapsed = timer.elapsed().as_millis() as usize;
-                let avg_speed = elapsed / count;
-                let remaining = ny - count;
-                eprint!(
-                    "\rRemaining: {:>4} | ETA: {:>4}s",
-                    remaining,
-                    remaining * avg_speed / 1000
-                );
+    let lights = Lights::new(lights).with_meshes(scene.emitters(), args.mesh_light_sampling);
+
+    let settings = RenderSettings {
+        max_depth,
+        min_throughput: args.min_throughput,
+        specular_depth: args.specular_depth.unwrap_or(max_depth),
+        bounce_limits: BounceLimits {
+            diffuse: args.diffuse_bounces.unwrap_or(usize::MAX),
+            specular: args.specular_bounces.unwrap_or(usize::MAX),
+            transmission: args.transmission_bounces.unwrap_or(usize::MAX),
+        },
+        clamp_direct: args.clamp_direct,
+        clamp_indirect: args.clamp_indirect,
+        exposure: if args.auto_exposure {
+            Exposure::Auto(args.exposure)
+        } else {
+            Exposure::Manual(args.exposure)
+        },
+        clay: args.clay,
+        environment,
+        lights,
+        sampler: args.sampler,
+        filter: Filter::new(
+            args.filter,
+            args.filter_radius
+                .unwrap_or_else(|| args.filter.default_radius()),
+        ),
+        cp_rotation: args.cp_rotation,
+        precision: args.precision,
+        bit_depth: args.bit_depth,
+        dither: args.dither,
+        transparent,
+        partial_write: args
+            .write_interval
+            .filter(|_| !dry)
+            .map(|secs| PartialWrite {
+                file_name: file_name.to_string(),
+                format,
+                interval: Duration::from_secs_f32(secs),
+            }),
+        epsilon: scene.ray_epsilon(),
+    };
+    if resumed_settings.is_some_and(|resumed| resumed != settings.fingerprint()) {
+        return Err(Error::InvalidSettings(
+            "the checkpoint was rendered with a different sampler, filter, max depth, clay mode \
+             or exposure; render again without `--resume`"
+                .to_string(),
+        ));
+    }
+    let toon_settings = ToonSettings::new(args.toon_bands, args.toon_palette.clone());
+
+    // 以所选的积分器渲染一帧, 路径追踪从 `framebuffer` 中未完成的行继续
+    let termination = match (args.passes, args.time_budget, args.error_target) {
+        (_, _, Some(target)) => Some(Termination::ErrorTarget(target)),
+        (_, Some(budget), _) => Some(Termination::TimeBudget(budget)),
+        (Some(passes), _, _) => Some(Termination::Passes(passes)),
+        (None, None, None) => None,
+    };
+    let render_frame =
+        |camera: &Camera, framebuffer: Framebuffer| match (args.integrator, termination) {
+            (Integrator::Toon, _) => Framebuffer::from_pixels(
+                toon::render(&scene, camera, nx, ny, &toon_settings, &settings),
+                None,
+                ny,
+            ),
+            (Integrator::Path, Some(termination)) => {
+                progressive::render(&scene, camera, nx, ny, ns, termination, &settings)
             }
+            (Integrator::Path, None) if args.splat => {
+                splat::render(&scene, camera, nx, ny, ns, &settings)
+            }
+            (Integrator::Path, None) => {
+                render::render(&scene, camera, nx, ny, ns, &settings, framebuffer)
+            }
+        };
 
-            // 渲染
-            (0..nx)
-                .flat_map(|x| {
-                    // 对每个像素进行多次采样
-                    let mut col = Vector3::zeros();
-                    for sy in 0..sqrt_ns {
-                        for sx in 0..sqrt_ns {
-                            let u = (x as f32 + (sx as f32 + rng.random::<f32>()) / sqrt_ns as f32)
-                                / nx as f32;
-                            let v = (y as f32 + (sy as f32 + rng.random::<f32>()) / sqrt_ns as f32)
-                                / ny as f32;
-                            col += ray_color(camera.camera_ray(u, v), &scene, max_depth);
-                        }
+    // 烘焙并写入纹理
+    if let Some(target) = bake_target {
+        eprint!("Baking...");
+        let texture = bake::bake(
+            &target,
+            &scene,
+            nx,
+            ny,
+            &BakeSettings {
+                mode: args.bake_mode,
+                samples: ns,
+                ao_distance: args.ao_distance,
+                epsilon: scene.ray_epsilon(),
+            },
+            |ray| render::ray_color(ray, &scene, &settings),
+        );
+        eprintln!("\rBaked{}", " ".repeat(10));
+
+        return if dry {
+            Ok(())
+        } else {
+            Ok(output::write_image(
+                &Image::rgb(&texture, nx, ny),
+                "bake",
+                format,
+            )?)
+        };
+    }
+
+    // 转台: 相机绕场景主体逐帧环绕, 依次写入 `<输出文件名>_0000` 等
+    if let Some(frames) = args.turntable {
+        let bounds = scene
+            .subject_bounds()
+            .ok_or_else(|| Error::InvalidSettings("cannot orbit an empty scene".to_string()))?;
+        let turntable = Turntable::new(&bounds, frames, args.turntable_elevation);
+        set_interrupt_handler()?;
+
+        for frame in 0..frames {
+            if INTERRUPTED.load(Ordering::SeqCst) {
+                break;
+            }
+            eprintln!("Frame {}/{frames}", frame + 1);
+
+            let camera = turntable
+                .camera(frame, 20.0, nx as f32 / ny as f32)
+                .with_resolution(nx, ny);
+            let framebuffer = render_frame(&camera, Framebuffer::new(nx, ny, transparent));
+
+            // 被中断的帧不写入, 以免序列中混入不完整的帧
+            if !dry && framebuffer.is_complete() {
+                let frame_name = format!("{file_name}_{frame:04}");
+                output::write_image(&framebuffer.image(nx, ny), &frame_name, format)?;
+            }
+        }
+
+        return Ok(());
+    }
+
+    if args.object_stats.is_some() {
+        stats::enable_object_stats();
+    }
+    // 合焦区域: 每个像素中心的深度与合焦的深度范围, 叠加到预览与最终图像上
+    let focus = args.focus_overlay.then(|| {
+        let focus_dist = camera.focus_distance();
+        let tolerance = args
+            .focus_tolerance
+            .unwrap_or(overlay::DEFAULT_FOCUS_TOLERANCE * focus_dist);
+        eprintln!(
+            "Focus distance {focus_dist:.3}, in focus from {:.3} to {:.3}",
+            focus_dist - tolerance,
+            focus_dist + tolerance
+        );
+        let depth = DepthPass::render(&scene, &camera, nx, ny, settings.epsilon);
+        (depth, focus_dist, tolerance)
+    });
+    let tint_focus = |pixels: &mut [u8]| {
+        if let Some((depth, focus_dist, tolerance)) = &focus {
+            overlay::tint_focus(pixels, &depth.depths, *focus_dist, *tolerance);
+        }
+    };
+
+    let stage_timer = Instant::now();
+    let mut framebuffer = if args.overlay_only {
+        Framebuffer::from_pixels(vec![0; 3 * nx * ny], None, ny)
+    } else {
+        if let Integrator::Path = args.integrator {
+            set_interrupt_handler()?;
+
+            // 逐级预览, 正式渲染在其上逐行覆盖
+            if args.preview {
+                let preview = preview::render(&scene, &camera, nx, ny, &settings, |preview| {
+                    if dry {
+                        return;
+                    }
+                    let mut pixels = preview.pixels.clone();
+                    tint_focus(&mut pixels);
+                    let image = Image {
+                        rgb: &pixels,
+                        alpha: preview.alpha.as_deref(),
+                        deep: None,
+                        nx,
+                        ny,
+                    };
+                    if let Err(e) = output::write_atomic(&image, file_name, format) {
+                        eprintln!("\rFailed to write preview: {e}");
                     }
+                });
+                framebuffer = preview;
+            }
+        }
+
+        match args.anaglyph {
+            // 立体像对: 左右眼各渲染一帧, 合成为红青立体图, 被中断时只写入部分结果
+            Some(separation) => {
+                let [left, right] = [-0.5, 0.5].map(|side| {
+                    eprintln!("{} eye", if side < 0.0 { "Left" } else { "Right" });
+                    render_frame(
+                        &camera.shifted(side * separation),
+                        Framebuffer::new(nx, ny, false),
+                    )
+                });
+                Framebuffer::from_pixels(stereo::anaglyph(&left.pixels, &right.pixels), None, ny)
+            }
+            None => render_frame(&camera, framebuffer),
+        }
+    };
+
+    stats.stages.push(("render", stage_timer.elapsed()));
+
+    // 求交次数最多的实体
+    if let Some(count) = args.object_stats {
+        stats.objects = scene.object_stats(count);
+        eprintln!("{:<24} {:>14} {:>14}", "Object", "Tests", "Hits");
+        for object in &stats.objects {
+            eprintln!(
+                "{:<24} {:>14} {:>14}",
+                object.name, object.tests, object.hits
+            );
+        }
+    }
+
+    // 叠加只画在 8 位的像素上, 此时输出 8 位图像
+    if args.bvh_overlay || args.wireframe || focus.is_some() {
+        framebuffer.deep = None;
+    }
+
+    // 叠加加速结构线框
+    if args.bvh_overlay {
+        overlay::draw_bvh(&mut framebuffer.pixels, nx, ny, &camera, &scene);
+    }
+
+    // 叠加三角形线框
+    if args.wireframe {
+        overlay::draw_wireframe(&mut framebuffer.pixels, nx, ny, &camera, &scene);
+    }
 
-                    // gamma 修正
-                    col.iter().map(correct_gamma).collect::<Vec<u8>>()
-                })
-                .collect::<Vec<u8>>()
-        })
-        .collect::<Vec<u8>>();
+    // 标出合焦区域
+    tint_focus(&mut framebuffer.pixels);
 
-    #[cfg(not(feature = "benchmark"))]
+    if texture.is_some()
+        || emission.is_some()
+        || args.environment.is_some()
+        || surface_maps.iter().any(Option::is_some)
+        || color_maps.iter().any(Option::is_some)
     {
+        let (loads, evictions) = texture::stats();
+        eprintln!("Texture cache: {loads} tiles loaded, {evictions} evicted");
+    }
+    if args.memory_budget.is_some() {
+        let (resident, loads, evictions) = memory::stats();
         eprintln!(
-            "\rRendered in {:.1}s{}",
-            timer.elapsed().as_secs_f32(),
-            " ".repeat(20)
+            "Geometry: {} MiB resident, {loads} pages loaded, {evictions} evicted",
+            resident >> 20
         );
     }
 
-    // 写入结果
-    if dry {
-        Ok(())
-    } else {
-        write_image(_image, nx, ny)
+    // 写入输出文件
+    let output_timer = Instant::now();
+    let write_output = || -> error::Result<()> {
+        if dry {
+            return Ok(());
+        }
+
+        // 写入深度通道
+        if args.depth_pass {
+            let depth = DepthPass::render(&scene, &camera, nx, ny, settings.epsilon).normalized(
+                &DepthSettings {
+                    near: args.depth_near,
+                    far: args.depth_far,
+                    encoding: args.depth_encoding,
+                },
+            );
+            let depth_name = format!("{file_name}_depth");
+            if args.depth_exr {
+                output::write_float_image(&depth, nx, ny, &depth_name)?;
+            } else {
+                let rgb = DepthPass::to_rgb(&depth);
+                output::write_image(&Image::rgb(&rgb, nx, ny), &depth_name, format)?;
+            }
+        }
+
+        // 写入耗时热力图
+        if args.heatmap
+            && let Some(times) = &framebuffer.times
+        {
+            let tile = args.heatmap_tile.max(1);
+            let (rgb, slowest) = heatmap::render(times, nx, ny, tile);
+            eprintln!(
+                "Slowest tile: ({}, {}) {}x{} px, {:.3}s",
+                slowest.x, slowest.y, tile, tile, slowest.time
+            );
+            output::write_image(
+                &Image::rgb(&rgb, nx, ny),
+                &format!("{file_name}_heatmap"),
+                format,
+            )?;
+        }
+
+        // 被中断: 写入部分结果与断点 (热重载时不保存断点)
+        if !framebuffer.is_complete() {
+            output::write_image(&framebuffer.image(nx, ny), file_name, format)?;
+            if args.watch {
+                return Ok(());
+            }
+            Checkpoint {
+                nx,
+                ny,
+                ns,
+                seed,
+                settings: settings.fingerprint(),
+                framebuffer,
+            }
+            .save(file_name)?;
+            eprintln!("Checkpoint saved, run again with `--resume` to continue");
+
+            return Ok(());
+        }
+
+        // 写入结果
+        output::write_image(&framebuffer.image(nx, ny), file_name, format)?;
+        Ok(Checkpoint::remove(file_name)?)
+    };
+    let result = write_output();
+    stats.stages.push(("output", output_timer.elapsed()));
+
+    // 写入统计
+    if let Some(path) = &args.stats_json {
+        fs::write(path, stats.to_json())?;
+    }
+    if args.watch {
+        *WATCHED_SCENE.lock().unwrap() = Some((geometry, scene));
     }
+
+    result
 }
@@ -1,30 +1,19 @@
 use crate::hittable::HitRecord;
 use crate::ray::Ray;
+use crate::rng::{self, hash_to_unit};
+use crate::sampling::{random_in_unit_sphere, sample_ggx};
+use crate::texture::{self, TextureId};
 
-use nalgebra::Vector3;
+use nalgebra::{Vector3, Vector4};
 use rand::Rng;
 
-/// 在单位球内随机采样一点
-fn random_in_unit_sphere() -> Vector3<f32> {
-    let mut rng = rand::rng();
-    let theta = rng.random_range(0.0..std::f32::consts::PI * 2.0);
-    let phi = rng.random_range(0.0..std::f32::consts::PI);
-    let r = rng.random::<f32>().cbrt();
-
-    Vector3::new(
-        r * theta.sin() * phi.cos(),
-        r * theta.sin() * phi.sin(),
-        r * theta.cos(),
-    )
-}
-
 /// 反射向量
-fn reflect(v: &Vector3<f32>, n: &Vector3<f32>) -> Vector3<f32> {
+pub(crate) fn reflect(v: &Vector3<f32>, n: &Vector3<f32>) -> Vector3<f32> {
     v - 2.0 * v.dot(n) * n
 }
 
 /// 折射向量
-fn refract(v: &Vector3<f32>, n: &Vector3<f32>, ni_over_nt: f32) -> Option<Vector3<f32>> {
+pub(crate) fn refract(v: &Vector3<f32>, n: &Vector3<f32>, ni_over_nt: f32) -> Option<Vector3<f32>> {
     let uv = v.normalize();
     let dt = uv.dot(n);
     let disc = 1.0 - ni_over_nt.powi(2) * (1.0 - dt.powi(2));
@@ -37,16 +26,189 @@ fn refract(v: &Vector3<f32>, n: &Vector3<f32>, ni_over_nt: f32) -> Option<Vector
 }
 
 /// Schlick 近似下的反射系数
-fn schlick(cosine: f32, ref_idx: f32) -> f32 {
+pub(crate) fn schlick(cosine: f32, ref_idx: f32) -> f32 {
     let r0 = ((1.0 - ref_idx) / (1.0 + ref_idx)).powi(2);
 
     (1.0 - r0) * (1.0 - cosine).powi(5) + r0
 }
 
+/// 金属片的微表面法线, 同一网格单元内的金属片朝向一致
+pub(crate) fn flake_normal(
+    position: &Vector3<f32>,
+    normal: &Vector3<f32>,
+    size: f32,
+    roughness: f32,
+) -> Vector3<f32> {
+    let cell = position.map(|c| (c / size).floor() as i32 as u32);
+    let seed = cell.x.wrapping_mul(73_856_093)
+        ^ cell.y.wrapping_mul(19_349_663)
+        ^ cell.z.wrapping_mul(83_492_791);
+
+    // 在单位球内取一个由单元决定的扰动
+    let theta = hash_to_unit(seed) * std::f32::consts::PI * 2.0;
+    let z = hash_to_unit(seed ^ 0x9e37_79b9) * 2.0 - 1.0;
+    let r = hash_to_unit(seed ^ 0x85eb_ca6b).cbrt();
+    let sxy = (1.0 - z * z).sqrt();
+    let offset = r * Vector3::new(sxy * theta.cos(), sxy * theta.sin(), z);
+
+    (normal + roughness * offset).normalize()
+}
+
+/// GGX 分布的 Smith 遮蔽函数, `cosine` 为方向与法线夹角的余弦
+fn smith_g1(cosine: f32, alpha: f32) -> f32 {
+    let cos2 = cosine * cosine;
+    let tan2 = (1.0 - cos2).max(0.0) / cos2.max(1e-8);
+
+    2.0 / (1.0 + (1.0 + alpha * alpha * tan2).sqrt())
+}
+
+/// 毛发纤维的切线方向, 取法线平面内朝上的方向 (经线), 使纤维像梳过一样竖直垂下
+pub(crate) fn fiber_tangent(normal: &Vector3<f32>) -> Vector3<f32> {
+    let up = if normal.y.abs() > 0.999 {
+        Vector3::new(1.0, 0.0, 0.0)
+    } else {
+        Vector3::new(0.0, 1.0, 0.0)
+    };
+
+    (up - up.dot(normal) * normal).normalize()
+}
+
+/// 毛发各波瓣 (R, TT, TRT 及更高阶残余) 的衰减
+pub(crate) fn hair_attenuation(
+    cos_theta_o: f32,
+    h: f32,
+    eta: f32,
+    transmittance: &Vector3<f32>,
+) -> [Vector3<f32>; 4] {
+    let cos_gamma_o = (1.0 - h * h).max(0.0).sqrt();
+    let f = schlick(cos_theta_o * cos_gamma_o, eta);
+    let one = Vector3::new(1.0, 1.0, 1.0);
+
+    let r = one * f;
+    let tt = (1.0 - f).powi(2) * transmittance;
+    let trt = tt.component_mul(transmittance) * f;
+    let residual = trt
+        .component_mul(transmittance)
+        .zip_map(transmittance, |a, t| a * f / (1.0 - t * f).max(1e-4));
+
+    [r, tt, trt, residual]
+}
+
+/// 毛发纵向散射的方差 v, 由纵向粗糙度换算
+pub(crate) fn hair_variance(beta_m: f32) -> f32 {
+    (0.726 * beta_m + 0.812 * beta_m.powi(2) + 3.7 * beta_m.powi(20))
+        .powi(2)
+        .max(1e-4)
+}
+
+/// 毛发方位角散射的 logistic 分布尺度 s, 由方位角粗糙度换算
+pub(crate) fn hair_logistic_scale(beta_n: f32) -> f32 {
+    ((std::f32::consts::PI / 8.0).sqrt()
+        * (0.265 * beta_n + 1.194 * beta_n.powi(2) + 5.372 * beta_n.powi(22)))
+    .max(1e-4)
+}
+
+/// 毛发纵向散射的采样 (以 sin 和 cos 表示出射角)
+fn sample_hair_longitudinal(
+    sin_theta_o: f32,
+    cos_theta_o: f32,
+    variance: f32,
+    u: (f32, f32),
+) -> (f32, f32) {
+    let cos_theta = 1.0 + variance * (u.0 + (1.0 - u.0) * (-2.0 / variance).exp()).max(1e-7).ln();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let cos_phi = (2.0 * std::f32::consts::PI * u.1).cos();
+    let sin_theta_i =
+        (-cos_theta * sin_theta_o + sin_theta * cos_phi * cos_theta_o).clamp(-1.0, 1.0);
+
+    (
+        sin_theta_i,
+        (1.0 - sin_theta_i * sin_theta_i).max(0.0).sqrt(),
+    )
+}
+
+/// 截断到 [-π, π] 的 logistic 分布采样
+fn sample_trimmed_logistic(u: f32, s: f32) -> f32 {
+    let pi = std::f32::consts::PI;
+    let cdf = |x: f32| 1.0 / (1.0 + (-x / s).exp());
+    let k = cdf(pi) - cdf(-pi);
+    let x = -s * (1.0 / (u * k + cdf(-pi)) - 1.0).ln();
+
+    x.clamp(-pi, pi)
+}
+
+/// 材质参数: 常数或由纹理的一个通道驱动
+#[derive(Clone, Copy, Debug)]
+pub enum Parameter {
+    /// 常数
+    Constant(f32),
+
+    /// 纹理的一个通道 (0, 1, 2 分别为红, 绿, 蓝), 可从打包的贴图 (如 glTF 的 ORM 贴图) 中取用
+    Texture { texture: TextureId, channel: usize },
+}
+
+impl Parameter {
+    /// 由灰度纹理 (红色通道) 驱动的参数
+    pub const fn grayscale(texture: TextureId) -> Self {
+        Self::Texture {
+            texture,
+            channel: 0,
+        }
+    }
+
+    /// 参数在纹理坐标 `uv` 处的值, 纹理按 `footprint` 的宽度过滤
+    pub fn at(self, uv: (f32, f32), footprint: f32) -> f32 {
+        match self {
+            Self::Constant(value) => value,
+            Self::Texture { texture, channel } => {
+                texture::sample_scalar(texture, uv, footprint, channel)
+            }
+        }
+    }
+}
+
+/// 颜色参数: 常数或纹理乘以系数 (如 glTF 的基础颜色贴图与基础颜色系数)
+#[derive(Clone, Copy, Debug)]
+pub enum ColorParameter {
+    /// 常数
+    Constant(Vector3<f32>),
+
+    /// 纹理的颜色逐分量乘以 `factor`
+    Texture {
+        texture: TextureId,
+        factor: Vector3<f32>,
+    },
+}
+
+impl ColorParameter {
+    /// 参数在纹理坐标 `uv` 处的值, 纹理按 `footprint` 的宽度过滤
+    pub fn at(self, uv: (f32, f32), footprint: f32) -> Vector3<f32> {
+        match self {
+            Self::Constant(color) => color,
+            Self::Texture { texture, factor } => {
+                texture::sample_filtered(texture, uv, footprint).component_mul(&factor)
+            }
+        }
+    }
+}
+
+/// 散射所经过的波瓣类型
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Lobe {
+    /// 漫反射
+    Diffuse,
+
+    /// 镜面或光泽反射
+    Specular,
+
+    /// 透射 (折射)
+    Transmission,
+}
+
 /// 可散射表面
 pub trait Scatter: Send + Sync {
-    /// 光线散射
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)>;
+    /// 光线散射, 返回散射光线, 衰减及所经过的波瓣
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>, Lobe)>;
 }
 
 /// 材质
@@ -55,19 +217,171 @@ pub enum Material {
     /// 漫反射
     Lambertian { albedo: Vector3<f32> },
 
+    /// 以图像纹理为颜色的漫反射
+    TexturedLambertian { texture: TextureId },
+
+    /// 自发光 (不散射): 辐射为颜色或纹理乘以强度
+    Emissive {
+        color: Vector3<f32>,
+        texture: Option<TextureId>,
+        intensity: f32,
+    },
+
+    /// 阴影捕捉: 自身不可见 (显示背后的环境), 只呈现其他实体投下的阴影, 由积分器特殊处理
+    ShadowCatcher,
+
     /// 金属
     Metal { albedo: Vector3<f32>, fuzz: f32 },
 
     /// 玻璃
     Dielectric { ref_idx: f32 },
+
+    /// 车漆 (清漆层 + 金属片层 + 底色层)
+    CarPaint {
+        base: Vector3<f32>,
+        flake: Vector3<f32>,
+        flake_density: f32,
+        flake_size: f32,
+        flake_roughness: f32,
+        coat_ior: f32,
+    },
+
+    /// 绒面 (漫反射底色 + 掠射角逆反射光泽)
+    Sheen {
+        albedo: Vector3<f32>,
+        sheen: Vector3<f32>,
+        roughness: f32,
+    },
+
+    /// 简化的迪士尼原则化材质: GGX 微表面的镜面反射 + 漫反射底色, 底色, 粗糙度, 金属度与高光强度可由纹理驱动
+    ///
+    /// 法线贴图为切线空间的法线 (线性数值, 绿色通道朝纹理坐标 v 增大的方向), 在带切线的网格与球体上扰动着色法线
+    Principled {
+        base: ColorParameter,
+        roughness: Parameter,
+        metallic: Parameter,
+        specular: Parameter,
+        normal_map: Option<TextureId>,
+    },
+
+    /// 毛发 (R, TT, TRT 三个波瓣, 纵向与方位角粗糙度分开)
+    Hair {
+        sigma_a: Vector3<f32>,
+        beta_m: f32,
+        beta_n: f32,
+        alpha: f32,
+        eta: f32,
+    },
 }
 
 impl Material {
+    /// 白模材质 (中性灰的漫反射)
+    pub const CLAY: Self = Self::lambertian(Vector3::new(0.5, 0.5, 0.5));
+
+    /// 白模渲染时的替代材质: 散射光线的材质替换为白模材质, 自发光与阴影捕捉保持不变
+    pub const fn clay(self) -> Self {
+        match self {
+            Self::Emissive { .. } | Self::ShadowCatcher => self,
+            _ => Self::CLAY,
+        }
+    }
+
     /// 构建漫反射
     pub const fn lambertian(albedo: Vector3<f32>) -> Self {
         Self::Lambertian { albedo }
     }
 
+    /// 构建以图像纹理为颜色的漫反射
+    pub const fn textured_lambertian(texture: TextureId) -> Self {
+        Self::TexturedLambertian { texture }
+    }
+
+    /// 构建原则化材质
+    pub const fn principled(
+        base: Vector3<f32>,
+        roughness: Parameter,
+        metallic: Parameter,
+        specular: Parameter,
+    ) -> Self {
+        Self::Principled {
+            base: ColorParameter::Constant(base),
+            roughness,
+            metallic,
+            specular,
+            normal_map: None,
+        }
+    }
+
+    /// 原则化材质的底色改由纹理 `texture` 乘以原有的底色给出, 其他材质不变
+    pub fn with_base_color_map(mut self, texture: TextureId) -> Self {
+        if let Self::Principled { base, .. } = &mut self {
+            let factor = match *base {
+                ColorParameter::Constant(color) => color,
+                ColorParameter::Texture { factor, .. } => factor,
+            };
+            *base = ColorParameter::Texture { texture, factor };
+        }
+
+        self
+    }
+
+    /// 为原则化材质加上法线贴图 `texture`, 其他材质不变
+    pub fn with_normal_map(mut self, texture: TextureId) -> Self {
+        if let Self::Principled { normal_map, .. } = &mut self {
+            *normal_map = Some(texture);
+        }
+
+        self
+    }
+
+    /// 按法线贴图扰动纹理坐标 `uv` 处的单位法线 `normal`, 没有法线贴图时不变
+    ///
+    /// `tangent` 的 xyz 为沿纹理坐标 u 增大方向的切线, w 为副切线 `w * normal × tangent` 的朝向
+    pub fn perturb_normal(
+        &self,
+        normal: &Vector3<f32>,
+        tangent: &Vector4<f32>,
+        uv: (f32, f32),
+        footprint: f32,
+    ) -> Vector3<f32> {
+        let Self::Principled {
+            normal_map: Some(texture),
+            ..
+        } = self
+        else {
+            return *normal;
+        };
+
+        let texel = texture::sample_filtered(*texture, uv, footprint) * 2.0 - Vector3::repeat(1.0);
+        let sign = tangent.w;
+        let tangent = (tangent.xyz() - normal * normal.dot(&tangent.xyz()))
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(|| fiber_tangent(normal));
+        let bitangent = sign * normal.cross(&tangent);
+
+        (tangent * texel.x + bitangent * texel.y + normal * texel.z)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or(*normal)
+    }
+
+    /// 构建自发光
+    pub const fn emissive(color: Vector3<f32>, intensity: f32) -> Self {
+        Self::Emissive {
+            color,
+            texture: None,
+            intensity,
+        }
+    }
+
+    /// 构建以图像纹理为辐射的自发光, 如屏幕, 霓虹灯牌与 HDR 灯板
+    pub const fn textured_emissive(texture: TextureId, intensity: f32) -> Self {
+        Self::Emissive {
+            color: Vector3::new(1.0, 1.0, 1.0),
+            texture: Some(texture),
+            intensity,
+        }
+    }
+
     /// 构建金属
     pub const fn metal(albedo: Vector3<f32>, fuzz: f32) -> Self {
         Self::Metal { albedo, fuzz }
@@ -77,17 +391,174 @@ impl Material {
     pub const fn dielectric(ref_idx: f32) -> Self {
         Self::Dielectric { ref_idx }
     }
+
+    /// 构建车漆
+    #[allow(unused)]
+    pub const fn car_paint(
+        base: Vector3<f32>,
+        flake: Vector3<f32>,
+        flake_density: f32,
+        flake_size: f32,
+        flake_roughness: f32,
+        coat_ior: f32,
+    ) -> Self {
+        Self::CarPaint {
+            base,
+            flake,
+            flake_density,
+            flake_size,
+            flake_roughness,
+            coat_ior,
+        }
+    }
+
+    /// 构建绒面
+    #[allow(unused)]
+    pub const fn sheen(albedo: Vector3<f32>, sheen: Vector3<f32>, roughness: f32) -> Self {
+        Self::Sheen {
+            albedo,
+            sheen,
+            roughness,
+        }
+    }
+
+    /// 构建毛发, `alpha` 为毛鳞片倾角 (弧度)
+    #[allow(unused)]
+    pub const fn hair(
+        sigma_a: Vector3<f32>,
+        beta_m: f32,
+        beta_n: f32,
+        alpha: f32,
+        eta: f32,
+    ) -> Self {
+        Self::Hair {
+            sigma_a,
+            beta_m,
+            beta_n,
+            alpha,
+            eta,
+        }
+    }
+
+    /// 由真黑色素与褐黑色素浓度构建毛发
+    #[allow(unused)]
+    pub fn hair_from_melanin(eumelanin: f32, pheomelanin: f32, beta_m: f32, beta_n: f32) -> Self {
+        let sigma_a = eumelanin * Vector3::new(0.419, 0.697, 1.37)
+            + pheomelanin * Vector3::new(0.187, 0.4, 1.05);
+
+        Self::hair(sigma_a, beta_m, beta_n, 2.0_f32.to_radians(), 1.55)
+    }
+}
+
+impl Material {
+    /// 材质在纹理坐标 `uv` 处的基础颜色, 用于非真实感渲染等不追踪散射的场合
+    pub fn albedo(&self, uv: (f32, f32)) -> Vector3<f32> {
+        match self {
+            Self::TexturedLambertian { texture } => texture::sample(*texture, uv),
+            Self::Lambertian { albedo }
+            | Self::Metal { albedo, .. }
+            | Self::Sheen { albedo, .. } => *albedo,
+            Self::Dielectric { .. } | Self::ShadowCatcher => Vector3::new(1.0, 1.0, 1.0),
+            Self::CarPaint { base, .. } => *base,
+            Self::Principled { base, .. } => base.at(uv, 0.0),
+            Self::Hair { sigma_a, .. } => sigma_a.map(|s| (-s).exp()),
+            Self::Emissive { color, texture, .. } => {
+                texture.map_or(*color, |texture| texture::sample(texture, uv))
+            }
+        }
+    }
+
+    /// 将基础颜色替换为 `albedo` 的材质 (如按顶点颜色着色), 没有基础颜色的材质不变
+    pub fn with_albedo(mut self, color: Vector3<f32>) -> Self {
+        match &mut self {
+            Self::TexturedLambertian { .. } => return Self::lambertian(color),
+            Self::Lambertian { albedo }
+            | Self::Metal { albedo, .. }
+            | Self::Sheen { albedo, .. } => *albedo = color,
+            Self::CarPaint { base, .. } => *base = color,
+            Self::Principled { base, .. } => *base = ColorParameter::Constant(color),
+            Self::Emissive {
+                color: emitted,
+                texture,
+                ..
+            } => {
+                *emitted = color;
+                *texture = None;
+            }
+            Self::Dielectric { .. } | Self::ShadowCatcher | Self::Hair { .. } => {}
+        }
+
+        self
+    }
+
+    /// 材质在纹理坐标 `uv` 处发出的辐射, 纹理按 `footprint` 的宽度过滤, 非自发光材质为零
+    pub fn emitted(&self, uv: (f32, f32), footprint: f32) -> Vector3<f32> {
+        match self {
+            Self::Emissive {
+                color,
+                texture,
+                intensity,
+            } => {
+                let color = texture.map_or(*color, |texture| {
+                    texture::sample_filtered(texture, uv, footprint)
+                });
+                *intensity * color
+            }
+            _ => Vector3::zeros(),
+        }
+    }
+
+    /// 漫反射的反照率, 灯光的直接光照只作用于漫反射表面; 其他材质为 `None`
+    pub fn diffuse_albedo(&self, uv: (f32, f32), footprint: f32) -> Option<Vector3<f32>> {
+        match self {
+            Self::Lambertian { albedo } => Some(*albedo),
+            Self::TexturedLambertian { texture } => {
+                Some(texture::sample_filtered(*texture, uv, footprint))
+            }
+            _ => None,
+        }
+    }
+
+    /// 是否为理想镜面 (玻璃或无模糊的金属), 其散射方向由入射方向唯一确定
+    pub fn is_specular(&self) -> bool {
+        match self {
+            Self::Dielectric { .. } => true,
+            Self::Metal { fuzz, .. } => *fuzz == 0.0,
+            _ => false,
+        }
+    }
 }
 
 impl Scatter for Material {
-    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>)> {
+    fn scatter(&self, ray: &Ray, hit: &HitRecord) -> Option<(Ray, Vector3<f32>, Lobe)> {
         match self {
             Self::Lambertian { albedo } => {
                 // 随机反射
-                let target = hit.position + hit.normal + random_in_unit_sphere();
+                let target = hit.position + hit.normal + random_in_unit_sphere(&mut rng::local());
+                let scattered = Ray::from(hit.position, target - hit.position);
+
+                Some((scattered, *albedo, Lobe::Diffuse))
+            }
+
+            Self::Emissive { .. } => None,
+
+            // 作为白色漫反射散射, 积分器以此估计接收到的光照
+            Self::ShadowCatcher => {
+                let target = hit.position + hit.normal + random_in_unit_sphere(&mut rng::local());
+                let scattered = Ray::from(hit.position, target - hit.position);
+
+                Some((scattered, Vector3::new(1.0, 1.0, 1.0), Lobe::Diffuse))
+            }
+
+            Self::TexturedLambertian { texture } => {
+                let target = hit.position + hit.normal + random_in_unit_sphere(&mut rng::local());
                 let scattered = Ray::from(hit.position, target - hit.position);
 
-                Some((scattered, *albedo))
+                Some((
+                    scattered,
+                    texture::sample_filtered(*texture, hit.uv, hit.footprint),
+                    Lobe::Diffuse,
+                ))
             }
 
             Self::Metal { albedo, fuzz } => {
@@ -95,13 +566,13 @@ impl Scatter for Material {
 
                 // 模糊
                 if *fuzz > 0.0 {
-                    reflected += *fuzz * random_in_unit_sphere();
+                    reflected += *fuzz * random_in_unit_sphere(&mut rng::local());
                 }
 
                 // 检查反射方向是否在表面上方
                 if reflected.dot(&hit.normal) > 0.0 {
                     let scattered = Ray::from(hit.position, reflected);
-                    Some((scattered, *albedo))
+                    Some((scattered, *albedo, Lobe::Specular))
                 } else {
                     None
                 }
@@ -124,16 +595,228 @@ impl Scatter for Material {
                 // 尝试折射
                 if let Some(refracted) = refract(&ray.direction(), &outward_normal, ni_over_nt) {
                     let reflect_prob = schlick(cosine, *ref_idx);
-                    if rand::rng().random::<f32>() >= reflect_prob {
+                    if rng::local().random::<f32>() >= reflect_prob {
                         let scattered = Ray::from(hit.position, refracted);
-                        return Some((scattered, attenuation));
+                        return Some((scattered, attenuation, Lobe::Transmission));
                     }
                 }
 
                 let reflected = reflect(&ray.direction(), &hit.normal);
                 let scattered = Ray::from(hit.position, reflected);
 
-                Some((scattered, attenuation))
+                Some((scattered, attenuation, Lobe::Specular))
+            }
+
+            Self::CarPaint {
+                base,
+                flake,
+                flake_density,
+                flake_size,
+                flake_roughness,
+                coat_ior,
+            } => {
+                let mut rng = rng::local();
+                let unit_direction = ray.direction().normalize();
+                let cosine = (-unit_direction.dot(&hit.normal)).clamp(0.0, 1.0);
+
+                // 清漆层: 按 Fresnel 系数镜面反射
+                if rng.random::<f32>() < schlick(cosine, *coat_ior) {
+                    let reflected = reflect(&unit_direction, &hit.normal);
+                    let scattered = Ray::from(hit.position, reflected);
+                    return Some((scattered, Vector3::new(1.0, 1.0, 1.0), Lobe::Specular));
+                }
+
+                // 金属片层: 按微表面法线反射
+                if rng.random::<f32>() < *flake_density {
+                    let normal =
+                        flake_normal(&hit.position, &hit.normal, *flake_size, *flake_roughness);
+                    let reflected = reflect(&unit_direction, &normal);
+                    if reflected.dot(&hit.normal) > 0.0 {
+                        let scattered = Ray::from(hit.position, reflected);
+                        return Some((scattered, *flake, Lobe::Specular));
+                    }
+                }
+
+                // 底色层: 漫反射
+                let target = hit.position + hit.normal + random_in_unit_sphere(&mut rng);
+                let scattered = Ray::from(hit.position, target - hit.position);
+
+                Some((scattered, *base, Lobe::Diffuse))
+            }
+
+            Self::Sheen {
+                albedo,
+                sheen,
+                roughness,
+            } => {
+                let mut rng = rng::local();
+                let unit_direction = ray.direction().normalize();
+                let cosine = (-unit_direction.dot(&hit.normal)).clamp(0.0, 1.0);
+
+                // 光泽层: 越接近掠射角越可能沿入射方向逆反射
+                if rng.random::<f32>() < (1.0 - cosine).powi(2) {
+                    let retro = -unit_direction + *roughness * random_in_unit_sphere(&mut rng);
+                    if retro.dot(&hit.normal) > 0.0 {
+                        let scattered = Ray::from(hit.position, retro);
+                        return Some((scattered, *sheen, Lobe::Specular));
+                    }
+                }
+
+                // 底色层: 漫反射
+                let target = hit.position + hit.normal + random_in_unit_sphere(&mut rng);
+                let scattered = Ray::from(hit.position, target - hit.position);
+
+                Some((scattered, *albedo, Lobe::Diffuse))
+            }
+
+            Self::Principled {
+                base,
+                roughness,
+                metallic,
+                specular,
+                ..
+            } => {
+                let mut rng = rng::local();
+                let base = base.at(hit.uv, hit.footprint);
+                let roughness = roughness.at(hit.uv, hit.footprint).clamp(0.0, 1.0);
+                let metallic = metallic.at(hit.uv, hit.footprint).clamp(0.0, 1.0);
+                let specular = specular.at(hit.uv, hit.footprint).clamp(0.0, 1.0);
+
+                let wo = -ray.direction().normalize();
+                let cos_o = wo.dot(&hit.normal);
+                if cos_o <= 0.0 {
+                    return None;
+                }
+
+                // 法向入射的反射率: 非金属为与高光强度成正比的灰色, 金属为底色
+                let f0 = Vector3::repeat(0.08 * specular).lerp(&base, metallic);
+                let fresnel = |cosine: f32| {
+                    f0 + (Vector3::repeat(1.0) - f0) * (1.0 - cosine.clamp(0.0, 1.0)).powi(5)
+                };
+
+                // 按镜面反射所占的比例选择波瓣, 金属没有漫反射
+                let spec_prob = fresnel(cos_o).mean() + (1.0 - fresnel(cos_o).mean()) * metallic;
+                if rng.random::<f32>() < spec_prob {
+                    // 镜面反射: 按 GGX 采样微表面法线, 权重为 F·G·(wo·h) / ((n·wo)(n·h))
+                    let alpha = (roughness * roughness).max(1e-3);
+                    let h = sample_ggx(&hit.normal, alpha, &mut rng);
+                    let wi = reflect(&-wo, &h);
+                    let cos_i = wi.dot(&hit.normal);
+                    if cos_i <= 0.0 {
+                        return None;
+                    }
+
+                    let cos_oh = wo.dot(&h).max(0.0);
+                    let g = smith_g1(cos_o, alpha) * smith_g1(cos_i, alpha);
+                    let weight = g * cos_oh / (cos_o * h.dot(&hit.normal)).max(1e-8);
+                    let scattered = Ray::from(hit.position, wi);
+
+                    return Some((
+                        scattered,
+                        fresnel(cos_oh) * weight / spec_prob,
+                        Lobe::Specular,
+                    ));
+                }
+
+                // 漫反射: 扣除在表面被镜面反射的部分
+                let target = hit.position + hit.normal + random_in_unit_sphere(&mut rng);
+                let scattered = Ray::from(hit.position, target - hit.position);
+                let diffuse =
+                    base.component_mul(&(Vector3::repeat(1.0) - fresnel(cos_o))) * (1.0 - metallic);
+
+                Some((scattered, diffuse / (1.0 - spec_prob), Lobe::Diffuse))
+            }
+
+            Self::Hair {
+                sigma_a,
+                beta_m,
+                beta_n,
+                alpha,
+                eta,
+            } => {
+                let mut rng = rng::local();
+                let pi = std::f32::consts::PI;
+
+                // 纤维局部坐标系: 切线 t, 法线 n, 副法线 b
+                let t = fiber_tangent(&hit.normal);
+                let n = hit.normal;
+                let b = t.cross(&n);
+
+                // 出射方向 (指向观察者) 的纵向角与方位角
+                let wo = -ray.direction().normalize();
+                let sin_theta_o = wo.dot(&t).clamp(-1.0, 1.0);
+                let cos_theta_o = (1.0 - sin_theta_o * sin_theta_o).max(0.0).sqrt();
+                let phi_o = wo.dot(&b).atan2(wo.dot(&n));
+
+                // 横截面上的偏移及折射后的角度
+                let h = phi_o.sin().clamp(-1.0, 1.0);
+                let gamma_o = h.asin();
+                let sin_theta_t = sin_theta_o / eta;
+                let cos_theta_t = (1.0 - sin_theta_t * sin_theta_t).max(1e-4).sqrt();
+                let etap =
+                    (eta * eta - sin_theta_o * sin_theta_o).max(0.0).sqrt() / cos_theta_o.max(1e-4);
+                let sin_gamma_t = (h / etap).clamp(-1.0, 1.0);
+                let cos_gamma_t = (1.0 - sin_gamma_t * sin_gamma_t).sqrt();
+                let gamma_t = sin_gamma_t.asin();
+
+                // 纤维内部一次穿行的透射率
+                let transmittance = sigma_a.map(|s| (-s * 2.0 * cos_gamma_t / cos_theta_t).exp());
+                let attenuations = hair_attenuation(cos_theta_o, h, *eta, &transmittance);
+
+                // 按衰减选取波瓣
+                let weights = attenuations.map(|a| a.mean());
+                let total: f32 = weights.iter().sum();
+                if total <= 0.0 {
+                    return None;
+                }
+                let mut pick = rng.random::<f32>() * total;
+                let mut p = 3;
+                for (i, w) in weights.iter().enumerate() {
+                    if pick < *w {
+                        p = i;
+                        break;
+                    }
+                    pick -= w;
+                }
+
+                // 纵向: 按毛鳞片倾角偏移后采样
+                let shift = match p {
+                    0 => -2.0 * alpha,
+                    1 => *alpha,
+                    2 => 4.0 * alpha,
+                    _ => 0.0,
+                };
+                let theta_op = sin_theta_o.asin() + shift;
+                let variance = hair_variance(*beta_m);
+                let (sin_theta_i, cos_theta_i) = sample_hair_longitudinal(
+                    theta_op.sin(),
+                    theta_op.cos(),
+                    variance,
+                    (rng.random(), rng.random()),
+                );
+
+                // 方位角: 以几何偏转为中心, 按 logistic 分布扰动
+                let dphi = if p < 3 {
+                    let p = p as f32;
+                    2.0 * p * gamma_t - 2.0 * gamma_o
+                        + p * pi
+                        + sample_trimmed_logistic(rng.random(), hair_logistic_scale(*beta_n))
+                } else {
+                    2.0 * pi * rng.random::<f32>()
+                };
+                let phi_i = phi_o + dphi;
+
+                let direction = sin_theta_i * t + cos_theta_i * (phi_i.cos() * n + phi_i.sin() * b);
+                let scattered = Ray::from(hit.position, direction);
+
+                // R 与 TRT 在表面及纤维内部反射, TT 及其余各阶穿过纤维
+                let lobe = if p == 0 || p == 2 {
+                    Lobe::Specular
+                } else {
+                    Lobe::Transmission
+                };
+
+                Some((scattered, attenuations[p] * total / weights[p], lobe))
             }
         }
     }
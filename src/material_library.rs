@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+
+use crate::material::Material;
+
+/// 按名称索引的材质库: 实体以下标引用其中的材质, 引用同一名称的实体共享一条定义
+///
+/// 库中也可加入不具名的材质 (只被一个实体引用); 修改定义后以 `Scene::set_materials`
+/// 替换场景的材质表, 所有引用该定义的实体随之改变, 无需重建加速结构
+#[derive(Clone, Default)]
+pub struct MaterialLibrary {
+    /// 材质表, 按加入的顺序
+    materials: Vec<Material>,
+
+    /// 名称到材质表下标的映射
+    names: HashMap<String, usize>,
+}
+
+impl MaterialLibrary {
+    /// 空的材质库
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 定义名为 `name` 的材质, 返回其下标; 名称已定义时替换原定义, 下标不变
+    pub fn define(&mut self, name: impl Into<String>, material: Material) -> usize {
+        let name = name.into();
+        if let Some(&index) = self.names.get(&name) {
+            self.materials[index] = material;
+            return index;
+        }
+
+        let index = self.push(material);
+        self.names.insert(name, index);
+        index
+    }
+
+    /// 加入不具名的材质, 返回其下标
+    pub fn push(&mut self, material: Material) -> usize {
+        self.materials.push(material);
+        self.materials.len() - 1
+    }
+
+    /// 名为 `name` 的材质的下标
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.names.get(name).copied()
+    }
+
+    /// 名为 `name` 的材质
+    pub fn get(&self, name: &str) -> Option<Material> {
+        self.index(name).map(|index| self.materials[index])
+    }
+
+    /// 材质表中的材质数
+    pub fn len(&self) -> usize {
+        self.materials.len()
+    }
+
+    /// 材质表是否为空
+    pub fn is_empty(&self) -> bool {
+        self.materials.is_empty()
+    }
+
+    /// 材质表, 下标与 `define` 和 `push` 返回的一致
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    /// 取出材质表, 用于 `Scene::set_materials`
+    pub fn into_materials(self) -> Vec<Material> {
+        self.materials
+    }
+}
@@ -0,0 +1,202 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+
+use crate::lru::Lru;
+
+/// 每页的元素数
+const PAGE_ELEMENTS: usize = 4096;
+
+/// 分页缓存至少可用的内存 (字节), 预算被常驻数据占满时仍能逐页渲染
+const MIN_CACHE: usize = 4 << 20;
+
+/// 分页缓存中页的键: 分页缓冲的编号与页号
+type PageKey = (u32, u32);
+
+/// 每个线程另外保留的最近读取的页数
+const RECENT_PAGES: usize = 4;
+
+/// 内存预算与几何分页缓存
+///
+/// 设置预算后, 载入的网格缓冲在预算内常驻内存, 超出时改为按页从磁盘缓存读取;
+/// 预算中常驻数据以外的部分由几何分页缓存与纹理缓存平分。只计入网格的顶点与索引缓冲,
+/// 不计入 BVH 与网格的面
+struct Memory {
+    /// 内存预算 (字节), 未设置时为 `usize::MAX`
+    budget: AtomicUsize,
+
+    /// 常驻内存的几何数据 (字节)
+    resident: AtomicUsize,
+
+    /// 分页缓冲的编号计数
+    next_id: AtomicU32,
+
+    pages: Mutex<Lru<PageKey, Arc<[u8]>>>,
+
+    /// 读取与淘汰的页数
+    loads: AtomicUsize,
+    evictions: AtomicUsize,
+}
+
+static MEMORY: LazyLock<Memory> = LazyLock::new(|| Memory {
+    budget: AtomicUsize::new(usize::MAX),
+    resident: AtomicUsize::new(0),
+    next_id: AtomicU32::new(0),
+    pages: Mutex::new(Lru::default()),
+    loads: AtomicUsize::new(0),
+    evictions: AtomicUsize::new(0),
+});
+
+thread_local! {
+    /// 本线程最近读取的页 (最近的在前), 命中时不必锁住全局的分页缓存
+    ///
+    /// 页的内容不变且编号不会复用, 全局缓存淘汰后这里的副本仍然有效, 只是暂不计入预算
+    static RECENT: RefCell<VecDeque<(PageKey, Arc<[u8]>)>> =
+        const { RefCell::new(VecDeque::new()) };
+}
+
+/// 设置内存预算 (字节)
+pub fn set_budget(bytes: usize) {
+    MEMORY.budget.store(bytes, Ordering::Relaxed);
+}
+
+/// 为常驻内存的几何数据预留 `bytes` 字节, 超出预算时不预留并返回 `false`
+pub fn reserve(bytes: usize) -> bool {
+    let budget = MEMORY.budget.load(Ordering::Relaxed);
+    MEMORY
+        .resident
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |resident| {
+            resident.checked_add(bytes).filter(|&total| total <= budget)
+        })
+        .is_ok()
+}
+
+/// 预算中分给一个缓存 (几何分页或纹理) 的内存 (字节): 常驻数据以外部分的一半
+pub fn cache_budget() -> usize {
+    let budget = MEMORY.budget.load(Ordering::Relaxed);
+    let resident = MEMORY.resident.load(Ordering::Relaxed);
+
+    (budget.saturating_sub(resident) / 2).max(MIN_CACHE)
+}
+
+/// 常驻内存的几何数据, 读取与淘汰的页数
+pub fn stats() -> (usize, usize, usize) {
+    (
+        MEMORY.resident.load(Ordering::Relaxed),
+        MEMORY.loads.load(Ordering::Relaxed),
+        MEMORY.evictions.load(Ordering::Relaxed),
+    )
+}
+
+/// 磁盘文件中的定长元素数组, 按页读取到全局的分页缓存
+pub struct PagedBuffer {
+    id: u32,
+    path: PathBuf,
+
+    /// 数组在文件中的起始位置
+    offset: u64,
+
+    /// 每个元素的字节数
+    stride: usize,
+
+    /// 元素数
+    len: usize,
+}
+
+impl PagedBuffer {
+    /// 文件 `path` 中自 `offset` 起的 `len` 个元素, 每个元素 `stride` 字节
+    pub fn new(path: &Path, offset: u64, stride: usize, len: usize) -> Self {
+        Self {
+            id: MEMORY.next_id.fetch_add(1, Ordering::Relaxed),
+            path: path.to_path_buf(),
+            offset,
+            stride,
+            len,
+        }
+    }
+
+    /// 元素数
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 数组占用的字节数
+    pub const fn byte_len(&self) -> usize {
+        self.stride * self.len
+    }
+
+    /// 第 `index` 个元素, 按小端序读作 `N` 个 32 位字 (`N * 4` 须等于元素的字节数)
+    ///
+    /// 读取失败 (如缓存文件被删除) 时为全零
+    pub fn words<const N: usize>(&self, index: usize) -> [u32; N] {
+        let page = (index / PAGE_ELEMENTS) as u32;
+        let start = index % PAGE_ELEMENTS * self.stride;
+        let decode = |data: &[u8]| {
+            std::array::from_fn(|i| {
+                u32::from_le_bytes(data[start + 4 * i..start + 4 * i + 4].try_into().unwrap())
+            })
+        };
+
+        let key = (self.id, page);
+        let recent = RECENT.with_borrow(|recent| {
+            recent
+                .iter()
+                .find(|(recent, _)| *recent == key)
+                .map(|(_, data)| decode(data))
+        });
+        if let Some(words) = recent {
+            return words;
+        }
+
+        let cached = MEMORY.pages.lock().unwrap().get(&key).cloned();
+        let data = match cached {
+            Some(data) => data,
+            None => {
+                // 未命中: 在锁外读取, 之后再放入缓存
+                let Ok(data) = self.read_page(page as usize) else {
+                    return [0; N];
+                };
+                insert(key, Arc::clone(&data));
+                data
+            }
+        };
+        let words = decode(&data);
+        RECENT.with_borrow_mut(|recent| {
+            recent.push_front((key, data));
+            recent.truncate(RECENT_PAGES);
+        });
+
+        words
+    }
+
+    fn read_page(&self, page: usize) -> io::Result<Arc<[u8]>> {
+        let first = page * PAGE_ELEMENTS;
+        let count = PAGE_ELEMENTS.min(self.len - first);
+        let mut data = vec![0; count * self.stride];
+
+        let mut file = File::open(&self.path)?;
+        file.seek(SeekFrom::Start(self.offset + (first * self.stride) as u64))?;
+        file.read_exact(&mut data)?;
+
+        Ok(data.into())
+    }
+}
+
+/// 放入页, 超出预算时淘汰最久未使用的页
+fn insert(key: PageKey, data: Arc<[u8]>) {
+    let size = data.len();
+    let budget = cache_budget();
+    let mut pages = MEMORY.pages.lock().unwrap();
+    if let Some(evicted) = pages.insert(key, data, size, budget) {
+        MEMORY.loads.fetch_add(1, Ordering::Relaxed);
+        MEMORY.evictions.fetch_add(evicted, Ordering::Relaxed);
+    }
+}
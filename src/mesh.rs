@@ -0,0 +1,644 @@
+use std::sync::Arc;
+
+use crate::accelerator::Accelerator;
+use crate::bvh::{AaBb, BVHNode, Bounded, ClosestPoint, MAX_OBJECTS};
+use crate::bvh_cache;
+use crate::error::{Error, Result};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::{Material, fiber_tangent};
+use crate::mesh_cache::PagedMesh;
+use crate::ray::Ray;
+use crate::triangle;
+
+use nalgebra::{Point3, SVector, Similarity3, Vector2, Vector3, Vector4};
+
+/// 三角形网格的顶点与索引缓冲
+///
+/// 位置, 法线与纹理坐标各自保存在共享的缓冲中, 变换或复制网格只复制引用;
+/// 每个面只记录网格与自身的序号, 不复制顶点数据
+#[derive(Clone)]
+pub struct TriangleMesh {
+    /// 顶点位置
+    positions: Arc<[Vector3<f32>]>,
+
+    /// 顶点法线, 插值为着色法线; 为 `None` 时使用面法线
+    normals: Option<Arc<[Vector3<f32>]>>,
+
+    /// 顶点纹理坐标; 为 `None` 时为面内的重心坐标
+    uvs: Option<Arc<[(f32, f32)]>>,
+
+    /// 顶点切线, 有纹理坐标时生成: xyz 为与法线正交的单位切线 (u 增大的方向),
+    /// w 为副切线的朝向 (±1), 副切线为 `w * normal × tangent`, 与 MikkTSpace 的约定相同
+    tangents: Option<Arc<[Vector4<f32>]>>,
+
+    /// 顶点颜色 (线性), 插值后替换材质的反照率
+    colors: Option<Arc<[Vector3<f32>]>>,
+
+    /// 每个面的三个顶点序号, 逆时针顺序为正面
+    indices: Arc<[[u32; 3]]>,
+
+    /// 材质
+    material: Material,
+}
+
+impl TriangleMesh {
+    /// 由顶点位置与面的顶点序号构建网格, 序号越界时返回错误
+    pub fn new(
+        positions: Vec<Vector3<f32>>,
+        indices: Vec<[u32; 3]>,
+        material: Material,
+    ) -> Result<Self> {
+        if let Some(index) = indices
+            .iter()
+            .flatten()
+            .find(|&&i| i as usize >= positions.len())
+        {
+            return Err(Error::InvalidSettings(format!(
+                "vertex index {index} out of range for {} vertices",
+                positions.len()
+            )));
+        }
+
+        Ok(Self {
+            positions: positions.into(),
+            normals: None,
+            uvs: None,
+            tangents: None,
+            colors: None,
+            indices: indices.into(),
+            material,
+        })
+    }
+
+    /// 附加顶点法线, 数量须与顶点相同; 已有纹理坐标时重新生成切线
+    pub fn with_normals(mut self, normals: Vec<Vector3<f32>>) -> Result<Self> {
+        self.check_vertex_count(normals.len(), "normals")?;
+        self.normals = Some(normals.into());
+        self.tangents = self
+            .uvs
+            .as_ref()
+            .map(|uvs| self.generate_tangents(uvs).into());
+
+        Ok(self)
+    }
+
+    /// 附加顶点纹理坐标, 数量须与顶点相同, 并由其生成顶点切线
+    pub fn with_uvs(mut self, uvs: Vec<(f32, f32)>) -> Result<Self> {
+        self.check_vertex_count(uvs.len(), "texture coordinates")?;
+        self.tangents = Some(self.generate_tangents(&uvs).into());
+        self.uvs = Some(uvs.into());
+
+        Ok(self)
+    }
+
+    /// 按 MikkTSpace 的方式生成顶点切线: 每个面由纹理坐标的梯度求出切线与副切线,
+    /// 在各角投影到顶点法线的切平面内并归一化, 以角的大小为权重累加到顶点,
+    /// 最后与法线正交化; 副切线的朝向取累加结果的符号
+    ///
+    /// 与 MikkTSpace 不同, 切线空间不连续处不拆分顶点, 两侧的切线被平均;
+    /// 纹理坐标退化的面不参与累加, 没有任何贡献的顶点取法线平面内的任意方向
+    fn generate_tangents(&self, uvs: &[(f32, f32)]) -> Vec<Vector4<f32>> {
+        let count = self.positions.len();
+        let mut tangents = vec![Vector3::zeros(); count];
+        let mut bitangents = vec![Vector3::zeros(); count];
+
+        // 没有顶点法线时以角度加权的面法线作为顶点法线, 与着色时的面法线对应
+        let normals: Vec<Vector3<f32>> = match &self.normals {
+            Some(normals) => normals.iter().map(|n| n.normalize()).collect(),
+            None => {
+                let mut normals = vec![Vector3::zeros(); count];
+                for face in self.indices.iter() {
+                    let [a, b, c] = face.map(|i| self.positions[i as usize]);
+                    let Some(normal) = (b - a).cross(&(c - a)).try_normalize(f32::EPSILON) else {
+                        continue;
+                    };
+                    for (k, &i) in face.iter().enumerate() {
+                        normals[i as usize] += corner_angle(&self.positions, face, k) * normal;
+                    }
+                }
+                normals
+                    .iter()
+                    .map(|n| n.try_normalize(f32::EPSILON).unwrap_or_else(Vector3::y))
+                    .collect()
+            }
+        };
+
+        for face in self.indices.iter() {
+            let [a, b, c] = face.map(|i| self.positions[i as usize]);
+            let [(u0, v0), (u1, v1), (u2, v2)] = face.map(|i| uvs[i as usize]);
+            let (e1, e2) = (b - a, c - a);
+            let (du1, dv1, du2, dv2) = (u1 - u0, v1 - v0, u2 - u0, v2 - v0);
+            let det = du1 * dv2 - du2 * dv1;
+            if det.abs() <= f32::EPSILON {
+                continue;
+            }
+            let tangent = (e1 * dv2 - e2 * dv1) / det;
+            let bitangent = (e2 * du1 - e1 * du2) / det;
+
+            for (k, &i) in face.iter().enumerate() {
+                let (i, normal) = (i as usize, normals[i as usize]);
+                let angle = corner_angle(&self.positions, face, k);
+                let project = |v: Vector3<f32>| {
+                    (v - normal * normal.dot(&v))
+                        .try_normalize(f32::EPSILON)
+                        .unwrap_or_else(Vector3::zeros)
+                };
+                tangents[i] += angle * project(tangent);
+                bitangents[i] += angle * project(bitangent);
+            }
+        }
+
+        (0..count)
+            .map(|i| {
+                let normal = normals[i];
+                let tangent = (tangents[i] - normal * normal.dot(&tangents[i]))
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or_else(|| fiber_tangent(&normal));
+                let sign = if normal.cross(&tangent).dot(&bitangents[i]) < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+
+                tangent.push(sign)
+            })
+            .collect()
+    }
+
+    /// 附加顶点颜色 (线性), 数量须与顶点相同
+    pub fn with_colors(mut self, colors: Vec<Vector3<f32>>) -> Result<Self> {
+        self.check_vertex_count(colors.len(), "colors")?;
+        self.colors = Some(colors.into());
+
+        Ok(self)
+    }
+
+    fn check_vertex_count(&self, count: usize, what: &str) -> Result<()> {
+        if count == self.positions.len() {
+            return Ok(());
+        }
+
+        Err(Error::InvalidSettings(format!(
+            "{count} {what} for {} vertices",
+            self.positions.len()
+        )))
+    }
+
+    /// 替换材质后的网格, 缓冲仍共享
+    pub fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    /// 顶点数
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    /// 面数
+    pub fn face_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// 顶点位置
+    pub(crate) fn positions(&self) -> &[Vector3<f32>] {
+        &self.positions
+    }
+
+    /// 顶点法线
+    pub(crate) fn normals(&self) -> Option<&[Vector3<f32>]> {
+        self.normals.as_deref()
+    }
+
+    /// 顶点纹理坐标
+    pub(crate) fn uvs(&self) -> Option<&[(f32, f32)]> {
+        self.uvs.as_deref()
+    }
+
+    /// 顶点切线 (xyz 为切线, w 为副切线的朝向), 没有纹理坐标时为 `None`
+    pub fn tangents(&self) -> Option<&[Vector4<f32>]> {
+        self.tangents.as_deref()
+    }
+
+    /// 顶点颜色
+    pub(crate) fn colors(&self) -> Option<&[Vector3<f32>]> {
+        self.colors.as_deref()
+    }
+
+    /// 每个面的三个顶点序号
+    pub(crate) fn indices(&self) -> &[[u32; 3]] {
+        &self.indices
+    }
+
+    /// 材质
+    pub(crate) const fn material(&self) -> Material {
+        self.material
+    }
+
+    /// 经相似变换后的网格: 位置, 法线与切线写入新的缓冲, 纹理坐标与索引仍共享
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        let positions = self
+            .positions
+            .iter()
+            .map(|p| transform.transform_point(&Point3::from(*p)).coords)
+            .collect();
+        let normals = self.normals.as_ref().map(|normals| {
+            normals
+                .iter()
+                .map(|n| transform.isometry.rotation * n)
+                .collect()
+        });
+        // 相似变换不改变手性, 副切线的朝向不变
+        let tangents = self.tangents.as_ref().map(|tangents| {
+            tangents
+                .iter()
+                .map(|t| (transform.isometry.rotation * t.xyz()).push(t.w))
+                .collect()
+        });
+
+        Self {
+            positions,
+            normals,
+            tangents,
+            ..self.clone()
+        }
+    }
+
+    /// 第 `face` 个面的三个顶点序号
+    fn face(&self, face: usize) -> [usize; 3] {
+        self.indices[face].map(|i| i as usize)
+    }
+}
+
+/// 网格的几何数据: 常驻内存的缓冲, 或按页从磁盘缓存读取的缓冲
+#[derive(Clone)]
+pub enum Geometry {
+    /// 常驻内存
+    Resident(TriangleMesh),
+
+    /// 按页读取, 读出的位置与法线经 `transform` 变换
+    Paged {
+        mesh: Arc<PagedMesh>,
+        transform: Similarity3<f32>,
+        material: Material,
+    },
+}
+
+impl Geometry {
+    /// 以 `material` 渲染的分页网格
+    pub fn paged(mesh: PagedMesh, material: Material) -> Self {
+        Self::Paged {
+            mesh: Arc::new(mesh),
+            transform: Similarity3::identity(),
+            material,
+        }
+    }
+
+    /// 面数
+    pub fn face_count(&self) -> usize {
+        match self {
+            Self::Resident(mesh) => mesh.face_count(),
+            Self::Paged { mesh, .. } => mesh.face_count(),
+        }
+    }
+
+    /// 经相似变换后的网格, 分页网格只记录变换
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        match self {
+            Self::Resident(mesh) => Self::Resident(mesh.transformed(transform)),
+            Self::Paged {
+                mesh,
+                transform: current,
+                material,
+            } => Self::Paged {
+                mesh: Arc::clone(mesh),
+                transform: transform * current,
+                material: *material,
+            },
+        }
+    }
+
+    /// 材质
+    pub(crate) fn material(&self) -> Material {
+        match self {
+            Self::Resident(mesh) => mesh.material,
+            Self::Paged { material, .. } => *material,
+        }
+    }
+
+    /// 第 `face` 个面的三个顶点序号
+    pub(crate) fn face(&self, face: usize) -> [usize; 3] {
+        match self {
+            Self::Resident(mesh) => mesh.face(face),
+            Self::Paged { mesh, .. } => mesh.face(face),
+        }
+    }
+
+    /// 顶点序号为 `indices` 的三个顶点
+    pub(crate) fn vertices(&self, indices: [usize; 3]) -> [Vector3<f32>; 3] {
+        match self {
+            Self::Resident(mesh) => indices.map(|i| mesh.positions[i]),
+            Self::Paged {
+                mesh, transform, ..
+            } => indices.map(|i| {
+                transform
+                    .transform_point(&Point3::from(mesh.position(i)))
+                    .coords
+            }),
+        }
+    }
+
+    /// 按重心坐标 `weights` 插值三个顶点的法线, 没有顶点法线时为 `None`
+    fn normal(&self, indices: [usize; 3], weights: [f32; 3]) -> Option<Vector3<f32>> {
+        match self {
+            Self::Resident(mesh) => mesh
+                .normals
+                .as_ref()
+                .map(|normals| interpolate(indices, weights, |i| normals[i])),
+            Self::Paged {
+                mesh, transform, ..
+            } => mesh.has_normals().then(|| {
+                transform.isometry.rotation * interpolate(indices, weights, |i| mesh.normal(i))
+            }),
+        }
+    }
+
+    /// 按重心坐标 `weights` 插值三个顶点的切线, 没有切线 (没有纹理坐标或分页网格) 时为 `None`
+    fn tangent(&self, indices: [usize; 3], weights: [f32; 3]) -> Option<Vector4<f32>> {
+        match self {
+            Self::Resident(mesh) => mesh.tangents.as_ref().map(|tangents| {
+                let tangent = interpolate(indices, weights, |i| tangents[i]);
+                let sign = tangents[indices[0]].w;
+
+                tangent.xyz().push(sign)
+            }),
+            Self::Paged { .. } => None,
+        }
+    }
+
+    /// 按重心坐标 `weights` 插值三个顶点的纹理坐标, 没有顶点纹理坐标时为 `None`
+    pub(crate) fn uv(&self, indices: [usize; 3], weights: [f32; 3]) -> Option<(f32, f32)> {
+        let uv = match self {
+            Self::Resident(mesh) => {
+                let uvs = mesh.uvs.as_ref()?;
+                interpolate(indices, weights, |i| Vector2::new(uvs[i].0, uvs[i].1))
+            }
+            Self::Paged { mesh, .. } if mesh.has_uvs() => interpolate(indices, weights, |i| {
+                let (u, v) = mesh.uv(i);
+                Vector2::new(u, v)
+            }),
+            Self::Paged { .. } => return None,
+        };
+
+        Some((uv.x, uv.y))
+    }
+
+    /// 是否带有顶点颜色
+    pub(crate) fn has_colors(&self) -> bool {
+        match self {
+            Self::Resident(mesh) => mesh.colors.is_some(),
+            Self::Paged { mesh, .. } => mesh.has_colors(),
+        }
+    }
+
+    /// 按重心坐标 `weights` 插值三个顶点的颜色, 没有顶点颜色时为 `None`
+    pub(crate) fn color(&self, indices: [usize; 3], weights: [f32; 3]) -> Option<Vector3<f32>> {
+        match self {
+            Self::Resident(mesh) => mesh
+                .colors
+                .as_ref()
+                .map(|colors| interpolate(indices, weights, |i| colors[i])),
+            Self::Paged { mesh, .. } => mesh
+                .has_colors()
+                .then(|| interpolate(indices, weights, |i| mesh.color(i))),
+        }
+    }
+}
+
+impl From<TriangleMesh> for Geometry {
+    fn from(mesh: TriangleMesh) -> Self {
+        Self::Resident(mesh)
+    }
+}
+
+/// 面 `face` 在第 `corner` 个顶点处的内角
+fn corner_angle(positions: &[Vector3<f32>], face: &[u32; 3], corner: usize) -> f32 {
+    let vertex = |k: usize| positions[face[(corner + k) % 3] as usize];
+    let (a, b) = (vertex(1) - vertex(0), vertex(2) - vertex(0));
+
+    a.angle(&b)
+}
+
+/// 按重心坐标插值三个顶点的属性
+fn interpolate<const D: usize>(
+    indices: [usize; 3],
+    weights: [f32; 3],
+    attribute: impl Fn(usize) -> SVector<f32, D>,
+) -> SVector<f32, D> {
+    (0..3).map(|k| weights[k] * attribute(indices[k])).sum()
+}
+
+/// 网格中的一个面
+struct Face {
+    geometry: Arc<Geometry>,
+    index: u32,
+}
+
+impl Face {
+    fn vertices(&self) -> [Vector3<f32>; 3] {
+        self.geometry
+            .vertices(self.geometry.face(self.index as usize))
+    }
+}
+
+impl Hittable for Face {
+    /// 法线, 纹理坐标与颜色按重心坐标插值顶点属性
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let geometry = &self.geometry;
+        let indices = geometry.face(self.index as usize);
+        let vertices = geometry.vertices(indices);
+        let (t, u, v) = triangle::intersect(&vertices, ray, t_min, t_max)?;
+        let weights = [1.0 - u - v, u, v];
+
+        let [a, b, c] = vertices;
+        let cross = (b - a).cross(&(c - a));
+        let normal = geometry
+            .normal(indices, weights)
+            .and_then(|normal| normal.try_normalize(f32::EPSILON))
+            .unwrap_or_else(|| cross.normalize());
+        let uv = geometry.uv(indices, weights).unwrap_or((u, v));
+        let material = match geometry.color(indices, weights) {
+            Some(color) => geometry.material().with_albedo(color),
+            None => geometry.material(),
+        };
+        let width = ray.cone().width_at(t * ray.direction().magnitude());
+        let footprint = width / cross.norm().sqrt();
+        let normal = match geometry.tangent(indices, weights) {
+            Some(tangent) => material.perturb_normal(&normal, &tangent, uv, footprint),
+            None => normal,
+        };
+
+        Some(HitRecord {
+            distance: t,
+            position: ray.point_at_t(t),
+            normal,
+            uv,
+            footprint,
+            material,
+            object: 0,
+        })
+    }
+}
+
+impl Bounded for Face {
+    fn bounding_box(&self) -> AaBb {
+        triangle::bounding_box(&self.vertices())
+    }
+
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let position = triangle::closest_point(&self.vertices(), point);
+
+        ClosestPoint {
+            object: 0,
+            position,
+            distance: (point - position).magnitude(),
+        }
+    }
+}
+
+/// 场景中的网格: 各个面由网格自身的 BVH 加速求交
+///
+/// 作为场景中的单个实体, 网格整体的可见性与编号相同
+pub struct Mesh {
+    source: Arc<Geometry>,
+    bvh: BVHNode,
+    bbox: AaBb,
+}
+
+impl Mesh {
+    pub fn new(geometry: impl Into<Geometry>) -> Self {
+        let geometry = Arc::new(geometry.into());
+        let faces: Vec<Arc<dyn Bounded + Sync + Send>> = (0..geometry.face_count() as u32)
+            .map(|index| {
+                Arc::new(Face {
+                    geometry: Arc::clone(&geometry),
+                    index,
+                }) as Arc<dyn Bounded + Sync + Send>
+            })
+            .collect();
+        let bbox = AaBb::all_surrounding_box(&faces);
+
+        Self {
+            source: geometry,
+            bvh: bvh_cache::build(faces, MAX_OBJECTS),
+            bbox,
+        }
+    }
+
+    /// 构建时的几何数据
+    pub fn source(&self) -> &Geometry {
+        &self.source
+    }
+
+    /// 面数
+    pub fn face_count(&self) -> usize {
+        self.source.face_count()
+    }
+}
+
+impl Hittable for Mesh {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        if !self.bbox.hit(ray) {
+            return None;
+        }
+
+        self.bvh.hit(ray, t_min, t_max)
+    }
+}
+
+impl Bounded for Mesh {
+    fn bounding_box(&self) -> AaBb {
+        self.bbox.clone()
+    }
+
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        self.bvh.closest_point(point).unwrap_or(ClosestPoint {
+            object: 0,
+            position: *point,
+            distance: f32::INFINITY,
+        })
+    }
+
+    fn emitter(&self) -> Option<Arc<Geometry>> {
+        matches!(self.source.material(), Material::Emissive { .. })
+            .then(|| Arc::clone(&self.source))
+    }
+
+    fn triangles(&self) -> Vec<[Vector3<f32>; 3]> {
+        (0..self.source.face_count())
+            .map(|face| self.source.vertices(self.source.face(face)))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// xy 平面上朝 +z 的单位正方形, 纹理坐标由 `uv` 给出
+    fn square(uv: impl Fn(f32, f32) -> (f32, f32)) -> TriangleMesh {
+        let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        let positions = corners.map(|(x, y)| Vector3::new(x, y, 0.0)).to_vec();
+        let uvs = corners.map(|(x, y)| uv(x, y)).to_vec();
+
+        TriangleMesh::new(positions, vec![[0, 1, 2], [0, 2, 3]], Material::CLAY)
+            .and_then(|mesh| mesh.with_uvs(uvs))
+            .unwrap()
+    }
+
+    fn assert_tangents(mesh: &TriangleMesh, expected: Vector4<f32>) {
+        for tangent in mesh.tangents().unwrap() {
+            assert!(
+                (tangent - expected).norm() < 1e-5,
+                "{tangent:?} != {expected:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn tangents_follow_the_u_direction() {
+        assert_tangents(&square(|x, y| (x, y)), Vector4::new(1.0, 0.0, 0.0, 1.0));
+        assert_tangents(
+            &square(|x, y| (y, 1.0 - x)),
+            Vector4::new(0.0, 1.0, 0.0, 1.0),
+        );
+    }
+
+    #[test]
+    fn mirrored_uvs_flip_the_bitangent_sign() {
+        assert_tangents(
+            &square(|x, y| (1.0 - x, y)),
+            Vector4::new(-1.0, 0.0, 0.0, -1.0),
+        );
+    }
+
+    #[test]
+    fn tangents_are_orthogonal_to_vertex_normals() {
+        let tilted = Vector3::new(0.3, 0.0, 1.0).normalize();
+        let mesh = square(|x, y| (x, y)).with_normals(vec![tilted; 4]).unwrap();
+
+        for tangent in mesh.tangents().unwrap() {
+            assert!(tangent.xyz().dot(&tilted).abs() < 1e-5);
+            assert!((tangent.xyz().norm() - 1.0).abs() < 1e-5);
+            assert_eq!(tangent.w, 1.0);
+        }
+    }
+
+    #[test]
+    fn meshes_without_uvs_have_no_tangents() {
+        let positions = vec![Vector3::zeros(), Vector3::x(), Vector3::y()];
+        let mesh = TriangleMesh::new(positions, vec![[0, 1, 2]], Material::CLAY).unwrap();
+
+        assert!(mesh.tangents().is_none());
+    }
+}
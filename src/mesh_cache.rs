@@ -0,0 +1,333 @@
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::bvh_cache::Reader;
+use crate::error::Result;
+use crate::material::Material;
+use crate::memory::{self, PagedBuffer};
+use crate::mesh::{Geometry, TriangleMesh};
+
+use nalgebra::{Vector3, Vector4};
+
+/// 缓存文件的标识与格式版本
+const MAGIC: &[u8; 8] = b"RTMESH01";
+
+/// 文件头的字节数: 标识, 源文件的大小与修改时间, 顶点数, 面数与标记
+const HEADER_SIZE: usize = 8 + 16 + 8 + 1;
+
+/// 缓存中各可选缓冲的标记位
+const HAS_NORMALS: u8 = 1;
+const HAS_UVS: u8 = 2;
+const HAS_COLORS: u8 = 4;
+
+/// 读取网格文件 `path`, 优先使用同目录下的预处理缓存 (`<文件名>.meshcache`)
+///
+/// 缓存保存解析与三角化后的顶点与索引缓冲, 以源文件的大小与修改时间判断是否过期;
+/// 缓存缺失或过期时以 `parse` 解析源文件并写入新的缓存, 写入失败 (如目录只读) 时只打印警告。
+/// 缓冲超出内存预算 (见 [`memory`]) 时返回按页读取缓存文件的网格。
+/// 材质不写入缓存, 每次使用 `material`
+pub fn load(
+    path: &Path,
+    material: Material,
+    parse: impl FnOnce(&Path, Material) -> Result<TriangleMesh>,
+) -> Result<Geometry> {
+    let stamp = source_stamp(path)?;
+    let cache_path = cache_path(path);
+    match Header::read(&cache_path, stamp) {
+        Some(header) if memory::reserve(header.resident_len()) => {
+            let data = fs::read(&cache_path)?;
+            if let Some(mesh) = decode(&data, stamp, material) {
+                return Ok(mesh.into());
+            }
+            eprintln!("\rIgnoring stale mesh cache {}", cache_path.display());
+        }
+        Some(header) => return Ok(Geometry::paged(header.paged(&cache_path), material)),
+        None if cache_path.exists() => {
+            eprintln!("\rIgnoring stale mesh cache {}", cache_path.display());
+        }
+        None => {}
+    }
+
+    let mesh = parse(path, material)?;
+    let temp_path = cache_path.with_extension(format!("{}.tmp", std::process::id()));
+    if let Err(err) = fs::write(&temp_path, encode(&mesh, stamp))
+        .and_then(|()| fs::rename(&temp_path, &cache_path))
+    {
+        eprintln!(
+            "\rFailed to write mesh cache {}: {err}",
+            cache_path.display()
+        );
+        if !memory::reserve(mesh_len(&mesh)) {
+            eprintln!("\rMesh {} exceeds the memory budget", path.display());
+        }
+        return Ok(mesh.into());
+    }
+
+    if memory::reserve(mesh_len(&mesh)) {
+        return Ok(mesh.into());
+    }
+    match Header::read(&cache_path, stamp) {
+        Some(header) => Ok(Geometry::paged(header.paged(&cache_path), material)),
+        None => Ok(mesh.into()),
+    }
+}
+
+/// 按页读取缓存文件的网格, 各缓冲的排列与缓存文件相同
+pub struct PagedMesh {
+    positions: PagedBuffer,
+    normals: Option<PagedBuffer>,
+    colors: Option<PagedBuffer>,
+    uvs: Option<PagedBuffer>,
+    indices: PagedBuffer,
+}
+
+impl PagedMesh {
+    /// 面数
+    pub const fn face_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    pub const fn has_normals(&self) -> bool {
+        self.normals.is_some()
+    }
+
+    pub const fn has_uvs(&self) -> bool {
+        self.uvs.is_some()
+    }
+
+    pub const fn has_colors(&self) -> bool {
+        self.colors.is_some()
+    }
+
+    /// 第 `face` 个面的三个顶点序号
+    pub fn face(&self, face: usize) -> [usize; 3] {
+        self.indices.words::<3>(face).map(|i| i as usize)
+    }
+
+    /// 第 `i` 个顶点的位置
+    pub fn position(&self, i: usize) -> Vector3<f32> {
+        vector(&self.positions, i)
+    }
+
+    /// 第 `i` 个顶点的法线, 没有顶点法线时为零向量
+    pub fn normal(&self, i: usize) -> Vector3<f32> {
+        self.normals
+            .as_ref()
+            .map_or_else(Vector3::zeros, |b| vector(b, i))
+    }
+
+    /// 第 `i` 个顶点的颜色, 没有顶点颜色时为零向量
+    pub fn color(&self, i: usize) -> Vector3<f32> {
+        self.colors
+            .as_ref()
+            .map_or_else(Vector3::zeros, |b| vector(b, i))
+    }
+
+    /// 第 `i` 个顶点的纹理坐标, 没有顶点纹理坐标时为 `(0, 0)`
+    pub fn uv(&self, i: usize) -> (f32, f32) {
+        self.uvs.as_ref().map_or((0.0, 0.0), |b| {
+            let [u, v] = b.words::<2>(i).map(f32::from_bits);
+            (u, v)
+        })
+    }
+}
+
+fn vector(buffer: &PagedBuffer, i: usize) -> Vector3<f32> {
+    Vector3::from(buffer.words::<3>(i).map(f32::from_bits))
+}
+
+/// 缓存文件头中的计数与标记, 已按源文件的时间戳与文件长度校验
+struct Header {
+    vertices: usize,
+    faces: usize,
+    flags: u8,
+}
+
+impl Header {
+    /// 读取缓存文件 `path` 的文件头, 文件缺失, 过期或长度不符时返回 `None`
+    fn read(path: &Path, stamp: [u64; 2]) -> Option<Self> {
+        let mut file = File::open(path).ok()?;
+        let mut data = [0; HEADER_SIZE];
+        file.read_exact(&mut data).ok()?;
+
+        let mut reader = Reader::new(&data);
+        if reader.take(MAGIC.len())? != MAGIC || [reader.u64()?, reader.u64()?] != stamp {
+            return None;
+        }
+        let header = Self {
+            vertices: reader.u32()? as usize,
+            faces: reader.u32()? as usize,
+            flags: reader.take(1)?[0],
+        };
+
+        let len = file.metadata().ok()?.len();
+        (len == (HEADER_SIZE + header.data_len()) as u64).then_some(header)
+    }
+
+    /// 文件头之后的数据的字节数
+    fn data_len(&self) -> usize {
+        4 * (floats_per_vertex(self.flags) * self.vertices + 3 * self.faces)
+    }
+
+    /// 读入内存后占用的字节数, 包括由纹理坐标生成的切线
+    fn resident_len(&self) -> usize {
+        let tangents = if self.flags & HAS_UVS != 0 {
+            size_of::<Vector4<f32>>() * self.vertices
+        } else {
+            0
+        };
+
+        self.data_len() + tangents
+    }
+
+    /// 按页读取缓存文件 `path` 的网格
+    fn paged(&self, path: &Path) -> PagedMesh {
+        let mut offset = HEADER_SIZE as u64;
+        let mut buffer = |present: bool, stride: usize, len: usize| {
+            present.then(|| {
+                let buffer = PagedBuffer::new(path, offset, stride, len);
+                offset += buffer.byte_len() as u64;
+                buffer
+            })
+        };
+
+        let has = |bit: u8| self.flags & bit != 0;
+        PagedMesh {
+            positions: buffer(true, 12, self.vertices).unwrap(),
+            normals: buffer(has(HAS_NORMALS), 12, self.vertices),
+            colors: buffer(has(HAS_COLORS), 12, self.vertices),
+            uvs: buffer(has(HAS_UVS), 8, self.vertices),
+            indices: buffer(true, 12, self.faces).unwrap(),
+        }
+    }
+}
+
+/// 每个顶点的浮点数个数
+fn floats_per_vertex(flags: u8) -> usize {
+    3 + [(HAS_NORMALS, 3), (HAS_COLORS, 3), (HAS_UVS, 2)]
+        .iter()
+        .filter(|(bit, _)| flags & bit != 0)
+        .map(|(_, n)| n)
+        .sum::<usize>()
+}
+
+/// 网格的顶点, 切线与索引缓冲的字节数
+fn mesh_len(mesh: &TriangleMesh) -> usize {
+    4 * (floats_per_vertex(flags(mesh)) * mesh.vertex_count() + 3 * mesh.face_count())
+        + size_of_val(mesh.tangents().unwrap_or_default())
+}
+
+/// 网格含有的可选缓冲的标记
+fn flags(mesh: &TriangleMesh) -> u8 {
+    [
+        (mesh.normals().is_some(), HAS_NORMALS),
+        (mesh.uvs().is_some(), HAS_UVS),
+        (mesh.colors().is_some(), HAS_COLORS),
+    ]
+    .iter()
+    .filter(|(present, _)| *present)
+    .fold(0, |flags, (_, bit)| flags | bit)
+}
+
+/// 网格文件 `path` 的缓存文件路径
+fn cache_path(path: &Path) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".meshcache");
+    path.with_file_name(name)
+}
+
+/// 源文件的大小与修改时间 (纳秒)
+fn source_stamp(path: &Path) -> io::Result<[u64; 2]> {
+    let metadata = fs::metadata(path)?;
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |duration| duration.as_nanos() as u64);
+
+    Ok([metadata.len(), modified])
+}
+
+/// 编码网格: 文件头之后依次为位置, 法线, 颜色, 纹理坐标与索引, 均为小端序
+fn encode(mesh: &TriangleMesh, stamp: [u64; 2]) -> Vec<u8> {
+    let (normals, uvs, colors) = (mesh.normals(), mesh.uvs(), mesh.colors());
+
+    let mut data = MAGIC.to_vec();
+    data.extend(stamp[0].to_le_bytes());
+    data.extend(stamp[1].to_le_bytes());
+    data.extend((mesh.vertex_count() as u32).to_le_bytes());
+    data.extend((mesh.face_count() as u32).to_le_bytes());
+    data.push(flags(mesh));
+
+    let vectors = mesh
+        .positions()
+        .iter()
+        .chain(normals.into_iter().flatten())
+        .chain(colors.into_iter().flatten());
+    for v in vectors {
+        v.iter().for_each(|c| data.extend(c.to_le_bytes()));
+    }
+    for (u, v) in uvs.into_iter().flatten() {
+        data.extend(u.to_le_bytes());
+        data.extend(v.to_le_bytes());
+    }
+    for index in mesh.indices().iter().flatten() {
+        data.extend(index.to_le_bytes());
+    }
+
+    data
+}
+
+/// 解码网格, 标识, 源文件的时间戳或长度不符时返回 `None`
+fn decode(data: &[u8], stamp: [u64; 2], material: Material) -> Option<TriangleMesh> {
+    let mut reader = Reader::new(data);
+    if reader.take(MAGIC.len())? != MAGIC || [reader.u64()?, reader.u64()?] != stamp {
+        return None;
+    }
+    let (vertices, faces) = (reader.u32()? as usize, reader.u32()? as usize);
+    let flags = reader.take(1)?[0];
+
+    // 先检查长度, 损坏的计数不会导致过大的分配
+    if data.len() != HEADER_SIZE + 4 * (floats_per_vertex(flags) * vertices + 3 * faces) {
+        return None;
+    }
+
+    let vectors = |reader: &mut Reader| -> Option<Vec<Vector3<f32>>> {
+        (0..vertices).map(|_| reader.vector()).collect()
+    };
+    let positions = vectors(&mut reader)?;
+    let normals = match flags & HAS_NORMALS {
+        0 => None,
+        _ => Some(vectors(&mut reader)?),
+    };
+    let colors = match flags & HAS_COLORS {
+        0 => None,
+        _ => Some(vectors(&mut reader)?),
+    };
+    let uvs = match flags & HAS_UVS {
+        0 => None,
+        _ => Some(
+            (0..vertices)
+                .map(|_| Some((reader.f32()?, reader.f32()?)))
+                .collect::<Option<Vec<_>>>()?,
+        ),
+    };
+    let indices = (0..faces)
+        .map(|_| Some([reader.u32()?, reader.u32()?, reader.u32()?]))
+        .collect::<Option<Vec<_>>>()?;
+
+    let mut mesh = TriangleMesh::new(positions, indices, material).ok()?;
+    if let Some(normals) = normals {
+        mesh = mesh.with_normals(normals).ok()?;
+    }
+    if let Some(uvs) = uvs {
+        mesh = mesh.with_uvs(uvs).ok()?;
+    }
+    if let Some(colors) = colors {
+        mesh = mesh.with_colors(colors).ok()?;
+    }
+
+    Some(mesh)
+}
@@ -0,0 +1,404 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::time::Duration;
+
+use crate::error::Result;
+
+use clap::ValueEnum;
+
+/// 输出图像的文件格式
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ImageFormat {
+    /// PPM (P3), 不含不透明度
+    Ppm,
+
+    /// PAM (P7), 含不透明度时为 `RGB_ALPHA`
+    Pam,
+
+    /// PNG (未压缩), 含不透明度时为 RGBA
+    Png,
+
+    /// TIFF (未压缩), 含不透明度时为 RGBA
+    Tiff,
+}
+
+/// 输出图像每个通道的位数, 均在色调映射 (曝光与 gamma 修正) 之后量化
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum)]
+pub enum BitDepth {
+    #[default]
+    #[value(name = "8")]
+    Eight,
+
+    /// 16 位, 用于需要超过 8 位但不能读取 EXR 的流程
+    #[value(name = "16")]
+    Sixteen,
+}
+
+impl ImageFormat {
+    /// 文件扩展名
+    pub const fn extension(self) -> &'static str {
+        match self {
+            Self::Ppm => "ppm",
+            Self::Pam => "pam",
+            Self::Png => "png",
+            Self::Tiff => "tiff",
+        }
+    }
+
+    /// 是否能保存不透明度
+    pub const fn supports_alpha(self) -> bool {
+        !matches!(self, Self::Ppm)
+    }
+}
+
+/// 待写入的图像 (首行为图像顶部)
+pub struct Image<'a> {
+    /// RGB 像素
+    pub rgb: &'a [u8],
+
+    /// 不透明度 (非预乘), 没有时视为完全不透明
+    pub alpha: Option<&'a [u8]>,
+
+    /// 16 位的 RGB 像素, 有时代替 `rgb` 写入, 不透明度随之扩展到 16 位
+    pub deep: Option<&'a [u16]>,
+
+    pub nx: usize,
+    pub ny: usize,
+}
+
+impl<'a> Image<'a> {
+    /// 不透明的 RGB 图像
+    pub const fn rgb(rgb: &'a [u8], nx: usize, ny: usize) -> Self {
+        Self {
+            rgb,
+            alpha: None,
+            deep: None,
+            nx,
+            ny,
+        }
+    }
+
+    /// 每个像素的通道数
+    const fn channels(&self) -> usize {
+        if self.alpha.is_some() { 4 } else { 3 }
+    }
+
+    /// 每个通道的最大值
+    const fn max_value(&self) -> u16 {
+        if self.deep.is_some() { 65535 } else { 255 }
+    }
+
+    /// 首行为图像顶部的一行像素, 按通道交错排列
+    fn samples(&self, y: usize) -> Vec<u16> {
+        let range = y * self.nx..(y + 1) * self.nx;
+        let rgb: Vec<u16> = match self.deep {
+            Some(deep) => deep[3 * range.start..3 * range.end].to_vec(),
+            None => self.rgb[3 * range.start..3 * range.end]
+                .iter()
+                .map(|&v| v as u16)
+                .collect(),
+        };
+        let scale = self.max_value() / 255;
+        match self.alpha {
+            Some(alpha) => rgb
+                .chunks(3)
+                .zip(&alpha[range])
+                .flat_map(|(rgb, &a)| [rgb[0], rgb[1], rgb[2], scale * a as u16])
+                .collect(),
+            None => rgb,
+        }
+    }
+
+    /// 一行像素的字节, 16 位的通道为大端序 (PAM, PNG 与大端序的 TIFF 相同)
+    fn row(&self, y: usize) -> Vec<u8> {
+        let samples = self.samples(y);
+        if self.deep.is_some() {
+            samples.iter().flat_map(|v| v.to_be_bytes()).collect()
+        } else {
+            samples.iter().map(|&v| v as u8).collect()
+        }
+    }
+}
+
+/// 渲染过程中定期写入部分结果
+pub struct PartialWrite {
+    /// 输出文件名 (不含扩展名)
+    pub file_name: String,
+
+    /// 输出格式
+    pub format: ImageFormat,
+
+    /// 写入间隔
+    pub interval: Duration,
+}
+
+/// 将 RGB 图像以 PPM (P3) 格式写入 `path`, 忽略不透明度
+fn write_ppm(path: &Path, image: &Image) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    writeln!(file, "P3\n{} {}\n{}", image.nx, image.ny, image.max_value())?;
+    match image.deep {
+        Some(deep) => {
+            for col in deep.chunks(3) {
+                writeln!(file, "{} {} {}", col[0], col[1], col[2])?;
+            }
+        }
+        None => {
+            for col in image.rgb.chunks(3) {
+                writeln!(file, "{} {} {}", col[0], col[1], col[2])?;
+            }
+        }
+    }
+
+    file.flush()
+}
+
+/// 将图像以 PAM (P7) 格式写入 `path`
+fn write_pam(path: &Path, image: &Image) -> io::Result<()> {
+    let mut file = BufWriter::new(File::create(path)?);
+    let tuple_type = if image.alpha.is_some() {
+        "RGB_ALPHA"
+    } else {
+        "RGB"
+    };
+    write!(
+        file,
+        "P7\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL {}\nTUPLTYPE {tuple_type}\nENDHDR\n",
+        image.nx,
+        image.ny,
+        image.channels(),
+        image.max_value()
+    )?;
+    for y in 0..image.ny {
+        file.write_all(&image.row(y))?;
+    }
+
+    file.flush()
+}
+
+/// CRC-32 (PNG 数据块校验)
+fn crc32(chunks: &[&[u8]]) -> u32 {
+    let mut crc = u32::MAX;
+    for &byte in chunks.iter().flat_map(|chunk| chunk.iter()) {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xedb8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+
+    !crc
+}
+
+/// 写入一个 PNG 数据块
+fn write_chunk(file: &mut impl Write, kind: &[u8; 4], data: &[u8]) -> io::Result<()> {
+    file.write_all(&(data.len() as u32).to_be_bytes())?;
+    file.write_all(kind)?;
+    file.write_all(data)?;
+    file.write_all(&crc32(&[kind, data]).to_be_bytes())
+}
+
+/// 将图像以 PNG 格式写入 `path`, 图像数据以未压缩的 deflate 块存储
+fn write_png(path: &Path, image: &Image) -> io::Result<()> {
+    // 每行之前为过滤类型 (0: 不过滤)
+    let bytes = if image.deep.is_some() { 2 } else { 1 };
+    let mut raw = Vec::with_capacity(image.ny * (1 + bytes * image.channels() * image.nx));
+    for y in 0..image.ny {
+        raw.push(0);
+        raw.extend(image.row(y));
+    }
+
+    // zlib 流: 头部, 未压缩的 deflate 块, Adler-32 校验
+    let mut zlib = vec![0x78, 0x01];
+    let blocks = raw.chunks(u16::MAX as usize);
+    let count = blocks.len();
+    for (i, block) in blocks.enumerate() {
+        let len = block.len() as u16;
+        zlib.push((i + 1 == count) as u8);
+        zlib.extend(len.to_le_bytes());
+        zlib.extend((!len).to_le_bytes());
+        zlib.extend(block);
+    }
+    let (a, b) = raw.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    zlib.extend(((b << 16) | a).to_be_bytes());
+
+    let mut header = vec![];
+    header.extend((image.nx as u32).to_be_bytes());
+    header.extend((image.ny as u32).to_be_bytes());
+    let color_type = if image.alpha.is_some() { 6 } else { 2 };
+    header.extend([8 * bytes as u8, color_type, 0, 0, 0]);
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"\x89PNG\r\n\x1a\n")?;
+    write_chunk(&mut file, b"IHDR", &header)?;
+    write_chunk(&mut file, b"IDAT", &zlib)?;
+    write_chunk(&mut file, b"IEND", &[])?;
+
+    file.flush()
+}
+
+/// 将图像以未压缩的大端序 TIFF 格式写入 `path`, 全部像素存为一个条带
+fn write_tiff(path: &Path, image: &Image) -> io::Result<()> {
+    let channels = image.channels() as u32;
+    let bits = if image.deep.is_some() { 16 } else { 8 };
+    let data: Vec<u8> = (0..image.ny).flat_map(|y| image.row(y)).collect();
+
+    // 目录项 (按标签递增): 标签, 类型 (3: SHORT, 4: LONG), 个数, 值或偏移
+    let mut entries: Vec<(u16, u16, u32, u32)> = vec![
+        (256, 4, 1, image.nx as u32),
+        (257, 4, 1, image.ny as u32),
+        (258, 3, channels, 0),
+        (259, 3, 1, 1),
+        (262, 3, 1, 2),
+        (273, 4, 1, 0),
+        (277, 3, 1, channels),
+        (278, 4, 1, image.ny as u32),
+        (279, 4, 1, data.len() as u32),
+        (284, 3, 1, 1),
+    ];
+    if image.alpha.is_some() {
+        // 附加通道为非预乘的不透明度
+        entries.push((338, 3, 1, 2));
+    }
+
+    // 文件头, 目录, 每通道位数的数组, 像素数据依次排列
+    let directory_len = 2 + 12 * entries.len() as u32 + 4;
+    let bits_offset = 8 + directory_len;
+    let data_offset = bits_offset + 2 * channels;
+    for entry in &mut entries {
+        match entry.0 {
+            258 => entry.3 = bits_offset,
+            273 => entry.3 = data_offset,
+            _ => {}
+        }
+    }
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(b"MM\0\x2a")?;
+    file.write_all(&8u32.to_be_bytes())?;
+    file.write_all(&(entries.len() as u16).to_be_bytes())?;
+    for (tag, kind, count, value) in entries {
+        file.write_all(&tag.to_be_bytes())?;
+        file.write_all(&kind.to_be_bytes())?;
+        file.write_all(&count.to_be_bytes())?;
+        // 单个 SHORT 值左对齐存放在 4 字节中
+        if kind == 3 && count == 1 {
+            file.write_all(&(value as u16).to_be_bytes())?;
+            file.write_all(&[0; 2])?;
+        } else {
+            file.write_all(&value.to_be_bytes())?;
+        }
+    }
+    file.write_all(&0u32.to_be_bytes())?;
+    for _ in 0..channels {
+        file.write_all(&(bits as u16).to_be_bytes())?;
+    }
+    file.write_all(&data)?;
+
+    file.flush()
+}
+
+/// 将单通道的浮点图像 (首行为图像顶部) 以未压缩的 OpenEXR 格式写入 `path`, 通道名为 `Y`
+fn write_exr(path: &Path, values: &[f32], nx: usize, ny: usize) -> io::Result<()> {
+    let mut header = vec![];
+    let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+        for s in [name, kind] {
+            header.extend(s.as_bytes());
+            header.push(0);
+        }
+        header.extend((value.len() as i32).to_le_bytes());
+        header.extend(value);
+    };
+
+    // 通道: 名称, 像素类型 (2: FLOAT), pLinear 与保留字节, x/y 采样间隔
+    let mut channels = b"Y\0".to_vec();
+    channels.extend(2i32.to_le_bytes());
+    channels.extend([0; 4]);
+    channels.extend(1i32.to_le_bytes());
+    channels.extend(1i32.to_le_bytes());
+    channels.push(0);
+
+    let window: Vec<u8> = [0, 0, nx as i32 - 1, ny as i32 - 1]
+        .iter()
+        .flat_map(|v| v.to_le_bytes())
+        .collect();
+
+    attribute("channels", "chlist", &channels);
+    attribute("compression", "compression", &[0]);
+    attribute("dataWindow", "box2i", &window);
+    attribute("displayWindow", "box2i", &window);
+    attribute("lineOrder", "lineOrder", &[0]);
+    attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+    attribute("screenWindowCenter", "v2f", &[0; 8]);
+    attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+    header.push(0);
+
+    let mut file = BufWriter::new(File::create(path)?);
+    file.write_all(&[0x76, 0x2f, 0x31, 0x01, 2, 0, 0, 0])?;
+    file.write_all(&header)?;
+
+    // 偏移表: 每行一个块, 块由行号, 数据长度和数据组成
+    let start = (8 + header.len() + 8 * ny) as u64;
+    let block_size = (8 + 4 * nx) as u64;
+    for y in 0..ny as u64 {
+        file.write_all(&(start + y * block_size).to_le_bytes())?;
+    }
+    for (y, row) in values.chunks(nx).enumerate() {
+        file.write_all(&(y as i32).to_le_bytes())?;
+        file.write_all(&(4 * nx as i32).to_le_bytes())?;
+        for value in row {
+            file.write_all(&value.to_le_bytes())?;
+        }
+    }
+
+    file.flush()
+}
+
+/// 调用 `write` 写入临时文件, 再重命名为 `file_path`
+fn replace(file_path: &str, write: impl FnOnce(&Path) -> io::Result<()>) -> io::Result<()> {
+    let temp_path = format!("{file_path}.tmp");
+    write(Path::new(&temp_path))?;
+
+    fs::rename(temp_path, file_path)
+}
+
+/// 先写入临时文件再重命名, 读者不会看到写了一半的图像
+pub fn write_atomic(image: &Image, file_name: &str, format: ImageFormat) -> Result<()> {
+    replace(
+        &format!("{file_name}.{}", format.extension()),
+        |temp| match format {
+            ImageFormat::Ppm => write_ppm(temp, image),
+            ImageFormat::Pam => write_pam(temp, image),
+            ImageFormat::Png => write_png(temp, image),
+            ImageFormat::Tiff => write_tiff(temp, image),
+        },
+    )?;
+
+    Ok(())
+}
+
+/// 写入单通道的浮点图像 (OpenEXR)
+pub fn write_float_image(values: &[f32], nx: usize, ny: usize, file_name: &str) -> Result<()> {
+    eprint!("Writing file...");
+    replace(&format!("{file_name}.exr"), |temp| {
+        write_exr(temp, values, nx, ny)
+    })?;
+    eprintln!("\rFile written{}", " ".repeat(10));
+
+    Ok(())
+}
+
+/// 写入最终图像
+pub fn write_image(image: &Image, file_name: &str, format: ImageFormat) -> Result<()> {
+    eprint!("Writing file...");
+    write_atomic(image, file_name, format)?;
+    eprintln!("\rFile written{}", " ".repeat(10));
+
+    Ok(())
+}
@@ -0,0 +1,139 @@
+use crate::accelerator::Accelerator;
+use crate::bvh::Bounded;
+use crate::camera::Camera;
+use crate::scene::Scene;
+
+use nalgebra::Vector3;
+
+/// 包围盒的 12 条棱 (以顶点序号表示, 顶点序号的三个比特分别对应 x, y, z)
+const BOX_EDGES: [(usize, usize); 12] = [
+    (0, 1),
+    (2, 3),
+    (4, 5),
+    (6, 7),
+    (0, 2),
+    (1, 3),
+    (4, 6),
+    (5, 7),
+    (0, 4),
+    (1, 5),
+    (2, 6),
+    (3, 7),
+];
+
+/// 三角形线框的颜色
+const WIRE_COLOR: Vector3<f32> = Vector3::new(1.0, 0.85, 0.2);
+
+/// 合焦区域的默认深度容差 (相对对焦距离的比例)
+pub const DEFAULT_FOCUS_TOLERANCE: f32 = 0.05;
+
+/// 合焦区域的着色
+const FOCUS_TINT: Vector3<f32> = Vector3::new(0.1, 1.0, 0.3);
+
+/// 合焦区域着色的不透明度
+const FOCUS_TINT_OPACITY: f32 = 0.5;
+
+/// 按深度着色, 浅层偏红, 深层偏蓝
+fn depth_color(depth: usize, max_depth: usize) -> Vector3<f32> {
+    let t = depth as f32 / max_depth.max(1) as f32;
+
+    Vector3::new(1.0 - t, 0.4 + 0.6 * (1.0 - (2.0 * t - 1.0).abs()), t)
+}
+
+/// 在图像上画一条线段 (DDA), 坐标为像素坐标, 原点在左上角
+fn draw_line(
+    image: &mut [u8],
+    nx: usize,
+    ny: usize,
+    from: (f32, f32),
+    to: (f32, f32),
+    color: Vector3<f32>,
+) {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let steps = dx.abs().max(dy.abs()).ceil().min((2 * (nx + ny)) as f32) as usize;
+    let pixel = color.map(|c| (255.99 * c.clamp(0.0, 1.0)) as u8);
+
+    for i in 0..=steps {
+        let k = if steps == 0 {
+            0.0
+        } else {
+            i as f32 / steps as f32
+        };
+        let (x, y) = (from.0 + k * dx, from.1 + k * dy);
+        if x < 0.0 || y < 0.0 || x >= nx as f32 || y >= ny as f32 {
+            continue;
+        }
+
+        let index = 3 * (y as usize * nx + x as usize);
+        image[index..index + 3].copy_from_slice(pixel.as_slice());
+    }
+}
+
+/// 在图像上叠加加速结构各结点的包围盒线框
+pub fn draw_bvh(
+    image: &mut [u8],
+    nx: usize,
+    ny: usize,
+    camera: &Camera,
+    accelerator: &dyn Accelerator,
+) {
+    let boxes = accelerator.boxes();
+    let max_depth = boxes.iter().map(|(_, depth)| *depth).max().unwrap_or(0);
+
+    for (bbox, depth) in boxes {
+        let color = depth_color(depth, max_depth);
+
+        // 投影到像素坐标, 位于相机后方的顶点为 `None`
+        let corners = bbox.corners().map(|corner| {
+            camera
+                .project(&corner)
+                .map(|(s, t)| (s * nx as f32, (1.0 - t) * ny as f32))
+        });
+
+        for (a, b) in BOX_EDGES {
+            if let (Some(from), Some(to)) = (corners[a], corners[b]) {
+                draw_line(image, nx, ny, from, to, color);
+            }
+        }
+    }
+}
+
+/// 在图像上叠加场景中各三角形 (三角形, 网格及其实例) 的线框, 不做遮挡剔除
+pub fn draw_wireframe(image: &mut [u8], nx: usize, ny: usize, camera: &Camera, scene: &Scene) {
+    let triangles = scene
+        .objects()
+        .filter_map(|info| scene.object(info.id))
+        .flat_map(|object| object.object.triangles());
+
+    for vertices in triangles {
+        // 投影到像素坐标, 位于相机后方的顶点为 `None`
+        let corners = vertices.map(|vertex| {
+            camera
+                .project(&vertex)
+                .map(|(s, t)| (s * nx as f32, (1.0 - t) * ny as f32))
+        });
+
+        for (a, b) in [(0, 1), (1, 2), (2, 0)] {
+            if let (Some(from), Some(to)) = (corners[a], corners[b]) {
+                draw_line(image, nx, ny, from, to, WIRE_COLOR);
+            }
+        }
+    }
+}
+
+/// 将深度 (首行为图像顶部, 与 `image` 一致) 与对焦距离相差不超过 `tolerance` 的像素染为绿色
+pub fn tint_focus(image: &mut [u8], depths: &[f32], focus_dist: f32, tolerance: f32) {
+    let tint = FOCUS_TINT.map(|c| 255.0 * c);
+
+    for (pixel, depth) in image.chunks_exact_mut(3).zip(depths) {
+        if (depth - focus_dist).abs() > tolerance {
+            continue;
+        }
+
+        for (channel, tint) in pixel.iter_mut().zip(tint.iter()) {
+            let blended =
+                (1.0 - FOCUS_TINT_OPACITY) * f32::from(*channel) + FOCUS_TINT_OPACITY * tint;
+            *channel = blended.round() as u8;
+        }
+    }
+}
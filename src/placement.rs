@@ -0,0 +1,106 @@
+use crate::rng::hash_to_unit;
+use crate::texture::{self, TextureId};
+
+use rand::Rng;
+use rand::seq::IndexedRandom;
+
+/// 噪声叠加的倍频数
+const OCTAVES: u32 = 4;
+
+/// 半径倍数的范围, 分布图的 0 与 1 分别对应两端
+const SCALE_RANGE: (f32, f32) = (0.5, 2.0);
+
+/// 地面上的分布图: 在地面坐标 `(x, z)` 处取 0 到 1 之间的值
+#[derive(Clone, Copy, Debug)]
+pub enum GroundMap {
+    /// 灰度贴图 (取红色通道), 铺满生成区域, 图像首行在最小 z 处
+    Texture(TextureId),
+
+    /// 分形值噪声, `frequency` 为最低倍频每单位长度的周期数
+    Noise { frequency: f32, seed: u32 },
+}
+
+impl GroundMap {
+    /// `(x, z)` 处的值, 贴图铺在 x 与 z 都位于 `[-extent, extent]` 的正方形上
+    pub fn value(self, x: f32, z: f32, extent: f32) -> f32 {
+        match self {
+            Self::Texture(texture) => {
+                let u = ((x + extent) / (2.0 * extent)).clamp(0.0, 1.0);
+                let v = 1.0 - ((z + extent) / (2.0 * extent)).clamp(0.0, 1.0);
+                texture::sample(texture, (u, v)).x.clamp(0.0, 1.0)
+            }
+            Self::Noise { frequency, seed } => {
+                let (mut sum, mut total, mut amplitude, mut frequency) = (0.0, 0.0, 1.0, frequency);
+                for octave in 0..OCTAVES {
+                    sum += amplitude * value_noise(x * frequency, z * frequency, seed ^ octave);
+                    total += amplitude;
+                    amplitude /= 2.0;
+                    frequency *= 2.0;
+                }
+
+                sum / total
+            }
+        }
+    }
+}
+
+/// 格点上取哈希值, 格点间以平滑插值过渡的值噪声, 取值在 0 到 1 之间
+fn value_noise(x: f32, z: f32, seed: u32) -> f32 {
+    let (x0, z0) = (x.floor(), z.floor());
+    let lattice = |i: f32, j: f32| {
+        let (i, j) = (i as i32 as u32, j as i32 as u32);
+        hash_to_unit(i.wrapping_mul(0x8da6_b343) ^ j.wrapping_mul(0xd816_3841) ^ seed)
+    };
+    let smooth = |t: f32| t * t * (3.0 - 2.0 * t);
+    let (sx, sz) = (smooth(x - x0), smooth(z - z0));
+
+    let top = lattice(x0, z0) + (lattice(x0 + 1.0, z0) - lattice(x0, z0)) * sx;
+    let bottom = lattice(x0, z0 + 1.0) + (lattice(x0 + 1.0, z0 + 1.0) - lattice(x0, z0 + 1.0)) * sx;
+
+    top + (bottom - top) * sz
+}
+
+/// 随机场景中小球的摆放方式: 密度, 大小与材质可分别由地面上的分布图控制
+///
+/// 未指定的分布图保持均匀随机的摆放, 所用的随机数与不使用分布图时相同
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Placement {
+    /// 放置小球的概率
+    pub density: Option<GroundMap>,
+
+    /// 小球的半径, 0 到 1 对应基准半径的 0.5 到 2 倍
+    pub scale: Option<GroundMap>,
+
+    /// 材质类别: 值在各类别的累积比例中的位置决定类别
+    pub material: Option<GroundMap>,
+
+    /// 分布图铺满的范围, x 与 z 都位于 `[-extent, extent]`
+    pub extent: f32,
+}
+
+impl Placement {
+    /// 是否在 `(x, z)` 处放置小球: 以密度图的值为概率, 没有密度图时总是放置
+    pub fn keep(&self, x: f32, z: f32, rng: &mut impl Rng) -> bool {
+        self.density
+            .is_none_or(|map| rng.random::<f32>() < map.value(x, z, self.extent))
+    }
+
+    /// `(x, z)` 处的小球半径相对基准半径的倍数, 没有大小图时为 1
+    pub fn scale(&self, x: f32, z: f32) -> f32 {
+        self.scale.map_or(1.0, |map| {
+            let (low, high) = SCALE_RANGE;
+            low + (high - low) * map.value(x, z, self.extent)
+        })
+    }
+
+    /// 在 `(x, z)` 处从 `choices` 中选取材质类别: 有材质图时按图中的值选取, 否则随机选取
+    pub fn choose<'a, T>(&self, x: f32, z: f32, choices: &'a [T], rng: &mut impl Rng) -> &'a T {
+        match self.material {
+            Some(map) => {
+                let index = (map.value(x, z, self.extent) * choices.len() as f32) as usize;
+                &choices[index.min(choices.len() - 1)]
+            }
+            None => choices.choose(rng).unwrap(),
+        }
+    }
+}
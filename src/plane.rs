@@ -0,0 +1,148 @@
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::sampling::Onb;
+use crate::triangle::MIN_THICKNESS;
+
+use nalgebra::{Point3, Similarity3, Vector3};
+
+/// 无界平面的包围盒在平面内的半边长: 求交不受此限制, 只用于放入 BVH
+const UNBOUNDED_EXTENT: f32 = 1e6;
+
+/// 平面, 用作地面时没有大球的弧度, 远处也不会因大半径的单精度运算而失真
+///
+/// 法线所指的一侧为正面 (外侧), 有界时为以 `point` 为中心, 边长 `2 * extent` 的正方形
+#[derive(Clone)]
+pub struct Plane {
+    /// 平面上的一点, 纹理坐标的原点
+    point: Vector3<f32>,
+
+    /// 平面内的两个单位轴与法线
+    onb: Onb,
+
+    /// 正方形的半边长, 为 `None` 时平面无界
+    extent: Option<f32>,
+
+    /// 材质
+    material: Material,
+}
+
+impl Plane {
+    /// 过 `point`, 法线为 `normal` 的无界平面
+    pub fn new(point: Vector3<f32>, normal: Vector3<f32>, material: Material) -> Self {
+        Self {
+            point,
+            onb: Onb::build_from_w(&normal.normalize()),
+            extent: None,
+            material,
+        }
+    }
+
+    /// 限制为以 `point` 为中心, 半边长为 `extent` 的正方形
+    pub const fn with_extent(mut self, extent: f32) -> Self {
+        self.extent = Some(extent);
+        self
+    }
+
+    /// 经相似变换后的平面
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        let rotation = transform.isometry.rotation;
+
+        Self {
+            point: transform.transform_point(&Point3::from(self.point)).coords,
+            onb: Onb {
+                u: rotation * self.onb.u,
+                v: rotation * self.onb.v,
+                w: rotation * self.onb.w,
+            },
+            extent: self.extent.map(|extent| extent * transform.scaling()),
+            material: self.material,
+        }
+    }
+
+    /// 包围盒在平面内的半边长
+    fn half_size(&self) -> f32 {
+        self.extent.unwrap_or(UNBOUNDED_EXTENT)
+    }
+}
+
+impl Hittable for Plane {
+    /// 纹理坐标: 无界时为沿两个轴的距离 (每个单位长度重复一次纹理), 有界时为正方形内的比例
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let normal = self.onb.w;
+        let d = ray.direction();
+        let denom = normal.dot(&d);
+        if denom.abs() < f32::EPSILON * d.norm() {
+            // 光线与平面平行
+            return None;
+        }
+
+        let t = normal.dot(&(self.point - ray.origin())) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let position = ray.point_at_t(t);
+        let planar = position - self.point;
+        let (x, y) = (planar.dot(&self.onb.u), planar.dot(&self.onb.v));
+        let width = ray.cone().width_at(t * d.magnitude());
+        let (uv, footprint) = match self.extent {
+            Some(extent) => {
+                if x.abs() > extent || y.abs() > extent {
+                    return None;
+                }
+                let size = 2.0 * extent;
+                ((x / size + 0.5, y / size + 0.5), width / size)
+            }
+            None => ((x, y), width),
+        };
+
+        Some(HitRecord {
+            distance: t,
+            position,
+            normal,
+            uv,
+            footprint,
+            material: self.material,
+            object: 0,
+        })
+    }
+}
+
+impl Bounded for Plane {
+    /// 无界时为半边长 `UNBOUNDED_EXTENT` 的正方形的包围盒, 沿法线至少有 `MIN_THICKNESS` 的厚度
+    fn bounding_box(&self) -> AaBb {
+        let half_size = self.half_size();
+        let half = (half_size * self.onb.u).abs()
+            + (half_size * self.onb.v).abs()
+            + Vector3::repeat(MIN_THICKNESS / 2.0);
+
+        AaBb {
+            min: self.point - half,
+            max: self.point + half,
+        }
+    }
+
+    /// 平面外侧的距离为正, 内侧为负; 有界时最近点限制在正方形内
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let offset = point - self.point;
+        let height = offset.dot(&self.onb.w);
+        let half_size = self.half_size();
+        let (x, y) = (
+            offset.dot(&self.onb.u).clamp(-half_size, half_size),
+            offset.dot(&self.onb.v).clamp(-half_size, half_size),
+        );
+        let position = self.point + x * self.onb.u + y * self.onb.v;
+
+        ClosestPoint {
+            object: 0,
+            position,
+            distance: (point - position).magnitude().copysign(height),
+        }
+    }
+
+    fn unbounded(&self) -> bool {
+        self.extent.is_none()
+    }
+}
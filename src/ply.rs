@@ -0,0 +1,412 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::{self, Error};
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+use crate::subdivision::Cage;
+use crate::texture::ColorSpace;
+
+use nalgebra::Vector3;
+
+/// 数据部分的编码
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+/// 属性的数值类型
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Scalar {
+    I8,
+    U8,
+    I16,
+    U16,
+    I32,
+    U32,
+    F32,
+    F64,
+}
+
+impl Scalar {
+    /// 由类型名解析, 同时接受 `uchar` 与 `uint8` 两种写法
+    fn parse(name: &str) -> Option<Self> {
+        match name {
+            "char" | "int8" => Some(Self::I8),
+            "uchar" | "uint8" => Some(Self::U8),
+            "short" | "int16" => Some(Self::I16),
+            "ushort" | "uint16" => Some(Self::U16),
+            "int" | "int32" => Some(Self::I32),
+            "uint" | "uint32" => Some(Self::U32),
+            "float" | "float32" => Some(Self::F32),
+            "double" | "float64" => Some(Self::F64),
+            _ => None,
+        }
+    }
+
+    const fn size(self) -> usize {
+        match self {
+            Self::I8 | Self::U8 => 1,
+            Self::I16 | Self::U16 => 2,
+            Self::I32 | Self::U32 | Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+
+    /// 整数类型的最大值, 用于将颜色归一化到 `[0, 1]`; 浮点类型为 `None`
+    const fn max_value(self) -> Option<f64> {
+        match self {
+            Self::I8 => Some(i8::MAX as f64),
+            Self::U8 => Some(u8::MAX as f64),
+            Self::I16 => Some(i16::MAX as f64),
+            Self::U16 => Some(u16::MAX as f64),
+            Self::I32 => Some(i32::MAX as f64),
+            Self::U32 => Some(u32::MAX as f64),
+            Self::F32 | Self::F64 => None,
+        }
+    }
+}
+
+/// 元素的属性: 单个数值, 或先写长度再写各项的列表 (如面的顶点序号)
+enum Property {
+    Scalar { name: String, ty: Scalar },
+    List { count: Scalar, item: Scalar },
+}
+
+/// 文件头中声明的元素 (如 `vertex`, `face`) 及其个数与属性
+struct Element {
+    name: String,
+    count: usize,
+    properties: Vec<Property>,
+}
+
+/// 数据部分的读取位置
+enum Body<'a> {
+    Ascii(std::str::SplitAsciiWhitespace<'a>),
+    Binary { bytes: &'a [u8], big_endian: bool },
+}
+
+impl Body<'_> {
+    /// 读取一个数值
+    fn read(&mut self, ty: Scalar) -> Result<f64, String> {
+        match self {
+            Self::Ascii(tokens) => {
+                let token = tokens.next().ok_or("unexpected end of data")?;
+                token
+                    .parse()
+                    .map_err(|_| format!("expected a number, found `{token}`"))
+            }
+            Self::Binary { bytes, big_endian } => {
+                let size = ty.size();
+                if bytes.len() < size {
+                    return Err("unexpected end of data".to_string());
+                }
+                let (head, rest) = bytes.split_at(size);
+                *bytes = rest;
+
+                let mut buf = [0; 8];
+                buf[..size].copy_from_slice(head);
+                if *big_endian {
+                    buf[..size].reverse();
+                }
+                Ok(match ty {
+                    Scalar::I8 => buf[0] as i8 as f64,
+                    Scalar::U8 => buf[0] as f64,
+                    Scalar::I16 => i16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    Scalar::U16 => u16::from_le_bytes([buf[0], buf[1]]) as f64,
+                    Scalar::I32 => i32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    Scalar::U32 => u32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    Scalar::F32 => f32::from_le_bytes([buf[0], buf[1], buf[2], buf[3]]) as f64,
+                    Scalar::F64 => f64::from_le_bytes(buf),
+                })
+            }
+        }
+    }
+}
+
+/// 读取 PLY 文件的顶点属性 (位置, 法线, 纹理坐标与颜色, 后三者可能为空), 每个多边形面的顶点序号交给 `face`
+fn read(path: &Path, mut face: impl FnMut(&[u32])) -> error::Result<Vertices> {
+    let invalid = |msg: String| Error::invalid_data(path, msg);
+    let data = fs::read(path)?;
+
+    // 文件头: 以 `end_header` 结束的文本行
+    let header_end = data
+        .windows(b"end_header".len())
+        .position(|window| window == b"end_header")
+        .ok_or_else(|| invalid("missing `end_header`".to_string()))?;
+    let body_start = data[header_end..]
+        .iter()
+        .position(|&byte| byte == b'\n')
+        .map_or(data.len(), |offset| header_end + offset + 1);
+    let header = String::from_utf8_lossy(&data[..header_end]);
+    let (format, elements) = parse_header(&header).map_err(invalid)?;
+
+    let body = &data[body_start..];
+    let mut body = match format {
+        Format::Ascii => Body::Ascii(
+            std::str::from_utf8(body)
+                .map_err(|_| invalid("ASCII data is not valid UTF-8".to_string()))?
+                .split_ascii_whitespace(),
+        ),
+        Format::BinaryLittleEndian | Format::BinaryBigEndian => Body::Binary {
+            bytes: body,
+            big_endian: format == Format::BinaryBigEndian,
+        },
+    };
+
+    let mut positions = vec![];
+    let mut normals = vec![];
+    let mut uvs = vec![];
+    let mut colors = vec![];
+    for element in &elements {
+        match element.name.as_str() {
+            "vertex" => read_vertices(
+                element,
+                &mut body,
+                (&mut positions, &mut normals, &mut uvs, &mut colors),
+            ),
+            "face" => read_faces(element, &mut body, &mut face),
+            _ => skip(element, &mut body),
+        }
+        .map_err(|msg| invalid(format!("element `{}`: {msg}", element.name)))?;
+    }
+
+    Ok((positions, normals, uvs, colors))
+}
+
+/// 读取 PLY 网格 (ASCII 或二进制), 如扫描得到的 Stanford bunny 与 dragon
+///
+/// 读取 `vertex` 元素的位置 `x y z`, 以及可选的法线 `nx ny nz`, 纹理坐标 (`u v`, `s t`
+/// 或 `texture_u texture_v`) 与颜色 `red green blue`; `face` 元素的顶点序号列表中,
+/// 多于三个顶点的多边形按扇形剖分为三角形, 其余元素与属性被忽略。
+/// 有顶点颜色时, 颜色插值后替换 `material` 的反照率; 整数颜色按 sRGB 解码, 浮点颜色视为线性
+pub fn load(path: impl AsRef<Path>, material: Material) -> error::Result<TriangleMesh> {
+    let mut indices = vec![];
+    let (positions, normals, uvs, colors) = read(path.as_ref(), |polygon| {
+        for i in 1..polygon.len() - 1 {
+            indices.push([polygon[0], polygon[i], polygon[i + 1]]);
+        }
+    })?;
+
+    let mut mesh = TriangleMesh::new(positions, indices, material)?;
+    if !normals.is_empty() {
+        mesh = mesh.with_normals(normals)?;
+    }
+    if !uvs.is_empty() {
+        mesh = mesh.with_uvs(uvs)?;
+    }
+    if !colors.is_empty() {
+        mesh = mesh.with_colors(colors)?;
+    }
+
+    Ok(mesh)
+}
+
+/// 读取 PLY 网格作为 Catmull–Clark 细分的控制网格, 多边形面 (通常为四边形) 保持原样;
+/// 顶点法线被忽略, 细分后重新计算, 纹理坐标与颜色同 `load`
+pub fn load_cage(path: impl AsRef<Path>, material: Material) -> error::Result<Cage> {
+    let mut faces = vec![];
+    let (positions, _, uvs, colors) = read(path.as_ref(), |polygon| faces.push(polygon.to_vec()))?;
+
+    let mut cage = Cage::new(positions, faces, material)?;
+    if !uvs.is_empty() {
+        cage = cage.with_uvs(uvs)?;
+    }
+    if !colors.is_empty() {
+        cage = cage.with_colors(colors)?;
+    }
+
+    Ok(cage)
+}
+
+/// 解析文件头, 返回数据的编码与各元素
+fn parse_header(header: &str) -> Result<(Format, Vec<Element>), String> {
+    let mut lines = header.lines().map(str::split_whitespace);
+    if lines.next().and_then(|mut fields| fields.next()) != Some("ply") {
+        return Err("not a PLY file".to_string());
+    }
+
+    let mut format = None;
+    let mut elements: Vec<Element> = vec![];
+    for mut fields in lines {
+        match fields.next() {
+            Some("format") => {
+                format = Some(match fields.next() {
+                    Some("ascii") => Format::Ascii,
+                    Some("binary_little_endian") => Format::BinaryLittleEndian,
+                    Some("binary_big_endian") => Format::BinaryBigEndian,
+                    other => return Err(format!("unknown format `{}`", other.unwrap_or(""))),
+                });
+            }
+            Some("element") => {
+                let (Some(name), Some(count)) = (fields.next(), fields.next()) else {
+                    return Err("`element` takes a name and a count".to_string());
+                };
+                elements.push(Element {
+                    name: name.to_string(),
+                    count: count
+                        .parse()
+                        .map_err(|_| format!("bad count `{count}` for element `{name}`"))?,
+                    properties: vec![],
+                });
+            }
+            Some("property") => {
+                let element = elements
+                    .last_mut()
+                    .ok_or("`property` before any `element`")?;
+                let fields: Vec<&str> = fields.collect();
+                let scalar = |name: &str| {
+                    Scalar::parse(name).ok_or_else(|| format!("unknown property type `{name}`"))
+                };
+                let property = match fields.as_slice() {
+                    ["list", count, item, _] => Property::List {
+                        count: scalar(count)?,
+                        item: scalar(item)?,
+                    },
+                    [ty, name] => Property::Scalar {
+                        name: name.to_string(),
+                        ty: scalar(ty)?,
+                    },
+                    _ => return Err("malformed `property`".to_string()),
+                };
+                element.properties.push(property);
+            }
+            _ => {}
+        }
+    }
+
+    Ok((format.ok_or("missing `format`")?, elements))
+}
+
+/// 读取一个元素实例中所有属性的值, 列表属性按顺序放在 `lists` 中
+fn read_instance(
+    element: &Element,
+    body: &mut Body,
+    values: &mut [f64],
+    lists: &mut Vec<Vec<f64>>,
+) -> Result<(), String> {
+    lists.clear();
+    for (property, value) in element.properties.iter().zip(values.iter_mut()) {
+        match property {
+            Property::Scalar { ty, .. } => *value = body.read(*ty)?,
+            Property::List { count, item } => {
+                let count = body.read(*count)? as usize;
+                let list = (0..count)
+                    .map(|_| body.read(*item))
+                    .collect::<Result<_, _>>()?;
+                lists.push(list);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// 标量属性 `names` 中第一个存在的属性的位置
+fn find(element: &Element, names: &[&str]) -> Option<(usize, Scalar)> {
+    element
+        .properties
+        .iter()
+        .enumerate()
+        .find_map(|(index, property)| match property {
+            Property::Scalar { name, ty } if names.contains(&name.as_str()) => Some((index, *ty)),
+            _ => None,
+        })
+}
+
+/// 读出的顶点位置, 法线, 纹理坐标与颜色
+type Vertices = (
+    Vec<Vector3<f32>>,
+    Vec<Vector3<f32>>,
+    Vec<(f32, f32)>,
+    Vec<Vector3<f32>>,
+);
+
+/// 顶点的位置, 法线, 纹理坐标与颜色
+type VertexBuffers<'a> = (
+    &'a mut Vec<Vector3<f32>>,
+    &'a mut Vec<Vector3<f32>>,
+    &'a mut Vec<(f32, f32)>,
+    &'a mut Vec<Vector3<f32>>,
+);
+
+fn read_vertices(
+    element: &Element,
+    body: &mut Body,
+    (positions, normals, uvs, colors): VertexBuffers,
+) -> Result<(), String> {
+    let triple = |names: [&str; 3]| -> Option<[(usize, Scalar); 3]> {
+        Some([
+            find(element, &[names[0]])?,
+            find(element, &[names[1]])?,
+            find(element, &[names[2]])?,
+        ])
+    };
+    let position = triple(["x", "y", "z"]).ok_or("missing `x`, `y` or `z`")?;
+    let normal = triple(["nx", "ny", "nz"]);
+    let color = triple(["red", "green", "blue"]);
+    let uv = find(element, &["u", "s", "texture_u", "texture_s"])
+        .zip(find(element, &["v", "t", "texture_v", "texture_t"]));
+
+    let mut values = vec![0.0; element.properties.len()];
+    let mut lists = vec![];
+    for _ in 0..element.count {
+        read_instance(element, body, &mut values, &mut lists)?;
+        let vector = |[x, y, z]: [(usize, Scalar); 3]| {
+            Vector3::new(values[x.0], values[y.0], values[z.0]).cast::<f32>()
+        };
+
+        positions.push(vector(position));
+        if let Some(normal) = normal {
+            normals.push(vector(normal));
+        }
+        if let Some(((u, _), (v, _))) = uv {
+            uvs.push((values[u] as f32, values[v] as f32));
+        }
+        if let Some(color) = color {
+            colors.push(match color[0].1.max_value() {
+                Some(max) => (vector(color) / max as f32).map(|c| ColorSpace::Srgb.to_linear(c)),
+                None => vector(color),
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// 读取各个面的顶点序号列表, 不足三个顶点的面被忽略
+fn read_faces(
+    element: &Element,
+    body: &mut Body,
+    face: &mut impl FnMut(&[u32]),
+) -> Result<(), String> {
+    let mut values = vec![0.0; element.properties.len()];
+    let mut lists = vec![];
+    let mut polygon = vec![];
+    for _ in 0..element.count {
+        read_instance(element, body, &mut values, &mut lists)?;
+        let list = lists.first().ok_or("missing vertex index list")?;
+        if list.len() < 3 {
+            continue;
+        }
+
+        polygon.clear();
+        polygon.extend(list.iter().map(|&index| index as u32));
+        face(&polygon);
+    }
+
+    Ok(())
+}
+
+/// 跳过不需要的元素
+fn skip(element: &Element, body: &mut Body) -> Result<(), String> {
+    let mut values = vec![0.0; element.properties.len()];
+    let mut lists = vec![];
+    for _ in 0..element.count {
+        read_instance(element, body, &mut values, &mut lists)?;
+    }
+
+    Ok(())
+}
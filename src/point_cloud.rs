@@ -0,0 +1,231 @@
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::accelerator::Accelerator;
+use crate::bvh::{AaBb, BVHNode, Bounded, ClosestPoint};
+use crate::error::{self, Error};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+
+use clap::ValueEnum;
+use nalgebra::Vector3;
+
+/// 点的绘制形状
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SplatShape {
+    /// 始终正对光线的圆盘
+    Disk,
+
+    /// 小球
+    Sphere,
+}
+
+/// 点云中的一个点
+#[derive(Clone, Copy)]
+struct Splat {
+    center: Vector3<f32>,
+    radius: f32,
+    color: Vector3<f32>,
+    shape: SplatShape,
+}
+
+impl Splat {
+    /// 光线与正对光线的圆盘相交: 交点为光线上离圆心最近的点, 法线朝向光线起点
+    fn hit_disk(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, Vector3<f32>)> {
+        let d = ray.direction();
+        let t = (self.center - ray.origin()).dot(&d) / d.dot(&d);
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        let p = ray.point_at_t(t);
+        if (p - self.center).norm_squared() > self.radius * self.radius {
+            return None;
+        }
+
+        Some((t, -d.normalize()))
+    }
+
+    /// 光线与小球相交
+    fn hit_sphere(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<(f32, Vector3<f32>)> {
+        let oc = ray.origin() - self.center;
+        let d = ray.direction();
+        let a = d.dot(&d);
+        let b = oc.dot(&d);
+        let c = oc.dot(&oc) - self.radius * self.radius;
+        let disc = b * b - a * c;
+        if disc <= 0.0 {
+            return None;
+        }
+
+        let sqrt_disc = disc.sqrt();
+        [(-b - sqrt_disc) / a, (-b + sqrt_disc) / a]
+            .into_iter()
+            .find(|&t| t > t_min && t < t_max)
+            .map(|t| (t, (ray.point_at_t(t) - self.center) / self.radius))
+    }
+}
+
+impl Hittable for Splat {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (t, normal) = match self.shape {
+            SplatShape::Disk => self.hit_disk(ray, t_min, t_max),
+            SplatShape::Sphere => self.hit_sphere(ray, t_min, t_max),
+        }?;
+
+        Some(HitRecord {
+            distance: t,
+            position: ray.point_at_t(t),
+            normal,
+            uv: (0.0, 0.0),
+            footprint: 0.0,
+            material: Material::lambertian(self.color),
+            object: 0,
+        })
+    }
+}
+
+impl Bounded for Splat {
+    fn bounding_box(&self) -> AaBb {
+        let r = Vector3::new(self.radius, self.radius, self.radius);
+
+        AaBb {
+            min: self.center - r,
+            max: self.center + r,
+        }
+    }
+
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let offset = point - self.center;
+        let direction = offset
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y);
+
+        ClosestPoint {
+            object: 0,
+            position: self.center + self.radius * direction,
+            distance: offset.magnitude() - self.radius,
+        }
+    }
+}
+
+/// 点云: 每个点有各自的颜色与半径, 以圆盘或小球绘制, 点之间由自身的 BVH 加速求交
+///
+/// 作为场景中的单个实体, 点云整体的可见性与编号相同
+pub struct PointCloud {
+    bvh: BVHNode,
+    bbox: AaBb,
+    len: usize,
+}
+
+impl PointCloud {
+    /// 由点的位置, 颜色 (线性, 用作漫反射的反照率) 与半径构建点云
+    pub fn new(
+        points: impl IntoIterator<Item = (Vector3<f32>, Vector3<f32>, f32)>,
+        shape: SplatShape,
+    ) -> Self {
+        let splats: Vec<Arc<dyn Bounded + Sync + Send>> = points
+            .into_iter()
+            .map(|(center, color, radius)| {
+                Arc::new(Splat {
+                    center,
+                    radius,
+                    color,
+                    shape,
+                }) as Arc<dyn Bounded + Sync + Send>
+            })
+            .collect();
+        let len = splats.len();
+        let bbox = AaBb::all_surrounding_box(&splats);
+
+        Self {
+            bvh: BVHNode::build(splats),
+            bbox,
+            len,
+        }
+    }
+
+    /// 读取文本格式的点云, 每行一个点:
+    ///
+    /// ```text
+    /// x y z [r g b [radius]]
+    /// ```
+    ///
+    /// 颜色缺省为浅灰, 文件中有分量大于 1 的颜色时按 0 到 255 解释; 半径缺省为 `radius`;
+    /// 空行与 `#` 开头的行被忽略
+    pub fn load(path: impl AsRef<Path>, radius: f32, shape: SplatShape) -> error::Result<Self> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path)?;
+
+        let mut points = vec![];
+        for (index, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let invalid = || {
+                let message = "expected `x y z [r g b [radius]]`".to_string();
+                Error::parse(path, index + 1, line, line, message)
+            };
+            let v: Vec<f32> = line
+                .split(|c: char| c.is_whitespace() || c == ',')
+                .filter(|field| !field.is_empty())
+                .map(str::parse)
+                .collect::<Result<_, _>>()
+                .map_err(|_| invalid())?;
+            let (color, point_radius) = match v.len() {
+                3 => (Vector3::new(0.7, 0.7, 0.7), radius),
+                6 => (Vector3::new(v[3], v[4], v[5]), radius),
+                7 => (Vector3::new(v[3], v[4], v[5]), v[6]),
+                _ => return Err(invalid()),
+            };
+            points.push((Vector3::new(v[0], v[1], v[2]), color, point_radius));
+        }
+
+        // 8 位颜色
+        if points.iter().any(|(_, color, _)| color.max() > 1.0) {
+            for (_, color, _) in &mut points {
+                *color /= 255.0;
+            }
+        }
+
+        Ok(Self::new(points, shape))
+    }
+
+    /// 点的数量
+    pub const fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 点云是否为空
+    pub const fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+}
+
+impl Hittable for PointCloud {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        if !self.bbox.hit(ray) {
+            return None;
+        }
+
+        self.bvh.hit(ray, t_min, t_max)
+    }
+}
+
+impl Bounded for PointCloud {
+    fn bounding_box(&self) -> AaBb {
+        self.bbox.clone()
+    }
+
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        self.bvh.closest_point(point).unwrap_or(ClosestPoint {
+            object: 0,
+            position: *point,
+            distance: f32::INFINITY,
+        })
+    }
+}
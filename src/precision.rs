@@ -0,0 +1,93 @@
+use crate::rng::{hash, hash_to_unit};
+
+use clap::ValueEnum;
+use half::f16;
+use nalgebra::Vector4;
+
+/// 帧缓冲的数值精度
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum Precision {
+    /// 单精度
+    F32,
+
+    /// 半精度: 累积缓冲的内存减半, 转换为 8 位时施加抖动以避免色带
+    F16,
+}
+
+impl Precision {
+    /// 将线性颜色分量 gamma 修正后转换为 8 位, 半精度时施加逐像素的三角分布抖动
+    pub fn encode(self, linear: f32, (x, y): (usize, usize), channel: usize) -> u8 {
+        let encoded = linear.max(0.0).sqrt();
+
+        match self {
+            Self::F32 => (255.99 * encoded.clamp(0.0, 1.0)) as u8,
+            Self::F16 => (255.0 * encoded + 0.5 + dither((x, y), channel)).clamp(0.0, 255.0) as u8,
+        }
+    }
+}
+
+/// 将线性颜色分量 gamma 修正后转换为 16 位, `dithered` 时施加逐像素的三角分布抖动
+pub fn encode_deep(linear: f32, (x, y): (usize, usize), channel: usize, dithered: bool) -> u16 {
+    let encoded = linear.max(0.0).sqrt();
+    let dither = if dithered {
+        dither((x, y), channel)
+    } else {
+        0.0
+    };
+
+    (65535.0 * encoded + 0.5 + dither).clamp(0.0, 65535.0) as u16
+}
+
+/// 像素 `(x, y)` 的通道 `channel` 的三角分布抖动, 范围为 (-1, 1) 个量化步长
+fn dither((x, y): (usize, usize), channel: usize) -> f32 {
+    let seed = hash(x as u32 ^ hash(y as u32 ^ hash(channel as u32)));
+
+    hash_to_unit(seed) + hash_to_unit(seed ^ 0x9e37_79b9) - 1.0
+}
+
+/// 像素的累积: 颜色 (按不透明度预乘) 与不透明度的均值, 亮度平方的均值与采样数
+pub trait Accumulator: Default + Send + Sync {
+    fn load(&self) -> (Vector4<f32>, f32, u32);
+
+    fn store(&mut self, mean: Vector4<f32>, mean_sq: f32, n: u32);
+}
+
+/// 单精度的累积
+#[derive(Default)]
+pub struct FullAccumulator {
+    mean: Vector4<f32>,
+    mean_sq: f32,
+    n: u32,
+}
+
+impl Accumulator for FullAccumulator {
+    fn load(&self) -> (Vector4<f32>, f32, u32) {
+        (self.mean, self.mean_sq, self.n)
+    }
+
+    fn store(&mut self, mean: Vector4<f32>, mean_sq: f32, n: u32) {
+        (self.mean, self.mean_sq, self.n) = (mean, mean_sq, n);
+    }
+}
+
+/// 半精度的累积, 保存均值而非总和, 避免采样数增大后精度不足
+#[derive(Default)]
+pub struct HalfAccumulator {
+    mean: [f16; 4],
+    mean_sq: f16,
+    n: u32,
+}
+
+impl Accumulator for HalfAccumulator {
+    fn load(&self) -> (Vector4<f32>, f32, u32) {
+        let [r, g, b, a] = self.mean.map(f16::to_f32);
+
+        (Vector4::new(r, g, b, a), self.mean_sq.to_f32(), self.n)
+    }
+
+    fn store(&mut self, mean: Vector4<f32>, mean_sq: f32, n: u32) {
+        self.mean = [mean.x, mean.y, mean.z, mean.w].map(f16::from_f32);
+        self.mean_sq = f16::from_f32(mean_sq);
+        self.n = n;
+    }
+}
@@ -0,0 +1,60 @@
+use crate::accelerator::Accelerator;
+use crate::camera::Camera;
+use crate::render::{Framebuffer, RenderSettings, camera_sample, resolve_pixel};
+
+use rayon::prelude::*;
+
+/// 各级预览相对全分辨率的缩小倍数, 从粗到细
+const LEVELS: [usize; 3] = [8, 4, 2];
+
+/// 逐级渲染低分辨率预览: 每级每个像素一个采样, 以最近邻放大到全分辨率后交给 `show`
+///
+/// 返回最后一级预览, 其各行均未完成, 正式渲染完成前未完成的行显示为预览
+pub fn render(
+    scene: &dyn Accelerator,
+    camera: &Camera,
+    nx: usize,
+    ny: usize,
+    settings: &RenderSettings,
+    mut show: impl FnMut(&Framebuffer),
+) -> Framebuffer {
+    let mut framebuffer = Framebuffer::new(nx, ny, settings.transparent);
+    let scale = settings.exposure.scale();
+
+    for factor in LEVELS {
+        let (lx, ly) = (nx.div_ceil(factor), ny.div_ceil(factor));
+
+        // 低分辨率的像素 (首行为图像底部)
+        let pixels: Vec<([u8; 3], u8)> = (0..ly)
+            .into_par_iter()
+            .flat_map_iter(|y| {
+                (0..lx).map(move |x| {
+                    let u = (x as f32 + 0.5) / lx as f32;
+                    let v = (y as f32 + 0.5) / ly as f32;
+                    let (col, alpha) = camera_sample(camera.camera_ray(u, v), scene, settings);
+
+                    resolve_pixel(&(scale * col), alpha, settings.precision, (x, y))
+                })
+            })
+            .collect();
+
+        // 放大到全分辨率 (首行为图像顶部)
+        for y in 0..ny {
+            let row = y * ly / ny;
+            for x in 0..nx {
+                let (rgb, a) = pixels[row * lx + x * lx / nx];
+                let offset = (ny - 1 - y) * nx + x;
+                framebuffer.pixels[3 * offset..3 * offset + 3].copy_from_slice(&rgb);
+                if let Some(alpha) = &mut framebuffer.alpha {
+                    alpha[offset] = a;
+                }
+            }
+        }
+
+        eprint!("\rPreview at 1/{factor} resolution{}", " ".repeat(10));
+        show(&framebuffer);
+    }
+    eprintln!();
+
+    framebuffer
+}
@@ -0,0 +1,329 @@
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+use crate::accelerator::Accelerator;
+use crate::camera::Camera;
+use crate::film::Film;
+use crate::output::write_atomic;
+use crate::precision::{Accumulator, FullAccumulator, HalfAccumulator, Precision};
+use crate::render::{Framebuffer, INTERRUPTED, RenderSettings, camera_sample, luminance};
+use crate::rng::{self, get_rng};
+use crate::sampler::build_sampler;
+use crate::stats::Throughput;
+
+use nalgebra::Vector4;
+use rayon::prelude::*;
+
+/// 图块边长
+const TILE_SIZE: usize = 16;
+
+/// 时间预算与误差目标下每轮平均每个像素追加的采样数
+const ROUND_SAMPLES: usize = 4;
+
+/// 误差目标下须达到目标的像素比例
+const CONVERGED_FRACTION: f32 = 0.95;
+
+/// 估计相对误差时分母的偏置, 避免暗处的误差被无限放大
+const LUMINANCE_BIAS: f32 = 1e-2;
+
+/// 图块
+struct Tile<A> {
+    x0: usize,
+    y0: usize,
+    x1: usize,
+    y1: usize,
+
+    /// 每个像素的累积
+    pixels: Vec<A>,
+
+    /// 各轮采样累计的耗时 (秒)
+    time: f32,
+}
+
+impl<A: Accumulator> Tile<A> {
+    const fn area(&self) -> usize {
+        (self.x1 - self.x0) * (self.y1 - self.y0)
+    }
+
+    /// 估计的相对误差: 各像素相对误差的平均
+    fn error(&self) -> f32 {
+        let total: f32 = self.pixels.iter().map(pixel_error).sum();
+
+        total / self.pixels.len() as f32
+    }
+
+    /// 相对误差不超过 `threshold` 的像素数
+    fn converged(&self, threshold: f32) -> usize {
+        self.pixels
+            .iter()
+            .filter(|&pixel| pixel_error(pixel) <= threshold)
+            .count()
+    }
+}
+
+/// 像素的相对误差: 均值估计的方差与亮度平方之比
+fn pixel_error<A: Accumulator>(pixel: &A) -> f32 {
+    let (mean, mean_sq, n) = pixel.load();
+    let mean = luminance(&mean.xyz());
+    let variance = (mean_sq - mean * mean).max(0.0) / n as f32;
+
+    variance / (mean * mean + LUMINANCE_BIAS)
+}
+
+/// 相对误差不超过 `threshold` 的像素所占的比例
+fn converged_fraction<A: Accumulator>(tiles: &[Tile<A>], threshold: f32) -> f32 {
+    let (converged, total) = tiles.iter().fold((0, 0), |(converged, total), tile| {
+        (converged + tile.converged(threshold), total + tile.area())
+    });
+
+    converged as f32 / total.max(1) as f32
+}
+
+/// 渲染所需的上下文
+struct Context<'a> {
+    scene: &'a dyn Accelerator,
+    camera: &'a Camera,
+    nx: usize,
+    ny: usize,
+
+    /// 采样器的分层规模
+    ns: usize,
+
+    settings: &'a RenderSettings,
+}
+
+impl Context<'_> {
+    /// 图块的每个像素再追加 `spp` 个采样
+    fn sample<A: Accumulator>(&self, tile: &mut Tile<A>, spp: usize) {
+        let start = Instant::now();
+        let settings = self.settings;
+        let mut sampler = build_sampler(settings.sampler, self.ns, get_rng(), settings.cp_rotation);
+        let width = tile.x1 - tile.x0;
+
+        for (i, pixel) in tile.pixels.iter_mut().enumerate() {
+            let (x, y) = (tile.x0 + i % width, tile.y0 + i / width);
+
+            // 在单精度下累积本轮的采样, 结束后写回
+            let (mean, mean_sq, mut n) = pixel.load();
+            if let Some(rng) = rng::start_pixel((x, y), n as usize) {
+                sampler = build_sampler(settings.sampler, self.ns, rng, settings.cp_rotation);
+            }
+            let (mut sum, mut sum_sq) = (mean * n as f32, mean_sq * n as f32);
+            for _ in 0..spp {
+                sampler.start_sample((x, y), n as usize);
+                let (ray, weight) =
+                    self.camera
+                        .generate_ray((x, y), sampler.as_mut(), &settings.filter, None);
+
+                let (col, alpha) = camera_sample(ray, self.scene, settings);
+                let (col, alpha) = (weight * col, weight * alpha);
+                let l = luminance(&col);
+                sum += Vector4::new(col.x, col.y, col.z, alpha);
+                sum_sq += l * l;
+                n += 1;
+            }
+            if n > 0 {
+                pixel.store(sum / n as f32, sum_sq / n as f32, n);
+            }
+        }
+        tile.time += start.elapsed().as_secs_f32();
+    }
+}
+
+/// 将各图块的累积写入帧缓冲 (首行为图像顶部)
+fn resolve<A: Accumulator>(
+    tiles: &[Tile<A>],
+    nx: usize,
+    ny: usize,
+    settings: &RenderSettings,
+) -> Framebuffer {
+    let mut film = Film::new(nx, ny);
+    let mut times = vec![0.0; nx * ny];
+    for tile in tiles {
+        let width = tile.x1 - tile.x0;
+        let time = tile.time / tile.area() as f32;
+        for (i, pixel) in tile.pixels.iter().enumerate() {
+            let (x, y) = (tile.x0 + i % width, tile.y0 + i / width);
+            let (mean, _, _) = pixel.load();
+            film.add_sample((x, y), &mean.xyz(), mean.w, 1.0);
+            times[(ny - 1 - y) * nx + x] = time;
+        }
+    }
+
+    Framebuffer {
+        times: Some(times),
+        ..film.to_framebuffer(settings)
+    }
+}
+
+/// 渐进式渲染的终止条件
+#[derive(Clone, Copy, Debug)]
+pub enum Termination {
+    /// 固定的轮数, 共用每个像素 `ns` 个采样的预算
+    Passes(usize),
+
+    /// 时间预算: 每轮按误差追加采样, 直到用尽预算
+    TimeBudget(Duration),
+
+    /// 误差目标: 每轮按误差追加采样, 直到 95% 的像素的相对误差不超过目标,
+    /// 或用尽每个像素平均 `ns` 个采样的预算
+    ErrorTarget(f32),
+}
+
+/// 渐进式路径追踪: 首轮每个像素均匀采样, 之后每轮按估计误差从高到低重新采样图块,
+/// 采样预算按误差分配, 使已收敛的区域 (如天空) 不再占用时间
+pub fn render(
+    scene: &dyn Accelerator,
+    camera: &Camera,
+    nx: usize,
+    ny: usize,
+    ns: usize,
+    termination: Termination,
+    settings: &RenderSettings,
+) -> Framebuffer {
+    let context = Context {
+        scene,
+        camera,
+        nx,
+        ny,
+        ns,
+        settings,
+    };
+
+    match settings.precision {
+        Precision::F32 => render_with::<FullAccumulator>(&context, termination),
+        Precision::F16 => render_with::<HalfAccumulator>(&context, termination),
+    }
+}
+
+/// 以 `A` 累积像素的渐进式渲染
+fn render_with<A: Accumulator>(context: &Context, termination: Termination) -> Framebuffer {
+    let (nx, ny, ns, settings) = (context.nx, context.ny, context.ns, context.settings);
+    let timer = Instant::now();
+    let throughput = Throughput::default();
+
+    // 时间预算的截止时刻, 到达后不再开始新的图块
+    let deadline = match termination {
+        Termination::TimeBudget(budget) => Some(timer + budget),
+        Termination::Passes(_) | Termination::ErrorTarget(_) => None,
+    };
+    let expired = || deadline.is_some_and(|deadline| Instant::now() >= deadline);
+
+    // 划分图块
+    let mut tiles = vec![];
+    for y0 in (0..ny).step_by(TILE_SIZE) {
+        for x0 in (0..nx).step_by(TILE_SIZE) {
+            let (x1, y1) = ((x0 + TILE_SIZE).min(nx), (y0 + TILE_SIZE).min(ny));
+            let area = (x1 - x0) * (y1 - y0);
+            tiles.push(Tile {
+                x0,
+                y0,
+                x1,
+                y1,
+                pixels: std::iter::repeat_with(A::default).take(area).collect(),
+                time: 0.0,
+            });
+        }
+    }
+
+    // 首轮: 均匀采样, 时间预算下只取估计误差所需的少量采样, 以便尽快得到完整的图像
+    let first = match termination {
+        Termination::Passes(passes) => (ns / passes.max(1)).max(1),
+        Termination::TimeBudget(_) | Termination::ErrorTarget(_) => ROUND_SAMPLES,
+    };
+    tiles
+        .par_iter_mut()
+        .for_each(|tile| context.sample(tile, first));
+    let mut remaining = (ns - first.min(ns)) * nx * ny;
+
+    for pass in 1.. {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            break;
+        }
+
+        // 本轮的采样预算
+        let budget = match termination {
+            Termination::Passes(passes) if pass < passes && remaining > 0 => {
+                remaining / (passes - pass)
+            }
+            Termination::TimeBudget(_) if !expired() => ROUND_SAMPLES * nx * ny,
+            Termination::ErrorTarget(target)
+                if remaining > 0 && converged_fraction(&tiles, target) < CONVERGED_FRACTION =>
+            {
+                (ROUND_SAMPLES * nx * ny).min(remaining)
+            }
+            _ => break,
+        };
+
+        // 按误差从高到低排序 (图块的顺序不影响结果), 本轮预算按误差比例分配
+        tiles.sort_by_cached_key(|tile| std::cmp::Reverse(tile.error().to_bits()));
+        let errors: Vec<f32> = tiles.iter().map(Tile::error).collect();
+        let total_error: f32 = errors.iter().sum();
+        if total_error <= 0.0 {
+            break;
+        }
+
+        let allocation: Vec<usize> = tiles
+            .iter()
+            .zip(&errors)
+            .map(|(tile, error)| {
+                (budget as f32 * error / total_error / tile.area() as f32).round() as usize
+            })
+            .collect();
+
+        tiles
+            .par_iter_mut()
+            .zip(&allocation)
+            .for_each(|(tile, &spp)| {
+                if !INTERRUPTED.load(Ordering::SeqCst) && !expired() {
+                    context.sample(tile, spp);
+                }
+            });
+
+        let spent: usize = tiles
+            .iter()
+            .zip(&allocation)
+            .map(|(t, a)| t.area() * a)
+            .sum();
+        remaining = remaining.saturating_sub(spent.max(1));
+
+        let progress = match termination {
+            Termination::Passes(passes) => format!("Pass {:>3}/{}", pass + 1, passes),
+            Termination::TimeBudget(budget) => format!(
+                "Pass {:>3} | {:.0}/{:.0}s",
+                pass + 1,
+                timer.elapsed().as_secs_f32(),
+                budget.as_secs_f32()
+            ),
+            Termination::ErrorTarget(target) => format!(
+                "Pass {:>3} | {:.1}% converged",
+                pass + 1,
+                100.0 * converged_fraction(&tiles, target)
+            ),
+        };
+        eprint!(
+            "\r{progress} | worst tile error: {:.2e} | {}",
+            errors[0],
+            throughput.summary()
+        );
+
+        // 每轮结束写入当前结果
+        if let Some(partial) = &settings.partial_write
+            && let Err(e) = write_atomic(
+                &resolve(&tiles, nx, ny, settings).image(nx, ny),
+                &partial.file_name,
+                partial.format,
+            )
+        {
+            eprintln!("\rFailed to write partial image: {e}");
+        }
+    }
+
+    eprintln!(
+        "\rRendered in {:.1}s{}",
+        timer.elapsed().as_secs_f32(),
+        " ".repeat(80)
+    );
+
+    resolve(&tiles, nx, ny, settings)
+}
@@ -0,0 +1,280 @@
+use std::mem::size_of;
+use std::sync::Arc;
+
+use crate::accelerator::Accelerator;
+use crate::bvh::{AaBb, BVHNode, Bounded, ClosestPoint, closest_among};
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::Ray;
+
+use nalgebra::Vector3;
+
+/// 量化的最大值
+const LEVELS: f32 = u8::MAX as f32;
+
+/// 量化时额外放宽的余量 (以量化单位计), 抵消还原时的浮点误差
+const MARGIN: f32 = 1e-3;
+
+/// 子结点的引用
+#[derive(Clone, Copy)]
+struct Child {
+    /// 内部结点在 `nodes` 中的下标, 或叶子结点首个实体在 `objects` 中的下标
+    index: u32,
+
+    /// 叶子结点包含的实体数, 内部结点为 0
+    count: u16,
+}
+
+/// 量化的 BVH 结点: 两个子结点的包围盒以当前结点的包围盒为参照, 各轴量化为 8 位
+struct QuantizedNode {
+    /// 子结点包围盒的最小点与最大点
+    bounds: [[[u8; 3]; 2]; 2],
+
+    /// 左右子结点
+    children: [Child; 2],
+}
+
+/// 量化的 BVH, 结点内存约为 `BVHNode` 的一半
+pub struct QuantizedBvh {
+    /// 根结点的包围盒
+    root_bounds: AaBb,
+
+    /// 根结点
+    root: Child,
+
+    /// 所有内部结点
+    nodes: Vec<QuantizedNode>,
+
+    /// 所有实体, 每个叶子结点占据连续的一段
+    objects: Vec<Arc<dyn Bounded + Sync + Send>>,
+}
+
+/// 将 `child` 量化到 `parent` 之中, 最小点向下取整, 最大点向上取整, 保证量化后的包围盒包裹原包围盒
+fn quantize(child: &AaBb, parent: &AaBb) -> [[u8; 3]; 2] {
+    let extent = parent.max - parent.min;
+    let scale = |axis: usize, value: f32| {
+        if extent[axis] > 0.0 {
+            ((value - parent.min[axis]) / extent[axis] * LEVELS).clamp(0.0, LEVELS)
+        } else {
+            0.0
+        }
+    };
+
+    [
+        std::array::from_fn(|axis| (scale(axis, child.min[axis]) - MARGIN).floor().max(0.0) as u8),
+        std::array::from_fn(|axis| {
+            (scale(axis, child.max[axis]) + MARGIN).ceil().min(LEVELS) as u8
+        }),
+    ]
+}
+
+/// 由量化值还原包围盒
+fn dequantize(bounds: &[[u8; 3]; 2], parent: &AaBb) -> AaBb {
+    let extent = (parent.max - parent.min) / LEVELS;
+    let [min, max] = bounds.map(|q| Vector3::new(q[0] as f32, q[1] as f32, q[2] as f32));
+
+    AaBb {
+        min: parent.min + min.component_mul(&extent),
+        max: parent.min + max.component_mul(&extent),
+    }
+}
+
+/// 光线进入包围盒时的参数, 未在 `[t_min, t_max]` 内穿过时为 `None`
+fn entry(bbox: &AaBb, ray: &Ray, inv_d: &Vector3<f32>, t_min: f32, t_max: f32) -> Option<f32> {
+    let t0s = (bbox.min - ray.origin()).component_mul(inv_d);
+    let t1s = (bbox.max - ray.origin()).component_mul(inv_d);
+
+    let t_enter = t0s.zip_map(&t1s, f32::min).max().max(t_min);
+    let t_exit = t0s.zip_map(&t1s, f32::max).min().min(t_max);
+
+    (t_enter <= t_exit).then_some(t_enter)
+}
+
+impl QuantizedBvh {
+    /// 由 BVH 树压缩得到
+    pub fn build(bvh: &BVHNode) -> Self {
+        let mut qbvh = Self {
+            root_bounds: bvh.bounding_box(),
+            root: Child { index: 0, count: 0 },
+            nodes: vec![],
+            objects: vec![],
+        };
+        qbvh.root = qbvh.flatten(bvh, &qbvh.root_bounds.clone());
+
+        qbvh
+    }
+
+    /// 将子树展开到 `nodes` 与 `objects` 中
+    ///
+    /// 子结点以 `bbox` (当前结点还原后的包围盒, 而非其精确包围盒) 为参照量化, 与遍历时一致
+    fn flatten(&mut self, node: &BVHNode, bbox: &AaBb) -> Child {
+        match node {
+            BVHNode::Leaf { objects } => {
+                let child = Child {
+                    index: self.objects.len() as u32,
+                    count: objects.len() as u16,
+                };
+                self.objects.extend(objects.iter().cloned());
+
+                child
+            }
+            BVHNode::Node { left, right, .. } => {
+                let bounds = [
+                    quantize(&left.bounding_box(), bbox),
+                    quantize(&right.bounding_box(), bbox),
+                ];
+                let index = self.nodes.len();
+                self.nodes.push(QuantizedNode {
+                    bounds,
+                    children: [Child { index: 0, count: 0 }; 2],
+                });
+
+                let children = [
+                    self.flatten(left, &dequantize(&bounds[0], bbox)),
+                    self.flatten(right, &dequantize(&bounds[1], bbox)),
+                ];
+                self.nodes[index].children = children;
+
+                Child {
+                    index: index as u32,
+                    count: 0,
+                }
+            }
+        }
+    }
+
+    /// 叶子结点中的实体
+    fn leaf(&self, child: Child) -> &[Arc<dyn Bounded + Sync + Send>] {
+        let start = child.index as usize;
+
+        &self.objects[start..start + child.count as usize]
+    }
+
+    /// 结点的两个子结点及其包围盒
+    fn children(&self, child: Child, bbox: &AaBb) -> [(Child, AaBb); 2] {
+        let node = &self.nodes[child.index as usize];
+
+        [0, 1].map(|i| (node.children[i], dequantize(&node.bounds[i], bbox)))
+    }
+
+    /// 光线与子树相交, 优先访问光线先进入的子结点, 更新最近的交点
+    fn hit_child(
+        &self,
+        (child, bbox): (Child, &AaBb),
+        ray: &Ray,
+        inv_d: &Vector3<f32>,
+        t_min: f32,
+        closest: &mut f32,
+        closest_hit: &mut Option<HitRecord>,
+    ) {
+        if child.count > 0 {
+            for obj in self.leaf(child) {
+                if let Some(hit) = obj.hit(ray, t_min, *closest) {
+                    *closest = hit.distance;
+                    *closest_hit = Some(hit);
+                }
+            }
+            return;
+        }
+
+        let [left, right] = self.children(child, bbox);
+        let t_left = entry(&left.1, ray, inv_d, t_min, *closest);
+        let t_right = entry(&right.1, ray, inv_d, t_min, *closest);
+        let (near, far) = match (t_left, t_right) {
+            (Some(l), Some(r)) if r < l => (right, Some((left, l))),
+            (Some(_), Some(r)) => (left, Some((right, r))),
+            (Some(_), None) => (left, None),
+            (None, Some(_)) => (right, None),
+            (None, None) => return,
+        };
+
+        self.hit_child((near.0, &near.1), ray, inv_d, t_min, closest, closest_hit);
+        if let Some(((c, b), t)) = far
+            && t <= *closest
+        {
+            self.hit_child((c, &b), ray, inv_d, t_min, closest, closest_hit);
+        }
+    }
+}
+
+impl Hittable for QuantizedBvh {
+    /// 光线与量化 BVH 相交
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let inv_d = ray.inv_direction();
+        entry(&self.root_bounds, ray, &inv_d, t_min, t_max)?;
+
+        let mut closest = t_max;
+        let mut closest_hit = None;
+        self.hit_child(
+            (self.root, &self.root_bounds),
+            ray,
+            &inv_d,
+            t_min,
+            &mut closest,
+            &mut closest_hit,
+        );
+
+        closest_hit
+    }
+}
+
+impl Accelerator for QuantizedBvh {
+    /// 所有结点还原后的包围盒及其所在深度
+    fn boxes(&self) -> Vec<(AaBb, usize)> {
+        let mut boxes = vec![];
+        if self.objects.is_empty() {
+            return boxes;
+        }
+
+        let mut stack = vec![(self.root, self.root_bounds.clone(), 0)];
+
+        while let Some((child, bbox, depth)) = stack.pop() {
+            if child.count == 0 {
+                for (c, b) in self.children(child, &bbox) {
+                    stack.push((c, b, depth + 1));
+                }
+            }
+            boxes.push((bbox, depth));
+        }
+
+        boxes
+    }
+
+    /// 分支限界: 跳过包围盒比当前最近表面更远的子树
+    fn closest_point(&self, point: &Vector3<f32>) -> Option<ClosestPoint> {
+        let mut best: Option<ClosestPoint> = None;
+        if self.objects.is_empty() {
+            return best;
+        }
+
+        let mut stack = vec![(self.root, self.root_bounds.clone())];
+
+        while let Some((child, bbox)) = stack.pop() {
+            if best
+                .as_ref()
+                .is_some_and(|b| bbox.distance_squared(point) >= b.distance.powi(2))
+            {
+                continue;
+            }
+
+            if child.count > 0 {
+                closest_among(self.leaf(child), point, &mut best);
+            } else {
+                let [left, right] = self.children(child, &bbox);
+                let (near, far) =
+                    if left.1.distance_squared(point) <= right.1.distance_squared(point) {
+                        (left, right)
+                    } else {
+                        (right, left)
+                    };
+                stack.push(far);
+                stack.push(near);
+            }
+        }
+
+        best
+    }
+
+    fn stats(&self) -> Option<(usize, usize)> {
+        Some((self.nodes.len(), size_of::<QuantizedNode>()))
+    }
+}
@@ -0,0 +1,131 @@
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+use crate::triangle::{self, MIN_THICKNESS};
+
+use nalgebra::{Point3, Similarity3, Vector3};
+
+/// 四边形 (平行四边形): 由一个角点与两条边张成, 用于墙面, 地板与面光源
+///
+/// 边 `u`, `v` 互相垂直时为矩形; 法线为 `u × v` 的方向, 与三角形相同只有正面可见散射
+#[derive(Clone)]
+pub struct Quad {
+    /// 角点
+    corner: Vector3<f32>,
+
+    /// 自角点出发的两条边
+    u: Vector3<f32>,
+    v: Vector3<f32>,
+
+    /// 材质
+    material: Material,
+}
+
+impl Quad {
+    pub const fn new(
+        corner: Vector3<f32>,
+        u: Vector3<f32>,
+        v: Vector3<f32>,
+        material: Material,
+    ) -> Self {
+        Self {
+            corner,
+            u,
+            v,
+            material,
+        }
+    }
+
+    /// 经相似变换后的四边形
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        Self {
+            corner: transform.transform_point(&Point3::from(self.corner)).coords,
+            u: transform.transform_vector(&self.u),
+            v: transform.transform_vector(&self.v),
+            material: self.material,
+        }
+    }
+
+    /// 拆分成的两个三角形, 法线方向与四边形相同
+    fn triangles(&self) -> [[Vector3<f32>; 3]; 2] {
+        let (a, b, d) = (self.corner, self.corner + self.u, self.corner + self.v);
+
+        [[a, b, d], [b + self.v, d, b]]
+    }
+}
+
+impl Hittable for Quad {
+    /// 与所在平面求交后检查平面坐标, 纹理坐标为沿两条边的比例
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let cross = self.u.cross(&self.v);
+        let d = ray.direction();
+        let denom = cross.dot(&d);
+        if denom.abs() < f32::EPSILON * cross.norm() * d.norm() {
+            // 光线与四边形所在平面平行
+            return None;
+        }
+
+        let t = cross.dot(&(self.corner - ray.origin())) / denom;
+        if t <= t_min || t >= t_max {
+            return None;
+        }
+
+        // 交点在两条边上的坐标
+        let position = ray.point_at_t(t);
+        let planar = position - self.corner;
+        let w = cross / cross.norm_squared();
+        let alpha = w.dot(&planar.cross(&self.v));
+        let beta = w.dot(&self.u.cross(&planar));
+        if !(0.0..=1.0).contains(&alpha) || !(0.0..=1.0).contains(&beta) {
+            return None;
+        }
+
+        // 光线锥的宽度按四边形的特征边长换算为纹理坐标的宽度
+        let width = ray.cone().width_at(t * d.magnitude());
+
+        Some(HitRecord {
+            distance: t,
+            position,
+            normal: cross.normalize(),
+            uv: (alpha, beta),
+            footprint: width / cross.norm().sqrt(),
+            material: self.material,
+            object: 0,
+        })
+    }
+}
+
+impl Bounded for Quad {
+    /// 各轴至少有 `MIN_THICKNESS` 的厚度
+    fn bounding_box(&self) -> AaBb {
+        let far = self.corner + self.u + self.v;
+        let pad = Vector3::repeat(MIN_THICKNESS / 2.0);
+        let corners = [self.corner + self.u, self.corner + self.v, far];
+
+        AaBb {
+            min: corners.iter().fold(self.corner, |m, c| m.inf(c)) - pad,
+            max: corners.iter().fold(self.corner, |m, c| m.sup(c)) + pad,
+        }
+    }
+
+    /// 距离总为非负
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let position = self
+            .triangles()
+            .map(|vertices| triangle::closest_point(&vertices, point))
+            .into_iter()
+            .min_by(|a, b| {
+                (point - a)
+                    .norm_squared()
+                    .total_cmp(&(point - b).norm_squared())
+            })
+            .unwrap();
+
+        ClosestPoint {
+            object: 0,
+            position,
+            distance: (point - position).magnitude(),
+        }
+    }
+}
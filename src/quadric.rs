@@ -0,0 +1,203 @@
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+
+use nalgebra::{Matrix4, Point3, Similarity3, Vector3, Vector4};
+
+/// 求最近点时牛顿迭代的次数
+const PROJECTION_STEPS: usize = 8;
+
+/// 二次曲面: 齐次坐标 `x` 满足 `xᵀ Q x = 0` 的点, 截取在包围盒内的部分
+///
+/// 椭球面, 抛物面, 双曲面等都由对称矩阵 `Q` 表示, 共用同一套解析求交;
+/// `xᵀ Q x < 0` 的一侧为内部, 法线为梯度方向
+#[derive(Clone)]
+pub struct Quadric {
+    /// 对称的 4x4 矩阵
+    q: Matrix4<f32>,
+
+    /// 截取范围, 无界的曲面 (如抛物面) 只保留其中的部分
+    bounds: AaBb,
+
+    /// 材质
+    material: Material,
+}
+
+impl Quadric {
+    /// 由矩阵 `q` (取其对称部分) 与截取范围构建二次曲面
+    pub fn new(q: Matrix4<f32>, bounds: AaBb, material: Material) -> Self {
+        Self {
+            q: (q + q.transpose()) / 2.0,
+            bounds,
+            material,
+        }
+    }
+
+    /// 以 `center` 为中心, 沿坐标轴的半轴长为 `radii` 的椭球面
+    pub fn ellipsoid(center: Vector3<f32>, radii: Vector3<f32>, material: Material) -> Self {
+        let q = Matrix4::from_diagonal(&Vector4::new(
+            radii.x.powi(-2),
+            radii.y.powi(-2),
+            radii.z.powi(-2),
+            -1.0,
+        ));
+        let bounds = AaBb {
+            min: -radii,
+            max: radii,
+        };
+
+        Self::new(q, bounds, material).translated(center)
+    }
+
+    /// 顶点在 `apex`, 沿 +y 开口的旋转抛物面, 截取到高度 `height`, 此处半径为 `radius`
+    pub fn paraboloid(apex: Vector3<f32>, radius: f32, height: f32, material: Material) -> Self {
+        // (x² + z²) / r² - y / h = 0
+        let mut q =
+            Matrix4::from_diagonal(&Vector4::new(radius.powi(-2), 0.0, radius.powi(-2), 0.0));
+        q[(1, 3)] = -0.5 / height;
+        q[(3, 1)] = -0.5 / height;
+        let bounds = AaBb {
+            min: Vector3::new(-radius, 0.0, -radius),
+            max: Vector3::new(radius, height, radius),
+        };
+
+        Self::new(q, bounds, material).translated(apex)
+    }
+
+    /// 以 `center` 为中心, 沿 y 轴的单叶旋转双曲面, 腰部半径为 `radius`, 截取总高度 `height`
+    pub fn hyperboloid(center: Vector3<f32>, radius: f32, height: f32, material: Material) -> Self {
+        // (x² + z²) / r² - y² / r² - 1 = 0
+        let k = radius.powi(-2);
+        let q = Matrix4::from_diagonal(&Vector4::new(k, -k, k, -1.0));
+        let half = height / 2.0;
+        let rim = (radius * radius + half * half).sqrt();
+        let bounds = AaBb {
+            min: Vector3::new(-rim, -half, -rim),
+            max: Vector3::new(rim, half, rim),
+        };
+
+        Self::new(q, bounds, material).translated(center)
+    }
+
+    /// 平移后的二次曲面
+    fn translated(self, offset: Vector3<f32>) -> Self {
+        self.transformed(&Similarity3::new(offset, Vector3::zeros(), 1.0))
+    }
+
+    /// 经相似变换后的二次曲面: `Q' = M⁻ᵀ Q M⁻¹`, 截取范围取变换后包围盒的包围盒
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        let inverse = transform.inverse().to_homogeneous();
+        let corners = self
+            .bounds
+            .corners()
+            .map(|corner| transform.transform_point(&Point3::from(corner)).coords);
+        let bounds = AaBb {
+            min: corners.iter().fold(corners[0], |a, c| a.inf(c)),
+            max: corners.iter().fold(corners[0], |a, c| a.sup(c)),
+        };
+
+        Self::new(
+            inverse.transpose() * self.q * inverse,
+            bounds,
+            self.material,
+        )
+    }
+
+    /// 隐函数 `xᵀ Q x` 在 `point` 处的值
+    fn value(&self, point: &Vector3<f32>) -> f32 {
+        let x = point.push(1.0);
+
+        x.dot(&(self.q * x))
+    }
+
+    /// 隐函数在 `point` 处的梯度
+    fn gradient(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        2.0 * (self.q * point.push(1.0)).xyz()
+    }
+
+    /// 点是否在截取范围内 (容许舍入误差)
+    fn contains(&self, point: &Vector3<f32>) -> bool {
+        self.bounds.distance_squared(point) <= 1e-8
+    }
+}
+
+impl Hittable for Quadric {
+    /// 光线与二次曲面相交: 代入光线方程得到关于 t 的二次方程
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        if !self.bounds.hit(ray) {
+            return None;
+        }
+
+        let o = ray.origin().push(1.0);
+        let d = ray.direction().push(0.0);
+        let (qo, qd) = (self.q * o, self.q * d);
+        let a = d.dot(&qd);
+        let b = d.dot(&qo);
+        let c = o.dot(&qo);
+
+        // a 接近 0 时 (如光线平行于抛物面的轴) 方程退化为一次
+        let roots = if a.abs() < 1e-12 {
+            if b == 0.0 {
+                return None;
+            }
+            [-c / (2.0 * b), f32::NAN]
+        } else {
+            let disc = b * b - a * c;
+            if disc < 0.0 {
+                return None;
+            }
+            let sqrt_disc = disc.sqrt();
+            let (t0, t1) = ((-b - sqrt_disc) / a, (-b + sqrt_disc) / a);
+            [t0.min(t1), t0.max(t1)]
+        };
+
+        roots
+            .into_iter()
+            .filter(|&t| t > t_min && t < t_max)
+            .map(|t| (t, ray.point_at_t(t)))
+            .find(|(_, p)| self.contains(p))
+            .map(|(t, p)| {
+                let extent = self.bounds.max - self.bounds.min;
+                let uv = (p - self.bounds.min).component_div(&extent);
+
+                HitRecord {
+                    distance: t,
+                    position: p,
+                    normal: self.gradient(&p).normalize(),
+                    uv: (uv.x, uv.y),
+                    footprint: 0.0,
+                    material: self.material,
+                    object: 0,
+                }
+            })
+    }
+}
+
+impl Bounded for Quadric {
+    fn bounding_box(&self) -> AaBb {
+        self.bounds.clone()
+    }
+
+    /// 以牛顿迭代沿梯度投影到曲面上 (近似的最近点), 位于内部时距离为负
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let mut position = *point;
+        for _ in 0..PROJECTION_STEPS {
+            let gradient = self.gradient(&position);
+            let norm_squared = gradient.norm_squared();
+            if norm_squared <= f32::EPSILON {
+                break;
+            }
+            position -= self.value(&position) / norm_squared * gradient;
+        }
+        let position = position.zip_zip_map(&self.bounds.min, &self.bounds.max, |p, lo, hi| {
+            p.clamp(lo, hi)
+        });
+
+        ClosestPoint {
+            object: 0,
+            position,
+            distance: (point - position).magnitude().copysign(self.value(point)),
+        }
+    }
+}
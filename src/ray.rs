@@ -1,5 +1,80 @@
 use nalgebra::Vector3;
 
+/// 光线的类型, 用于按实体的可见性设置筛选求交
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RayKind {
+    /// 从相机发出的主光线
+    Camera,
+
+    /// 判断遮挡的阴影光线
+    Shadow,
+
+    /// 散射后的间接光线
+    Indirect,
+}
+
+/// 光线锥: 以宽度与扩张角近似光线覆盖的范围 (像素的足迹), 用于选择纹理的过滤宽度
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RayCone {
+    /// 起点处的宽度
+    pub width: f32,
+
+    /// 每单位距离宽度的增加量 (扩张角)
+    pub spread: f32,
+}
+
+impl RayCone {
+    /// 距起点 `distance` 处的宽度
+    pub fn width_at(self, distance: f32) -> f32 {
+        self.width + self.spread * distance
+    }
+
+    /// 传播 `distance` 后的光线锥, 作为散射光线的起点 (忽略曲面与粗糙度带来的扩张)
+    pub fn propagate(self, distance: f32) -> Self {
+        Self {
+            width: self.width_at(distance),
+            spread: self.spread,
+        }
+    }
+}
+
+/// 自相交容差相对场景主体包围盒对角线的比例
+const SCENE_EPSILON: f32 = 1e-5;
+
+/// 交点坐标的舍入误差相对其量级 (起点坐标的最大分量与光线走过的距离) 的估计, 约为 32 个 ulp
+const POSITION_ERROR: f32 = 1.0 / 262_144.0;
+
+/// 光线求交的最小距离 (自相交容差): 由场景尺度给出基础容差, 再按光线起点的坐标量级与
+/// 上一段光线的长度放大, 使毫米级与千米级的场景都不出现自相交的暗斑或接缝
+#[derive(Clone, Copy, Debug)]
+pub struct RayEpsilon {
+    /// 基础容差 (距离)
+    base: f32,
+}
+
+impl RayEpsilon {
+    /// 与场景尺度无关的容差, 适合尺度约为 1 到 100 的场景
+    pub const DEFAULT: Self = Self { base: 1e-3 };
+
+    /// 按场景主体包围盒的对角线长度确定的容差
+    pub fn for_extent(diagonal: f32) -> Self {
+        if diagonal.is_finite() && diagonal > 0.0 {
+            Self {
+                base: diagonal * SCENE_EPSILON,
+            }
+        } else {
+            Self::DEFAULT
+        }
+    }
+
+    /// 光线 `ray` 求交的最小 t, `travelled` 为到达其起点的上一段光线的长度 (主光线为 0)
+    pub fn t_min(self, ray: &Ray, travelled: f32) -> f32 {
+        let error = POSITION_ERROR * (ray.origin.amax() + travelled);
+
+        (self.base + error) / ray.direction.magnitude()
+    }
+}
+
 /// 光线
 pub struct Ray {
     /// 起点
@@ -7,11 +82,94 @@ pub struct Ray {
 
     /// 方向
     direction: Vector3<f32>,
+
+    /// 类型
+    kind: RayKind,
+
+    /// 光线锥, 宽度为零时纹理取最近邻
+    cone: RayCone,
+
+    /// 方向各分量的倒数, 供包围盒的 slab 测试使用
+    inv_direction: Vector3<f32>,
+
+    /// 方向各分量的符号 (为负时为 1), 用于直接选取包围盒各轴上先进入的一侧
+    sign: [usize; 3],
+
+    /// 光线的时刻 (快门开启期间的运动模糊), 为 `None` 时场景静止
+    time: Option<f32>,
+
+    /// 光线携带的波长 (nm, 光谱渲染), 为 `None` 时携带 RGB 三个通道
+    wavelength: Option<f32>,
 }
 
 impl Ray {
+    /// 构建间接光线
     pub const fn from(origin: Vector3<f32>, direction: Vector3<f32>) -> Self {
-        Self { origin, direction }
+        let [x, y, z] = direction.data.0[0];
+
+        Self {
+            origin,
+            direction,
+            kind: RayKind::Indirect,
+            cone: RayCone {
+                width: 0.0,
+                spread: 0.0,
+            },
+            inv_direction: Vector3::new(1.0 / x, 1.0 / y, 1.0 / z),
+            sign: [
+                x.is_sign_negative() as usize,
+                y.is_sign_negative() as usize,
+                z.is_sign_negative() as usize,
+            ],
+            time: None,
+            wavelength: None,
+        }
+    }
+
+    /// 改变光线的类型
+    pub const fn with_kind(mut self, kind: RayKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub const fn kind(&self) -> RayKind {
+        self.kind
+    }
+
+    /// 改变光线锥
+    pub const fn with_cone(mut self, cone: RayCone) -> Self {
+        self.cone = cone;
+        self
+    }
+
+    pub const fn cone(&self) -> RayCone {
+        self.cone
+    }
+
+    /// 改变光线的时刻
+    pub const fn with_time(mut self, time: Option<f32>) -> Self {
+        self.time = time;
+        self
+    }
+
+    pub const fn time(&self) -> Option<f32> {
+        self.time
+    }
+
+    /// 改变光线携带的波长
+    pub const fn with_wavelength(mut self, wavelength: Option<f32>) -> Self {
+        self.wavelength = wavelength;
+        self
+    }
+
+    pub const fn wavelength(&self) -> Option<f32> {
+        self.wavelength
+    }
+
+    /// 沿用 `parent` 的时刻与波长, 用于由 `parent` 散射或变换得到的光线
+    pub const fn continuing(self, parent: &Self) -> Self {
+        self.with_time(parent.time)
+            .with_wavelength(parent.wavelength)
     }
 
     pub const fn origin(&self) -> Vector3<f32> {
@@ -22,6 +180,16 @@ impl Ray {
         self.direction
     }
 
+    /// 方向各分量的倒数 (分量为零时为无穷大)
+    pub const fn inv_direction(&self) -> Vector3<f32> {
+        self.inv_direction
+    }
+
+    /// 方向各分量的符号, 为负时为 1
+    pub const fn sign(&self) -> [usize; 3] {
+        self.sign
+    }
+
     /// 光线上 t 处的点
     pub fn point_at_t(&self, t: f32) -> Vector3<f32> {
         self.origin + t * self.direction
@@ -0,0 +1,780 @@
+use std::f32::consts::FRAC_1_PI;
+#[cfg(not(feature = "benchmark"))]
+use std::sync::Arc;
+use std::sync::Mutex;
+#[cfg(not(feature = "benchmark"))]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread;
+use std::time::Instant;
+
+use crate::accelerator::Accelerator;
+use crate::background::Environment;
+use crate::camera::Camera;
+use crate::exposure::{Exposure, Histogram};
+use crate::filter::Filter;
+use crate::hittable::{HitRecord, Hittable};
+use crate::light::Lights;
+use crate::material::{Lobe, Material, Scatter};
+use crate::output::{BitDepth, Image, PartialWrite, write_atomic};
+use crate::precision::{Precision, encode_deep};
+use crate::ray::{Ray, RayEpsilon, RayKind};
+use crate::rng::{self, get_rng};
+use crate::sampler::{SamplerKind, build_sampler};
+#[cfg(not(feature = "benchmark"))]
+use crate::stats::Throughput;
+use crate::stats::{self, Counter};
+
+use nalgebra::Vector3;
+use rayon::prelude::*;
+
+/// 渲染设置
+pub struct RenderSettings {
+    /// 最大追踪深度, 理想镜面的反弹不计入
+    pub max_depth: usize,
+
+    /// 路径通量 (各通道的最大值) 低于该值时提前终止路径, 为 0 时不终止
+    pub min_throughput: f32,
+
+    /// 包括理想镜面反弹在内的最大追踪深度, 不小于 `max_depth`, 用于延长玻璃套玻璃等镜面链
+    pub specular_depth: usize,
+
+    /// 各类波瓣各自的最大反弹次数
+    pub bounce_limits: BounceLimits,
+
+    /// 直接光照 (至多一次散射后到达相机) 每次贡献各通道的上限, 为 `None` 时不限制
+    pub clamp_direct: Option<f32>,
+
+    /// 间接光照 (两次及以上散射后到达相机) 每次贡献各通道的上限, 用于抑制萤火虫噪点
+    pub clamp_indirect: Option<f32>,
+
+    /// 曝光
+    pub exposure: Exposure,
+
+    /// 白模渲染: 散射光线的材质替换为中性灰的漫反射, 自发光材质保持不变
+    pub clay: bool,
+
+    /// 环境 (背景及其朝向与强度)
+    pub environment: Environment,
+
+    /// 点状灯光与网格光源, 由漫反射表面处的直接光照采样
+    pub lights: Lights,
+
+    /// 采样器类型
+    pub sampler: SamplerKind,
+
+    /// 像素重建滤波器
+    pub filter: Filter,
+
+    /// 是否对像素采样施加 Cranley–Patterson 旋转
+    pub cp_rotation: bool,
+
+    /// 帧缓冲的数值精度
+    pub precision: Precision,
+
+    /// 输出图像每个通道的位数, 16 位时另外保留 16 位的像素
+    pub bit_depth: BitDepth,
+
+    /// 16 位的像素施加逐像素的三角分布抖动
+    pub dither: bool,
+
+    /// 透明背景: 主光线未击中时不透明度为 0, 阴影捕捉只保留阴影的不透明度
+    pub transparent: bool,
+
+    /// 渲染过程中定期写入部分结果
+    pub partial_write: Option<PartialWrite>,
+
+    /// 光线求交的自相交容差
+    pub epsilon: RayEpsilon,
+}
+
+impl RenderSettings {
+    /// 钳制经过 `scatters` 次散射到达相机的贡献: 超过上限时等比缩小, 保持色相
+    fn clamp(&self, contribution: Vector3<f32>, scatters: usize) -> Vector3<f32> {
+        let limit = if scatters <= 1 {
+            self.clamp_direct
+        } else {
+            self.clamp_indirect
+        };
+
+        match limit {
+            Some(limit) if contribution.max() > limit => {
+                contribution * (limit / contribution.max())
+            }
+            _ => contribution,
+        }
+    }
+
+    /// 决定像素值的设置 (采样器, 滤波器, 各类深度限制, 钳制, 白模, 曝光, 环境与透明背景) 的 64 位 FNV-1a 哈希,
+    /// 断点以此拒绝以不同的设置继续渲染; 环境以其调试输出 (浮点数可精确往返) 参与哈希
+    pub fn fingerprint(&self) -> u64 {
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |bytes: &[u8]| {
+            for &byte in bytes {
+                hash = (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+
+        let (exposure_kind, compensation) = match self.exposure {
+            Exposure::Manual(ev) => (0, ev),
+            Exposure::Auto(ev) => (1, ev),
+        };
+        feed(&[
+            self.sampler as u8,
+            self.filter.kind() as u8,
+            self.clay as u8,
+            exposure_kind,
+            self.cp_rotation as u8,
+            self.transparent as u8,
+        ]);
+        feed(&self.filter.radius().to_le_bytes());
+        feed(&compensation.to_le_bytes());
+        feed(&self.min_throughput.to_le_bytes());
+        for depth in [
+            self.max_depth,
+            self.specular_depth,
+            self.bounce_limits.diffuse,
+            self.bounce_limits.specular,
+            self.bounce_limits.transmission,
+        ] {
+            feed(&(depth as u64).to_le_bytes());
+        }
+        for clamp in [self.clamp_direct, self.clamp_indirect] {
+            // 不钳制时以 NaN 区别于任何上限
+            feed(&clamp.unwrap_or(f32::NAN).to_le_bytes());
+        }
+        feed(format!("{:?}", self.environment).as_bytes());
+
+        hash
+    }
+}
+
+/// 路径上漫反射, 镜面反射与透射各自的最大反弹次数, 在总深度的限制之外另行生效
+#[derive(Clone, Copy)]
+pub struct BounceLimits {
+    pub diffuse: usize,
+    pub specular: usize,
+    pub transmission: usize,
+}
+
+impl BounceLimits {
+    /// 不限制各类波瓣的反弹次数
+    pub const UNLIMITED: Self = Self {
+        diffuse: usize::MAX,
+        specular: usize::MAX,
+        transmission: usize::MAX,
+    };
+
+    /// 波瓣 `lobe` 的最大反弹次数
+    const fn limit(&self, lobe: Lobe) -> usize {
+        match lobe {
+            Lobe::Diffuse => self.diffuse,
+            Lobe::Specular => self.specular,
+            Lobe::Transmission => self.transmission,
+        }
+    }
+}
+
+/// 阴影光线相对于到灯光的距离缩短的比例
+const SHADOW_EPSILON: f32 = 1e-3;
+
+/// 收到中断信号后置位, 渲染不再开始新的行
+pub static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// 渲染中的图像
+pub struct Framebuffer {
+    /// RGB 像素, 未完成的行为黑色
+    pub pixels: Vec<u8>,
+
+    /// 不透明度 (非预乘), 不透明的图像为 `None`
+    pub alpha: Option<Vec<u8>>,
+
+    /// 16 位的 RGB 像素, 仅在输出 16 位图像时保留
+    pub deep: Option<Vec<u16>>,
+
+    /// 每一行 (首行为图像顶部) 是否已完成
+    pub rows_done: Vec<bool>,
+
+    /// 线性 (非预乘) 的 RGB 颜色, 仅在自动曝光时保留
+    pub radiance: Option<Vec<f32>>,
+
+    /// 每个像素的渲染耗时 (秒), 只有路径追踪记录, 未渲染的像素为 0
+    pub times: Option<Vec<f32>>,
+}
+
+impl Framebuffer {
+    pub fn new(nx: usize, ny: usize, transparent: bool) -> Self {
+        Self {
+            pixels: vec![0; 3 * nx * ny],
+            alpha: transparent.then(|| vec![0; nx * ny]),
+            deep: None,
+            rows_done: vec![false; ny],
+            radiance: None,
+            times: None,
+        }
+    }
+
+    /// 由完整的图像构建
+    pub fn from_pixels(pixels: Vec<u8>, alpha: Option<Vec<u8>>, ny: usize) -> Self {
+        Self {
+            pixels,
+            alpha,
+            deep: None,
+            rows_done: vec![true; ny],
+            radiance: None,
+            times: None,
+        }
+    }
+
+    /// 待写入的图像
+    pub fn image(&self, nx: usize, ny: usize) -> Image<'_> {
+        Image {
+            rgb: &self.pixels,
+            alpha: self.alpha.as_deref(),
+            deep: self.deep.as_deref(),
+            nx,
+            ny,
+        }
+    }
+
+    /// 是否所有行都已完成
+    pub fn is_complete(&self) -> bool {
+        self.rows_done.iter().all(|&done| done)
+    }
+
+    /// 按已完成各行的亮度直方图自动曝光, 重新编码这些行, 未保留线性颜色时不做处理
+    fn auto_expose(&mut self, nx: usize, compensation: f32, settings: &RenderSettings) {
+        let Some(radiance) = &self.radiance else {
+            return;
+        };
+
+        let ny = self.rows_done.len();
+        let done_pixels = || {
+            (0..ny)
+                .filter(|&row| self.rows_done[row])
+                .flat_map(move |row| (0..nx).map(move |x| (row * nx + x, (x, ny - 1 - row))))
+        };
+
+        let mut histogram = Histogram::default();
+        for (offset, _) in done_pixels() {
+            histogram.add(&Vector3::from_column_slice(
+                &radiance[3 * offset..3 * offset + 3],
+            ));
+        }
+        let scale = histogram.exposure(compensation);
+
+        for (offset, (x, y)) in done_pixels().collect::<Vec<_>>() {
+            let color = scale * Vector3::from_column_slice(&radiance[3 * offset..3 * offset + 3]);
+            let rgb = [0, 1, 2].map(|c| settings.precision.encode(color[c], (x, y), c));
+            self.pixels[3 * offset..3 * offset + 3].copy_from_slice(&rgb);
+            if let Some(deep) = &mut self.deep {
+                let rgb = [0, 1, 2].map(|c| encode_deep(color[c], (x, y), c, settings.dither));
+                deep[3 * offset..3 * offset + 3].copy_from_slice(&rgb);
+            }
+        }
+    }
+
+    /// 按设置保留 16 位的像素, 已完成的行 (如从断点继续时) 只有 8 位的数据, 由其扩展
+    fn keep_deep(&mut self, settings: &RenderSettings) {
+        if settings.bit_depth == BitDepth::Sixteen && self.deep.is_none() {
+            self.deep = Some(self.pixels.iter().map(|&v| 257 * v as u16).collect());
+        }
+    }
+}
+
+/// 亮度
+pub fn luminance(c: &Vector3<f32>) -> f32 {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
+/// 光线颜色
+pub fn ray_color(
+    ray: Ray,
+    scene: &(impl Hittable + ?Sized),
+    settings: &RenderSettings,
+) -> Vector3<f32> {
+    trace(ray, scene, settings, settings.max_depth)
+}
+
+/// 主光线的颜色 (按不透明度预乘) 与不透明度
+///
+/// 透明背景下未击中的光线完全透明, 阴影捕捉的颜色为黑色, 不透明度为被遮挡的比例
+pub fn camera_sample(
+    ray: Ray,
+    scene: &(impl Hittable + ?Sized),
+    settings: &RenderSettings,
+) -> (Vector3<f32>, f32) {
+    stats::count(Counter::Samples, 1);
+    if !settings.transparent {
+        return (ray_color(ray, scene, settings), 1.0);
+    }
+
+    stats::count(Counter::Rays, 1);
+    match scene.hit(&ray, settings.epsilon.t_min(&ray, 0.0), f32::MAX) {
+        None => (Vector3::zeros(), 0.0),
+        Some(hit) if matches!(hit.material, Material::ShadowCatcher) => {
+            let alpha = hit
+                .material
+                .scatter(&ray, &hit)
+                .map_or(0.0, |(scattered, _, _)| {
+                    1.0 - shadow_ratio(
+                        scattered.continuing(&ray),
+                        scene,
+                        settings,
+                        settings.max_depth - 1,
+                    )
+                });
+
+            (Vector3::zeros(), alpha)
+        }
+        Some(_) => (ray_color(ray, scene, settings), 1.0),
+    }
+}
+
+/// 阴影捕捉处接收到的与无遮挡时的亮度之比, 不超过 1, 第一段光线按阴影光线判断遮挡
+fn shadow_ratio(
+    scattered: Ray,
+    scene: &(impl Hittable + ?Sized),
+    settings: &RenderSettings,
+    depth: usize,
+) -> f32 {
+    let unoccluded = luminance(&settings.clamp(settings.environment.color(&scattered), 0));
+    let received = luminance(&trace(
+        scattered.with_kind(RayKind::Shadow),
+        scene,
+        settings,
+        depth,
+    ));
+
+    if unoccluded > 0.0 {
+        (received / unoccluded).min(1.0)
+    } else {
+        1.0
+    }
+}
+
+/// 将预乘的颜色与不透明度转换为 8 位的非预乘颜色与不透明度
+pub fn resolve_pixel(
+    premultiplied: &Vector3<f32>,
+    alpha: f32,
+    precision: Precision,
+    (x, y): (usize, usize),
+) -> ([u8; 3], u8) {
+    let color = if alpha > 0.0 {
+        premultiplied / alpha
+    } else {
+        Vector3::zeros()
+    };
+
+    (
+        [0, 1, 2].map(|c| precision.encode(color[c], (x, y), c)),
+        (255.99 * alpha.clamp(0.0, 1.0)) as u8,
+    )
+}
+
+/// 将预乘的颜色转换为 16 位的非预乘颜色
+pub fn resolve_deep(
+    premultiplied: &Vector3<f32>,
+    alpha: f32,
+    dither: bool,
+    (x, y): (usize, usize),
+) -> [u16; 3] {
+    let color = if alpha > 0.0 {
+        premultiplied / alpha
+    } else {
+        Vector3::zeros()
+    };
+
+    [0, 1, 2].map(|c| encode_deep(color[c], (x, y), c, dither))
+}
+
+/// 在 `depth` 次非镜面反弹以内追踪光线
+///
+/// 理想镜面的反弹不计入 `depth`, 只受 `settings.specular_depth` 限制; 某类波瓣的反弹次数超过
+/// `settings.bounce_limits` 或通量过低的路径提前终止
+fn trace(
+    mut ray: Ray,
+    scene: &(impl Hittable + ?Sized),
+    settings: &RenderSettings,
+    depth: usize,
+) -> Vector3<f32> {
+    stats::count(Counter::Paths, 1);
+    let mut color = Vector3::new(1.0, 1.0, 1.0);
+
+    // 路径上自发光表面贡献的辐射
+    let mut emitted = Vector3::zeros();
+
+    // 已计入 `depth` 的反弹次数
+    let mut bounces = 0;
+
+    // 漫反射, 镜面反射与透射各自的反弹次数
+    let mut lobe_bounces = [0; 3];
+
+    // 上一段光线的长度, 用于估计当前光线起点的舍入误差
+    let mut travelled = 0.0;
+
+    // 当前光线是否从做过直接光照的漫反射表面散射而来
+    let mut sampled_lights = false;
+
+    // 在设定的深度以内
+    for scatters in 0..settings.specular_depth.max(depth) {
+        if bounces >= depth {
+            break;
+        }
+
+        stats::count(Counter::Rays, 1);
+        let t_min = settings.epsilon.t_min(&ray, travelled);
+        if let Some(hit) = scene.hit(&ray, t_min, f32::MAX) {
+            // 击中: 更新颜色和光线
+            let material = if settings.clay {
+                hit.material.clay()
+            } else {
+                hit.material
+            };
+
+            // 阴影捕捉: 背后的环境乘以遮挡比例 (接收到的与无遮挡时的亮度之比, 只会变暗)
+            if let Material::ShadowCatcher = material {
+                let Some((scattered, _, _)) = material.scatter(&ray, &hit) else {
+                    break;
+                };
+                let ratio = shadow_ratio(
+                    scattered.continuing(&ray),
+                    scene,
+                    settings,
+                    depth - bounces - 1,
+                );
+                let behind = settings.environment.color(&ray);
+
+                return emitted + ratio * settings.clamp(color.component_mul(&behind), scatters);
+            }
+
+            // 上一次反弹已由直接光照计入网格光源的辐射
+            if !(sampled_lights && settings.lights.is_emitter(hit.object)) {
+                let radiance = material.emitted(hit.uv, hit.footprint);
+                emitted += settings.clamp(color.component_mul(&radiance), scatters);
+            }
+            sampled_lights = false;
+            if let Some(albedo) = material.diffuse_albedo(hit.uv, hit.footprint) {
+                let direct = direct_light(&ray, &hit, &albedo, scene, settings);
+                emitted += settings.clamp(color.component_mul(&direct), scatters + 1);
+                sampled_lights = true;
+            }
+            if let Some((scattered, attenuation, lobe)) = material.scatter(&ray, &hit) {
+                lobe_bounces[lobe as usize] += 1;
+                if lobe_bounces[lobe as usize] > settings.bounce_limits.limit(lobe) {
+                    break;
+                }
+
+                color = color.zip_map(&attenuation, |l, r| l * r);
+                if color.max() < settings.min_throughput {
+                    break;
+                }
+                if !material.is_specular() {
+                    bounces += 1;
+                }
+
+                // 散射光线从交点处的光线锥继续扩张
+                let distance = hit.distance * ray.direction().magnitude();
+                ray = scattered
+                    .with_cone(ray.cone().propagate(distance))
+                    .continuing(&ray);
+                travelled = distance;
+            } else {
+                break;
+            }
+        } else {
+            // 未击中: 打到天空, 设为背景颜色 (上一次反弹已由直接光照计入太阳)
+            let background = if sampled_lights {
+                settings.environment.color_without_sun(&ray)
+            } else {
+                settings.environment.color(&ray)
+            };
+
+            return emitted + settings.clamp(color.component_mul(&background), scatters);
+        }
+    }
+
+    emitted
+}
+
+/// 漫反射表面在交点 `hit` 处反射的灯光的辐射, 每次选取一个灯光并以阴影光线检查遮挡
+///
+/// 反射一侧与漫反射散射相同, 为表面法线所指的一侧
+fn direct_light(
+    ray: &Ray,
+    hit: &HitRecord,
+    albedo: &Vector3<f32>,
+    scene: &(impl Hittable + ?Sized),
+    settings: &RenderSettings,
+) -> Vector3<f32> {
+    if settings.lights.is_empty() {
+        return Vector3::zeros();
+    }
+    let Some(sample) = settings.lights.sample(&hit.position, &mut rng::local()) else {
+        return Vector3::zeros();
+    };
+    let cosine = hit.normal.normalize().dot(&sample.direction);
+    if cosine <= 0.0 {
+        return Vector3::zeros();
+    }
+
+    stats::count(Counter::Rays, 1);
+    let shadow = Ray::from(hit.position, sample.direction)
+        .with_kind(RayKind::Shadow)
+        .continuing(ray);
+    let t_min = settings
+        .epsilon
+        .t_min(&shadow, hit.distance * ray.direction().magnitude());
+    // 略微缩短阴影光线, 不与网格光源自身在采样点处相交
+    let t_max = sample.distance * (1.0 - SHADOW_EPSILON);
+    if scene.hit(&shadow, t_min, t_max).is_some() {
+        return Vector3::zeros();
+    }
+
+    (cosine * FRAC_1_PI) * albedo.component_mul(&sample.incident)
+}
+
+/// 路径追踪渲染, 跳过 `framebuffer` 中已完成的行, 被中断时返回部分结果
+pub fn render(
+    scene: &dyn Accelerator,
+    camera: &Camera,
+    nx: usize,
+    ny: usize,
+    ns: usize,
+    settings: &RenderSettings,
+    mut framebuffer: Framebuffer,
+) -> Framebuffer {
+    let spp = build_sampler(settings.sampler, ns, get_rng(), false).samples_per_pixel();
+
+    // 自动曝光需要保留线性颜色, 渲染过程中先按曝光补偿预览
+    if let Exposure::Auto(_) = settings.exposure {
+        framebuffer
+            .radiance
+            .get_or_insert_with(|| vec![0.0; 3 * nx * ny]);
+    }
+    let scale = settings.exposure.scale();
+    framebuffer.times.get_or_insert_with(|| vec![0.0; nx * ny]);
+    framebuffer.keep_deep(settings);
+
+    // 跟踪渲染进度
+    #[cfg(not(feature = "benchmark"))]
+    let finished_count = Arc::new(AtomicUsize::new(
+        framebuffer.rows_done.iter().filter(|&&done| done).count(),
+    ));
+    #[cfg(not(feature = "benchmark"))]
+    let timer = Instant::now();
+    #[cfg(not(feature = "benchmark"))]
+    let throughput = Throughput::default();
+
+    // 已完成的行写入共享的帧缓冲
+    let framebuffer = Mutex::new(framebuffer);
+
+    thread::scope(|s| {
+        // 定期将帧缓冲写入文件, 渲染结束时关闭通道以停止
+        let (stop, stopped) = mpsc::channel::<()>();
+        if let Some(partial) = &settings.partial_write {
+            let framebuffer = &framebuffer;
+            s.spawn(move || {
+                while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(partial.interval) {
+                    let snapshot = {
+                        let framebuffer = framebuffer.lock().unwrap();
+                        Framebuffer::from_pixels(
+                            framebuffer.pixels.clone(),
+                            framebuffer.alpha.clone(),
+                            ny,
+                        )
+                    };
+                    let image = snapshot.image(nx, ny);
+                    if let Err(e) = write_atomic(&image, &partial.file_name, partial.format) {
+                        eprintln!("\rFailed to write partial image: {e}");
+                    }
+                }
+            });
+        }
+
+        // 并行渲染
+        (0..ny).into_par_iter().rev().for_each(|y| {
+            // 首行为图像顶部
+            let row_index = ny - 1 - y;
+            if INTERRUPTED.load(Ordering::SeqCst)
+                || framebuffer.lock().unwrap().rows_done[row_index]
+            {
+                return;
+            }
+
+            let mut sampler = build_sampler(settings.sampler, ns, get_rng(), settings.cp_rotation);
+
+            // 渲染
+            let mut row_radiance = Vec::with_capacity(3 * nx);
+            let mut row_times = Vec::with_capacity(nx);
+            let mut row_deep = Vec::with_capacity(nx);
+            let (row, row_alpha): (Vec<[u8; 3]>, Vec<u8>) = (0..nx)
+                .map(|x| {
+                    if let Some(rng) = rng::start_pixel((x, y), 0) {
+                        sampler = build_sampler(settings.sampler, ns, rng, settings.cp_rotation);
+                    }
+
+                    // 对每个像素进行多次采样
+                    let start = Instant::now();
+                    let (mut col, mut alpha) = (Vector3::zeros(), 0.0);
+                    for index in 0..spp {
+                        sampler.start_sample((x, y), index);
+                        let (ray, weight) =
+                            camera.generate_ray((x, y), sampler.as_mut(), &settings.filter, None);
+                        let (c, a) = camera_sample(ray, scene, settings);
+                        col += weight * c;
+                        alpha += weight * a;
+                    }
+
+                    // gamma 修正
+                    let (col, alpha) = (col / spp as f32, alpha / spp as f32);
+                    if alpha > 0.0 {
+                        row_radiance.extend((col / alpha).iter());
+                    } else {
+                        row_radiance.extend([0.0; 3]);
+                    }
+                    row_times.push(start.elapsed().as_secs_f32());
+                    if settings.bit_depth == BitDepth::Sixteen {
+                        row_deep.push(resolve_deep(&(scale * col), alpha, settings.dither, (x, y)));
+                    }
+                    resolve_pixel(&(scale * col), alpha, settings.precision, (x, y))
+                })
+                .unzip();
+
+            let offset = nx * row_index;
+            let mut framebuffer = framebuffer.lock().unwrap();
+            framebuffer.pixels[3 * offset..3 * (offset + nx)].copy_from_slice(row.as_flattened());
+            if let Some(alpha) = &mut framebuffer.alpha {
+                alpha[offset..offset + nx].copy_from_slice(&row_alpha);
+            }
+            if let Some(radiance) = &mut framebuffer.radiance {
+                radiance[3 * offset..3 * (offset + nx)].copy_from_slice(&row_radiance);
+            }
+            if let Some(deep) = &mut framebuffer.deep {
+                deep[3 * offset..3 * (offset + nx)].copy_from_slice(row_deep.as_flattened());
+            }
+            if let Some(times) = &mut framebuffer.times {
+                times[offset..offset + nx].copy_from_slice(&row_times);
+            }
+            framebuffer.rows_done[row_index] = true;
+            drop(framebuffer);
+
+            // 更新进度
+            #[cfg(not(feature = "benchmark"))]
+            {
+                let count = finished_count.fetch_add(1, Ordering::SeqCst) + 1;
+                let elapsed = timer.elapsed().as_millis() as usize;
+                let avg_speed = elapsed / count;
+                let remaining = ny - count;
+                eprint!(
+                    "\rRemaining: {:>4} | ETA: {:>4}s | {}",
+                    remaining,
+                    remaining * avg_speed / 1000,
+                    throughput.summary()
+                );
+            }
+        });
+
+        drop(stop);
+    });
+
+    #[cfg(not(feature = "benchmark"))]
+    {
+        eprintln!(
+            "\rRendered in {:.1}s{}",
+            timer.elapsed().as_secs_f32(),
+            " ".repeat(80)
+        );
+    }
+
+    let mut framebuffer = framebuffer.into_inner().unwrap();
+    if let Exposure::Auto(compensation) = settings.exposure {
+        framebuffer.auto_expose(nx, compensation, settings);
+    }
+
+    framebuffer
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::background::Background;
+    use crate::filter::FilterKind;
+
+    /// 修改设置中的一项
+    type Change = fn(&mut RenderSettings);
+
+    fn settings() -> RenderSettings {
+        RenderSettings {
+            max_depth: 8,
+            min_throughput: 0.0,
+            specular_depth: 8,
+            bounce_limits: BounceLimits::UNLIMITED,
+            clamp_direct: None,
+            clamp_indirect: None,
+            exposure: Exposure::Manual(0.0),
+            clay: false,
+            environment: Environment::new(Background::Sky),
+            lights: Lights::default(),
+            sampler: SamplerKind::Stratified,
+            filter: Filter::default(),
+            cp_rotation: false,
+            precision: Precision::F32,
+            bit_depth: BitDepth::Eight,
+            dither: false,
+            transparent: false,
+            partial_write: None,
+            epsilon: RayEpsilon::DEFAULT,
+        }
+    }
+
+    #[test]
+    fn fingerprint_covers_every_setting_that_changes_pixels() {
+        let base = settings().fingerprint();
+        assert_eq!(base, settings().fingerprint());
+
+        let changes: [(&str, Change); 18] = [
+            ("max_depth", |s| s.max_depth = 9),
+            ("min_throughput", |s| s.min_throughput = 0.01),
+            ("specular_depth", |s| s.specular_depth = 16),
+            ("bounce_limits.diffuse", |s| s.bounce_limits.diffuse = 2),
+            ("bounce_limits.specular", |s| s.bounce_limits.specular = 2),
+            ("bounce_limits.transmission", |s| {
+                s.bounce_limits.transmission = 2
+            }),
+            ("clamp_direct", |s| s.clamp_direct = Some(10.0)),
+            ("clamp_indirect", |s| s.clamp_indirect = Some(10.0)),
+            ("exposure", |s| s.exposure = Exposure::Manual(1.0)),
+            ("clay", |s| s.clay = true),
+            ("environment.background", |s| {
+                s.environment = Environment::new(Background::STUDIO)
+            }),
+            ("environment.rotation", |s| {
+                s.environment = s.environment.rotated(90.0, 0.0)
+            }),
+            ("environment.intensity", |s| {
+                s.environment = s.environment.scaled(2.0)
+            }),
+            ("sampler", |s| s.sampler = SamplerKind::Halton),
+            ("filter.kind", |s| {
+                s.filter = Filter::new(FilterKind::Gaussian, s.filter.radius())
+            }),
+            ("filter.radius", |s| {
+                s.filter = Filter::new(s.filter.kind(), 2.0 * s.filter.radius())
+            }),
+            ("cp_rotation", |s| s.cp_rotation = true),
+            ("transparent", |s| s.transparent = true),
+        ];
+        for (name, change) in changes {
+            let mut changed = settings();
+            change(&mut changed);
+            assert_ne!(
+                changed.fingerprint(),
+                base,
+                "{name} does not change the fingerprint"
+            );
+        }
+    }
+}
@@ -1,5 +1,69 @@
-use rand::SeedableRng;
+use std::cell::RefCell;
+use std::sync::atomic::{AtomicBool, Ordering};
+
 use rand::rngs::StdRng;
+use rand::{RngCore, SeedableRng};
+
+/// 确定性模式: 每个像素的随机数只由像素坐标与采样轮次决定, 与线程数及调度无关
+static DETERMINISTIC: AtomicBool = AtomicBool::new(false);
+
+thread_local! {
+    /// 路径追踪 (材质散射, 灯光采样等) 所用的线程局部 RNG
+    static LOCAL: RefCell<StdRng> = RefCell::new(StdRng::from_rng(&mut rand::rng()));
+}
+
+/// 启用确定性模式, 须在构建场景与渲染之前调用
+pub fn set_deterministic() {
+    DETERMINISTIC.store(true, Ordering::Relaxed);
+}
+
+pub fn is_deterministic() -> bool {
+    DETERMINISTIC.load(Ordering::Relaxed)
+}
+
+/// 线程局部 RNG 的句柄
+pub struct LocalRng;
+
+impl RngCore for LocalRng {
+    fn next_u32(&mut self) -> u32 {
+        LOCAL.with_borrow_mut(|rng| rng.next_u32())
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        LOCAL.with_borrow_mut(|rng| rng.next_u64())
+    }
+
+    fn fill_bytes(&mut self, dst: &mut [u8]) {
+        LOCAL.with_borrow_mut(|rng| rng.fill_bytes(dst));
+    }
+}
+
+/// 当前线程的 RNG, 确定性模式下由 `start_pixel` 按像素重置
+pub const fn local() -> LocalRng {
+    LocalRng
+}
+
+/// 开始像素 `pixel` 的第 `pass` 轮采样
+///
+/// 确定性模式下按像素与轮次重置线程局部的 RNG, 并返回该像素的采样器所用的 RNG
+/// (调用者以其重建采样器); 否则不做处理, 返回 `None`
+pub fn start_pixel((x, y): (usize, usize), pass: usize) -> Option<StdRng> {
+    if !is_deterministic() {
+        return None;
+    }
+
+    let seed = |salt: u32| hash(x as u32 ^ hash(y as u32 ^ hash(pass as u32 ^ salt)));
+    reseed((u64::from(seed(0)) << 32) | u64::from(seed(1)));
+
+    Some(StdRng::seed_from_u64(
+        (u64::from(seed(2)) << 32) | u64::from(seed(3)),
+    ))
+}
+
+/// 以 `seed` 重置当前线程的 RNG
+pub fn reseed(seed: u64) {
+    LOCAL.with_borrow_mut(|rng| *rng = StdRng::seed_from_u64(seed));
+}
 
 /// 获取 RNG, 当启用 benchmark / course 时由一个固定种子生成
 pub fn get_rng() -> StdRng {
@@ -11,3 +75,32 @@ pub fn get_rng() -> StdRng {
         StdRng::from_rng(&mut rand::rng())
     }
 }
+
+/// 场景构建所用的种子, 当启用 benchmark / course 或确定性模式时为固定值
+pub fn scene_seed() -> u64 {
+    if cfg!(feature = "benchmark") {
+        171
+    } else if cfg!(feature = "course") {
+        1337
+    } else if is_deterministic() {
+        0
+    } else {
+        rand::random()
+    }
+}
+
+/// 整数哈希
+pub const fn hash(mut x: u32) -> u32 {
+    x ^= x >> 16;
+    x = x.wrapping_mul(0x7feb_352d);
+    x ^= x >> 15;
+    x = x.wrapping_mul(0x846c_a68b);
+    x ^= x >> 16;
+
+    x
+}
+
+/// 整数哈希, 映射到 [0, 1)
+pub fn hash_to_unit(x: u32) -> f32 {
+    (hash(x) >> 8) as f32 / (1 << 24) as f32
+}
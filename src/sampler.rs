@@ -0,0 +1,252 @@
+use crate::rng::{hash, hash_to_unit};
+
+use clap::ValueEnum;
+use rand::Rng;
+use rand::rngs::StdRng;
+
+/// Halton 序列各维度的底数 (前 64 个素数), 超出的维度退回独立随机数
+const PRIMES: [u32; 64] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53, 59, 61, 67, 71, 73, 79, 83, 89, 97,
+    101, 103, 107, 109, 113, 127, 131, 137, 139, 149, 151, 157, 163, 167, 173, 179, 181, 191, 193,
+    197, 199, 211, 223, 227, 229, 233, 239, 241, 251, 257, 263, 269, 271, 277, 281, 283, 293, 307,
+    311,
+];
+
+/// 采样器类型
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum SamplerKind {
+    /// 分层抖动采样
+    Stratified,
+
+    /// Owen 置乱的 Halton 低差异序列
+    Halton,
+}
+
+/// 像素采样序列, 按维度依次取出 [0, 1) 内的采样值
+pub trait Sampler {
+    /// 每个像素的采样数
+    fn samples_per_pixel(&self) -> usize;
+
+    /// 开始像素 `pixel` 的第 `index` 个采样, 维度从 0 开始计数
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize);
+
+    /// 下一个维度的一维采样
+    fn next_1d(&mut self) -> f32;
+
+    /// 下两个维度的二维采样, 每个采样的第一个二维采样为像素内的偏移
+    fn next_2d(&mut self) -> (f32, f32) {
+        (self.next_1d(), self.next_1d())
+    }
+}
+
+/// 分层采样: 像素被划分为 `sqrt_ns * sqrt_ns` 个格子, 每个格子内随机抖动,
+/// 其余维度为独立的随机数
+pub struct StratifiedSampler {
+    /// 每个维度上的分层数
+    sqrt_ns: usize,
+
+    /// 当前采样所在的格子
+    stratum: (usize, usize),
+
+    /// 当前采样已取出的维度数
+    dimension: usize,
+
+    rng: StdRng,
+}
+
+impl StratifiedSampler {
+    pub fn new(ns: usize, rng: StdRng) -> Self {
+        Self {
+            sqrt_ns: ((ns as f32).sqrt() as usize).max(1),
+            stratum: (0, 0),
+            dimension: 0,
+            rng,
+        }
+    }
+}
+
+impl Sampler for StratifiedSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.sqrt_ns * self.sqrt_ns
+    }
+
+    fn start_sample(&mut self, _pixel: (usize, usize), index: usize) {
+        // 渐进式渲染中序号可能超出格子数, 此时从头再遍历一次格子
+        let index = index % self.samples_per_pixel();
+        self.stratum = (index % self.sqrt_ns, index / self.sqrt_ns);
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        self.dimension += 1;
+        self.rng.random()
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        if self.dimension == 0 {
+            // 像素内偏移: 在所在格子内抖动
+            self.dimension = 2;
+            let n = self.sqrt_ns as f32;
+            let u = (self.stratum.0 as f32 + self.rng.random::<f32>()) / n;
+            let v = (self.stratum.1 as f32 + self.rng.random::<f32>()) / n;
+            (u, v)
+        } else {
+            (self.next_1d(), self.next_1d())
+        }
+    }
+}
+
+/// 底数为 `base` 的 Owen 置乱根式反演: 每一位数字按其前缀决定的随机置换打乱
+fn owen_scrambled_radical_inverse(base: u32, mut a: u64, seed: u32) -> f32 {
+    let inv_base = 1.0 / base as f64;
+
+    // 保证 f32 精度所需的位数
+    let digits = (32.0 * std::f64::consts::LN_2 / (base as f64).ln()).ceil() as u32;
+
+    let mut reversed: u64 = 0;
+    let mut inv_base_m = 1.0;
+    for _ in 0..digits {
+        let digit = (a % base as u64) as u32;
+        a /= base as u64;
+
+        // 置换依赖于已经确定的更高位, 即 Owen 置乱树上的结点
+        let offset = hash(seed ^ hash(reversed as u32 ^ (reversed >> 32) as u32)) % base;
+        reversed = reversed * base as u64 + ((digit + offset) % base) as u64;
+        inv_base_m *= inv_base;
+    }
+
+    ((reversed as f64 * inv_base_m) as f32).min(1.0 - f32::EPSILON)
+}
+
+/// Halton 采样: 第 d 维取以第 d 个素数为底的根式反演, 每个像素每个维度独立 Owen 置乱
+pub struct HaltonSampler {
+    ns: usize,
+
+    /// 当前像素的哈希
+    seed: u32,
+
+    /// 当前采样在像素内的序号
+    index: u64,
+
+    /// 当前采样已取出的维度数
+    dimension: usize,
+
+    /// 维度用尽后使用的随机数
+    rng: StdRng,
+}
+
+impl HaltonSampler {
+    pub fn new(ns: usize, rng: StdRng) -> Self {
+        Self {
+            ns: ns.max(1),
+            seed: 0,
+            index: 0,
+            dimension: 0,
+            rng,
+        }
+    }
+}
+
+impl Sampler for HaltonSampler {
+    fn samples_per_pixel(&self) -> usize {
+        self.ns
+    }
+
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize) {
+        self.seed = hash(
+            (pixel.0 as u32).wrapping_mul(73_856_093) ^ (pixel.1 as u32).wrapping_mul(19_349_663),
+        );
+        self.index = index as u64;
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+
+        match PRIMES.get(dimension) {
+            Some(&base) => owen_scrambled_radical_inverse(
+                base,
+                self.index,
+                hash(self.seed ^ (dimension as u32).wrapping_mul(0x9e37_79b9)),
+            ),
+            None => self.rng.random(),
+        }
+    }
+}
+
+/// Cranley–Patterson 旋转: 每个像素的每个维度加上一个由像素决定的随机偏移 (模 1),
+/// 使相邻像素的低差异序列不再相同, 打散结构性的走样
+pub struct CranleyPatterson<S: Sampler> {
+    inner: S,
+
+    /// 当前像素的哈希
+    seed: u32,
+
+    /// 当前采样已取出的维度数
+    dimension: u32,
+}
+
+impl<S: Sampler> CranleyPatterson<S> {
+    pub const fn new(inner: S) -> Self {
+        Self {
+            inner,
+            seed: 0,
+            dimension: 0,
+        }
+    }
+
+    /// 对下一个维度施加偏移
+    fn rotate(&mut self, value: f32) -> f32 {
+        let offset = hash_to_unit(self.seed ^ self.dimension.wrapping_mul(0x9e37_79b9));
+        self.dimension += 1;
+
+        let rotated = value + offset;
+        if rotated >= 1.0 {
+            rotated - 1.0
+        } else {
+            rotated
+        }
+    }
+}
+
+impl<S: Sampler> Sampler for CranleyPatterson<S> {
+    fn samples_per_pixel(&self) -> usize {
+        self.inner.samples_per_pixel()
+    }
+
+    fn start_sample(&mut self, pixel: (usize, usize), index: usize) {
+        self.inner.start_sample(pixel, index);
+        self.seed = hash(
+            (pixel.0 as u32).wrapping_mul(73_856_093) ^ (pixel.1 as u32).wrapping_mul(19_349_663),
+        );
+        self.dimension = 0;
+    }
+
+    fn next_1d(&mut self) -> f32 {
+        let value = self.inner.next_1d();
+        self.rotate(value)
+    }
+
+    fn next_2d(&mut self) -> (f32, f32) {
+        let (u, v) = self.inner.next_2d();
+        (self.rotate(u), self.rotate(v))
+    }
+}
+
+/// 构建渲染一行像素所用的采样器
+pub fn build_sampler(
+    kind: SamplerKind,
+    ns: usize,
+    rng: StdRng,
+    cp_rotation: bool,
+) -> Box<dyn Sampler> {
+    match (kind, cp_rotation) {
+        (SamplerKind::Stratified, false) => Box::new(StratifiedSampler::new(ns, rng)),
+        (SamplerKind::Stratified, true) => {
+            Box::new(CranleyPatterson::new(StratifiedSampler::new(ns, rng)))
+        }
+        (SamplerKind::Halton, false) => Box::new(HaltonSampler::new(ns, rng)),
+        (SamplerKind::Halton, true) => Box::new(CranleyPatterson::new(HaltonSampler::new(ns, rng))),
+    }
+}
@@ -0,0 +1,266 @@
+use nalgebra::Vector3;
+use rand::Rng;
+
+/// 以 `w` 为第三个轴的正交基
+#[derive(Clone, Copy, Debug)]
+pub struct Onb {
+    pub u: Vector3<f32>,
+    pub v: Vector3<f32>,
+    pub w: Vector3<f32>,
+}
+
+impl Onb {
+    /// 由单位向量 `w` 构建右手正交基, 另外两个轴的朝向任意
+    pub fn build_from_w(w: &Vector3<f32>) -> Self {
+        let a = if w.x.abs() > 0.9 {
+            Vector3::y()
+        } else {
+            Vector3::x()
+        };
+        let u = w.cross(&a).normalize();
+
+        Self {
+            u,
+            v: w.cross(&u),
+            w: *w,
+        }
+    }
+
+    /// 局部坐标 `a` 对应的世界向量
+    pub fn local(&self, a: &Vector3<f32>) -> Vector3<f32> {
+        a.x * self.u + a.y * self.v + a.z * self.w
+    }
+}
+
+/// 在单位球面上均匀采样一个方向
+pub fn random_unit_vector(rng: &mut impl Rng) -> Vector3<f32> {
+    let z = rng.random_range(-1.0..=1.0_f32);
+    let phi = rng.random_range(0.0..std::f32::consts::TAU);
+    let r = (1.0 - z * z).max(0.0).sqrt();
+
+    Vector3::new(r * phi.cos(), r * phi.sin(), z)
+}
+
+/// 在单位球内均匀采样一点
+pub fn random_in_unit_sphere(rng: &mut impl Rng) -> Vector3<f32> {
+    rng.random::<f32>().cbrt() * random_unit_vector(rng)
+}
+
+/// 在 xy 平面的单位圆内均匀采样一点
+pub fn random_in_unit_disk(rng: &mut impl Rng) -> Vector3<f32> {
+    sample_unit_disk((rng.random(), rng.random()))
+}
+
+/// 将 [0, 1)² 内的采样值 `(u, v)` 映射到 xy 平面的单位圆内, 均匀的输入得到均匀的输出
+pub fn sample_unit_disk((u, v): (f32, f32)) -> Vector3<f32> {
+    let theta = std::f32::consts::TAU * u;
+    let r = v.sqrt();
+
+    Vector3::new(r * theta.cos(), r * theta.sin(), 0.0)
+}
+
+/// 将 [0, 1)² 内的采样值映射为三角形内均匀分布的点的重心坐标
+pub fn sample_triangle((u, v): (f32, f32)) -> [f32; 3] {
+    let r = u.sqrt();
+
+    [1.0 - r, r * (1.0 - v), r * v]
+}
+
+/// 在单位球面上的球面三角形 `[a, b, c]` (三个单位向量) 内按立体角均匀采样方向 (Arvo 的方法),
+/// 返回方向与三角形的立体角; 三角形退化 (立体角过小) 时为 `None`
+pub fn sample_spherical_triangle(
+    [a, b, c]: &[Vector3<f32>; 3],
+    (u, v): (f32, f32),
+) -> Option<(Vector3<f32>, f32)> {
+    // 三个顶点处的球面角, 球面角盈余即立体角
+    let (n_ab, n_bc, n_ca) = (
+        a.cross(b).try_normalize(f32::EPSILON)?,
+        b.cross(c).try_normalize(f32::EPSILON)?,
+        c.cross(a).try_normalize(f32::EPSILON)?,
+    );
+    let angle = |n: Vector3<f32>, m: Vector3<f32>| (-n.dot(&m)).clamp(-1.0, 1.0).acos();
+    let alpha = angle(n_ab, n_ca);
+    let beta = angle(n_bc, n_ab);
+    let gamma = angle(n_ca, n_bc);
+    let area = alpha + beta + gamma - std::f32::consts::PI;
+    if area.is_nan() || area < 1e-5 {
+        return None;
+    }
+
+    // 按面积比例 u 确定子三角形 a b ĉ 的顶点 ĉ
+    let area_hat = u * area;
+    let (s, t) = (area_hat - alpha).sin_cos();
+    let p = t - alpha.cos();
+    let q = s + alpha.sin() * a.dot(b);
+    let cos_b =
+        (((q * t - p * s) * alpha.cos() - q) / ((q * s + p * t) * alpha.sin())).clamp(-1.0, 1.0);
+    let c_hat = cos_b * a
+        + (1.0 - cos_b * cos_b).max(0.0).sqrt() * (c - c.dot(a) * a).try_normalize(f32::EPSILON)?;
+
+    // 在 b 到 ĉ 的大圆弧上按 v 确定方向
+    let z = 1.0 - v * (1.0 - c_hat.dot(b));
+    let direction = z * b
+        + (1.0 - z * z).max(0.0).sqrt() * (c_hat - c_hat.dot(b) * b).try_normalize(f32::EPSILON)?;
+
+    Some((direction, area))
+}
+
+/// 以单位法线 `normal` 为中心的余弦加权方向 (法线加上单位球面上的随机方向, 未归一化)
+pub fn random_cosine_direction(normal: &Vector3<f32>, rng: &mut impl Rng) -> Vector3<f32> {
+    normal + random_unit_vector(rng)
+}
+
+/// 按 GGX 分布 D(h)·cos θh 采样微表面法线, `alpha` 为粗糙度的平方
+pub fn sample_ggx(normal: &Vector3<f32>, alpha: f32, rng: &mut impl Rng) -> Vector3<f32> {
+    let (u, v): (f32, f32) = (rng.random(), rng.random());
+    let tan2 = alpha * alpha * u / (1.0 - u).max(1e-8);
+    let cos_theta = 1.0 / (1.0 + tan2).sqrt();
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = std::f32::consts::TAU * v;
+    let onb = Onb::build_from_w(normal);
+
+    onb.local(&Vector3::new(
+        sin_theta * phi.cos(),
+        sin_theta * phi.sin(),
+        cos_theta,
+    ))
+    .normalize()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use rand::SeedableRng;
+    use rand::rngs::StdRng;
+
+    const SAMPLES: usize = 200_000;
+
+    fn rng() -> StdRng {
+        StdRng::seed_from_u64(0x5eed)
+    }
+
+    /// `samples` 个采样值 `f` 的平均
+    fn mean(samples: usize, mut f: impl FnMut() -> f32) -> f32 {
+        (0..samples).map(|_| f() as f64).sum::<f64>() as f32 / samples as f32
+    }
+
+    /// 各点到原点距离的一阶与二阶矩, 以及质心
+    fn moments(points: &[Vector3<f32>]) -> (f32, f32, Vector3<f32>) {
+        let n = points.len() as f32;
+        let r = points.iter().map(|p| p.norm()).sum::<f32>() / n;
+        let r2 = points.iter().map(|p| p.norm_squared()).sum::<f32>() / n;
+
+        (r, r2, points.iter().sum::<Vector3<f32>>() / n)
+    }
+
+    #[test]
+    fn onb_is_orthonormal_and_right_handed() {
+        let mut rng = rng();
+        let near_axes = [
+            Vector3::x(),
+            -Vector3::x(),
+            Vector3::y(),
+            -Vector3::y(),
+            Vector3::z(),
+            -Vector3::z(),
+            Vector3::new(0.9, 1e-4, 1e-4),
+            Vector3::new(1e-4, 0.9, 1e-4),
+            Vector3::new(1e-4, 1e-4, -0.9),
+        ];
+        let random = (0..1000).map(|_| random_unit_vector(&mut rng));
+
+        for w in near_axes.into_iter().chain(random) {
+            let w = w.normalize();
+            let onb = Onb::build_from_w(&w);
+            for axis in [onb.u, onb.v, onb.w] {
+                assert!((axis.norm() - 1.0).abs() < 1e-5, "{w:?}");
+            }
+            assert!(onb.u.dot(&onb.v).abs() < 1e-5, "{w:?}");
+            assert!(onb.v.dot(&onb.w).abs() < 1e-5, "{w:?}");
+            assert!(onb.w.dot(&onb.u).abs() < 1e-5, "{w:?}");
+            assert!((onb.u.cross(&onb.v) - onb.w).norm() < 1e-5, "{w:?}");
+            assert!((onb.local(&Vector3::z()) - w).norm() < 1e-6, "{w:?}");
+        }
+    }
+
+    #[test]
+    fn unit_disk_is_uniform() {
+        let mut rng = rng();
+        let points: Vec<_> = (0..SAMPLES)
+            .map(|_| random_in_unit_disk(&mut rng))
+            .collect();
+        assert!(points.iter().all(|p| p.norm() <= 1.0 && p.z == 0.0));
+
+        // 均匀分布时 r 的密度为 2r: E[r] = 2/3, E[r²] = 1/2, 质心在原点
+        let (r, r2, center) = moments(&points);
+        assert!((r - 2.0 / 3.0).abs() < 5e-3, "E[r] = {r}");
+        assert!((r2 - 0.5).abs() < 5e-3, "E[r²] = {r2}");
+        assert!(center.norm() < 1e-2, "{center:?}");
+    }
+
+    #[test]
+    fn unit_sphere_is_uniform() {
+        let mut rng = rng();
+        let points: Vec<_> = (0..SAMPLES)
+            .map(|_| random_in_unit_sphere(&mut rng))
+            .collect();
+        assert!(points.iter().all(|p| p.norm() <= 1.0 + 1e-6));
+
+        // 均匀分布时 r 的密度为 3r²: E[r] = 3/4, E[r²] = 3/5, 质心在原点
+        let (r, r2, center) = moments(&points);
+        assert!((r - 0.75).abs() < 5e-3, "E[r] = {r}");
+        assert!((r2 - 0.6).abs() < 5e-3, "E[r²] = {r2}");
+        assert!(center.norm() < 1e-2, "{center:?}");
+    }
+
+    #[test]
+    fn cosine_direction_has_mean_cosine_two_thirds() {
+        let mut rng = rng();
+        for normal in [Vector3::y(), Vector3::new(1.0, -2.0, 0.5).normalize()] {
+            let cos = mean(SAMPLES, || {
+                random_cosine_direction(&normal, &mut rng)
+                    .try_normalize(0.0)
+                    .map_or(1.0, |d| d.dot(&normal))
+            });
+            assert!((cos - 2.0 / 3.0).abs() < 5e-3, "E[cos θ] = {cos}");
+        }
+    }
+
+    #[test]
+    fn ggx_matches_distribution() {
+        const BINS: usize = 16;
+
+        let mut rng = rng();
+        let normal = Vector3::new(0.3, 1.0, -0.2).normalize();
+        for alpha in [0.1, 0.5, 1.0] {
+            let mut histogram = [0usize; BINS];
+            for _ in 0..SAMPLES {
+                let h = sample_ggx(&normal, alpha, &mut rng);
+                let cos = h.dot(&normal);
+                assert!(cos > 0.0 && (h.norm() - 1.0).abs() < 1e-5);
+                histogram[((cos * BINS as f32) as usize).min(BINS - 1)] += 1;
+            }
+
+            // 按 cos θ 分箱, 每箱的期望概率为 D(h)·cos θ 在该箱对应立体角上的积分
+            let a2 = alpha * alpha;
+            let d = |c: f32| a2 / (std::f32::consts::PI * (c * c * (a2 - 1.0) + 1.0).powi(2));
+            let steps = 1000;
+            for (bin, &count) in histogram.iter().enumerate() {
+                let width = 1.0 / (BINS * steps) as f32;
+                let expected = (0..steps)
+                    .map(|i| {
+                        let c = (bin * steps + i) as f32 * width + width / 2.0;
+                        std::f32::consts::TAU * d(c) * c * width
+                    })
+                    .sum::<f32>();
+                let observed = count as f32 / SAMPLES as f32;
+                let sigma = (expected / SAMPLES as f32).sqrt();
+                assert!(
+                    (observed - expected).abs() < 5.0 * sigma + 1e-4,
+                    "alpha {alpha}, bin {bin}: {observed} vs {expected}"
+                );
+            }
+        }
+    }
+}
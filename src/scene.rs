@@ -0,0 +1,369 @@
+use std::sync::Arc;
+
+use crate::accelerator::{Accelerator, AcceleratorKind};
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::bvh_cache;
+use crate::error::{Error, Result};
+use crate::grid::{UniformGrid, partition_oversized};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::mesh::Geometry;
+use crate::qbvh::QuantizedBvh;
+use crate::ray::{Ray, RayEpsilon, RayKind};
+use crate::stats::{self, ObjectStats};
+
+use nalgebra::Vector3;
+
+/// 光线查询的结果
+#[derive(Clone, Debug)]
+pub struct RaycastHit {
+    /// 被击中实体的编号, 即其在构建场景时的下标
+    pub object: usize,
+
+    /// 交点到光线起点的距离
+    pub distance: f32,
+
+    /// 交点的位置
+    pub position: Vector3<f32>,
+
+    /// 交点处的表面法线 (单位向量, 朝向实体外侧)
+    pub normal: Vector3<f32>,
+}
+
+/// 实体对各类光线是否可见
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Visibility {
+    /// 对相机发出的主光线可见
+    pub camera: bool,
+
+    /// 对阴影光线可见 (投下阴影)
+    pub shadow: bool,
+
+    /// 对散射后的间接光线可见 (出现在反射, 折射中并遮挡间接光照)
+    pub indirect: bool,
+}
+
+impl Visibility {
+    /// 对所有光线可见
+    pub const ALL: Self = Self {
+        camera: true,
+        shadow: true,
+        indirect: true,
+    };
+
+    /// 对 `kind` 类型的光线是否可见
+    pub const fn visible_to(self, kind: RayKind) -> bool {
+        match kind {
+            RayKind::Camera => self.camera,
+            RayKind::Shadow => self.shadow,
+            RayKind::Indirect => self.indirect,
+        }
+    }
+}
+
+impl Default for Visibility {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+/// 带编号的实体, 击中时将编号写入 `HitRecord`, 对不可见的光线类型不相交
+struct Tagged {
+    id: usize,
+    object: Arc<dyn Bounded + Sync + Send>,
+    visibility: Visibility,
+}
+
+impl Hittable for Tagged {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        if !self.visibility.visible_to(ray.kind()) {
+            return None;
+        }
+
+        let hit = self.object.hit(ray, t_min, t_max);
+        stats::count_object(self.id, hit.is_some());
+
+        hit.map(|hit| HitRecord {
+            object: self.id,
+            ..hit
+        })
+    }
+}
+
+impl Bounded for Tagged {
+    fn bounding_box(&self) -> AaBb {
+        self.object.bounding_box()
+    }
+
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        ClosestPoint {
+            object: self.id,
+            ..self.object.closest_point(point)
+        }
+    }
+    fn unbounded(&self) -> bool {
+        self.object.unbounded()
+    }
+
+    fn emitter(&self) -> Option<Arc<Geometry>> {
+        self.object.emitter()
+    }
+
+    fn triangles(&self) -> Vec<[Vector3<f32>; 3]> {
+        self.object.triangles()
+    }
+}
+
+/// 场景中的一个实体
+#[derive(Clone)]
+pub struct SceneObject {
+    /// 名称
+    pub name: String,
+
+    /// 实体
+    pub object: Arc<dyn Bounded + Sync + Send>,
+
+    /// 对各类光线的可见性
+    pub visibility: Visibility,
+
+    /// 材质在场景材质表中的下标, 为 `None` 时使用实体自身的材质
+    pub material: Option<usize>,
+}
+
+impl SceneObject {
+    /// 对所有光线可见的实体
+    pub fn new(name: impl Into<String>, object: Arc<dyn Bounded + Sync + Send>) -> Self {
+        Self {
+            name: name.into(),
+            object,
+            visibility: Visibility::ALL,
+            material: None,
+        }
+    }
+}
+
+/// 场景中实体的信息
+#[derive(Clone, Debug)]
+pub struct ObjectInfo<'a> {
+    /// 编号
+    pub id: usize,
+
+    /// 名称
+    pub name: &'a str,
+
+    /// 包围盒
+    pub bounds: AaBb,
+}
+
+/// 场景: 一组实体及其加速结构
+///
+/// 实体可以按下标引用场景的材质表, 击中时以表中的材质取代实体自身的材质;
+/// 替换材质表无需重建加速结构
+pub struct Scene {
+    /// 所有实体, 下标即编号
+    objects: Vec<SceneObject>,
+
+    /// 加速结构
+    accelerator: Box<dyn Accelerator>,
+
+    /// 材质表
+    materials: Vec<Material>,
+}
+
+impl Scene {
+    /// 构建场景, 实体的编号为其在 `objects` 中的下标
+    pub fn build(objects: Vec<SceneObject>, kind: AcceleratorKind, leaf_size: usize) -> Self {
+        let tagged = objects
+            .iter()
+            .enumerate()
+            .map(|(id, obj)| {
+                Arc::new(Tagged {
+                    id,
+                    object: obj.object.clone(),
+                    visibility: obj.visibility,
+                }) as Arc<dyn Bounded + Sync + Send>
+            })
+            .collect();
+
+        let accelerator: Box<dyn Accelerator> = match kind {
+            AcceleratorKind::Bvh => Box::new(bvh_cache::build(tagged, leaf_size)),
+            AcceleratorKind::Qbvh => {
+                Box::new(QuantizedBvh::build(&bvh_cache::build(tagged, leaf_size)))
+            }
+            AcceleratorKind::Grid => Box::new(UniformGrid::build(tagged)),
+        };
+
+        Self {
+            objects,
+            accelerator,
+            materials: vec![],
+        }
+    }
+
+    /// 替换材质表; 有实体引用的下标超出表的范围时返回错误, 原有的材质表不变
+    pub fn set_materials(&mut self, materials: Vec<Material>) -> Result<()> {
+        if let Some(index) = self
+            .objects
+            .iter()
+            .filter_map(|obj| obj.material)
+            .find(|&index| index >= materials.len())
+        {
+            return Err(Error::InvalidSettings(format!(
+                "material {index} out of range for {} materials",
+                materials.len()
+            )));
+        }
+        self.materials = materials;
+
+        Ok(())
+    }
+
+    /// 实体数量
+    pub fn len(&self) -> usize {
+        self.objects.len()
+    }
+
+    /// 场景是否为空
+    pub fn is_empty(&self) -> bool {
+        self.objects.is_empty()
+    }
+
+    /// 按编号顺序遍历所有实体
+    pub fn objects(&self) -> impl Iterator<Item = ObjectInfo<'_>> {
+        self.objects.iter().enumerate().map(|(id, obj)| ObjectInfo {
+            id,
+            name: &obj.name,
+            bounds: obj.object.bounding_box(),
+        })
+    }
+
+    /// 编号为 `id` 的实体
+    pub fn object(&self, id: usize) -> Option<&SceneObject> {
+        self.objects.get(id)
+    }
+
+    /// 名称为 `name` 的首个实体的编号
+    pub fn find(&self, name: &str) -> Option<usize> {
+        self.objects.iter().position(|obj| obj.name == name)
+    }
+
+    /// 编号为 `id` 的实体的包围盒
+    pub fn object_bounds(&self, id: usize) -> Option<AaBb> {
+        self.objects.get(id).map(|obj| obj.object.bounding_box())
+    }
+
+    /// 包裹所有实体的包围盒, 场景为空时为 `None`
+    pub fn world_bounds(&self) -> Option<AaBb> {
+        let objects: Vec<_> = self.objects.iter().map(|obj| obj.object.clone()).collect();
+
+        (!objects.is_empty()).then(|| AaBb::all_surrounding_box(&objects))
+    }
+
+    /// 包裹除无界实体与过大实体 (如作为地面的平面或大球) 以外所有实体的包围盒, 即场景的主体,
+    /// 场景为空时为 `None`
+    pub fn subject_bounds(&self) -> Option<AaBb> {
+        let objects: Vec<_> = self
+            .objects
+            .iter()
+            .filter(|obj| !obj.object.unbounded())
+            .map(|obj| obj.object.clone())
+            .collect();
+        let (_, subject) = partition_oversized(objects);
+
+        (!subject.is_empty()).then(|| AaBb::all_surrounding_box(&subject))
+    }
+
+    /// 求交次数最多的 `count` 个实体的统计, 需先以 `stats::enable_object_stats` 开启统计
+    pub fn object_stats(&self, count: usize) -> Vec<ObjectStats> {
+        let mut objects: Vec<ObjectStats> = stats::object_totals()
+            .into_iter()
+            .enumerate()
+            .filter_map(|(id, [tests, hits])| {
+                Some(ObjectStats {
+                    name: self.objects.get(id)?.name.clone(),
+                    tests,
+                    hits,
+                })
+            })
+            .collect();
+        objects.sort_by_key(|object| std::cmp::Reverse(object.tests));
+        objects.truncate(count);
+
+        objects
+    }
+
+    /// 场景中的网格光源: 自发光的三角形网格及其编号
+    pub fn emitters(&self) -> impl Iterator<Item = (usize, Arc<Geometry>)> + '_ {
+        self.objects
+            .iter()
+            .enumerate()
+            .filter_map(|(id, obj)| Some((id, obj.object.emitter()?)))
+    }
+
+    /// 按场景主体的尺度确定的自相交容差, 场景为空时取默认值
+    pub fn ray_epsilon(&self) -> RayEpsilon {
+        self.subject_bounds().map_or(RayEpsilon::DEFAULT, |bounds| {
+            RayEpsilon::for_extent((bounds.max - bounds.min).magnitude())
+        })
+    }
+
+    /// 从 `origin` 沿 `direction` 发出光线, 返回最近的交点, 与渲染过程无关
+    ///
+    /// 查询光线视为相机光线, 只与对相机可见的实体相交
+    pub fn raycast(&self, origin: Vector3<f32>, direction: Vector3<f32>) -> Option<RaycastHit> {
+        let direction = direction.try_normalize(f32::EPSILON)?;
+        let hit = self.accelerator.hit(
+            &Ray::from(origin, direction).with_kind(RayKind::Camera),
+            0.0,
+            f32::MAX,
+        )?;
+
+        Some(RaycastHit {
+            object: hit.object,
+            distance: hit.distance,
+            position: hit.position,
+            normal: hit.normal.normalize(),
+        })
+    }
+
+    /// 所有实体表面上距 `point` 最近的点, 场景为空时为 `None`
+    pub fn closest_point(&self, point: &Vector3<f32>) -> Option<ClosestPoint> {
+        self.accelerator.closest_point(point)
+    }
+
+    /// `point` 到最近表面的有符号距离, 位于该表面所属实体内部时为负, 场景为空时为正无穷
+    pub fn signed_distance(&self, point: &Vector3<f32>) -> f32 {
+        self.closest_point(point)
+            .map_or(f32::INFINITY, |closest| closest.distance)
+    }
+}
+
+impl Hittable for Scene {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let hit = self.accelerator.hit(ray, t_min, t_max)?;
+
+        // 尚未设置材质表时保留实体自身的材质
+        let material = self.objects[hit.object]
+            .material
+            .and_then(|index| self.materials.get(index).copied());
+
+        Some(match material {
+            Some(material) => HitRecord { material, ..hit },
+            None => hit,
+        })
+    }
+}
+
+impl Accelerator for Scene {
+    fn boxes(&self) -> Vec<(AaBb, usize)> {
+        self.accelerator.boxes()
+    }
+
+    fn stats(&self) -> Option<(usize, usize)> {
+        self.accelerator.stats()
+    }
+
+    fn closest_point(&self, point: &Vector3<f32>) -> Option<ClosestPoint> {
+        self.accelerator.closest_point(point)
+    }
+}
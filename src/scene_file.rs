@@ -0,0 +1,821 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::fs;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::background::Background;
+use crate::bvh::Bounded;
+use crate::camera::Camera;
+use crate::capsule::Capsule;
+use crate::cone::Cone;
+use crate::curve::{Curve, CurveKind, Hair};
+use crate::ellipsoid::Ellipsoid;
+use crate::error::{Error, Result};
+use crate::graph::Lens;
+use crate::heightfield::Heightfield;
+use crate::hittable::HittableList;
+use crate::ies::IesProfile;
+use crate::instance::Instance;
+use crate::light::Light;
+use crate::material::Material;
+use crate::material_library::MaterialLibrary;
+use crate::mesh::{Geometry, Mesh};
+use crate::mesh_cache;
+use crate::plane::Plane;
+use crate::ply;
+use crate::quad::Quad;
+use crate::quadric::Quadric;
+use crate::sdf::Sdf;
+use crate::sphere::{Sphere, Tessellation};
+use crate::stl;
+use crate::subdivision::{self, MAX_LEVELS};
+use crate::sun_sky::SunSky;
+use crate::triangle::Triangle;
+
+use nalgebra::{
+    Matrix4, Rotation3, Similarity3, Translation3, Unit, UnitQuaternion, Vector2, Vector3,
+};
+
+/// 场景文件中的相机
+pub struct CameraSpec {
+    pub look_from: Vector3<f32>,
+    pub look_at: Vector3<f32>,
+    pub lens: Lens,
+}
+
+impl CameraSpec {
+    /// 按宽高比 `aspect` 构建相机
+    pub fn build(&self, aspect: f32) -> Camera {
+        Camera::from(
+            self.look_from,
+            self.look_at,
+            Vector3::new(0.0, 1.0, 0.0),
+            self.lens.vertical_fov,
+            aspect,
+            self.lens.aperture,
+            self.lens.focus_dist,
+        )
+    }
+}
+
+/// 从文本文件读取的场景
+///
+/// 每行一条语句, `#` 开头的注释到行尾:
+///
+/// ```text
+/// camera <from x y z> <at x y z> <vfov> [<aperture> <focus_dist>]
+/// material <name> <material ...>
+/// sphere <x y z> <radius> lambertian <r g b>
+/// sphere <x y z> <radius> metal <r g b> <fuzz>
+/// sphere <x y z> <radius> dielectric <ior>
+/// sphere <x y z> <radius> emissive <r g b> [<intensity>]
+/// ellipsoid <x y z> <rx ry rz> [<axis x y z> <angle>] <material ...>
+/// paraboloid <apex x y z> <radius> <height> <material ...>
+/// hyperboloid <x y z> <radius> <height> <material ...>
+/// triangle <a x y z> <b x y z> <c x y z> <material ...>
+/// quad <corner x y z> <u x y z> <v x y z> <material ...>
+/// plane <x y z> <normal x y z> [<extent>] <material ...>
+/// cone <apex x y z> <axis x y z> <half angle> <height> <material ...>
+/// capsule <a x y z> <b x y z> <radius> <material ...>
+/// torus <x y z> <major radius> <minor radius> <material ...>
+/// mandelbulb <x y z> <radius> <power> <material ...>
+/// tube <p0 x y z> <p1 x y z> <p2 x y z> <p3 x y z> <width0> <width1> <material ...>
+/// ribbon <p0 x y z> <p1 x y z> <p2 x y z> <p3 x y z> <width0> <width1> <material ...>
+/// grass <corner x y z> <size x z> <count> <height> <width> <material ...>
+/// fur <x y z> <radius> <count> <length> <width> <material ...>
+/// mesh <file.ply|file.stl> <x y z> <scale> [<subdivision level>] <material ...>
+/// heightfield <file.ppm> <corner x y z> <size x y z> <material ...>
+/// point_light <x y z> <r g b> [<intensity>]
+/// spot_light <x y z> <target x y z> <r g b> <intensity> <angle> [<inner angle>]
+/// ies_light <file.ies> <x y z> <target x y z> <r g b> <intensity>
+/// sun_sky <latitude> <month> <day> <hour> [<turbidity>]
+/// studio [<zenith r g b> <horizon r g b> <ground r g b> [<softness>]]
+/// ```
+///
+/// 形状语句的材质也可以写作此前以 `material` 语句定义的名称, 引用同一名称的实体共享一条定义,
+/// 预览中修改定义即作用于所有引用它的实体;
+/// 二次曲面, 三角形与四边形的材质写法与球体相同, 三角形的法线由顶点的逆时针顺序确定,
+/// 四边形由角点与自角点出发的两条边 `u`, `v` 张成, 法线沿 `u × v`;
+/// 平面过点 `(x, y, z)`, 给出 `extent` 时为以该点为中心, 半边长为 `extent` 的正方形, 否则无界;
+/// 圆锥自顶点沿轴张开半角 `half angle` (度), 截取到高度 `height` 并封底;
+/// 椭球为单位球沿各轴缩放到半轴长 `(rx, ry, rz)` 后, 绕过中心的轴 `axis` 旋转 `angle` (度), 纹理坐标与球体相同;
+/// 胶囊体为半径 `radius` 的球沿线段 `a`–`b` 扫过的形状;
+/// 圆环环绕过中心的竖直轴, 曼德尔球为外接半径 `radius` 的 `power` 次分形, 两者都以有符号距离场步进求交;
+/// 圆管与带子为以 `p0`–`p3` 为控制点的三次贝塞尔曲线, 宽度自 `width0` 线性变化到 `width1`,
+/// 带子始终正对光线, 圆管的着色如同圆柱; 草地在自角点起沿 x 与 z 轴大小为 `size` 的矩形上随机种下 `count` 片高约 `height` 的草叶,
+/// 毛发在球面上随机长出 `count` 根长约 `length` 的毛, 两者的宽度自根部的 `width` 收窄到尖端为零, 随机分布由几何参数确定;
+/// 网格文件 (PLY 或二进制 STL, 按扩展名区分) 的路径相对于场景文件所在的目录, 网格缩放 `scale` 倍后平移到 `(x, y, z)`,
+/// 给出细分层级时先以 Loop 细分平滑 (层级至多为 6); 解析结果缓存在网格文件旁的 `.meshcache` 文件中,
+/// 同一网格文件以同一层级只加载并细分一次, 各语句作为共享几何体的实例,
+/// 之后的语句以各自的材质覆盖 (顶点颜色只在首条语句中保留), 自发光的网格除外;
+/// 高度场读取灰度高度图 (路径相对于场景文件所在的目录), 铺在自角点起沿 x 与 z 轴大小为 `size` 的矩形上,
+/// 图像首行在最小 z 处, 亮度为 1 的像素高出角点 `size y`;
+/// 灯光的发光强度为颜色乘以 `intensity`, 随距离平方反比衰减, 聚光灯与 IES 灯光的主轴指向 `target`:
+/// 聚光灯在半角 `angle` 处衰减为零 (`inner angle` 以内全亮, 默认为 `angle`),
+/// IES 灯光按配光曲线 (路径相对于场景文件所在的目录) 分布, `intensity` 为曲线峰值方向上的强度;
+/// 太阳与天空由纬度 (度, 北纬为正), 日期与当地太阳时 `hour` 确定, 浑浊度 `turbidity` 默认为 3,
+/// 场景中 y 轴向上, -z 为北; 摄影棚背景不带参数时使用默认的颜色, `softness` 默认为 0.1
+pub struct SceneFile {
+    /// 场景中的实体, 按文件中的顺序
+    pub list: HittableList,
+
+    /// 相机, 未指定时由调用者决定
+    pub camera: Option<CameraSpec>,
+
+    /// 点状灯光, 按文件中的顺序
+    pub lights: Vec<Light>,
+
+    /// 背景 (太阳与天空或摄影棚), 未指定时由调用者决定
+    pub background: Option<Background>,
+
+    /// 文件坐标系中场景坐标系的原点, 场景中的位置为文件中的位置减去该点
+    pub origin: Vector3<f64>,
+
+    /// 可单独替换的材质, 按文件中的顺序; `material` 语句定义的具名材质各占一项
+    pub materials: MaterialLibrary,
+
+    /// 各实体的材质在 `materials` 中的下标, 与 `list` 一一对应;
+    /// 自发光的网格 (作为光源采样) 与带顶点颜色的网格不能单独替换材质, 为 `None`
+    pub material_indices: Vec<Option<usize>>,
+
+    /// 几何的指纹: 形状语句除去可替换的材质后的散列; 两次解析的指纹相同时,
+    /// 两个场景的实体只有材质不同, 可沿用已构建的加速结构
+    pub geometry: u64,
+}
+
+impl SceneFile {
+    /// 读取并解析场景文件
+    pub fn load(path: impl AsRef<Path>) -> Result<Self> {
+        Self::load_with_origin(path.as_ref(), false)
+    }
+
+    /// 读取并解析场景文件, 以相机位置为场景坐标系的原点 (相机相对渲染)
+    ///
+    /// 位置先以双精度读取并减去相机位置, 再转换为单精度, 远离原点的场景 (如大地坐标中的地形)
+    /// 在相机附近仍保留完整的精度, 不会因单精度的相消而抖动
+    pub fn load_camera_relative(path: impl AsRef<Path>) -> Result<Self> {
+        Self::load_with_origin(path.as_ref(), true)
+    }
+
+    /// 读取并解析场景文件, `relative` 时以首个相机的位置为原点
+    fn load_with_origin(path: &Path, relative: bool) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))?;
+        let statements = text.lines().enumerate().filter_map(|(index, line)| {
+            let content = line.split('#').next().unwrap_or_default();
+            let fields: Vec<&str> = content.split_whitespace().collect();
+
+            (!fields.is_empty()).then_some((index, line, fields))
+        });
+        let invalid = |index: usize, line: &str, fields: &[&str], err: FieldError| {
+            let at = err.field.unwrap_or(fields[0]);
+            Error::parse(path, index + 1, line, at, err.message)
+        };
+
+        let mut origin = Vector3::zeros();
+        if relative {
+            let camera = statements
+                .clone()
+                .find(|(_, _, fields)| fields[0] == "camera");
+            if let Some((index, line, fields)) = camera {
+                let v = numbers(&fields[1..fields.len().min(4)])
+                    .map_err(|err| invalid(index, line, &fields, err))?;
+                if v.len() < 3 {
+                    let err = FieldError::from("`camera` takes 7 or 9 numbers".to_string());
+                    return Err(invalid(index, line, &fields, err));
+                }
+                origin = Vector3::new(v[0], v[1], v[2]);
+            }
+        }
+
+        let mut scene = Self {
+            list: HittableList::default(),
+            camera: None,
+            lights: vec![],
+            background: None,
+            origin,
+            materials: MaterialLibrary::new(),
+            material_indices: vec![],
+            geometry: 0,
+        };
+        let dir = path.parent().unwrap_or(Path::new(""));
+        let mut meshes = HashMap::new();
+        let mut geometry = DefaultHasher::new();
+        origin.map(f64::to_bits).hash(&mut geometry);
+        for (index, line, fields) in statements {
+            let count = scene.list.list.len();
+            let material = scene
+                .parse_line(&fields, dir, &mut meshes)
+                .map_err(|err| invalid(index, line, &fields, err))?;
+            if scene.list.list.len() == count {
+                continue;
+            }
+
+            // 可替换的材质自语句中最后一个不是数的字段 (材质名) 起, 不计入指纹;
+            // 引用具名材质的实体共享其下标
+            let end = match material {
+                Some((material, named)) => {
+                    let index = named.unwrap_or_else(|| scene.materials.push(material));
+                    scene.material_indices.push(Some(index));
+                    fields
+                        .iter()
+                        .rposition(|field| field.parse::<f64>().is_err())
+                        .unwrap_or(fields.len())
+                }
+                None => {
+                    scene.material_indices.push(None);
+                    fields.len()
+                }
+            };
+            fields[..end].hash(&mut geometry);
+        }
+        scene.geometry = geometry.finish();
+
+        Ok(scene)
+    }
+
+    /// 解析一条语句, 语句中的相对路径相对于 `dir`, 返回加入的实体可单独替换的材质,
+    /// 引用具名材质时一并返回其在材质库中的下标
+    ///
+    /// 同一网格文件以同一层级细分只加载一次, 由 `meshes` 按路径与层级记录, 之后的语句以实例引用
+    fn parse_line<'a>(
+        &mut self,
+        fields: &[&'a str],
+        dir: &Path,
+        meshes: &mut HashMap<(PathBuf, u32), Arc<dyn Bounded + Sync + Send>>,
+    ) -> std::result::Result<Option<(Material, Option<usize>)>, FieldError<'a>> {
+        let origin = self.origin;
+        let vector = |v: &[f64]| Vector3::new(v[0], v[1], v[2]).cast::<f32>();
+        let position = |v: &[f64]| (Vector3::new(v[0], v[1], v[2]) - origin).cast::<f32>();
+
+        // 形状语句: 关键字, `count` 个几何参数, 材质名及其参数或具名材质的名称
+        let parsed = Cell::new(None);
+        let library = &self.materials;
+        let shape = |fields: &[&'a str], count: usize, what: &str| {
+            if fields.len() < count + 2 {
+                let message = format!("`{}` takes {what} and a material", fields[0]);
+                return Err(FieldError::from(message));
+            }
+            let v = numbers(&fields[1..=count])?;
+            let (material, named) = match &fields[count + 1..] {
+                [name] if !BUILTIN_MATERIALS.contains(name) => {
+                    let index = library.index(name).ok_or_else(|| {
+                        FieldError::at(name, format!("undefined material `{name}`"))
+                    })?;
+                    (library.materials()[index], Some(index))
+                }
+                params => (parse_material(params)?, None),
+            };
+            parsed.set(Some((material, named)));
+
+            Ok((v, material))
+        };
+
+        match fields[0] {
+            "camera" => {
+                let v = numbers(&fields[1..])?;
+                let (aperture, focus_dist) = match v.len() {
+                    7 => (0.0, 1.0),
+                    9 => (v[7] as f32, v[8] as f32),
+                    _ => return Err("`camera` takes 7 or 9 numbers".to_string().into()),
+                };
+                self.camera = Some(CameraSpec {
+                    look_from: position(&v[0..3]),
+                    look_at: position(&v[3..6]),
+                    lens: Lens {
+                        vertical_fov: v[6] as f32,
+                        aperture,
+                        focus_dist,
+                    },
+                });
+            }
+            "material" => {
+                let [_, name, params @ ..] = fields else {
+                    return Err("`material` takes a name and a material".to_string().into());
+                };
+                if BUILTIN_MATERIALS.contains(name) || name.parse::<f64>().is_ok() {
+                    return Err(FieldError::at(
+                        name,
+                        format!("invalid material name `{name}`"),
+                    ));
+                }
+                if self.materials.index(name).is_some() {
+                    let message = format!("material `{name}` is already defined");
+                    return Err(FieldError::at(name, message));
+                }
+                let material = parse_material(params)?;
+                self.materials.define(*name, material);
+            }
+            "sphere" => {
+                // 半径之后为 `quad` 或 `uv` 与层级时剖分为三角形网格
+                let kind = match fields.get(5..7) {
+                    Some(["quad", level]) if level.parse::<f64>().is_ok() => {
+                        Some(Tessellation::Quad)
+                    }
+                    Some(["uv", level]) if level.parse::<f64>().is_ok() => Some(Tessellation::Uv),
+                    _ => None,
+                };
+                if let Some(kind) = kind {
+                    let level = numbers(&fields[6..7])?[0];
+                    if level.fract() != 0.0 || !(0.0..=MAX_LEVELS as f64).contains(&level) {
+                        let message =
+                            format!("tessellation level must be an integer from 0 to {MAX_LEVELS}");
+                        return Err(FieldError::at(fields[6], message));
+                    }
+                    let (v, material) = shape(
+                        &[&fields[..5], &fields[7..]].concat(),
+                        4,
+                        "a center, a radius, a tessellation and a level",
+                    )?;
+                    let mesh = Sphere::from(position(&v[0..3]), v[3] as f32, material)
+                        .tessellate(kind, level as u32)
+                        .map_err(|err| FieldError::at(fields[6], err.to_string()))?;
+                    if let Material::Emissive { .. } = material {
+                        // 网格光源随材质一同采样, 材质改变时须重建
+                        parsed.set(None);
+                    }
+                    self.list.push(Mesh::new(mesh));
+                } else {
+                    let (v, material) = shape(fields, 4, "a center and a radius")?;
+                    self.list
+                        .push(Sphere::from(position(&v[0..3]), v[3] as f32, material));
+                }
+            }
+            "ellipsoid" => {
+                // 材质名之前有 10 个数时带有旋转
+                let count = if fields.get(7).is_some_and(|f| f.parse::<f64>().is_ok()) {
+                    10
+                } else {
+                    6
+                };
+                let (v, material) = shape(fields, count, "a center and three radii")?;
+                let ellipsoid = Ellipsoid::new(position(&v[0..3]), vector(&v[3..6]), material);
+                self.list.push(if count == 10 {
+                    let axis = Unit::new_normalize(vector(&v[6..9]));
+                    ellipsoid.rotated(&Rotation3::from_axis_angle(
+                        &axis,
+                        (v[9] as f32).to_radians(),
+                    ))
+                } else {
+                    ellipsoid
+                });
+            }
+            "paraboloid" => {
+                let (v, material) = shape(fields, 5, "an apex, a radius and a height")?;
+                self.list.push(Quadric::paraboloid(
+                    position(&v[0..3]),
+                    v[3] as f32,
+                    v[4] as f32,
+                    material,
+                ));
+            }
+            "hyperboloid" => {
+                let (v, material) = shape(fields, 5, "a center, a radius and a height")?;
+                self.list.push(Quadric::hyperboloid(
+                    position(&v[0..3]),
+                    v[3] as f32,
+                    v[4] as f32,
+                    material,
+                ));
+            }
+            "triangle" => {
+                let (v, material) = shape(fields, 9, "three vertices")?;
+                self.list.push(Triangle::new(
+                    position(&v[0..3]),
+                    position(&v[3..6]),
+                    position(&v[6..9]),
+                    material,
+                ));
+            }
+            "quad" => {
+                let (v, material) = shape(fields, 9, "a corner and two edges")?;
+                self.list.push(Quad::new(
+                    position(&v[0..3]),
+                    vector(&v[3..6]),
+                    vector(&v[6..9]),
+                    material,
+                ));
+            }
+            "plane" => {
+                // 材质名之前有 7 个数时带有半边长
+                let count = if fields.get(7).is_some_and(|f| f.parse::<f64>().is_ok()) {
+                    7
+                } else {
+                    6
+                };
+                let (v, material) = shape(fields, count, "a point and a normal")?;
+                let plane = Plane::new(position(&v[0..3]), vector(&v[3..6]), material);
+                self.list.push(match v.get(6) {
+                    Some(&extent) => plane.with_extent(extent as f32),
+                    None => plane,
+                });
+            }
+            "cone" => {
+                let (v, material) =
+                    shape(fields, 8, "an apex, an axis, a half-angle and a height")?;
+                self.list.push(Cone::new(
+                    position(&v[0..3]),
+                    vector(&v[3..6]),
+                    v[6] as f32,
+                    v[7] as f32,
+                    material,
+                ));
+            }
+            "capsule" => {
+                let (v, material) = shape(fields, 7, "two end points and a radius")?;
+                self.list.push(Capsule::new(
+                    position(&v[0..3]),
+                    position(&v[3..6]),
+                    v[6] as f32,
+                    material,
+                ));
+            }
+            "torus" => {
+                let (v, material) = shape(fields, 5, "a center and two radii")?;
+                self.list.push(Sdf::torus(
+                    position(&v[0..3]),
+                    v[3] as f32,
+                    v[4] as f32,
+                    material,
+                ));
+            }
+            "mandelbulb" => {
+                let (v, material) = shape(fields, 5, "a center, a radius and a power")?;
+                self.list.push(Sdf::mandelbulb(
+                    position(&v[0..3]),
+                    v[3] as f32,
+                    v[4] as f32,
+                    material,
+                ));
+            }
+            "tube" | "ribbon" => {
+                let (v, material) = shape(fields, 14, "four control points and two widths")?;
+                let kind = if fields[0] == "tube" {
+                    CurveKind::Cylinder
+                } else {
+                    CurveKind::Flat
+                };
+                self.list.push(Curve::new(
+                    [0, 3, 6, 9].map(|i| position(&v[i..i + 3])),
+                    [v[12] as f32, v[13] as f32],
+                    kind,
+                    material,
+                ));
+            }
+            "grass" => {
+                let (v, material) =
+                    shape(fields, 8, "a corner, a size, a count, a height and a width")?;
+                let count = count(fields[6], v[5])?;
+                self.list.push(Hair::grass(
+                    position(&v[0..3]),
+                    Vector2::new(v[3], v[4]).cast::<f32>(),
+                    count,
+                    v[6] as f32,
+                    v[7] as f32,
+                    material,
+                    seed(&fields[1..=8]),
+                ));
+            }
+            "fur" => {
+                let (v, material) = shape(
+                    fields,
+                    7,
+                    "a center, a radius, a count, a length and a width",
+                )?;
+                let count = count(fields[5], v[4])?;
+                self.list.push(Hair::fur(
+                    position(&v[0..3]),
+                    v[3] as f32,
+                    count,
+                    v[5] as f32,
+                    v[6] as f32,
+                    material,
+                    seed(&fields[1..=7]),
+                ));
+            }
+            "heightfield" => {
+                // 几何参数之前是文件路径
+                if fields.len() < 9 {
+                    let message = "`heightfield` takes a file, a corner, a size and a material";
+                    return Err(message.to_string().into());
+                }
+                let (v, material) = shape(&fields[1..], 6, "")?;
+                let field = Heightfield::load(
+                    dir.join(fields[1]),
+                    position(&v[0..3]),
+                    vector(&v[3..6]),
+                    material,
+                )
+                .map_err(|err| FieldError::at(fields[1], err.to_string()))?;
+                self.list.push(field);
+            }
+            "mesh" => {
+                // 几何参数之前是文件路径
+                if fields.len() < 7 {
+                    let message = "`mesh` takes a file, a position, a scale and a material";
+                    return Err(message.to_string().into());
+                }
+                // 材质名之前有 5 个数时带有细分层级
+                let count = if fields.get(6).is_some_and(|f| f.parse::<f64>().is_ok()) {
+                    5
+                } else {
+                    4
+                };
+                let (v, material) = shape(&fields[1..], count, "")?;
+                let levels = match v.get(4) {
+                    Some(&level)
+                        if level.fract() != 0.0 || !(0.0..=MAX_LEVELS as f64).contains(&level) =>
+                    {
+                        let message =
+                            format!("subdivision level must be an integer from 0 to {MAX_LEVELS}");
+                        return Err(FieldError::at(fields[6], message));
+                    }
+                    Some(&level) => level as u32,
+                    None => 0,
+                };
+                let file = dir.join(fields[1]);
+                let load = || {
+                    let extension = file.extension().and_then(|ext| ext.to_str());
+                    let geometry = match extension.map(str::to_ascii_lowercase).as_deref() {
+                        Some("ply") => {
+                            mesh_cache::load(&file, material, |path, m| ply::load(path, m))
+                        }
+                        Some("stl") => {
+                            mesh_cache::load(&file, material, |path, m| stl::load(path, m))
+                        }
+                        _ => {
+                            let message = "unknown mesh format, expected `.ply` or `.stl`";
+                            return Err(FieldError::at(fields[1], message.to_string()));
+                        }
+                    }
+                    .map_err(|err| FieldError::at(fields[1], err.to_string()))?;
+
+                    match geometry {
+                        _ if levels == 0 => Ok(geometry),
+                        Geometry::Resident(mesh) => subdivision::subdivide(&mesh, levels)
+                            .map(Geometry::from)
+                            .map_err(|err| FieldError::at(fields[6], err.to_string())),
+                        Geometry::Paged { .. } => {
+                            let message = "mesh exceeds the memory budget and cannot be subdivided";
+                            Err(FieldError::at(fields[1], message.to_string()))
+                        }
+                    }
+                };
+                let (offset, scale) = (position(&v[0..3]), v[3] as f32);
+                let transform = Matrix4::new_translation(&offset) * Matrix4::new_scaling(scale);
+                // 缩放为 0 时变换不可逆
+                let singular = || {
+                    let message = format!("mesh scale must be non-zero, found `{}`", fields[5]);
+                    FieldError::at(fields[5], message)
+                };
+                let place = |shared| Instance::new(shared, transform).ok_or_else(singular);
+
+                if let Material::Emissive { .. } = material {
+                    // 网格光源在世界坐标中采样, 不与其他语句共享
+                    if scale == 0.0 {
+                        return Err(singular());
+                    }
+                    let transform = Similarity3::from_parts(
+                        Translation3::from(offset),
+                        UnitQuaternion::identity(),
+                        scale,
+                    );
+                    self.list.push(Mesh::new(load()?.transformed(&transform)));
+                    parsed.set(None);
+                } else if let Some(shared) = meshes.get(&(file.clone(), levels)) {
+                    self.list.push(
+                        place(shared.clone())?
+                            .with_material(Arc::new([material]), 0)
+                            .expect("single-entry material table"),
+                    );
+                } else {
+                    let geometry = load()?;
+                    if geometry.has_colors() {
+                        parsed.set(None);
+                    }
+                    let shared: Arc<dyn Bounded + Sync + Send> = Arc::new(Mesh::new(geometry));
+                    meshes.insert((file, levels), shared.clone());
+                    self.list.push(place(shared)?);
+                }
+            }
+            "subdivision_surface" => {
+                // 几何参数之前是控制网格的文件路径
+                if fields.len() < 8 {
+                    let message = "`subdivision_surface` takes a file, a position, a scale, a level and a material";
+                    return Err(message.to_string().into());
+                }
+                let (v, material) = shape(&fields[1..], 5, "")?;
+                if v[4].fract() != 0.0 || !(0.0..=MAX_LEVELS as f64).contains(&v[4]) {
+                    let message =
+                        format!("subdivision level must be an integer from 0 to {MAX_LEVELS}");
+                    return Err(FieldError::at(fields[6], message));
+                }
+                let scale = v[3] as f32;
+                if scale == 0.0 {
+                    let message = format!("scale must be non-zero, found `{}`", fields[5]);
+                    return Err(FieldError::at(fields[5], message));
+                }
+
+                let cage = ply::load_cage(dir.join(fields[1]), material)
+                    .map_err(|err| FieldError::at(fields[1], err.to_string()))?;
+                let mesh = subdivision::catmull_clark(&cage, v[4] as u32)
+                    .map_err(|err| FieldError::at(fields[6], err.to_string()))?
+                    .transformed(&Similarity3::from_parts(
+                        Translation3::from(position(&v[0..3])),
+                        UnitQuaternion::identity(),
+                        scale,
+                    ));
+                if mesh.colors().is_some() || matches!(material, Material::Emissive { .. }) {
+                    // 网格光源与带顶点颜色的网格在材质改变时须重建, 与 `mesh` 相同
+                    parsed.set(None);
+                }
+                self.list.push(Mesh::new(mesh));
+            }
+            "point_light" => {
+                let v = numbers(&fields[1..])?;
+                let intensity = match v.len() {
+                    6 => 1.0,
+                    7 => v[6] as f32,
+                    _ => return Err("`point_light` takes 6 or 7 numbers".to_string().into()),
+                };
+                self.lights.push(Light::point(
+                    position(&v[0..3]),
+                    vector(&v[3..6]),
+                    intensity,
+                ));
+            }
+            "spot_light" => {
+                let v = numbers(&fields[1..])?;
+                if !(11..=12).contains(&v.len()) {
+                    return Err("`spot_light` takes 11 or 12 numbers".to_string().into());
+                }
+                let (from, to) = (position(&v[0..3]), position(&v[3..6]));
+                let angle = v[10] as f32;
+                self.lights.push(Light::spot(
+                    from,
+                    to - from,
+                    vector(&v[6..9]),
+                    v[9] as f32,
+                    angle,
+                    v.get(11).map_or(angle, |&inner| inner as f32),
+                ));
+            }
+            "ies_light" => {
+                // 几何参数之前是文件路径
+                let v = numbers(fields.get(2..).unwrap_or_default())?;
+                if v.len() != 10 {
+                    return Err("`ies_light` takes a file and 10 numbers".to_string().into());
+                }
+                let profile = IesProfile::load(dir.join(fields[1]))
+                    .map_err(|err| FieldError::at(fields[1], err.to_string()))?;
+                let (from, to) = (position(&v[0..3]), position(&v[3..6]));
+                self.lights.push(Light::ies(
+                    from,
+                    to - from,
+                    vector(&v[6..9]),
+                    v[9] as f32,
+                    Arc::new(profile),
+                ));
+            }
+            "sun_sky" => {
+                let v = numbers(&fields[1..])?;
+                let turbidity = match v.len() {
+                    4 => 3.0,
+                    5 => v[4] as f32,
+                    _ => return Err("`sun_sky` takes 4 or 5 numbers".to_string().into()),
+                };
+                let (month, day) = (v[1], v[2]);
+                if month.fract() != 0.0 || !(1.0..=12.0).contains(&month) {
+                    return Err(FieldError::at(fields[2], format!("invalid month {month}")));
+                }
+                if day.fract() != 0.0 || !(1.0..=31.0).contains(&day) {
+                    return Err(FieldError::at(fields[3], format!("invalid day {day}")));
+                }
+                self.background = Some(Background::SunSky(SunSky::new(
+                    v[0] as f32,
+                    month as u32,
+                    day as u32,
+                    v[3] as f32,
+                    turbidity,
+                )));
+            }
+            "studio" => {
+                let v = numbers(&fields[1..])?;
+                self.background = Some(match v.len() {
+                    0 => Background::STUDIO,
+                    9 | 10 => Background::Studio {
+                        zenith: vector(&v[0..3]),
+                        horizon: vector(&v[3..6]),
+                        ground: vector(&v[6..9]),
+                        softness: v.get(9).map_or(0.1, |&softness| softness as f32),
+                    },
+                    _ => return Err("`studio` takes 0, 9 or 10 numbers".to_string().into()),
+                });
+            }
+            keyword => return Err(format!("unknown statement `{keyword}`").into()),
+        }
+
+        Ok(parsed.get())
+    }
+}
+
+impl From<HittableList> for SceneFile {
+    /// 只有实体的场景, 实体的材质不能单独替换
+    fn from(list: HittableList) -> Self {
+        let count = list.list.len();
+
+        Self {
+            list,
+            camera: None,
+            lights: vec![],
+            background: None,
+            origin: Vector3::zeros(),
+            materials: MaterialLibrary::new(),
+            material_indices: vec![None; count],
+            geometry: 0,
+        }
+    }
+}
+
+/// 内置的材质名, 不能用作具名材质的名称
+const BUILTIN_MATERIALS: [&str; 4] = ["lambertian", "metal", "dielectric", "emissive"];
+
+/// 解析材质名及其参数, 如 `metal 0.8 0.6 0.2 0.05`, 写法与场景文件中形状语句的材质部分相同
+pub fn parse_material<'a>(fields: &[&'a str]) -> std::result::Result<Material, FieldError<'a>> {
+    let Some((name, params)) = fields.split_first() else {
+        return Err("expected a material".to_string().into());
+    };
+    let params = numbers(params)?;
+    let color = |v: &[f64]| Vector3::new(v[0], v[1], v[2]).cast::<f32>();
+
+    match (*name, params.len()) {
+        ("lambertian", 3) => Ok(Material::lambertian(color(&params))),
+        ("metal", 4) => Ok(Material::metal(color(&params), params[3] as f32)),
+        ("dielectric", 1) => Ok(Material::dielectric(params[0] as f32)),
+        ("emissive", 3) => Ok(Material::emissive(color(&params), 1.0)),
+        ("emissive", 4) => Ok(Material::emissive(color(&params), params[3] as f32)),
+        (name, _) => Err(FieldError::at(
+            fields[0],
+            format!("unknown material or parameters `{name}`"),
+        )),
+    }
+}
+
+/// 字段 `field` 中的个数 `value`, 须为正整数
+fn count(field: &str, value: f64) -> std::result::Result<usize, FieldError<'_>> {
+    if value.fract() != 0.0 || value < 1.0 {
+        return Err(FieldError::at(
+            field,
+            format!("expected a positive count, found `{field}`"),
+        ));
+    }
+
+    Ok(value as usize)
+}
+
+/// 由几何参数的字段确定的随机种子, 同样的语句生成同样的随机分布
+fn seed(fields: &[&str]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    fields.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// 以双精度解析一组数
+fn numbers<'a>(fields: &[&'a str]) -> std::result::Result<Vec<f64>, FieldError<'a>> {
+    fields
+        .iter()
+        .map(|field| {
+            field
+                .parse::<f64>()
+                .map_err(|_| FieldError::at(field, format!("expected a number, found `{field}`")))
+        })
+        .collect()
+}
+
+/// 语句中的错误, `field` 为出错的字段, 为 `None` 时指整条语句
+pub struct FieldError<'a> {
+    pub field: Option<&'a str>,
+    pub message: String,
+}
+
+impl<'a> FieldError<'a> {
+    /// 字段 `field` 处的错误
+    pub const fn at(field: &'a str, message: String) -> Self {
+        Self {
+            field: Some(field),
+            message,
+        }
+    }
+}
+
+impl From<String> for FieldError<'_> {
+    fn from(message: String) -> Self {
+        Self {
+            field: None,
+            message,
+        }
+    }
+}
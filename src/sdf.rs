@@ -0,0 +1,315 @@
+use std::f32::consts::PI;
+use std::sync::Arc;
+
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+
+use nalgebra::{Point3, Similarity3, Vector2, Vector3};
+
+/// 每条光线最多步进的次数, 用尽时视为未击中
+const MAX_STEPS: usize = 256;
+
+/// 越过表面后二分细化交点的次数
+const REFINE_STEPS: usize = 16;
+
+/// 求最近点时沿梯度投影的次数
+const PROJECTION_STEPS: usize = 4;
+
+/// 判定击中的距离与差分步长相对包围盒对角线的比例
+const EPSILON: f32 = 1e-4;
+
+/// 曼德尔球的迭代次数
+const MANDELBULB_ITERATIONS: usize = 10;
+
+/// 曼德尔球在单位尺度下的外接半径
+const MANDELBULB_EXTENT: f32 = 1.2;
+
+/// 有符号距离函数: 点到表面的距离, 位于内部时为负
+pub type DistanceFn = dyn Fn(Vector3<f32>) -> f32 + Send + Sync;
+
+/// 有符号距离场: 以球面追踪 (sphere tracing) 求交的隐式曲面, 截取在包围盒内的部分
+///
+/// 距离函数可以是任意闭包, 只需不高估到表面的距离; 平滑混合与分形等只给出距离下界的函数
+/// 可用 `with_step_scale` 缩小步长以免越过细小的结构. 法线由距离函数的中心差分估计
+#[derive(Clone)]
+pub struct Sdf {
+    /// 有符号距离函数
+    distance: Arc<DistanceFn>,
+
+    /// 截取范围, 同时限定步进的区间
+    bounds: AaBb,
+
+    /// 材质
+    material: Material,
+
+    /// 步长相对距离函数值的比例
+    step_scale: f32,
+
+    /// 判定击中的距离, 也是中心差分的步长
+    epsilon: f32,
+}
+
+impl Sdf {
+    /// 由有符号距离函数 `distance` 与截取范围构建隐式曲面
+    pub fn new(
+        distance: impl Fn(Vector3<f32>) -> f32 + Send + Sync + 'static,
+        bounds: AaBb,
+        material: Material,
+    ) -> Self {
+        Self::from_arc(Arc::new(distance), bounds, material)
+    }
+
+    fn from_arc(distance: Arc<DistanceFn>, bounds: AaBb, material: Material) -> Self {
+        let epsilon = (bounds.max - bounds.min).magnitude() * EPSILON;
+
+        Self {
+            distance,
+            bounds,
+            material,
+            step_scale: 1.0,
+            epsilon,
+        }
+    }
+
+    /// 以 `center` 为中心, 环绕 y 轴的圆环, 环的半径为 `major`, 管的半径为 `minor`
+    pub fn torus(center: Vector3<f32>, major: f32, minor: f32, material: Material) -> Self {
+        let extent = Vector3::new(major + minor, minor, major + minor);
+        let bounds = AaBb {
+            min: center - extent,
+            max: center + extent,
+        };
+
+        Self::new(
+            move |p| {
+                let q = p - center;
+                Vector2::new(q.xz().magnitude() - major, q.y).magnitude() - minor
+            },
+            bounds,
+            material,
+        )
+    }
+
+    /// 以 `center` 为中心, 外接半径为 `radius` 的 `power` 次曼德尔球 (三维分形), 极轴沿 y 轴
+    pub fn mandelbulb(center: Vector3<f32>, radius: f32, power: f32, material: Material) -> Self {
+        let scale = radius / MANDELBULB_EXTENT;
+        let extent = Vector3::repeat(radius);
+        let bounds = AaBb {
+            min: center - extent,
+            max: center + extent,
+        };
+
+        Self::new(
+            move |p| {
+                let q = (p - center) / scale;
+                scale * mandelbulb(Vector3::new(q.x, q.z, q.y), power)
+            },
+            bounds,
+            material,
+        )
+        .with_step_scale(0.8)
+    }
+
+    /// 以步长比例 `step_scale` (0 到 1) 步进, 距离函数只是距离的下界估计时使用
+    pub const fn with_step_scale(mut self, step_scale: f32) -> Self {
+        self.step_scale = step_scale;
+        self
+    }
+
+    /// 与 `other` 平滑并合 (多项式平滑最小值), 接缝处在宽度约 `k` 的范围内过渡, 使用自身的材质
+    pub fn smooth_union(&self, other: &Self, k: f32) -> Self {
+        let (a, b) = (self.distance.clone(), other.distance.clone());
+        let k = k.max(f32::EPSILON);
+        // 平滑最小值不小于两者的最小值减去 k / 4
+        let margin = Vector3::repeat(k / 4.0);
+        let bounds = AaBb::surrounding_box(&self.bounds, &other.bounds);
+        let bounds = AaBb {
+            min: bounds.min - margin,
+            max: bounds.max + margin,
+        };
+
+        Self::from_arc(
+            Arc::new(move |p| {
+                let (da, db) = (a(p), b(p));
+                let h = (0.5 + 0.5 * (db - da) / k).clamp(0.0, 1.0);
+                db + (da - db) * h - k * h * (1.0 - h)
+            }),
+            bounds,
+            self.material,
+        )
+        .with_step_scale(self.step_scale.min(other.step_scale))
+    }
+
+    /// 经相似变换后的隐式曲面: 距离为局部空间中的距离乘以缩放, 截取范围取变换后包围盒的包围盒
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        let distance = self.distance.clone();
+        let (inverse, scale) = (transform.inverse(), transform.scaling());
+        let corners = self
+            .bounds
+            .corners()
+            .map(|corner| transform.transform_point(&Point3::from(corner)).coords);
+        let bounds = AaBb {
+            min: corners.iter().fold(corners[0], |a, c| a.inf(c)),
+            max: corners.iter().fold(corners[0], |a, c| a.sup(c)),
+        };
+
+        Self::from_arc(
+            Arc::new(move |p| scale * distance(inverse.transform_point(&Point3::from(p)).coords)),
+            bounds,
+            self.material,
+        )
+        .with_step_scale(self.step_scale)
+    }
+
+    /// `point` 处的有符号距离
+    pub fn distance(&self, point: &Vector3<f32>) -> f32 {
+        (self.distance)(*point)
+    }
+
+    /// `point` 处距离函数的梯度方向 (由中心差分估计), 即表面的外法线
+    pub fn normal(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        let h = self.epsilon;
+        let gradient = Vector3::from_fn(|axis, _| {
+            let offset = Vector3::ith(axis, h);
+            self.distance(&(point + offset)) - self.distance(&(point - offset))
+        });
+
+        gradient
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y)
+    }
+}
+
+/// 单位尺度下 `power` 次曼德尔球的距离估计
+fn mandelbulb(point: Vector3<f32>, power: f32) -> f32 {
+    let mut z = point;
+    let mut dr = 1.0;
+    let mut r = z.magnitude();
+    for _ in 0..MANDELBULB_ITERATIONS {
+        if r > 2.0 {
+            break;
+        }
+        // 球坐标下模长取 power 次幂, 两个角乘以 power
+        let theta = (z.z / r).clamp(-1.0, 1.0).acos() * power;
+        let phi = z.y.atan2(z.x) * power;
+        dr = r.powf(power - 1.0) * power * dr + 1.0;
+        z = r.powf(power)
+            * Vector3::new(
+                theta.sin() * phi.cos(),
+                theta.sin() * phi.sin(),
+                theta.cos(),
+            )
+            + point;
+        r = z.magnitude();
+    }
+
+    if r <= f32::EPSILON {
+        return 0.0;
+    }
+
+    0.5 * r.ln() * r / dr
+}
+
+/// 在 `outside` (与起点同侧) 与 `inside` (越过表面) 之间二分光线上的交点, 返回同侧一端
+fn refine(signed: impl Fn(f32) -> f32, side: f32, mut outside: f32, mut inside: f32) -> f32 {
+    for _ in 0..REFINE_STEPS {
+        let middle = (outside + inside) / 2.0;
+        if side * signed(middle) < 0.0 {
+            inside = middle;
+        } else {
+            outside = middle;
+        }
+    }
+
+    outside
+}
+
+impl Hittable for Sdf {
+    /// 在光线穿过包围盒的区间内步进: 每步前进距离函数的值, 距离小于容差或越过表面时击中
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (enter, exit) = self.bounds.span(ray);
+        let (start, end) = (enter.max(t_min), exit.min(t_max));
+        if start >= end {
+            return None;
+        }
+
+        let length = ray.direction().magnitude();
+        let signed = |t: f32| self.distance(&ray.point_at_t(t));
+
+        // 起点离表面不足容差时 (如从表面出发的次级光线) 先离开容差带, 再按起点所在的一侧判定击中
+        let mut side = 1.0;
+        let mut armed = false;
+        let (mut previous, mut t) = (start, start);
+        let mut found = None;
+        for _ in 0..MAX_STEPS {
+            let value = signed(t);
+            if !armed {
+                side = if value < 0.0 { -1.0 } else { 1.0 };
+                armed = value.abs() >= self.epsilon;
+            }
+            let d = side * value;
+            if armed && d < 0.0 {
+                found = Some(refine(signed, side, previous, t));
+                break;
+            }
+            if armed && d < self.epsilon {
+                found = Some(t);
+                break;
+            }
+            if t > end {
+                break;
+            }
+            previous = t;
+            t += (d * self.step_scale).max(self.epsilon) / length;
+        }
+
+        let t = found.filter(|&t| t > t_min && t <= end)?;
+        let position = ray.point_at_t(t);
+        let normal = self.normal(&position);
+
+        // 纹理坐标取自包围盒中心看去的经纬度
+        let center = (self.bounds.min + self.bounds.max) / 2.0;
+        let direction = (position - center)
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y);
+        let theta = (-direction.y).clamp(-1.0, 1.0).acos();
+        let phi = direction.z.atan2(-direction.x).rem_euclid(2.0 * PI);
+        let radius = (self.bounds.max - self.bounds.min).magnitude() / 2.0;
+
+        Some(HitRecord {
+            distance: t,
+            position,
+            normal,
+            uv: (phi / (2.0 * PI), theta / PI),
+            footprint: ray.cone().width_at(t * length) / (PI * radius),
+            material: self.material,
+            object: 0,
+        })
+    }
+}
+
+impl Bounded for Sdf {
+    fn bounding_box(&self) -> AaBb {
+        self.bounds.clone()
+    }
+
+    /// 沿梯度投影到曲面上 (近似的最近点), 位于内部时距离为负
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let mut position = *point;
+        for _ in 0..PROJECTION_STEPS {
+            position -= self.distance(&position) * self.normal(&position);
+        }
+        let position = position.zip_zip_map(&self.bounds.min, &self.bounds.max, |p, lo, hi| {
+            p.clamp(lo, hi)
+        });
+
+        ClosestPoint {
+            object: 0,
+            position,
+            distance: (point - position)
+                .magnitude()
+                .copysign(self.distance(point)),
+        }
+    }
+}
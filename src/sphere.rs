@@ -1,9 +1,28 @@
-use crate::bvh::{AaBb, Bounded};
+use std::collections::HashMap;
+use std::f32::consts::{FRAC_PI_4, PI, TAU};
+
+use crate::bake::Parameterized;
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::error::{Error, Result};
 use crate::hittable::{HitRecord, Hittable};
 use crate::material::Material;
+use crate::mesh::TriangleMesh;
 use crate::ray::Ray;
+use crate::subdivision::MAX_LEVELS;
+
+use nalgebra::{Point3, Similarity3, Vector3, Vector4};
+
+/// 球面剖分为三角形网格的方式
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Tessellation {
+    /// 立方体球: 立方体的六个面各分为 `2^level × 2^level` 格 (按等角度分布) 后投影到球面,
+    /// 三角形的大小较为均匀, 没有极点处的聚集
+    Quad,
 
-use nalgebra::Vector3;
+    /// 经纬球: `4 · 2^level` 条经线与 `2 · 2^level` 条纬线带, 网格与纹理坐标的经纬线对齐,
+    /// 两极附近的三角形细而密
+    Uv,
+}
 
 /// 球体
 #[derive(Clone)]
@@ -27,6 +46,99 @@ impl Sphere {
         }
     }
 
+    /// 单位法线对应的纹理坐标, 与 `surface_at` 互逆
+    fn uv(normal: &Vector3<f32>) -> (f32, f32) {
+        let theta = (-normal.y).clamp(-1.0, 1.0).acos();
+        let phi = normal.z.atan2(-normal.x).rem_euclid(std::f32::consts::TAU);
+
+        (phi / std::f32::consts::TAU, theta / std::f32::consts::PI)
+    }
+
+    /// 替换材质后的球体
+    pub const fn with_material(mut self, material: Material) -> Self {
+        self.material = material;
+        self
+    }
+
+    pub const fn material(&self) -> &Material {
+        &self.material
+    }
+
+    /// 经相似变换 (平移, 旋转与均匀缩放) 后的球体, 纹理坐标仍按世界坐标的朝向计算
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        Self {
+            center: transform.transform_point(&Point3::from(self.center)).coords,
+            radius: self.radius * transform.scaling(),
+            material: self.material,
+        }
+    }
+
+    /// 剖分为 `level` 级 (不超过 `MAX_LEVELS`) 的三角形网格, 用于位移或统一的网格流程;
+    /// 顶点位于球面上, 法线取球面法线, 纹理坐标与解析的球体相同
+    ///
+    /// 纹理坐标的接缝 (u = 0 与 1) 与两极处的顶点按所在的三角形拆分, 其余顶点共享
+    pub fn tessellate(&self, kind: Tessellation, level: u32) -> Result<TriangleMesh> {
+        if level > MAX_LEVELS {
+            return Err(Error::InvalidSettings(format!(
+                "tessellation level {level} exceeds the maximum of {MAX_LEVELS}"
+            )));
+        }
+        let n = 1usize << level;
+        let triangles = match kind {
+            Tessellation::Quad => quad_sphere(n),
+            Tessellation::Uv => uv_sphere(4 * n, 2 * n),
+        };
+
+        let mut vertices = HashMap::new();
+        let (mut normals, mut uvs, mut indices) = (vec![], vec![], vec![]);
+        for mut triangle in triangles {
+            let [a, b, c] = triangle;
+            let cross = (b - a).cross(&(c - a));
+            if cross.norm() <= f32::EPSILON * a.norm() {
+                continue;
+            }
+            // 逆时针 (从球外看) 为正面
+            if cross.dot(&(a + b + c)) < 0.0 {
+                triangle.swap(1, 2);
+            }
+
+            // 三角形内的纹理坐标以重心处为准展开: 跨过接缝的 u 移到同一侧, 极点取重心处的 u
+            let (u_center, _) = Self::uv(&(a + b + c).normalize());
+            let face = triangle.map(|normal| {
+                let (u, v) = Self::uv(&normal);
+                let u = if normal.x.abs().max(normal.z.abs()) <= f32::EPSILON {
+                    u_center
+                } else {
+                    u - (u - u_center).round()
+                };
+                let key = [normal.x, normal.y, normal.z, u, v].map(|x| (x + 0.0).to_bits());
+
+                *vertices.entry(key).or_insert_with(|| {
+                    normals.push(normal);
+                    uvs.push((u, v));
+                    normals.len() as u32 - 1
+                })
+            });
+            indices.push(face);
+        }
+
+        let positions = normals
+            .iter()
+            .map(|normal| self.center + self.radius * normal)
+            .collect();
+
+        TriangleMesh::new(positions, indices, self.material)?
+            .with_normals(normals)?
+            .with_uvs(uvs)
+    }
+
+    /// 光线锥在距离为 `t` 的交点处覆盖的纹理坐标宽度 (按经线方向的长度 πr 换算)
+    fn footprint(&self, ray: &Ray, t: f32) -> f32 {
+        let width = ray.cone().width_at(t * ray.direction().magnitude());
+
+        width / (std::f32::consts::PI * self.radius)
+    }
+
     /// 球体是否重合
     pub fn overlaps(center: Vector3<f32>, radius: f32, other: &Self) -> bool {
         let d = center - other.center;
@@ -50,50 +162,119 @@ impl Sphere {
     }
 }
 
+/// 立方体球的三角形 (单位方向), 立方体每个面分为 `n × n` 格
+///
+/// 格点在面内按等角度分布 (`tan` 映射), 比均匀分布投影后的大小更一致;
+/// 共享的棱上的格点在各面中坐标相同, 投影后完全重合
+fn quad_sphere(n: usize) -> Vec<[Vector3<f32>; 3]> {
+    // 格点在面内的坐标, 两端恰为 ±1 且关于中点对称
+    let mut warp: Vec<f32> = (0..=n)
+        .map(|i| (FRAC_PI_4 * (2.0 * i as f32 / n as f32 - 1.0)).tan())
+        .collect();
+    warp[0] = -1.0;
+    for i in 0..n.div_ceil(2) {
+        warp[n - i] = -warp[i];
+    }
+
+    let mut triangles = vec![];
+    for axis in 0..3 {
+        for side in [-1.0, 1.0] {
+            let point = |i: usize, j: usize| {
+                let mut point = Vector3::zeros();
+                point[axis] = side;
+                point[(axis + 1) % 3] = warp[i];
+                point[(axis + 2) % 3] = warp[j];
+
+                point.normalize()
+            };
+            for i in 0..n {
+                for j in 0..n {
+                    let [p00, p10, p11, p01] =
+                        [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)].map(|(i, j)| point(i, j));
+                    triangles.push([p00, p10, p11]);
+                    triangles.push([p00, p11, p01]);
+                }
+            }
+        }
+    }
+
+    triangles
+}
+
+/// 经纬球的三角形 (单位方向), `segments` 条经线, `rings` 条纬线带, 与 `Sphere::uv` 的参数化一致
+fn uv_sphere(segments: usize, rings: usize) -> Vec<[Vector3<f32>; 3]> {
+    // 经度取模, 使 u = 1 处的格点与 u = 0 处完全重合; 两极处纬线圈的半径恰为 0
+    let point = |i: usize, j: usize| {
+        let theta = PI * j as f32 / rings as f32;
+        let phi = TAU * (i % segments) as f32 / segments as f32;
+        let ring = if j == 0 || j == rings {
+            0.0
+        } else {
+            theta.sin()
+        };
+
+        Vector3::new(-phi.cos() * ring, -theta.cos(), phi.sin() * ring)
+    };
+
+    let mut triangles = vec![];
+    for i in 0..segments {
+        for j in 0..rings {
+            let [p00, p10, p11, p01] =
+                [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)].map(|(i, j)| point(i, j));
+            // 极点处的一个三角形退化, 由 `tessellate` 跳过
+            triangles.push([p00, p10, p11]);
+            triangles.push([p00, p11, p01]);
+        }
+    }
+
+    triangles
+}
+
 impl Hittable for Sphere {
     /// 光线与球体相交
+    ///
+    /// 以双精度求解: 巨大的球体 (如作为地面的大球) 或远离原点的场景在单精度下因相消产生条纹
     fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
         // 光线起点到球心的向量
-        let oc = ray.origin() - self.center;
+        let oc = ray.origin().cast::<f64>() - self.center.cast::<f64>();
 
         // 方程系数
-        let d = ray.direction();
+        let d = ray.direction().cast::<f64>();
         let a = d.dot(&d);
         let b = oc.dot(&d);
-        let c = oc.dot(&oc) - self.radius * self.radius;
 
-        // 判定式
-        let disc = b.powi(2) - a * c;
+        // 判定式, 由球心到光线所在直线的距离给出, 比 b² - ac 的相消更小
+        let l = oc - (b / a) * d;
+        let r = f64::from(self.radius);
+        let disc = a * (r * r - l.dot(&l));
 
         if disc > 0.0 {
             let sqrt_disc = disc.sqrt();
 
-            // 交点 1
-            let t = (-b - sqrt_disc) / a;
-            if t > t_min && t < t_max {
-                let p = ray.point_at_t(t);
-                let normal = (p - self.center) / self.radius;
-
-                return Some(HitRecord {
-                    distance: t,
-                    position: p,
-                    normal,
-                    material: self.material,
-                });
-            }
+            // 交点 1 与交点 2
+            for t in [(-b - sqrt_disc) / a, (-b + sqrt_disc) / a] {
+                let t = t as f32;
+                if t > t_min && t < t_max {
+                    let p = ray.point_at_t(t);
+                    let normal = (p - self.center) / self.radius;
+                    let uv = Self::uv(&normal);
+                    let footprint = self.footprint(ray, t);
+
+                    // 切线沿 u (经度) 增大的方向, 副切线朝 v 增大的方向 (向上)
+                    let tangent = Vector4::new(normal.z, 0.0, -normal.x, 1.0);
 
-            // 交点 2
-            let t = (-b + sqrt_disc) / a;
-            if t > t_min && t < t_max {
-                let p = ray.point_at_t(t);
-                let normal = (p - self.center) / self.radius;
-
-                return Some(HitRecord {
-                    distance: t,
-                    position: p,
-                    normal,
-                    material: self.material,
-                });
+                    return Some(HitRecord {
+                        distance: t,
+                        position: p,
+                        normal: self
+                            .material
+                            .perturb_normal(&normal, &tangent, uv, footprint),
+                        uv,
+                        footprint,
+                        material: self.material,
+                        object: 0,
+                    });
+                }
             }
         }
 
@@ -108,4 +289,125 @@ impl Bounded for Sphere {
 
         AaBb { min, max }
     }
+
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let offset = point - self.center;
+        let direction = offset
+            .try_normalize(f32::EPSILON)
+            .unwrap_or_else(Vector3::y);
+
+        ClosestPoint {
+            object: 0,
+            position: self.center + self.radius * direction,
+            distance: offset.magnitude() - self.radius,
+        }
+    }
+}
+
+impl Parameterized for Sphere {
+    /// 球面参数化: u 对应绕 y 轴的方位角, v 从南极 (0) 到北极 (1)
+    fn surface_at(&self, u: f32, v: f32) -> (Vector3<f32>, Vector3<f32>) {
+        let theta = v * std::f32::consts::PI;
+        let phi = u * std::f32::consts::PI * 2.0;
+        let normal = Vector3::new(
+            -phi.cos() * theta.sin(),
+            -theta.cos(),
+            phi.sin() * theta.sin(),
+        );
+
+        (self.center + self.radius * normal, normal)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::mesh::Mesh;
+
+    fn sphere() -> Sphere {
+        Sphere::from(Vector3::new(1.0, 2.0, 3.0), 2.0, Material::CLAY)
+    }
+
+    /// 每条边 (按端点的位置) 恰好被两个面共享
+    fn assert_closed(mesh: &TriangleMesh) {
+        let key = |i: u32| mesh.positions()[i as usize].map(f32::to_bits);
+        let mut edges = HashMap::new();
+        for face in mesh.indices() {
+            for k in 0..3 {
+                let (a, b) = (key(face[k]), key(face[(k + 1) % 3]));
+                let edge = if a.as_slice() < b.as_slice() {
+                    (a, b)
+                } else {
+                    (b, a)
+                };
+                *edges.entry(edge).or_insert(0) += 1;
+            }
+        }
+
+        assert!(edges.values().all(|&count| count == 2));
+    }
+
+    #[test]
+    fn tessellated_spheres_are_closed_and_on_the_surface() {
+        let sphere = sphere();
+        for (kind, level, faces) in [
+            (Tessellation::Quad, 0, 12),
+            (Tessellation::Quad, 2, 6 * 2 * 16),
+            (Tessellation::Uv, 0, 4 * 2 * 2 - 2 * 4),
+            (Tessellation::Uv, 2, 16 * 8 * 2 - 2 * 16),
+        ] {
+            let mesh = sphere.tessellate(kind, level).unwrap();
+            assert_eq!(mesh.face_count(), faces, "{kind:?} level {level}");
+            assert_closed(&mesh);
+
+            for (position, normal) in mesh.positions().iter().zip(mesh.normals().unwrap()) {
+                assert!(((position - sphere.center).norm() - sphere.radius).abs() < 1e-5);
+                assert!((normal - (position - sphere.center) / sphere.radius).norm() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn tessellated_uvs_match_the_analytic_sphere_away_from_the_seam() {
+        let mesh = sphere().tessellate(Tessellation::Quad, 3).unwrap();
+        let uvs = mesh.uvs().unwrap();
+
+        for (normal, &(u, v)) in mesh.normals().unwrap().iter().zip(uvs) {
+            let (expected_u, expected_v) = Sphere::uv(normal);
+            assert!((v - expected_v).abs() < 1e-5);
+            if normal.x.abs().max(normal.z.abs()) > 1e-3 && u > 1e-3 && u < 1.0 - 1e-3 {
+                assert!((u - expected_u).abs() < 1e-5);
+            }
+        }
+
+        // 跨过接缝的面的纹理坐标不绕过整个球面
+        for face in mesh.indices() {
+            let u = face.map(|i| uvs[i as usize].0);
+            let spread = u.iter().copied().fold(f32::MIN, f32::max)
+                - u.iter().copied().fold(f32::MAX, f32::min);
+            assert!(spread < 0.5, "{u:?}");
+        }
+    }
+
+    #[test]
+    fn tessellated_sphere_is_hit_near_the_analytic_surface() {
+        let sphere = sphere();
+        let mesh = Mesh::new(sphere.tessellate(Tessellation::Uv, 4).unwrap());
+        let ray = Ray::from(Vector3::new(1.3, 2.2, -10.0), Vector3::new(0.0, 0.0, 1.0));
+
+        let exact = sphere.hit(&ray, 0.0, f32::INFINITY).unwrap();
+        let approximate = mesh.hit(&ray, 0.0, f32::INFINITY).unwrap();
+        assert!((exact.distance - approximate.distance).abs() < 0.02);
+        assert!((exact.normal - approximate.normal).norm() < 0.02);
+    }
+
+    #[test]
+    fn tessellation_levels_are_capped() {
+        assert!(
+            sphere()
+                .tessellate(Tessellation::Quad, MAX_LEVELS + 1)
+                .is_err()
+        );
+    }
 }
@@ -0,0 +1,126 @@
+use std::sync::Mutex;
+#[cfg(not(feature = "benchmark"))]
+use std::sync::atomic::AtomicUsize;
+use std::sync::atomic::Ordering;
+use std::time::Instant;
+
+use crate::accelerator::Accelerator;
+use crate::camera::Camera;
+use crate::film::{Film, Window};
+use crate::render::{Framebuffer, INTERRUPTED, RenderSettings, camera_sample};
+use crate::rng::{self, get_rng};
+use crate::sampler::build_sampler;
+#[cfg(not(feature = "benchmark"))]
+use crate::stats::Throughput;
+
+use rayon::prelude::*;
+
+/// 分散写入的路径追踪: 采样在像素内均匀抖动, 按重建滤波器的权重贡献给半径内的相邻像素,
+/// 像素值为权重归一化的和; 被中断时只有已完成的行有贡献
+pub fn render(
+    scene: &dyn Accelerator,
+    camera: &Camera,
+    nx: usize,
+    ny: usize,
+    ns: usize,
+    settings: &RenderSettings,
+) -> Framebuffer {
+    let spp = build_sampler(settings.sampler, ns, get_rng(), false).samples_per_pixel();
+    let filter = &settings.filter;
+
+    // 每行的耗时与是否完成, 首行为图像顶部
+    let times = Mutex::new(vec![0.0; nx * ny]);
+    let rows_done = Mutex::new(vec![false; ny]);
+
+    #[cfg(not(feature = "benchmark"))]
+    let finished_count = AtomicUsize::new(0);
+    #[cfg(not(feature = "benchmark"))]
+    let timer = Instant::now();
+    #[cfg(not(feature = "benchmark"))]
+    let throughput = Throughput::default();
+
+    // 将第 y 行的采样累积到 `film`
+    let render_row = |mut film: Film, y: usize| {
+        if INTERRUPTED.load(Ordering::SeqCst) {
+            return film;
+        }
+
+        let mut sampler = build_sampler(settings.sampler, ns, get_rng(), settings.cp_rotation);
+        let mut row_times = Vec::with_capacity(nx);
+        for x in 0..nx {
+            if let Some(rng) = rng::start_pixel((x, y), 0) {
+                sampler = build_sampler(settings.sampler, ns, rng, settings.cp_rotation);
+            }
+            let start = Instant::now();
+            for index in 0..spp {
+                sampler.start_sample((x, y), index);
+                let (dx, dy) = sampler.next_2d();
+                let (px, py) = (x as f32 + dx, y as f32 + dy);
+                let ray = camera.camera_ray(px / nx as f32, py / ny as f32);
+                let (color, alpha) = camera_sample(ray, scene, settings);
+                film.splat((px, py), &color, alpha, filter);
+            }
+            row_times.push(start.elapsed().as_secs_f32());
+        }
+
+        let row_index = ny - 1 - y;
+        times.lock().unwrap()[row_index * nx..(row_index + 1) * nx].copy_from_slice(&row_times);
+        rows_done.lock().unwrap()[row_index] = true;
+
+        // 更新进度
+        #[cfg(not(feature = "benchmark"))]
+        {
+            let count = finished_count.fetch_add(1, Ordering::SeqCst) + 1;
+            let elapsed = timer.elapsed().as_millis() as usize;
+            let remaining = ny - count;
+            eprint!(
+                "\rRemaining: {:>4} | ETA: {:>4}s | {}",
+                remaining,
+                remaining * (elapsed / count) / 1000,
+                throughput.summary()
+            );
+        }
+
+        film
+    };
+
+    let film = if rng::is_deterministic() {
+        // 每行累积到只覆盖其滤波半径的胶片, 再按行序依次合并, 浮点累加的顺序与线程调度无关
+        let reach = filter.radius().ceil() as usize + 1;
+        let films: Vec<Film> = (0..ny)
+            .into_par_iter()
+            .rev()
+            .map(|y| {
+                let window = Window {
+                    x0: 0,
+                    y0: y.saturating_sub(reach),
+                    x1: nx,
+                    y1: y + reach + 1,
+                };
+                render_row(Film::with_window(nx, ny, window), y)
+            })
+            .collect();
+        films.into_iter().fold(Film::new(nx, ny), Film::merged)
+    } else {
+        (0..ny)
+            .into_par_iter()
+            .rev()
+            .fold(|| Film::new(nx, ny), render_row)
+            .reduce(|| Film::new(nx, ny), Film::merged)
+    };
+
+    #[cfg(not(feature = "benchmark"))]
+    {
+        eprintln!(
+            "\rRendered in {:.1}s{}",
+            timer.elapsed().as_secs_f32(),
+            " ".repeat(80)
+        );
+    }
+
+    Framebuffer {
+        rows_done: rows_done.into_inner().unwrap(),
+        times: Some(times.into_inner().unwrap()),
+        ..film.to_framebuffer(settings)
+    }
+}
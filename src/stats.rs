@@ -0,0 +1,239 @@
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+
+/// 计数的事件
+#[derive(Clone, Copy, Debug)]
+pub enum Counter {
+    /// 与场景求交的光线
+    Rays,
+
+    /// 追踪的路径
+    Paths,
+
+    /// 像素采样
+    Samples,
+}
+
+/// 一个线程的各项计数
+type Counts = [AtomicU64; 3];
+
+/// 各线程的计数器, 每个线程只写入自己的计数器, 读取时求和
+static COUNTERS: Mutex<Vec<&'static Counts>> = Mutex::new(vec![]);
+
+thread_local! {
+    /// 本线程的计数器, 首次使用时创建并登记, 随进程存在
+    static COUNTS: &'static Counts = {
+        let counts: &'static Counts = Box::leak(Box::default());
+        COUNTERS.lock().unwrap().push(counts);
+        counts
+    };
+}
+
+/// 记录 `n` 次事件
+pub fn count(counter: Counter, n: u64) {
+    COUNTS.with(|counts| {
+        // 只有本线程写入, 无需原子的读-改-写
+        let count = &counts[counter as usize];
+        count.store(count.load(Ordering::Relaxed) + n, Ordering::Relaxed);
+    });
+}
+
+/// 所有线程的事件总数
+pub fn total(counter: Counter) -> u64 {
+    COUNTERS
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|counts| counts[counter as usize].load(Ordering::Relaxed))
+        .sum()
+}
+
+/// 一个线程中各实体的求交次数与击中次数, 下标为实体的编号
+type ObjectCounts = Mutex<Vec<[u64; 2]>>;
+
+/// 是否统计各实体的求交次数, 有额外的开销, 只在统计模式下开启
+static OBJECT_STATS: AtomicBool = AtomicBool::new(false);
+
+/// 各线程的实体计数, 每个线程只写入自己的计数, 读取时求和
+static OBJECT_COUNTERS: Mutex<Vec<&'static ObjectCounts>> = Mutex::new(vec![]);
+
+thread_local! {
+    /// 本线程的实体计数, 首次使用时创建并登记, 随进程存在
+    static OBJECT_COUNTS: &'static ObjectCounts = {
+        let counts: &'static ObjectCounts = Box::leak(Box::default());
+        OBJECT_COUNTERS.lock().unwrap().push(counts);
+        counts
+    };
+}
+
+/// 开启各实体求交次数的统计
+pub fn enable_object_stats() {
+    OBJECT_STATS.store(true, Ordering::Relaxed);
+}
+
+/// 记录实体 `object` 的一次求交, `hit` 为是否击中; 未开启统计时不做任何事
+pub fn count_object(object: usize, hit: bool) {
+    if !OBJECT_STATS.load(Ordering::Relaxed) {
+        return;
+    }
+
+    OBJECT_COUNTS.with(|counts| {
+        // 只有本线程写入, 锁没有竞争
+        let mut counts = counts.lock().unwrap();
+        if counts.len() <= object {
+            counts.resize(object + 1, [0; 2]);
+        }
+        counts[object][0] += 1;
+        counts[object][1] += u64::from(hit);
+    });
+}
+
+/// 所有线程中各实体的求交次数与击中次数, 下标为实体的编号
+pub fn object_totals() -> Vec<[u64; 2]> {
+    let mut totals: Vec<[u64; 2]> = vec![];
+    for counts in OBJECT_COUNTERS.lock().unwrap().iter() {
+        let counts = counts.lock().unwrap();
+        if totals.len() < counts.len() {
+            totals.resize(counts.len(), [0; 2]);
+        }
+        for (total, count) in totals.iter_mut().zip(counts.iter()) {
+            total[0] += count[0];
+            total[1] += count[1];
+        }
+    }
+
+    totals
+}
+
+/// 一个实体的求交统计
+pub struct ObjectStats {
+    pub name: String,
+
+    /// 求交次数
+    pub tests: u64,
+
+    /// 击中次数
+    pub hits: u64,
+}
+
+/// 自创建起的吞吐量
+pub struct Throughput {
+    start: Instant,
+
+    /// 创建时的各项计数
+    base: [u64; 3],
+}
+
+impl Default for Throughput {
+    fn default() -> Self {
+        Self {
+            start: Instant::now(),
+            base: [Counter::Rays, Counter::Paths, Counter::Samples].map(total),
+        }
+    }
+}
+
+impl Throughput {
+    /// 用于进度行的吞吐量, 如 `12.34 Mrays/s | 0.51 Msamples/s | 0.52 Mpaths/s`
+    pub fn summary(&self) -> String {
+        let seconds = self.start.elapsed().as_secs_f64().max(1e-9);
+        let rate = |counter: Counter| {
+            (total(counter) - self.base[counter as usize]) as f64 / seconds / 1e6
+        };
+
+        format!(
+            "{:.2} Mrays/s | {:.2} Msamples/s | {:.2} Mpaths/s",
+            rate(Counter::Rays),
+            rate(Counter::Samples),
+            rate(Counter::Paths)
+        )
+    }
+}
+
+/// 进程的内存峰值 (字节), 取自 `/proc/self/status` 的 `VmHWM`, 不支持的平台为 `None`
+pub fn peak_memory() -> Option<u64> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let line = status.lines().find(|line| line.starts_with("VmHWM:"))?;
+    let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+
+    Some(kib * 1024)
+}
+
+/// 一次渲染的统计
+pub struct RenderStats {
+    pub width: usize,
+    pub height: usize,
+    pub samples: usize,
+
+    /// 各阶段的名称与耗时, 按执行顺序
+    pub stages: Vec<(&'static str, Duration)>,
+
+    /// 求交次数最多的实体, 按求交次数从多到少; 未统计时为空
+    pub objects: Vec<ObjectStats>,
+}
+
+impl RenderStats {
+    /// 阶段 `name` 的耗时, 未执行时为零
+    fn stage(&self, name: &str) -> Duration {
+        self.stages
+            .iter()
+            .filter(|(stage, _)| *stage == name)
+            .map(|(_, duration)| *duration)
+            .sum()
+    }
+
+    /// 以 JSON 表示的统计, 光线数与内存峰值取自当前的计数
+    pub fn to_json(&self) -> String {
+        let render = self.stage("render").as_secs_f64();
+        let rays = total(Counter::Rays);
+        let rays_per_second = if render > 0.0 {
+            rays as f64 / render
+        } else {
+            0.0
+        };
+        let peak_memory = peak_memory().map_or("null".to_string(), |bytes| bytes.to_string());
+
+        let mut json = String::from("{\n");
+        let _ = writeln!(json, "  \"width\": {},", self.width);
+        let _ = writeln!(json, "  \"height\": {},", self.height);
+        let _ = writeln!(json, "  \"samples\": {},", self.samples);
+        let _ = writeln!(json, "  \"render_seconds\": {render:.6},");
+        let _ = writeln!(json, "  \"rays_traced\": {rays},");
+        let _ = writeln!(json, "  \"rays_per_second\": {rays_per_second:.1},");
+        let _ = writeln!(json, "  \"paths_traced\": {},", total(Counter::Paths));
+        let _ = writeln!(json, "  \"samples_taken\": {},", total(Counter::Samples));
+        let _ = writeln!(
+            json,
+            "  \"accelerator_build_seconds\": {:.6},",
+            self.stage("accelerator").as_secs_f64()
+        );
+        let _ = writeln!(json, "  \"peak_memory_bytes\": {peak_memory},");
+        json.push_str("  \"stages\": {");
+        for (index, (name, duration)) in self.stages.iter().enumerate() {
+            let separator = if index == 0 { "" } else { "," };
+            let _ = write!(
+                json,
+                "{separator}\n    \"{name}\": {:.6}",
+                duration.as_secs_f64()
+            );
+        }
+        json.push_str("\n  },\n  \"objects\": [");
+        for (index, object) in self.objects.iter().enumerate() {
+            let separator = if index == 0 { "" } else { "," };
+            let _ = write!(
+                json,
+                "{separator}\n    {{\"name\": {:?}, \"tests\": {}, \"hits\": {}}}",
+                object.name, object.tests, object.hits
+            );
+        }
+        json.push_str(if self.objects.is_empty() {
+            "]\n}\n"
+        } else {
+            "\n  ]\n}\n"
+        });
+
+        json
+    }
+}
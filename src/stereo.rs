@@ -0,0 +1,12 @@
+/// 将左右眼的 RGB 图像合成为红青立体图 (半彩色): 红色通道取左眼的亮度, 绿色与蓝色通道取右眼
+///
+/// 左眼只保留亮度, 以减轻红色物体在两眼间的竞争
+pub fn anaglyph(left: &[u8], right: &[u8]) -> Vec<u8> {
+    left.chunks_exact(3)
+        .zip(right.chunks_exact(3))
+        .flat_map(|(l, r)| {
+            let luminance = 0.299 * l[0] as f32 + 0.587 * l[1] as f32 + 0.114 * l[2] as f32;
+            [luminance.round().min(255.0) as u8, r[1], r[2]]
+        })
+        .collect()
+}
@@ -0,0 +1,69 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::error::{Error, Result};
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+
+use nalgebra::Vector3;
+
+/// 文件头的字节数 (80 字节的注释与 4 字节的三角形数)
+const HEADER_SIZE: usize = 84;
+
+/// 每个三角形记录的字节数: 法线与三个顶点各 3 个 `f32`, 以及 2 字节的属性
+const RECORD_SIZE: usize = 50;
+
+/// 读取二进制 STL 网格, 如 CAD 软件导出的零件
+///
+/// STL 中每个三角形的顶点各自独立, 位置相同的顶点合并为一个; 不附加顶点法线, 着色使用面法线。
+/// 记录的法线与顶点的逆时针顺序相反时调换顶点顺序, 使面法线与记录一致
+pub fn load(path: impl AsRef<Path>, material: Material) -> Result<TriangleMesh> {
+    let path = path.as_ref();
+    let invalid = |msg: String| Error::invalid_data(path, msg);
+    let data = fs::read(path)?;
+
+    // 二进制 STL 的大小由三角形数决定, 不符时多半是 ASCII STL (以 `solid` 开头)
+    let count = data
+        .get(80..HEADER_SIZE)
+        .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()) as usize);
+    let Some(count) = count.filter(|count| data.len() == HEADER_SIZE + count * RECORD_SIZE) else {
+        return Err(invalid(if data.starts_with(b"solid") {
+            "only binary STL files are supported".to_string()
+        } else {
+            "file size does not match the triangle count".to_string()
+        }));
+    };
+
+    let mut positions = vec![];
+    let mut lookup = HashMap::new();
+    let mut indices = Vec::with_capacity(count);
+    for record in data[HEADER_SIZE..].chunks_exact(RECORD_SIZE) {
+        let vector = |i: usize| {
+            let component = |j: usize| {
+                let offset = 12 * i + 4 * j;
+                f32::from_le_bytes(record[offset..offset + 4].try_into().unwrap())
+            };
+            Vector3::new(component(0), component(1), component(2))
+        };
+        let normal = vector(0);
+        let mut vertices = [vector(1), vector(2), vector(3)];
+
+        let [a, b, c] = vertices;
+        if (b - a).cross(&(c - a)).dot(&normal) < 0.0 {
+            vertices.swap(1, 2);
+        }
+
+        indices.push(vertices.map(|v| {
+            // 加 0 使 -0 与 0 合并
+            *lookup
+                .entry(v.map(|c| (c + 0.0).to_bits()))
+                .or_insert_with(|| {
+                    positions.push(v);
+                    positions.len() as u32 - 1
+                })
+        }));
+    }
+
+    TriangleMesh::new(positions, indices, material)
+}
@@ -0,0 +1,572 @@
+use std::collections::HashMap;
+use std::f32::consts::PI;
+
+use crate::error::{Error, Result};
+use crate::material::Material;
+use crate::mesh::TriangleMesh;
+
+use nalgebra::Vector3;
+
+/// 细分层级的上限, 每一级面数变为四倍
+pub const MAX_LEVELS: u32 = 6;
+
+/// 以 Loop 细分将三角形网格细分 `levels` 级, 低模经几级细分后趋于光滑的极限曲面
+///
+/// 每一级把每个三角形分为四个: 边上插入的新顶点取两端点与两个对角顶点的加权平均,
+/// 原顶点向一环邻域的平均靠拢; 边界与多于两个面共享的边按折痕处理, 只沿折痕平滑,
+/// 折痕交汇的角点保持不动。多边形在读取时已剖分为三角形, 四边形网格同样适用。
+/// 位置相同的顶点 (如纹理坐标的接缝) 在拓扑上视为同一顶点, 接缝两侧平滑一致;
+/// 纹理坐标与顶点颜色沿边线性插值, 法线按细分后的面重新计算
+pub fn subdivide(mesh: &TriangleMesh, levels: u32) -> Result<TriangleMesh> {
+    let mut level = Level {
+        positions: mesh.positions().to_vec(),
+        uvs: mesh.uvs().map(<[_]>::to_vec),
+        colors: mesh.colors().map(<[_]>::to_vec),
+        indices: mesh.indices().to_vec(),
+    };
+    for _ in 0..levels {
+        level = level.subdivided()?;
+    }
+    let normals = level.normals();
+
+    let mut subdivided = TriangleMesh::new(level.positions, level.indices, mesh.material())?
+        .with_normals(normals)?;
+    if let Some(uvs) = level.uvs {
+        subdivided = subdivided.with_uvs(uvs)?;
+    }
+    if let Some(colors) = level.colors {
+        subdivided = subdivided.with_colors(colors)?;
+    }
+
+    Ok(subdivided)
+}
+
+/// Catmull–Clark 细分的控制网格 (cage): 任意多边形面, 通常为四边形
+///
+/// 与 `TriangleMesh` 不同, 面不剖分为三角形, 细分规则按多边形的边数取权重
+pub struct Cage {
+    positions: Vec<Vector3<f32>>,
+    uvs: Option<Vec<(f32, f32)>>,
+    colors: Option<Vec<Vector3<f32>>>,
+
+    /// 每个面按逆时针顺序的顶点序号, 至少三个
+    faces: Vec<Vec<u32>>,
+
+    material: Material,
+}
+
+impl Cage {
+    /// 由顶点位置与多边形面构建控制网格, 面的顶点不足三个或序号越界时返回错误
+    pub fn new(
+        positions: Vec<Vector3<f32>>,
+        faces: Vec<Vec<u32>>,
+        material: Material,
+    ) -> Result<Self> {
+        if faces.iter().any(|face| face.len() < 3) {
+            return Err(Error::InvalidSettings(
+                "cage face with fewer than 3 vertices".to_string(),
+            ));
+        }
+        if let Some(index) = faces
+            .iter()
+            .flatten()
+            .find(|&&i| i as usize >= positions.len())
+        {
+            return Err(Error::InvalidSettings(format!(
+                "vertex index {index} out of range for {} vertices",
+                positions.len()
+            )));
+        }
+
+        Ok(Self {
+            positions,
+            uvs: None,
+            colors: None,
+            faces,
+            material,
+        })
+    }
+
+    /// 附加顶点纹理坐标, 数量须与顶点相同
+    pub fn with_uvs(mut self, uvs: Vec<(f32, f32)>) -> Result<Self> {
+        self.check_vertex_count(uvs.len(), "texture coordinates")?;
+        self.uvs = Some(uvs);
+
+        Ok(self)
+    }
+
+    /// 附加顶点颜色 (线性), 数量须与顶点相同
+    pub fn with_colors(mut self, colors: Vec<Vector3<f32>>) -> Result<Self> {
+        self.check_vertex_count(colors.len(), "colors")?;
+        self.colors = Some(colors);
+
+        Ok(self)
+    }
+
+    fn check_vertex_count(&self, count: usize, what: &str) -> Result<()> {
+        if count == self.positions.len() {
+            return Ok(());
+        }
+
+        Err(Error::InvalidSettings(format!(
+            "{count} {what} for {} vertices",
+            self.positions.len()
+        )))
+    }
+
+    /// 面数
+    pub fn face_count(&self) -> usize {
+        self.faces.len()
+    }
+
+    /// 细分一级, 之后所有的面均为四边形
+    ///
+    /// 每个面插入面点 (各顶点的平均), 每条边插入边点 (两端点与两侧面点的平均),
+    /// 原顶点移到 `(F + 2R + (n - 3) P) / n`, 其中 `F` 与 `R` 为相邻面点与相邻边中点的平均,
+    /// `n` 为顶点的度; 边界与多于两个面共享的边按折痕处理, 与 Loop 细分相同
+    fn subdivided(&self) -> Result<Self> {
+        let welded = weld(&self.positions);
+        let key = |a: u32, b: u32| (a.min(b), a.max(b));
+        let p = |i: u32| self.positions[i as usize];
+
+        // 面点, 以及按代表顶点统计各边相邻的面
+        let face_points: Vec<Vector3<f32>> = self
+            .faces
+            .iter()
+            .map(|face| face.iter().map(|&i| p(i)).sum::<Vector3<f32>>() / face.len() as f32)
+            .collect();
+        let mut edges: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+        for (f, face) in self.faces.iter().enumerate() {
+            for k in 0..face.len() {
+                let (a, b) = (face[k], face[(k + 1) % face.len()]);
+                edges
+                    .entry(key(welded[a as usize], welded[b as usize]))
+                    .or_default()
+                    .push(f);
+            }
+        }
+
+        // 原顶点的新位置
+        let mut rings: HashMap<u32, Ring> = HashMap::new();
+        for (&(a, b), faces) in &edges {
+            let crease = faces.len() != 2;
+            for (from, to) in [(a, b), (b, a)] {
+                let ring = rings.entry(from).or_default();
+                ring.neighbors.push(to);
+                if crease {
+                    ring.creases.push(to);
+                }
+                ring.faces.extend(faces);
+            }
+        }
+        let mut positions: Vec<Vector3<f32>> = (0..self.positions.len() as u32)
+            .map(|i| {
+                let Some(Ring {
+                    neighbors: ring,
+                    creases,
+                    faces: adjacent,
+                }) = rings.get(&welded[i as usize])
+                else {
+                    return p(i);
+                };
+                let v = p(welded[i as usize]);
+                match creases.len() {
+                    0 => {
+                        // 每个相邻面经两条边各记录一次
+                        let n = ring.len() as f32;
+                        let f = adjacent
+                            .iter()
+                            .map(|&f| face_points[f])
+                            .sum::<Vector3<f32>>()
+                            / adjacent.len() as f32;
+                        let r = ring.iter().map(|&j| (v + p(j)) / 2.0).sum::<Vector3<f32>>() / n;
+                        (f + 2.0 * r + (n - 3.0) * v) / n
+                    }
+                    2 => 0.75 * v + 0.125 * (p(creases[0]) + p(creases[1])),
+                    _ => v,
+                }
+            })
+            .collect();
+
+        let mut uvs = self.uvs.clone();
+        let mut colors = self.colors.clone();
+        let mut push = |position: Vector3<f32>,
+                        uv: Option<(f32, f32)>,
+                        color: Option<Vector3<f32>>|
+         -> Result<u32> {
+            let index = u32::try_from(positions.len()).map_err(|_| {
+                Error::InvalidSettings("too many vertices after subdivision".to_string())
+            })?;
+            positions.push(position);
+            if let (Some(uvs), Some(uv)) = (&mut uvs, uv) {
+                uvs.push(uv);
+            }
+            if let (Some(colors), Some(color)) = (&mut colors, color) {
+                colors.push(color);
+            }
+
+            Ok(index)
+        };
+        let average_uv = |corners: &[u32]| {
+            self.uvs.as_ref().map(|uvs| {
+                let (u, v) = corners.iter().fold((0.0, 0.0), |(u, v), &i| {
+                    (u + uvs[i as usize].0, v + uvs[i as usize].1)
+                });
+                (u / corners.len() as f32, v / corners.len() as f32)
+            })
+        };
+        let average_color = |corners: &[u32]| {
+            self.colors.as_ref().map(|colors| {
+                corners
+                    .iter()
+                    .map(|&i| colors[i as usize])
+                    .sum::<Vector3<f32>>()
+                    / corners.len() as f32
+            })
+        };
+
+        // 边点: 每条原始的边 (接缝两侧各算一条) 插入一个; 每个面分为与顶点数相同的四边形
+        let mut edge_points: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut faces = Vec::with_capacity(self.faces.iter().map(Vec::len).sum());
+        for (f, face) in self.faces.iter().enumerate() {
+            let center = push(face_points[f], average_uv(face), average_color(face))?;
+            let mut middle = Vec::with_capacity(face.len());
+            for k in 0..face.len() {
+                let (a, b) = (face[k], face[(k + 1) % face.len()]);
+                let index = match edge_points.get(&key(a, b)) {
+                    Some(&index) => index,
+                    None => {
+                        let (wa, wb) = (welded[a as usize], welded[b as usize]);
+                        let position = match edges[&key(wa, wb)][..] {
+                            [f0, f1] => (p(wa) + p(wb) + face_points[f0] + face_points[f1]) / 4.0,
+                            _ => (p(wa) + p(wb)) / 2.0,
+                        };
+                        let index = push(position, average_uv(&[a, b]), average_color(&[a, b]))?;
+                        edge_points.insert(key(a, b), index);
+                        index
+                    }
+                };
+                middle.push(index);
+            }
+
+            for k in 0..face.len() {
+                let previous = middle[(k + face.len() - 1) % face.len()];
+                faces.push(vec![face[k], middle[k], center, previous]);
+            }
+        }
+
+        Ok(Self {
+            positions,
+            uvs,
+            colors,
+            faces,
+            material: self.material,
+        })
+    }
+}
+
+/// 控制网格中一个顶点 (代表顶点) 的一环邻域
+#[derive(Default)]
+struct Ring {
+    /// 相邻的顶点
+    neighbors: Vec<u32>,
+
+    /// 经折痕相邻的顶点
+    creases: Vec<u32>,
+
+    /// 各条相邻边两侧的面, 内部顶点的每个相邻面出现两次
+    faces: Vec<usize>,
+}
+
+/// 以 Catmull–Clark 细分将控制网格细分 `levels` 级后剖分为三角形网格, 四边形的控制网格趋于光滑的极限曲面
+///
+/// 位置相同的顶点在拓扑上视为同一顶点, 纹理坐标与顶点颜色按面点与边点线性插值,
+/// 法线按细分后的面重新计算; 每一级面数约变为四倍, 不得超过 `MAX_LEVELS` 级
+pub fn catmull_clark(cage: &Cage, levels: u32) -> Result<TriangleMesh> {
+    if levels > MAX_LEVELS {
+        return Err(Error::InvalidSettings(format!(
+            "subdivision level {levels} exceeds the maximum of {MAX_LEVELS}"
+        )));
+    }
+
+    let mut subdivided = None;
+    for _ in 0..levels {
+        subdivided = Some(subdivided.as_ref().unwrap_or(cage).subdivided()?);
+    }
+    let cage = subdivided.as_ref().unwrap_or(cage);
+
+    // 四边形沿对角线剖分, 其他多边形 (未细分时) 按扇形剖分
+    let indices = cage
+        .faces
+        .iter()
+        .flat_map(|face| (1..face.len() - 1).map(move |i| [face[0], face[i], face[i + 1]]))
+        .collect();
+    let level = Level {
+        positions: cage.positions.clone(),
+        uvs: cage.uvs.clone(),
+        colors: cage.colors.clone(),
+        indices,
+    };
+    let normals = level.normals();
+
+    let mut mesh =
+        TriangleMesh::new(level.positions, level.indices, cage.material)?.with_normals(normals)?;
+    if let Some(uvs) = level.uvs {
+        mesh = mesh.with_uvs(uvs)?;
+    }
+    if let Some(colors) = level.colors {
+        mesh = mesh.with_colors(colors)?;
+    }
+
+    Ok(mesh)
+}
+
+/// 每个顶点的代表顶点: 位置相同的顶点中序号最小的一个
+fn weld(positions: &[Vector3<f32>]) -> Vec<u32> {
+    let mut first = HashMap::new();
+    positions
+        .iter()
+        .enumerate()
+        .map(|(index, p)| *first.entry(p.map(f32::to_bits)).or_insert(index as u32))
+        .collect()
+}
+
+/// 一级细分的网格
+struct Level {
+    positions: Vec<Vector3<f32>>,
+    uvs: Option<Vec<(f32, f32)>>,
+    colors: Option<Vec<Vector3<f32>>>,
+    indices: Vec<[u32; 3]>,
+}
+
+/// 拓扑上的一条边 (两端为代表顶点, 序号小的在前)
+#[derive(Default)]
+struct Edge {
+    /// 共享该边的各个面中与边相对的顶点
+    opposite: Vec<u32>,
+}
+
+impl Level {
+    /// 细分一级
+    fn subdivided(&self) -> Result<Self> {
+        let welded = weld(&self.positions);
+        let key = |a: u32, b: u32| (a.min(b), a.max(b));
+
+        // 按代表顶点统计各边相邻的面
+        let mut edges: HashMap<(u32, u32), Edge> = HashMap::new();
+        for face in &self.indices {
+            let w = face.map(|i| welded[i as usize]);
+            for k in 0..3 {
+                let (a, b, c) = (w[k], w[(k + 1) % 3], w[(k + 2) % 3]);
+                edges.entry(key(a, b)).or_default().opposite.push(c);
+            }
+        }
+
+        // 原顶点的新位置: 内部顶点按 Warren 的权重向一环邻域靠拢, 折痕上的顶点只沿折痕平滑
+        let mut neighbors: HashMap<u32, (Vec<u32>, Vec<u32>)> = HashMap::new();
+        for (&(a, b), edge) in &edges {
+            let crease = edge.opposite.len() != 2;
+            for (from, to) in [(a, b), (b, a)] {
+                let (ring, creases) = neighbors.entry(from).or_default();
+                ring.push(to);
+                if crease {
+                    creases.push(to);
+                }
+            }
+        }
+        let p = |i: u32| self.positions[i as usize];
+        let mut positions: Vec<Vector3<f32>> = (0..self.positions.len() as u32)
+            .map(|i| {
+                let Some((ring, creases)) = neighbors.get(&welded[i as usize]) else {
+                    return p(i);
+                };
+                let v = p(welded[i as usize]);
+                match creases.len() {
+                    0 => {
+                        let n = ring.len() as f32;
+                        let beta = if ring.len() == 3 {
+                            3.0 / 16.0
+                        } else {
+                            3.0 / (8.0 * n)
+                        };
+                        (1.0 - n * beta) * v
+                            + beta * ring.iter().map(|&j| p(j)).sum::<Vector3<f32>>()
+                    }
+                    2 => 0.75 * v + 0.125 * (p(creases[0]) + p(creases[1])),
+                    _ => v,
+                }
+            })
+            .collect();
+
+        // 边上的新顶点: 每条原始的边 (接缝两侧各算一条) 插入一个
+        let mut uvs = self.uvs.clone();
+        let mut colors = self.colors.clone();
+        let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut indices = Vec::with_capacity(self.indices.len() * 4);
+        for face in &self.indices {
+            let mut middle = [0; 3];
+            for k in 0..3 {
+                let (a, b) = (face[k], face[(k + 1) % 3]);
+                middle[k] = match midpoints.get(&key(a, b)) {
+                    Some(&index) => index,
+                    None => {
+                        let (wa, wb) = (welded[a as usize], welded[b as usize]);
+                        let edge = &edges[&key(wa, wb)];
+                        let position = match edge.opposite[..] {
+                            [c, d] => 0.375 * (p(wa) + p(wb)) + 0.125 * (p(c) + p(d)),
+                            _ => 0.5 * (p(wa) + p(wb)),
+                        };
+                        let index = u32::try_from(positions.len()).map_err(|_| {
+                            Error::InvalidSettings(
+                                "too many vertices after subdivision".to_string(),
+                            )
+                        })?;
+                        positions.push(position);
+                        if let Some(uvs) = &mut uvs {
+                            let (ua, ub) = (uvs[a as usize], uvs[b as usize]);
+                            uvs.push(((ua.0 + ub.0) / 2.0, (ua.1 + ub.1) / 2.0));
+                        }
+                        if let Some(colors) = &mut colors {
+                            colors.push((colors[a as usize] + colors[b as usize]) / 2.0);
+                        }
+                        midpoints.insert(key(a, b), index);
+                        index
+                    }
+                };
+            }
+
+            let [a, b, c] = *face;
+            let [ab, bc, ca] = middle;
+            indices.extend([[a, ab, ca], [ab, b, bc], [ca, bc, c], [ab, bc, ca]]);
+        }
+
+        Ok(Self {
+            positions,
+            uvs,
+            colors,
+            indices,
+        })
+    }
+
+    /// 顶点法线: 相邻面法线按角度加权的平均, 位置相同的顶点共用一条法线
+    fn normals(&self) -> Vec<Vector3<f32>> {
+        let welded = weld(&self.positions);
+        let mut sums = vec![Vector3::zeros(); self.positions.len()];
+        for face in &self.indices {
+            let w = face.map(|i| welded[i as usize]);
+            let [a, b, c] = w.map(|i| self.positions[i as usize]);
+            let Some(normal) = (b - a).cross(&(c - a)).try_normalize(f32::EPSILON) else {
+                continue;
+            };
+            for k in 0..3 {
+                let v = self.positions[w[k] as usize];
+                let e1 = self.positions[w[(k + 1) % 3] as usize] - v;
+                let e2 = self.positions[w[(k + 2) % 3] as usize] - v;
+                let angle = e1.angle(&e2);
+                if angle.is_finite() && angle < PI {
+                    sums[w[k] as usize] += angle * normal;
+                }
+            }
+        }
+
+        welded
+            .iter()
+            .map(|&i| {
+                sums[i as usize]
+                    .try_normalize(f32::EPSILON)
+                    .unwrap_or_else(Vector3::y)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 以原点为中心, 边长为 2 的立方体, 六个逆时针 (从外看) 的四边形面
+    fn cube() -> Cage {
+        let positions = (0..8)
+            .map(|i| {
+                let coordinate = |bit: usize| if i & bit == 0 { -1.0 } else { 1.0 };
+                Vector3::new(coordinate(1), coordinate(2), coordinate(4))
+            })
+            .collect();
+        let faces = [
+            [0, 2, 3, 1],
+            [4, 5, 7, 6],
+            [0, 1, 5, 4],
+            [2, 6, 7, 3],
+            [0, 4, 6, 2],
+            [1, 3, 7, 5],
+        ]
+        .map(|face| face.to_vec())
+        .to_vec();
+
+        Cage::new(positions, faces, Material::CLAY).unwrap()
+    }
+
+    fn contains(mesh: &TriangleMesh, expected: Vector3<f32>) -> bool {
+        mesh.positions()
+            .iter()
+            .any(|position| (position - expected).norm() < 1e-5)
+    }
+
+    #[test]
+    fn catmull_clark_matches_the_cube_rules() {
+        let mesh = catmull_clark(&cube(), 1).unwrap();
+        assert_eq!(mesh.face_count(), 6 * 4 * 2);
+
+        // 角点: (F + 2R) / 3, F 为三个面点的平均, R 为三条边中点的平均
+        assert!(contains(&mesh, Vector3::repeat(5.0 / 9.0)));
+        // 边点: 两端点与两侧面点的平均
+        assert!(contains(&mesh, Vector3::new(0.75, 0.75, 0.0)));
+        // 面点
+        assert!(contains(&mesh, Vector3::new(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn subdivided_cube_stays_closed_and_outward_facing() {
+        let mesh = catmull_clark(&cube(), 3).unwrap();
+
+        let mut edges: HashMap<(u32, u32), i32> = HashMap::new();
+        for face in mesh.indices() {
+            for k in 0..3 {
+                let (a, b) = (face[k], face[(k + 1) % 3]);
+                *edges.entry((a.min(b), a.max(b))).or_default() += 1;
+            }
+        }
+        assert!(edges.values().all(|&count| count == 2));
+
+        for (position, normal) in mesh.positions().iter().zip(mesh.normals().unwrap()) {
+            assert!(position.norm() < 1.0 && position.norm() > 0.5);
+            assert!(normal.dot(position) > 0.0);
+        }
+    }
+
+    #[test]
+    fn boundary_edges_are_creases() {
+        // 平面上 2 × 2 格的四边形, 细分后仍在平面内, 中心的顶点不动
+        let positions = (0..9)
+            .map(|i| Vector3::new((i % 3) as f32, (i / 3) as f32, 0.0))
+            .collect();
+        let faces = [[0, 1, 4, 3], [1, 2, 5, 4], [3, 4, 7, 6], [4, 5, 8, 7]]
+            .map(|face| face.to_vec())
+            .to_vec();
+        let cage = Cage::new(positions, faces, Material::CLAY).unwrap();
+        let mesh = catmull_clark(&cage, 2).unwrap();
+
+        assert!(mesh.positions().iter().all(|position| position.z == 0.0));
+        assert!(contains(&mesh, Vector3::new(1.0, 1.0, 0.0)));
+        // 边界的中点只沿边界平滑
+        assert!(contains(&mesh, Vector3::new(1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn invalid_cages_and_levels_are_rejected() {
+        let positions = vec![Vector3::zeros(), Vector3::x(), Vector3::y()];
+        assert!(Cage::new(positions.clone(), vec![vec![0, 1]], Material::CLAY).is_err());
+        assert!(Cage::new(positions, vec![vec![0, 1, 3]], Material::CLAY).is_err());
+        assert!(catmull_clark(&cube(), MAX_LEVELS + 1).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+use nalgebra::Vector3;
+
+/// 亮度的单位换算: 1 kcd/m² 对应的渲染辐射亮度, 晴天正午阳光下的白色漫反射表面约为 1
+const SKY_SCALE: f32 = 0.045;
+
+/// 大气层外垂直于阳光的照度 (klux)
+const SUN_ILLUMINANCE: f32 = 128.0;
+
+/// 太阳的角半径 (弧度)
+const SUN_RADIUS: f32 = 0.00465;
+
+/// 民用晨昏蒙影的深度 (弧度): 太阳在地平线下 6° 以内时天空逐渐变暗
+const TWILIGHT: f32 = 6.0 * PI / 180.0;
+
+/// 各月份之前的天数 (平年)
+const DAYS_BEFORE_MONTH: [u32; 12] = [0, 31, 59, 90, 120, 151, 181, 212, 243, 273, 304, 334];
+
+/// 太阳与天空: 由纬度, 日期与当地太阳时确定太阳的方向, 天空为 Preetham 解析模型
+///
+/// 世界坐标中 y 轴向上, -z 为北, +x 为东; 天空的亮度与太阳的颜色随太阳高度与浑浊度变化,
+/// 地平线以下的方向取地平线处的颜色
+#[derive(Clone, Copy, Debug)]
+pub struct SunSky {
+    /// 指向太阳的单位方向
+    sun: Vector3<f32>,
+
+    /// 亮度 Y 与色度 x, y 的 Perez 分布参数 A–E
+    perez: [[f32; 5]; 3],
+
+    /// 天顶处的 Y, x, y 各除以天顶方向上的 Perez 函数值
+    zenith: [f32; 3],
+
+    /// 太阳在地平线附近时天空的亮度比例 (晨昏蒙影)
+    twilight: f32,
+
+    /// 地面垂直于阳光的表面接收到的辐照度 (经大气衰减)
+    sun_irradiance: Vector3<f32>,
+}
+
+impl SunSky {
+    /// 纬度 `latitude` (度, 北纬为正) 处 `month` 月 `day` 日当地太阳时 `hour` (小时) 的太阳与天空,
+    /// `turbidity` 为大气浑浊度 (2 为非常晴朗, 3 为晴朗, 6 以上有雾霾)
+    pub fn new(latitude: f32, month: u32, day: u32, hour: f32, turbidity: f32) -> Self {
+        let sun = sun_direction(latitude, month, day, hour);
+        let t = turbidity.max(1.0);
+
+        // 天空的模型只对地平线以上的太阳有效, 地平线以下时取地平线上的分布并整体变暗
+        let theta_s = sun.y.clamp(-1.0, 1.0).acos().min(FRAC_PI_2 - 0.01);
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (PI - 2.0 * theta_s);
+        let luminance = ((4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192).max(0.0);
+        let cubic = |c: [f32; 4]| ((c[0] * theta_s + c[1]) * theta_s + c[2]) * theta_s + c[3];
+        let x = t * t * cubic([0.00166, -0.00375, 0.00209, 0.0])
+            + t * cubic([-0.02903, 0.06377, -0.03202, 0.00394])
+            + cubic([0.11693, -0.21196, 0.06052, 0.25886]);
+        let y = t * t * cubic([0.00275, -0.00610, 0.00317, 0.0])
+            + t * cubic([-0.04214, 0.08970, -0.04153, 0.00516])
+            + cubic([0.15346, -0.26756, 0.06670, 0.26688]);
+        // 天顶方向与天顶的夹角为 0, 与太阳的夹角为 θs
+        let zenith = [luminance, x, y];
+        let zenith = [0, 1, 2].map(|i| zenith[i] / perez_f(&perez[i], 1.0, theta_s));
+
+        let elevation = sun.y.clamp(-1.0, 1.0).asin();
+        let twilight = ((elevation + TWILIGHT) / TWILIGHT).clamp(0.0, 1.0);
+
+        Self {
+            sun,
+            perez,
+            zenith,
+            twilight: twilight * twilight,
+            sun_irradiance: if elevation > 0.0 {
+                SUN_ILLUMINANCE * SKY_SCALE * transmittance(elevation, t)
+            } else {
+                Vector3::zeros()
+            },
+        }
+    }
+
+    /// 指向太阳的单位方向
+    pub const fn sun_direction(&self) -> Vector3<f32> {
+        self.sun
+    }
+
+    /// 垂直于阳光的表面接收到的辐照度, 太阳在地平线以下时为零
+    pub const fn sun_irradiance(&self) -> Vector3<f32> {
+        self.sun_irradiance
+    }
+
+    /// 单位方向 `direction` 上天空的辐射亮度 (线性 sRGB), 不含太阳本身
+    pub fn sky(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        let cos_theta = direction.y.max(0.01);
+        let gamma = direction.dot(&self.sun).clamp(-1.0, 1.0).acos();
+        let [luminance, x, y] =
+            [0, 1, 2].map(|i| self.zenith[i] * perez_f(&self.perez[i], cos_theta, gamma));
+        let luminance = luminance * SKY_SCALE * self.twilight;
+        if y <= 0.0 {
+            return Vector3::zeros();
+        }
+
+        // xyY 转换为 XYZ, 再转换为线性 sRGB
+        let xyz = Vector3::new(x / y * luminance, luminance, (1.0 - x - y) / y * luminance);
+        Vector3::new(
+            3.2406 * xyz.x - 1.5372 * xyz.y - 0.4986 * xyz.z,
+            -0.9689 * xyz.x + 1.8758 * xyz.y + 0.0415 * xyz.z,
+            0.0557 * xyz.x - 0.2040 * xyz.y + 1.0570 * xyz.z,
+        )
+        .map(|c| c.max(0.0))
+    }
+
+    /// 单位方向 `direction` 上的辐射亮度, 包括太阳的圆盘
+    pub fn radiance(&self, direction: &Vector3<f32>) -> Vector3<f32> {
+        let sky = self.sky(direction);
+        if direction.dot(&self.sun) < SUN_RADIUS.cos() {
+            return sky;
+        }
+
+        let solid_angle = TAU * (1.0 - SUN_RADIUS.cos());
+        sky + self.sun_irradiance / solid_angle
+    }
+}
+
+/// Perez 天空分布函数, `cos_theta` 为方向与天顶夹角的余弦, `gamma` 为方向与太阳的夹角
+fn perez_f([a, b, c, d, e]: &[f32; 5], cos_theta: f32, gamma: f32) -> f32 {
+    let cos_gamma = gamma.cos();
+
+    (1.0 + a * (b / cos_theta.max(0.01)).exp())
+        * (1.0 + c * (d * gamma).exp() + e * cos_gamma * cos_gamma)
+}
+
+/// 纬度 `latitude` 处 `month` 月 `day` 日当地太阳时 `hour` 指向太阳的单位方向 (忽略时差方程)
+pub fn sun_direction(latitude: f32, month: u32, day: u32, hour: f32) -> Vector3<f32> {
+    let day_of_year = DAYS_BEFORE_MONTH[(month.clamp(1, 12) - 1) as usize] + day;
+    let declination = -23.44_f32.to_radians() * (TAU / 365.0 * (day_of_year as f32 + 10.0)).cos();
+    let hour_angle = (15.0 * (hour - 12.0)).to_radians();
+    let latitude = latitude.to_radians();
+
+    let sin_elevation =
+        latitude.sin() * declination.sin() + latitude.cos() * declination.cos() * hour_angle.cos();
+    let elevation = sin_elevation.clamp(-1.0, 1.0).asin();
+
+    // 方位角自北向东量起, 上午在东侧
+    let cos_azimuth = (declination.sin() - sin_elevation * latitude.sin())
+        / (elevation.cos() * latitude.cos()).max(f32::EPSILON);
+    let azimuth = cos_azimuth.clamp(-1.0, 1.0).acos();
+    let azimuth = if hour_angle > 0.0 {
+        TAU - azimuth
+    } else {
+        azimuth
+    };
+
+    Vector3::new(
+        azimuth.sin() * elevation.cos(),
+        elevation.sin(),
+        -azimuth.cos() * elevation.cos(),
+    )
+}
+
+/// 阳光穿过大气的透过率 (R, G, B 分别取 680, 550, 440 nm): 瑞利散射与气溶胶的消光,
+/// 大气质量按 Kasten–Young 公式随高度角 `elevation` 增加, 低角度的太阳因此偏红
+fn transmittance(elevation: f32, turbidity: f32) -> Vector3<f32> {
+    let zenith_angle = (FRAC_PI_2 - elevation).to_degrees();
+    let air_mass =
+        1.0 / (elevation.sin() + 0.50572 * (96.07995 - zenith_angle).max(0.1).powf(-1.6364));
+    let beta = (0.04608 * turbidity - 0.04586).max(0.0);
+
+    Vector3::new(0.68_f32, 0.55, 0.44).map(|lambda| {
+        let rayleigh = 0.008735 * lambda.powf(-4.08);
+        let aerosol = beta * lambda.powf(-1.3);
+        (-air_mass * (rayleigh + aerosol)).exp()
+    })
+}
@@ -0,0 +1,388 @@
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{LazyLock, Mutex, RwLock};
+
+use crate::error::{Error, Result};
+use crate::lru::Lru;
+
+use clap::ValueEnum;
+use nalgebra::Vector3;
+
+/// 缓存图块的边长
+const TILE_SIZE: usize = 64;
+
+/// 纹理缓存默认的内存预算 (字节)
+pub const DEFAULT_BUDGET: usize = 256 << 20;
+
+/// UDIM 纹理集文件名中代表图块编号的占位符
+pub const UDIM_TOKEN: &str = "<UDIM>";
+
+/// 过滤时每个方向最多的采样数
+const MAX_FILTER_TAPS: usize = 4;
+
+/// UDIM 图块编号的范围 (u 方向 10 个图块, v 方向 10 行)
+const UDIM_RANGE: std::ops::RangeInclusive<u32> = 1001..=1100;
+
+/// 纹理文件中像素值的色彩空间, 读取时转换为渲染使用的线性 sRGB (Rec. 709 原色, D65 白点)
+///
+/// 两者原色与白点相同, 无需色适应, 只需撤销传递函数
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum ColorSpace {
+    /// sRGB 传递函数编码, 颜色贴图 (漫反射颜色, 自发光, 环境贴图) 通常如此
+    Srgb,
+
+    /// 线性数值, 粗糙度, 金属度等数据贴图通常如此
+    Linear,
+}
+
+impl ColorSpace {
+    /// 将 `[0, 1]` 内的编码值转换为线性值
+    pub fn to_linear(self, value: f32) -> f32 {
+        match self {
+            Self::Srgb if value <= 0.04045 => value / 12.92,
+            Self::Srgb => ((value + 0.055) / 1.055).powf(2.4),
+            Self::Linear => value,
+        }
+    }
+
+    /// 文件头注释中的色彩空间标记, 如 `# colorspace: linear`
+    fn from_tag(comment: &str) -> Option<Self> {
+        let name = comment
+            .trim()
+            .to_ascii_lowercase()
+            .strip_prefix("colorspace")?
+            .trim_start_matches(':')
+            .trim()
+            .to_string();
+
+        Self::from_str(&name, true).ok()
+    }
+}
+
+/// 纹理编号
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct TextureId(u32);
+
+/// 纹理: 单张图像, 或按 UDIM 编号平铺在 UV 空间中的一组图像
+enum Texture {
+    Image(TextureFile),
+
+    /// UDIM 纹理集, `(u, v)` 落在 `[i, i + 1) × [j, j + 1)` 时取编号 `1001 + i + 10 j` 的图像
+    Udim(HashMap<u32, TextureFile>),
+}
+
+/// 缓存中图块的键: 纹理, UDIM 编号 (单张图像为 0), 图块坐标
+type TileKey = (TextureId, u32, usize, usize);
+
+/// 纹理文件: 只读取文件头, 像素在用到时按图块读取
+struct TextureFile {
+    path: PathBuf,
+    width: usize,
+    height: usize,
+
+    /// 像素数据在文件中的起始位置
+    data_offset: u64,
+
+    /// 每个分量的最大值
+    max_value: u16,
+
+    /// 像素值的色彩空间
+    color_space: ColorSpace,
+}
+
+impl TextureFile {
+    /// 读取二进制 PPM (P6) 的文件头
+    ///
+    /// 色彩空间依次取 `forced`, 文件头注释中的标记与 `fallback`; 文件不存在时为 `Error::MissingTexture`
+    fn open(path: &Path, forced: Option<ColorSpace>, fallback: ColorSpace) -> Result<Self> {
+        let invalid = |msg: &str| Error::invalid_data(path, msg);
+        let file = File::open(path).map_err(|err| match err.kind() {
+            io::ErrorKind::NotFound => Error::MissingTexture(path.to_path_buf()),
+            _ => Error::Io(err),
+        })?;
+        let mut reader = BufReader::new(file);
+
+        // 文件头的四个字段, 以空白分隔, `#` 开头的注释到行尾
+        let mut fields = vec![];
+        let mut tag = None;
+        let mut offset = 0;
+        while fields.len() < 4 {
+            let mut line = String::new();
+            let read = reader.read_line(&mut line)?;
+            if read == 0 {
+                return Err(invalid("truncated header"));
+            }
+            offset += read as u64;
+            let (content, comment) = line.split_once('#').unwrap_or((&line, ""));
+            tag = tag.or_else(|| ColorSpace::from_tag(comment));
+            fields.extend(content.split_whitespace().map(str::to_string));
+        }
+        if fields.len() > 4 {
+            return Err(invalid("pixel data must start on a new line"));
+        }
+        if fields[0] != "P6" {
+            return Err(invalid("only binary PPM (P6) textures are supported"));
+        }
+
+        let parse = |field: &str| field.parse::<usize>().map_err(|_| invalid("bad header"));
+        let (width, height, max_value) =
+            (parse(&fields[1])?, parse(&fields[2])?, parse(&fields[3])?);
+        if width == 0 || height == 0 || !(1..=u16::MAX as usize).contains(&max_value) {
+            return Err(invalid("bad header"));
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            width,
+            height,
+            data_offset: offset,
+            max_value: max_value as u16,
+            color_space: forced.or(tag).unwrap_or(fallback),
+        })
+    }
+
+    /// 每个分量的字节数
+    const fn sample_bytes(&self) -> usize {
+        if self.max_value > u8::MAX as u16 {
+            2
+        } else {
+            1
+        }
+    }
+
+    /// 读取一个图块, 像素值转换为线性值
+    fn read_tile(&self, tile_x: usize, tile_y: usize) -> Result<Vec<Vector3<f32>>> {
+        let x0 = tile_x * TILE_SIZE;
+        let y0 = tile_y * TILE_SIZE;
+
+        self.read_region(
+            x0,
+            y0,
+            TILE_SIZE.min(self.width - x0),
+            TILE_SIZE.min(self.height - y0),
+        )
+    }
+
+    /// 读取左上角为 `(x0, y0)` 的矩形区域, 像素值转换为线性值
+    fn read_region(
+        &self,
+        x0: usize,
+        y0: usize,
+        width: usize,
+        height: usize,
+    ) -> Result<Vec<Vector3<f32>>> {
+        let bytes = self.sample_bytes();
+
+        let mut file = File::open(&self.path)?;
+        let mut row = vec![0; 3 * width * bytes];
+        let mut texels = Vec::with_capacity(width * height);
+        for y in y0..y0 + height {
+            let offset = self.data_offset + (3 * (y * self.width + x0) * bytes) as u64;
+            file.seek(SeekFrom::Start(offset))?;
+            file.read_exact(&mut row)?;
+
+            texels.extend(row.chunks(3 * bytes).map(|texel| {
+                Vector3::from_fn(|c, _| {
+                    let value = if bytes == 2 {
+                        u16::from_be_bytes([texel[2 * c], texel[2 * c + 1]])
+                    } else {
+                        texel[c] as u16
+                    };
+                    self.color_space
+                        .to_linear(value as f32 / self.max_value as f32)
+                })
+            }));
+        }
+
+        Ok(texels)
+    }
+}
+
+/// 纹理缓存: 按需读取图块, 超出内存预算时淘汰最久未使用的图块
+pub struct TextureCache {
+    textures: RwLock<Vec<Texture>>,
+    tiles: Mutex<Lru<TileKey, Vec<Vector3<f32>>>>,
+
+    /// 内存预算 (字节)
+    budget: AtomicUsize,
+
+    /// 读取与淘汰的图块数
+    loads: AtomicUsize,
+    evictions: AtomicUsize,
+}
+
+/// 全局的纹理缓存, 材质通过 `TextureId` 引用其中的纹理
+static CACHE: LazyLock<TextureCache> = LazyLock::new(|| TextureCache {
+    textures: RwLock::new(vec![]),
+    tiles: Mutex::new(Lru::default()),
+    budget: AtomicUsize::new(DEFAULT_BUDGET),
+    loads: AtomicUsize::new(0),
+    evictions: AtomicUsize::new(0),
+});
+
+/// 不经过缓存读取整张二进制 PPM (P6) 图像, 返回宽, 高与线性的像素值 (首行为图像顶部)
+///
+/// 文件头中没有色彩空间标记时按 `fallback` 解码
+pub fn read_image(
+    path: impl AsRef<Path>,
+    fallback: ColorSpace,
+) -> Result<(usize, usize, Vec<Vector3<f32>>)> {
+    let file = TextureFile::open(path.as_ref(), None, fallback)?;
+    let texels = file.read_region(0, 0, file.width, file.height)?;
+
+    Ok((file.width, file.height, texels))
+}
+
+/// 注册一张纹理, 此时只读取文件头
+///
+/// 文件头注释中标记了色彩空间 (如 `# colorspace: linear`) 时按标记解码, 否则按 `fallback`
+/// 解码: 颜色贴图应为 `Srgb`, 数据贴图应为 `Linear`。
+/// 路径中含有 `<UDIM>` 时注册为 UDIM 纹理集, 读取所有存在的图块文件 (如 `wood.1001.ppm`, `wood.1002.ppm`)
+pub fn register(path: impl AsRef<Path>, fallback: ColorSpace) -> Result<TextureId> {
+    register_with(path.as_ref(), None, fallback)
+}
+
+/// 注册一张纹理, 忽略文件头中的标记, 按 `color_space` 解码
+pub fn register_as(path: impl AsRef<Path>, color_space: ColorSpace) -> Result<TextureId> {
+    register_with(path.as_ref(), Some(color_space), color_space)
+}
+
+fn register_with(
+    path: &Path,
+    forced: Option<ColorSpace>,
+    fallback: ColorSpace,
+) -> Result<TextureId> {
+    let open = |path: &Path| TextureFile::open(path, forced, fallback);
+    let pattern = path.to_string_lossy();
+    let texture = if pattern.contains(UDIM_TOKEN) {
+        let mut images = HashMap::new();
+        for udim in UDIM_RANGE {
+            let tile_path = PathBuf::from(pattern.replace(UDIM_TOKEN, &udim.to_string()));
+            if tile_path.exists() {
+                images.insert(udim, open(&tile_path)?);
+            }
+        }
+        if images.is_empty() {
+            return Err(Error::MissingTexture(path.to_path_buf()));
+        }
+
+        Texture::Udim(images)
+    } else {
+        Texture::Image(open(path)?)
+    };
+
+    let mut textures = CACHE.textures.write().unwrap();
+    textures.push(texture);
+
+    Ok(TextureId(textures.len() as u32 - 1))
+}
+
+/// 设置纹理缓存的内存预算 (字节)
+pub fn set_budget(bytes: usize) {
+    CACHE.budget.store(bytes, Ordering::Relaxed);
+}
+
+/// 读取与淘汰的图块数
+pub fn stats() -> (usize, usize) {
+    (
+        CACHE.loads.load(Ordering::Relaxed),
+        CACHE.evictions.load(Ordering::Relaxed),
+    )
+}
+
+/// 取纹理在 `(u, v)` 处第 `channel` 个通道的数值, 用于粗糙度等非颜色参数 (纹理应以线性注册)
+pub fn sample_scalar(id: TextureId, uv: (f32, f32), footprint: f32, channel: usize) -> f32 {
+    sample_filtered(id, uv, footprint)[channel.min(2)]
+}
+
+/// 纹理每单位纹理坐标的纹素数 (取宽高中的较大者), UDIM 纹理集取其中任一图像
+fn resolution(id: TextureId) -> usize {
+    let textures = CACHE.textures.read().unwrap();
+    let image = match textures.get(id.0 as usize) {
+        Some(Texture::Image(image)) => Some(image),
+        Some(Texture::Udim(images)) => images.values().next(),
+        None => None,
+    };
+
+    image.map_or(0, |image| image.width.max(image.height))
+}
+
+/// 取纹理在 `(u, v)` 附近宽为 `footprint` (纹理坐标单位) 的区域内的平均颜色
+///
+/// 区域不足一个纹素时即为最近邻; 否则在区域内均匀取至多 4×4 个纹素平均, 避免远处的细节闪烁
+pub fn sample_filtered(id: TextureId, (u, v): (f32, f32), footprint: f32) -> Vector3<f32> {
+    let texels = footprint * resolution(id) as f32;
+    if texels.is_nan() || texels <= 1.0 {
+        return sample(id, (u, v));
+    }
+
+    let taps = (texels.ceil() as usize).min(MAX_FILTER_TAPS);
+    let offset = |i: usize| ((i as f32 + 0.5) / taps as f32 - 0.5) * footprint;
+    let mut sum = Vector3::zeros();
+    for i in 0..taps {
+        for j in 0..taps {
+            sum += sample(id, (u + offset(i), v + offset(j)));
+        }
+    }
+
+    sum / (taps * taps) as f32
+}
+
+/// 取纹理在 `(u, v)` 处的颜色 (最近邻), `v = 0` 为图像底部
+///
+/// 单张图像的 `u` 环绕, `v` 截断; UDIM 纹理集按 `(u, v)` 所在的单位方格选取图像。
+/// 读取失败或没有对应的图像时返回品红色, 使缺失的纹理在图像中一目了然
+pub fn sample(id: TextureId, (u, v): (f32, f32)) -> Vector3<f32> {
+    const MISSING: Vector3<f32> = Vector3::new(1.0, 0.0, 1.0);
+
+    let textures = CACHE.textures.read().unwrap();
+    let (image, udim, u, v) = match textures.get(id.0 as usize) {
+        Some(Texture::Image(image)) => (image, 0, u.rem_euclid(1.0), v.clamp(0.0, 1.0)),
+        Some(Texture::Udim(images)) => {
+            let (i, j) = (u.floor(), v.floor());
+            if !(0.0..10.0).contains(&i) || j < 0.0 {
+                return MISSING;
+            }
+            let udim = 1001 + i as u32 + 10 * j as u32;
+            let Some(image) = images.get(&udim) else {
+                return MISSING;
+            };
+
+            (image, udim, u - i, v - j)
+        }
+        None => return MISSING,
+    };
+
+    let x = ((u * image.width as f32) as usize).min(image.width - 1);
+    let y = (((1.0 - v) * image.height as f32) as usize).min(image.height - 1);
+    let key = (id, udim, x / TILE_SIZE, y / TILE_SIZE);
+    let tile_width = TILE_SIZE.min(image.width - key.2 * TILE_SIZE);
+    let index = (y % TILE_SIZE) * tile_width + x % TILE_SIZE;
+
+    if let Some(texels) = CACHE.tiles.lock().unwrap().get(&key) {
+        return texels[index];
+    }
+
+    // 未命中: 在锁外读取图块, 之后再放入缓存
+    let Ok(texels) = image.read_tile(key.2, key.3) else {
+        return MISSING;
+    };
+    let texel = texels[index];
+    insert(key, texels);
+
+    texel
+}
+
+/// 放入图块, 超出预算时淘汰最久未使用的图块
+fn insert(key: TileKey, texels: Vec<Vector3<f32>>) {
+    let size = texels.len() * size_of::<Vector3<f32>>();
+    let budget = CACHE.budget.load(Ordering::Relaxed);
+    let mut tiles = CACHE.tiles.lock().unwrap();
+    if let Some(evicted) = tiles.insert(key, texels, size, budget) {
+        CACHE.loads.fetch_add(1, Ordering::Relaxed);
+        CACHE.evictions.fetch_add(evicted, Ordering::Relaxed);
+    }
+}
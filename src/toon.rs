@@ -0,0 +1,180 @@
+use crate::accelerator::Accelerator;
+use crate::camera::Camera;
+use crate::ray::{Ray, RayKind};
+use crate::render::RenderSettings;
+use crate::stats::{self, Counter};
+
+use nalgebra::Vector3;
+use rayon::prelude::*;
+
+/// 描边的深度阈值 (相对深度差)
+const DEPTH_THRESHOLD: f32 = 0.1;
+
+/// 描边的法线阈值 (相邻法线夹角的余弦)
+const NORMAL_THRESHOLD: f32 = 0.8;
+
+/// 环境光强度, 保证背光面不会全黑
+const AMBIENT: f32 = 0.2;
+
+/// 解析十六进制颜色, 如 `#ff8000` 或 `ff8000`
+pub fn parse_color(s: &str) -> Result<Vector3<f32>, String> {
+    let hex = s.trim().trim_start_matches('#');
+    if hex.len() != 6 {
+        return Err(format!("invalid color `{s}`, expected `#rrggbb`"));
+    }
+
+    let channel = |i: usize| {
+        u8::from_str_radix(&hex[i..i + 2], 16)
+            .map(|c| c as f32 / 255.0)
+            .map_err(|e| format!("invalid color `{s}`: {e}"))
+    };
+
+    Ok(Vector3::new(channel(0)?, channel(2)?, channel(4)?))
+}
+
+/// 卡通渲染设置
+pub struct ToonSettings {
+    /// 色阶数
+    bands: usize,
+
+    /// 调色板, 非空时每个色阶取对应颜色
+    palette: Vec<Vector3<f32>>,
+
+    /// 主光源方向 (指向光源)
+    light: Vector3<f32>,
+
+    /// 描边颜色
+    ink: Vector3<f32>,
+}
+
+impl ToonSettings {
+    pub fn new(bands: usize, palette: Vec<Vector3<f32>>) -> Self {
+        let bands = if palette.is_empty() {
+            bands.max(1)
+        } else {
+            palette.len()
+        };
+
+        Self {
+            bands,
+            palette,
+            light: Vector3::new(1.0, 2.0, 1.5).normalize(),
+            ink: Vector3::zeros(),
+        }
+    }
+
+    /// 第 `band` 个色阶的明暗
+    fn shade(&self, band: usize, albedo: Vector3<f32>) -> Vector3<f32> {
+        if let Some(color) = self.palette.get(band) {
+            color.component_mul(&albedo)
+        } else {
+            let level = (band + 1) as f32 / self.bands as f32;
+            (AMBIENT + (1.0 - AMBIENT) * level) * albedo
+        }
+    }
+}
+
+/// 主光线的击中信息
+#[derive(Clone, Copy)]
+struct GSample {
+    depth: f32,
+    normal: Vector3<f32>,
+    color: Vector3<f32>,
+}
+
+/// 两个相邻像素之间是否需要描边
+fn is_edge(a: Option<GSample>, b: Option<GSample>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => {
+            (a.depth - b.depth).abs() / a.depth.min(b.depth) > DEPTH_THRESHOLD
+                || a.normal.dot(&b.normal) < NORMAL_THRESHOLD
+        }
+        (None, None) => false,
+        _ => true,
+    }
+}
+
+/// 第 `y` 行 (自图像底部起) 第 `x` 列像素中心的屏幕坐标
+fn pixel_center(x: usize, y: usize, nx: usize, ny: usize) -> (f32, f32) {
+    ((x as f32 + 0.5) / nx as f32, (y as f32 + 0.5) / ny as f32)
+}
+
+/// 卡通渲染: 量化的漫反射色阶, 以及由深度和法线突变得到的描边
+pub fn render(
+    scene: &dyn Accelerator,
+    camera: &Camera,
+    nx: usize,
+    ny: usize,
+    settings: &ToonSettings,
+    render_settings: &RenderSettings,
+) -> Vec<u8> {
+    // 第一遍: 主光线的几何信息与着色
+    let buffer = (0..ny)
+        .into_par_iter()
+        .rev()
+        .flat_map_iter(|y| {
+            (0..nx).map(move |x| {
+                let (u, v) = pixel_center(x, y, nx, ny);
+                let ray = camera.center_ray(u, v);
+
+                stats::count(Counter::Rays, 1);
+                let epsilon = render_settings.epsilon;
+                let hit = scene.hit(&ray, epsilon.t_min(&ray, 0.0), f32::MAX)?;
+                let normal = if hit.normal.dot(&ray.direction()) > 0.0 {
+                    -hit.normal
+                } else {
+                    hit.normal
+                };
+
+                // 朝向光源的漫反射, 被遮挡时取最暗的色阶
+                let shadow = Ray::from(hit.position, settings.light).with_kind(RayKind::Shadow);
+                stats::count(Counter::Rays, 1);
+                let t_min = epsilon.t_min(&shadow, hit.distance * ray.direction().magnitude());
+                let diffuse = if scene.hit(&shadow, t_min, f32::MAX).is_some() {
+                    0.0
+                } else {
+                    normal.dot(&settings.light).max(0.0)
+                };
+                let band = ((diffuse * settings.bands as f32) as usize).min(settings.bands - 1);
+                let material = if render_settings.clay {
+                    hit.material.clay()
+                } else {
+                    hit.material
+                };
+
+                Some(GSample {
+                    depth: hit.distance * ray.direction().magnitude(),
+                    normal,
+                    color: settings.shade(band, material.albedo(hit.uv)),
+                })
+            })
+        })
+        .collect::<Vec<_>>();
+
+    // 第二遍: 描边
+    (0..ny)
+        .into_par_iter()
+        .flat_map_iter(|row| {
+            let buffer = &buffer;
+            (0..nx).flat_map(move |x| {
+                let index = row * nx + x;
+                let sample = buffer[index];
+                let edge = (x + 1 < nx && is_edge(sample, buffer[index + 1]))
+                    || (row + 1 < ny && is_edge(sample, buffer[index + nx]));
+
+                let color = if edge {
+                    settings.ink
+                } else if let Some(sample) = sample {
+                    sample.color
+                } else {
+                    // 与第一遍的主光线相同, 行号自图像顶部起
+                    let (u, v) = pixel_center(x, ny - 1 - row, nx, ny);
+                    render_settings.environment.color(&camera.center_ray(u, v))
+                };
+
+                let color = color.map(|c| (255.99 * c.sqrt().clamp(0.0, 1.0)) as u8);
+                [color.x, color.y, color.z]
+            })
+        })
+        .collect()
+}
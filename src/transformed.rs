@@ -0,0 +1,202 @@
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::hittable::{HitRecord, Hittable};
+use crate::ray::{Ray, RayCone};
+
+use nalgebra::{Matrix3, Matrix4, Point3, Rotation3, Similarity3, Vector3};
+
+/// 经仿射变换 (平移, 旋转与缩放, 可非均匀) 放置的实体
+///
+/// 实体在自己的局部坐标系中构建, 光线变换到局部坐标系求交, 参数 `t` 不变;
+/// 法线以变换的逆转置变换回世界空间, 包围盒取局部包围盒八个角点变换后的包围盒
+///
+/// 自发光的网格经变换后不作为网格光源采样, 只在被光线击中时贡献亮度
+#[derive(Clone)]
+pub struct Transformed<H> {
+    /// 局部坐标系中的实体
+    inner: H,
+
+    /// 局部坐标系到世界坐标系的变换
+    to_world: Matrix4<f32>,
+
+    /// 世界坐标系到局部坐标系的变换
+    to_local: Matrix4<f32>,
+
+    /// 法线的变换: `to_world` 线性部分的逆转置
+    normal_matrix: Matrix3<f32>,
+
+    /// 变换的平均缩放 (体积比的立方根), 用于换算光线锥的宽度
+    scale: f32,
+}
+
+impl<H> Transformed<H> {
+    /// 以仿射变换 `to_world` 放置 `inner`, 变换不可逆 (如某一轴的缩放为 0) 时为 `None`
+    pub fn new(inner: H, to_world: Matrix4<f32>) -> Option<Self> {
+        let to_local = to_world.try_inverse()?;
+
+        Some(Self::from_parts(inner, to_world, to_local))
+    }
+
+    /// 以相似变换 `transform` (总是可逆) 放置 `inner`
+    pub fn similar(inner: H, transform: &Similarity3<f32>) -> Self {
+        Self::from_parts(
+            inner,
+            transform.to_homogeneous(),
+            transform.inverse().to_homogeneous(),
+        )
+    }
+
+    /// 由互逆的两个变换构造
+    fn from_parts(inner: H, to_world: Matrix4<f32>, to_local: Matrix4<f32>) -> Self {
+        let linear = to_world.fixed_view::<3, 3>(0, 0).into_owned();
+        // 仿射变换之逆的线性部分即线性部分之逆
+        let inverse = to_local.fixed_view::<3, 3>(0, 0).into_owned();
+
+        Self {
+            inner,
+            to_world,
+            to_local,
+            normal_matrix: inverse.transpose(),
+            scale: linear.determinant().abs().cbrt(),
+        }
+    }
+
+    /// 再平移 `offset`
+    pub fn translated(self, offset: &Vector3<f32>) -> Self {
+        self.then(
+            Matrix4::new_translation(offset),
+            Matrix4::new_translation(&-offset),
+        )
+    }
+
+    /// 再绕原点旋转
+    pub fn rotated(self, rotation: &Rotation3<f32>) -> Self {
+        self.then(
+            rotation.to_homogeneous(),
+            rotation.inverse().to_homogeneous(),
+        )
+    }
+
+    /// 再以原点为中心沿各轴缩放 `factors` 倍, 有缩放为 0 的轴时为 `None`
+    pub fn scaled(self, factors: &Vector3<f32>) -> Option<Self> {
+        if factors.iter().any(|&factor| factor == 0.0) {
+            return None;
+        }
+
+        Some(self.then(
+            Matrix4::new_nonuniform_scaling(factors),
+            Matrix4::new_nonuniform_scaling(&factors.map(f32::recip)),
+        ))
+    }
+
+    /// 再经相似变换
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self
+    where
+        H: Clone,
+    {
+        Self::from_parts(
+            self.inner.clone(),
+            transform.to_homogeneous() * self.to_world,
+            self.to_local * transform.inverse().to_homogeneous(),
+        )
+    }
+
+    /// 在当前变换之后再施加 `matrix`, `inverse` 为其逆
+    fn then(self, matrix: Matrix4<f32>, inverse: Matrix4<f32>) -> Self {
+        Self::from_parts(self.inner, matrix * self.to_world, self.to_local * inverse)
+    }
+
+    /// 局部坐标系中的实体
+    pub const fn inner(&self) -> &H {
+        &self.inner
+    }
+
+    /// 局部坐标系到世界坐标系的变换
+    pub const fn matrix(&self) -> &Matrix4<f32> {
+        &self.to_world
+    }
+
+    /// 世界坐标中的光线变换到局部坐标系, 方向不归一化, 两者的 t 相同
+    fn to_local_ray(&self, ray: &Ray) -> Ray {
+        let cone = ray.cone();
+
+        Ray::from(
+            self.to_local
+                .transform_point(&Point3::from(ray.origin()))
+                .coords,
+            self.to_local.transform_vector(&ray.direction()),
+        )
+        .with_kind(ray.kind())
+        .with_cone(RayCone {
+            width: cone.width / self.scale,
+            spread: cone.spread,
+        })
+        .continuing(ray)
+    }
+
+    fn to_world_point(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        self.to_world.transform_point(&Point3::from(*point)).coords
+    }
+}
+
+impl<H: Hittable> Hittable for Transformed<H> {
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let hit = self.inner.hit(&self.to_local_ray(ray), t_min, t_max)?;
+
+        Some(HitRecord {
+            position: ray.point_at_t(hit.distance),
+            normal: (self.normal_matrix * hit.normal).normalize(),
+            ..hit
+        })
+    }
+}
+
+impl<H: Bounded> Bounded for Transformed<H> {
+    fn bounding_box(&self) -> AaBb {
+        let local = self.inner.bounding_box();
+        let mut bounds = AaBb {
+            min: Vector3::repeat(f32::INFINITY),
+            max: Vector3::repeat(f32::NEG_INFINITY),
+        };
+        for corner in 0..8 {
+            let point = Vector3::from_fn(|i, _| {
+                if corner & (1 << i) == 0 {
+                    local.min[i]
+                } else {
+                    local.max[i]
+                }
+            });
+            let point = self.to_world_point(&point);
+            bounds.min = bounds.min.inf(&point);
+            bounds.max = bounds.max.sup(&point);
+        }
+
+        bounds
+    }
+
+    /// 近似: 取局部坐标系中的最近点变换回世界空间, 非均匀缩放时不一定是世界空间中最近的点,
+    /// 但始终是到表面上某点的距离; 符号沿用局部坐标系中的内外
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let local = self
+            .inner
+            .closest_point(&self.to_local.transform_point(&Point3::from(*point)).coords);
+        let position = self.to_world_point(&local.position);
+
+        ClosestPoint {
+            position,
+            distance: (point - position).magnitude().copysign(local.distance),
+            ..local
+        }
+    }
+
+    fn unbounded(&self) -> bool {
+        self.inner.unbounded()
+    }
+
+    fn triangles(&self) -> Vec<[Vector3<f32>; 3]> {
+        self.inner
+            .triangles()
+            .into_iter()
+            .map(|vertices| vertices.map(|vertex| self.to_world_point(&vertex)))
+            .collect()
+    }
+}
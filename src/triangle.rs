@@ -0,0 +1,186 @@
+use crate::bvh::{AaBb, Bounded, ClosestPoint};
+use crate::hittable::{HitRecord, Hittable};
+use crate::material::Material;
+use crate::ray::Ray;
+
+use nalgebra::{Point3, Similarity3, Vector3};
+
+/// 包围盒各轴的最小厚度, 避免与坐标轴对齐的三角形的包围盒退化为平面
+pub(crate) const MIN_THICKNESS: f32 = 1e-4;
+
+/// 三角形
+///
+/// 法线由顶点的逆时针顺序按右手定则确定, 封闭网格中应朝外, 与球体的外法线一致
+#[derive(Clone)]
+pub struct Triangle {
+    /// 三个顶点
+    vertices: [Vector3<f32>; 3],
+
+    /// 材质
+    material: Material,
+}
+
+impl Triangle {
+    pub const fn new(
+        a: Vector3<f32>,
+        b: Vector3<f32>,
+        c: Vector3<f32>,
+        material: Material,
+    ) -> Self {
+        Self {
+            vertices: [a, b, c],
+            material,
+        }
+    }
+
+    /// 三个顶点
+    pub const fn vertices(&self) -> &[Vector3<f32>; 3] {
+        &self.vertices
+    }
+
+    /// 经相似变换后的三角形
+    pub fn transformed(&self, transform: &Similarity3<f32>) -> Self {
+        Self {
+            vertices: self
+                .vertices
+                .map(|v| transform.transform_point(&Point3::from(v)).coords),
+            material: self.material,
+        }
+    }
+
+    /// 未归一化的法线, 长度为面积的两倍
+    fn cross(&self) -> Vector3<f32> {
+        let [a, b, c] = self.vertices;
+
+        (b - a).cross(&(c - a))
+    }
+}
+
+impl Hittable for Triangle {
+    /// Möller–Trumbore 求交, 纹理坐标为重心坐标 (对应顶点 b 与 c 的权重)
+    fn hit(&self, ray: &Ray, t_min: f32, t_max: f32) -> Option<HitRecord> {
+        let (t, u, v) = intersect(&self.vertices, ray, t_min, t_max)?;
+        let d = ray.direction();
+
+        // 光线锥的宽度按三角形的特征边长换算为重心坐标的宽度
+        let cross = self.cross();
+        let width = ray.cone().width_at(t * d.magnitude());
+
+        Some(HitRecord {
+            distance: t,
+            position: ray.point_at_t(t),
+            normal: cross.normalize(),
+            uv: (u, v),
+            footprint: width / cross.norm().sqrt(),
+            material: self.material,
+            object: 0,
+        })
+    }
+}
+
+/// Möller–Trumbore 求交, 返回距离与顶点 b, c 的重心坐标
+pub(crate) fn intersect(
+    [a, b, c]: &[Vector3<f32>; 3],
+    ray: &Ray,
+    t_min: f32,
+    t_max: f32,
+) -> Option<(f32, f32, f32)> {
+    let (e1, e2) = (b - a, c - a);
+    let d = ray.direction();
+
+    let p = d.cross(&e2);
+    let det = e1.dot(&p);
+    if det.abs() < f32::EPSILON * e1.norm() * e2.norm() * d.norm() {
+        // 光线与三角形所在平面平行
+        return None;
+    }
+    let inv_det = 1.0 / det;
+
+    let s = ray.origin() - a;
+    let u = s.dot(&p) * inv_det;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = s.cross(&e1);
+    let v = d.dot(&q) * inv_det;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = e2.dot(&q) * inv_det;
+    (t > t_min && t < t_max).then_some((t, u, v))
+}
+
+/// 三角形的包围盒, 各轴至少有 `MIN_THICKNESS` 的厚度
+pub(crate) fn bounding_box([a, b, c]: &[Vector3<f32>; 3]) -> AaBb {
+    let pad = Vector3::repeat(MIN_THICKNESS / 2.0);
+
+    AaBb {
+        min: a.inf(b).inf(c) - pad,
+        max: a.sup(b).sup(c) + pad,
+    }
+}
+
+/// 三角形上距 `point` 最近的点 (按所在的 Voronoi 区域分情况)
+pub(crate) fn closest_point([a, b, c]: &[Vector3<f32>; 3], point: &Vector3<f32>) -> Vector3<f32> {
+    let (a, b, c) = (*a, *b, *c);
+    let (ab, ac, ap) = (b - a, c - a, point - a);
+
+    let (d1, d2) = (ab.dot(&ap), ac.dot(&ap));
+    if d1 <= 0.0 && d2 <= 0.0 {
+        return a;
+    }
+
+    let bp = point - b;
+    let (d3, d4) = (ab.dot(&bp), ac.dot(&bp));
+    if d3 >= 0.0 && d4 <= d3 {
+        return b;
+    }
+
+    let vc = d1 * d4 - d3 * d2;
+    if vc <= 0.0 && d1 >= 0.0 && d3 <= 0.0 {
+        return a + d1 / (d1 - d3) * ab;
+    }
+
+    let cp = point - c;
+    let (d5, d6) = (ab.dot(&cp), ac.dot(&cp));
+    if d6 >= 0.0 && d5 <= d6 {
+        return c;
+    }
+
+    let vb = d5 * d2 - d1 * d6;
+    if vb <= 0.0 && d2 >= 0.0 && d6 <= 0.0 {
+        return a + d2 / (d2 - d6) * ac;
+    }
+
+    let va = d3 * d6 - d5 * d4;
+    if va <= 0.0 && d4 - d3 >= 0.0 && d5 - d6 >= 0.0 {
+        return b + (d4 - d3) / ((d4 - d3) + (d5 - d6)) * (c - b);
+    }
+
+    // 位于三角形内部的投影
+    let denom = 1.0 / (va + vb + vc);
+    a + vb * denom * ab + vc * denom * ac
+}
+
+impl Bounded for Triangle {
+    fn bounding_box(&self) -> AaBb {
+        bounding_box(&self.vertices)
+    }
+
+    /// 距离总为非负
+    fn closest_point(&self, point: &Vector3<f32>) -> ClosestPoint {
+        let position = closest_point(&self.vertices, point);
+
+        ClosestPoint {
+            object: 0,
+            position,
+            distance: (point - position).magnitude(),
+        }
+    }
+
+    fn triangles(&self) -> Vec<[Vector3<f32>; 3]> {
+        vec![self.vertices]
+    }
+}
@@ -0,0 +1,51 @@
+use crate::bvh::AaBb;
+use crate::camera::Camera;
+
+use nalgebra::Vector3;
+
+/// 转台: 相机以固定仰角绕包围球的中心环绕一周
+pub struct Turntable {
+    /// 包围球的中心
+    center: Vector3<f32>,
+
+    /// 包围球的半径
+    radius: f32,
+
+    /// 帧数
+    frames: usize,
+
+    /// 仰角 (角度)
+    elevation: f32,
+}
+
+impl Turntable {
+    /// 环绕包围盒 `bounds` 的外接球, 共 `frames` 帧
+    pub fn new(bounds: &AaBb, frames: usize, elevation: f32) -> Self {
+        Self {
+            center: (bounds.min + bounds.max) / 2.0,
+            radius: (bounds.max - bounds.min).magnitude() / 2.0,
+            frames: frames.max(1),
+            elevation,
+        }
+    }
+
+    /// 第 `frame` 帧的相机, 距离使包围球恰好充满竖直视野
+    pub fn camera(&self, frame: usize, vertical_fov: f32, aspect: f32) -> Camera {
+        let azimuth = std::f32::consts::TAU * frame as f32 / self.frames as f32;
+        let elevation = self.elevation.to_radians();
+        let direction = Vector3::new(
+            elevation.cos() * azimuth.sin(),
+            elevation.sin(),
+            elevation.cos() * azimuth.cos(),
+        );
+        let distance = self.radius / (vertical_fov.to_radians() / 2.0).sin();
+
+        Camera::from_without_focus(
+            self.center + distance * direction,
+            self.center,
+            Vector3::new(0.0, 1.0, 0.0),
+            vertical_fov,
+            aspect,
+        )
+    }
+}
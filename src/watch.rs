@@ -0,0 +1,36 @@
+use std::io;
+use std::path::Path;
+
+use crate::error::Result;
+
+use notify::{Event, EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+/// 监视文件 `path` 的变化, 文件被修改, 创建或替换时在监视线程上调用 `on_change`
+///
+/// 编辑器常先写入临时文件再重命名覆盖原文件, 故监视文件所在的目录并按路径过滤;
+/// 返回的监视器被丢弃时停止监视
+pub fn watch(path: &Path, on_change: impl Fn() + Send + 'static) -> Result<RecommendedWatcher> {
+    let failed = |err: notify::Error| io::Error::other(format!("{}: {err}", path.display()));
+    let path = path
+        .canonicalize()
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {err}", path.display())))?;
+    let dir = path.parent().unwrap_or(Path::new("/")).to_path_buf();
+
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+        let Ok(event) = event else {
+            return;
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+        if event.paths.contains(&path) {
+            on_change();
+        }
+    })
+    .map_err(failed)?;
+    watcher
+        .watch(&dir, RecursiveMode::NonRecursive)
+        .map_err(failed)?;
+
+    Ok(watcher)
+}